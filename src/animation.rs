@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use x11rb::protocol::xproto::{Rectangle, Window as Wid};
+
+use crate::config::AnimationConfig;
+
+#[derive(Debug, Clone, Copy)]
+struct Transition {
+    from: Rectangle,
+    to: Rectangle,
+    step: u16,
+}
+
+fn lerp(a: i32, b: i32, num: i32, den: i32) -> i32 {
+    a + (b - a) * num / den
+}
+
+/// Interpolates geometries of windows moving from one place to another.
+/// All running transitions advance together so that a layout change
+/// which moves many windows at once is carried out in the same few steps.
+#[derive(Debug)]
+pub struct Animator {
+    config: AnimationConfig,
+    transitions: HashMap<Wid, Transition>,
+}
+
+impl Animator {
+    pub fn new(config: AnimationConfig) -> Self {
+        Self {
+            config,
+            transitions: HashMap::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled && self.config.steps > 1
+    }
+
+    /// Starts (or retargets) the transition of `wid` from `from` to `to`.
+    pub fn start(&mut self, wid: Wid, from: Rectangle, to: Rectangle) {
+        if from == to {
+            self.transitions.remove(&wid);
+            return;
+        }
+
+        // A new goal restarts the transition from where the window is now.
+        if let Some(tr) = self.transitions.get_mut(&wid) {
+            if tr.to != to {
+                *tr = Transition { from, to, step: 0 };
+            }
+            return;
+        }
+
+        self.transitions
+            .insert(wid, Transition { from, to, step: 0 });
+    }
+
+    pub fn cancel(&mut self, wid: Wid) {
+        self.transitions.remove(&wid);
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.transitions.clear();
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.transitions.is_empty()
+    }
+
+    /// Advances all transitions by one step and returns the geometries to apply.
+    pub fn step(&mut self) -> Vec<(Wid, Rectangle)> {
+        let steps = self.config.steps as i32;
+        let mut geometries = Vec::with_capacity(self.transitions.len());
+        for (&wid, tr) in self.transitions.iter_mut() {
+            tr.step += 1;
+            let n = tr.step as i32;
+            let (a, b) = (tr.from, tr.to);
+            let rect = Rectangle {
+                x: lerp(a.x as i32, b.x as i32, n, steps) as i16,
+                y: lerp(a.y as i32, b.y as i32, n, steps) as i16,
+                width: lerp(a.width as i32, b.width as i32, n, steps).max(1) as u16,
+                height: lerp(a.height as i32, b.height as i32, n, steps).max(1) as u16,
+            };
+            geometries.push((wid, rect));
+        }
+        self.transitions.retain(|_, tr| (tr.step as i32) < steps);
+        geometries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i16, y: i16, width: u16, height: u16) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn animator(steps: u16) -> Animator {
+        Animator::new(AnimationConfig {
+            enabled: true,
+            duration_ms: 100,
+            steps,
+        })
+    }
+
+    #[test]
+    fn transition_reaches_target() {
+        let mut anim = animator(4);
+        anim.start(1, rect(0, 0, 100, 100), rect(100, 40, 300, 100));
+
+        let xs: Vec<i16> = (0..4).map(|_| anim.step()[0].1.x).collect();
+        assert_eq!(xs, vec![25, 50, 75, 100]);
+        assert!(!anim.is_active());
+        assert!(anim.step().is_empty());
+    }
+
+    #[test]
+    fn windows_move_together() {
+        let mut anim = animator(2);
+        anim.start(1, rect(0, 0, 10, 10), rect(10, 0, 10, 10));
+        anim.start(2, rect(0, 0, 10, 10), rect(0, 10, 10, 10));
+
+        assert_eq!(anim.step().len(), 2);
+        let mut last = anim.step();
+        last.sort_unstable_by_key(|&(wid, _)| wid);
+        assert_eq!(last[0].1, rect(10, 0, 10, 10));
+        assert_eq!(last[1].1, rect(0, 10, 10, 10));
+        assert!(!anim.is_active());
+    }
+
+    #[test]
+    fn no_transition_for_unchanged_geometry() {
+        let mut anim = animator(4);
+        anim.start(1, rect(0, 0, 10, 10), rect(5, 0, 10, 10));
+        anim.start(1, rect(5, 0, 10, 10), rect(5, 0, 10, 10));
+        assert!(!anim.is_active());
+    }
+}
@@ -2,10 +2,33 @@ use x11rb::atom_manager;
 
 atom_manager! {
     pub AtomCollection: AtomCollectionCookie {
+        MANAGER,
         WM_DELETE_WINDOW,
         WM_PROTOCOLS,
         WM_STATE,
+        _DAILY_CAPABILITIES,
+        _DAILY_LAYOUTS,
+        _DAILY_METRICS,
+        _NET_ACTIVE_WINDOW,
+        _NET_CLOSE_WINDOW,
+        _NET_CURRENT_DESKTOP,
+        _NET_NUMBER_OF_DESKTOPS,
         _NET_WM_WINDOW_TYPE,
         _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_WINDOW_TYPE_DOCK,
+        _NET_WM_WINDOW_TYPE_NOTIFICATION,
+        _NET_WM_WINDOW_OPACITY,
+        _NET_WM_BYPASS_COMPOSITOR,
+        _NET_WM_STRUT,
+        _NET_WM_STRUT_PARTIAL,
+        _NET_WM_DESKTOP,
+        _NET_WM_ICON,
+        _NET_WM_MOVERESIZE,
+        _NET_WM_STATE,
+        _NET_WM_STATE_ABOVE,
+        _NET_WM_STATE_BELOW,
+        _NET_WM_STATE_FULLSCREEN,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
     }
 }
@@ -1,189 +1,530 @@
 #![allow(dead_code)]
 
 use crossbeam_channel::{select, tick, unbounded, Receiver, Sender};
-use log::debug;
+use log::{debug, error, info, warn};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{Window as Wid, *};
-use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
 
-use crate::context::Context;
+use crate::atom::AtomCollection;
+
+use crate::config::BarColors;
+use crate::context::{self, Context, XConnection};
 use crate::error::{Error, Result};
 use crate::event::{EventHandler as _, EventHandlerMethods};
+use crate::shape;
 use crate::spawn_named_thread;
 
-#[derive(Debug)]
+/// The height of the bar on monitors which are not scaled.
+pub const BAR_HEIGHT: u16 = 16;
+
+/// How many times a failed bar thread is restarted before giving up.
+const MAX_RESTARTS: usize = 5;
+/// How long a restarted bar thread has to run before its past failures are forgotten.
+const HEALTHY_DURATION: Duration = Duration::from_secs(60);
+
+/// The maximum width of a title in the taskbar.
+const MAX_TASK_WIDTH: u16 = 200;
+
+#[derive(Debug, Clone)]
 pub enum Request {
     GetWindowId,
-    Configure { geometry: Rectangle },
-    UpdateContent { content: Content },
+    Configure {
+        geometry: Rectangle,
+    },
+    UpdateContent {
+        content: Box<Content>,
+    },
     Show,
     Hide,
+    /// Puts the bar above the windows covering it, e.g. in the full-screen layout.
+    Raise,
+    /// Destroys the bar window and terminates the thread.
+    Shutdown,
 }
 
 #[derive(Debug)]
 pub enum Response {
     WindowId(u32),
     Success,
-    Error { reason: String },
+    /// The bar thread failed and has terminated.
+    Error {
+        reason: String,
+    },
 }
 
-#[derive(Debug, Clone, Default)]
+/// A window listed in the taskbar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Task {
+    pub frame: Wid,
+    pub title: String,
+    pub focused: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Content {
     pub max_screen: usize,
     pub current_screen: usize,
     pub focused: bool,
+    /// The number of mapped windows on the screen.
+    pub window_count: usize,
+    /// Whether to show the error indicator.
+    pub error: bool,
+    /// The name of the current keyboard layout (XKB group), e.g. "us".
+    pub keyboard_layout: String,
+    /// The name of the layout of the screen, e.g. "monocle".
+    pub layout_name: &'static str,
+    /// The output of the last media command, e.g. "vol 50%".
+    pub level: Option<String>,
+    /// The current page of the layout and the number of pages, if the windows are paged.
+    pub page: Option<(usize, usize)>,
+    /// The position of the focused window among the stacked ones and their number (monocle).
+    pub position: Option<(usize, usize)>,
+    /// The windows of the screen, if the taskbar is enabled.
+    pub tasks: Vec<Task>,
+    /// The colors of the current theme.
+    pub colors: BarColors,
+}
+
+/// Returns the length of text drawn with the font for the scale.
+fn text_width(text: &str, scale: u16) -> i16 {
+    (context::char_width(scale) as usize * text.len()) as i16
+}
+
+/// Returns the horizontal range of the bar between the left items and the right ones,
+/// which is used by the taskbar.
+fn taskbar_range(cont: &Content, width: u16, scale: u16) -> (i16, i16) {
+    // See `Bar::draw` for the items.
+    let s = scale as i16;
+    let mut left = (2 + (cont.max_screen * 12) as i16 + 16) * s;
+    left += (8 * format!("[{}]", cont.window_count).len() as i16 + 16) * s;
+    if let Some((page, pages)) = cont.page {
+        left += text_width(&format!("page {}/{}", page + 1, pages), scale) + 16 * s;
+    }
+    if let Some((index, count)) = cont.position {
+        left += text_width(&format!("{}/{}", index + 1, count), scale) + 16 * s;
+    }
+    if let Some(level) = &cont.level {
+        let len = level.chars().take(32).count() as i16;
+        left += context::char_width(scale) as i16 * len + 16 * s;
+    }
+
+    let mut right = width as i16 - (136 + 8) * s;
+    if !cont.keyboard_layout.is_empty() {
+        right -= text_width(&cont.keyboard_layout, scale) + 16 * s;
+    }
+    right -= text_width(&format!("[{}]", cont.layout_name), scale) + 16 * s;
+    (left, right)
+}
+
+/// Returns the horizontal range of the clock at the right end of the bar.
+fn clock_range(width: u16, scale: u16) -> (i16, i16) {
+    let s = scale as i16;
+    let right = width as i16 - 8 * s;
+    (right - 128 * s, right)
+}
+
+/// Returns the x and the width of the title of each task.
+fn task_spans(cont: &Content, width: u16, scale: u16) -> Vec<(i16, u16)> {
+    let (left, right) = taskbar_range(cont, width, scale);
+    if cont.tasks.is_empty() || right <= left {
+        return Vec::new();
+    }
+    let max_width = MAX_TASK_WIDTH * scale;
+    let each = ((right - left) as u16 / cont.tasks.len() as u16).min(max_width);
+    (0..cont.tasks.len())
+        .map(|i| (left + (each as usize * i) as i16, each))
+        .collect()
+}
+
+/// Abbreviates the title to `max_chars` with "..", replacing non-ASCII characters
+/// which core fonts cannot show.
+fn abbreviate(title: &str, max_chars: usize) -> Vec<u8> {
+    let mut text: Vec<u8> = title
+        .chars()
+        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+        .collect();
+    if text.len() > max_chars {
+        text.truncate(max_chars.saturating_sub(2));
+        text.extend_from_slice(&b".."[..max_chars.min(2)]);
+    }
+    text
+}
+
+/// Returns the scale of the monitor from the geometry of its bar.
+fn scale_of(geometry: Rectangle) -> u16 {
+    (geometry.height / BAR_HEIGHT).max(1)
+}
+
+/// How the bar window is drawn.
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    corner_radius: Option<u16>,
+    double_buffer: bool,
+}
+
+fn spawn_bar_thread(
+    id: usize,
+    display: Option<String>,
+    style: Style,
+) -> (Sender<Request>, Receiver<Response>, JoinHandle<()>) {
+    let (req_tx, req_rx) = unbounded::<Request>();
+    let (resp_tx, resp_rx) = unbounded::<Response>();
+
+    let name = format!("bar-main.{}", id);
+    let thread = spawn_named_thread(name, move || {
+        if let Err(err) = thread_main(display, style, req_rx, &resp_tx) {
+            let reason = err.to_string();
+            let _ = resp_tx.send(Response::Error { reason });
+        }
+    });
+
+    (req_tx, resp_rx, thread)
 }
 
 #[derive(Debug)]
 pub struct BarHandle {
+    id: usize,
+    display: Option<String>,
+    style: Style,
     tx: Sender<Request>,
     rx: Receiver<Response>,
+    thread: Option<JoinHandle<()>>,
+    restarts: usize,
+    /// The time of the last restart.
+    restarted_at: Option<Instant>,
+    disabled: bool,
+
+    // The last state requested, restored after a restart.
+    geometry: Option<Rectangle>,
+    content: Option<Content>,
+    visible: bool,
 }
 
 impl BarHandle {
     pub fn new(ctx: &Context, id: usize) -> Self {
-        let (req_tx, req_rx) = unbounded::<Request>();
-        let (resp_tx, resp_rx) = unbounded::<Response>();
-
         let display = ctx.display.clone();
-        let name = format!("bar-main.{}", id);
-        spawn_named_thread(name, move || {
-            let _ = thread_main(display, req_rx, resp_tx);
-        });
+        let style = Style {
+            corner_radius: ctx.config.rounded_corners.radius(),
+            double_buffer: ctx.config.bar.double_buffer,
+        };
+        let (tx, rx, thread) = spawn_bar_thread(id, display.clone(), style);
 
         Self {
-            tx: req_tx,
-            rx: resp_rx,
+            id,
+            display,
+            style,
+            tx,
+            rx,
+            thread: Some(thread),
+            restarts: 0,
+            restarted_at: None,
+            disabled: false,
+            geometry: None,
+            content: None,
+            visible: false,
         }
     }
 
-    fn send_recv(&mut self, req: Request) -> Result<Response> {
-        self.tx.send(req).map_err(|_| Error::BrokenChannel)?;
-        self.rx.recv().map_err(|_| Error::BrokenChannel)
+    /// Sends a request and waits for the response.
+    /// The failure is reported as the reason in `Err`.
+    fn try_send_recv(&mut self, req: Request) -> std::result::Result<Response, String> {
+        let terminated = || "the bar thread has terminated".to_owned();
+        if self.tx.send(req).is_err() {
+            // The thread may have left the reason before terminating.
+            return match self.rx.try_recv() {
+                Ok(Response::Error { reason }) => Err(reason),
+                _ => Err(terminated()),
+            };
+        }
+        match self.rx.recv() {
+            Ok(Response::Error { reason }) => Err(reason),
+            Ok(resp) => Ok(resp),
+            Err(_) => Err(terminated()),
+        }
     }
 
-    pub fn configure(&mut self, x: i16, y: i16, width: u16, height: u16) -> Result<()> {
-        let req = Request::Configure {
-            geometry: Rectangle {
-                x,
-                y,
-                width,
-                height,
-            },
-        };
-        match self.send_recv(req)? {
-            Response::Success => Ok(()),
-            resp => panic!("Invalid Mesage: {:?}", resp),
+    /// Starts a new bar thread and brings it to the last requested state.
+    fn restart(&mut self) -> std::result::Result<(), String> {
+        // The failed thread has already left its loop.
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        let (tx, rx, thread) = spawn_bar_thread(self.id, self.display.clone(), self.style);
+        self.tx = tx;
+        self.rx = rx;
+        self.thread = Some(thread);
+
+        if let Some(geometry) = self.geometry {
+            self.try_send_recv(Request::Configure { geometry })?;
+        }
+        if let Some(content) = self.content.clone() {
+            self.try_send_recv(Request::UpdateContent {
+                content: Box::new(content),
+            })?;
         }
+        if self.visible {
+            self.try_send_recv(Request::Show)?;
+        }
+        Ok(())
     }
 
-    pub fn update_content(&mut self, content: Content) -> Result<()> {
-        match self.send_recv(Request::UpdateContent { content })? {
-            Response::Success => Ok(()),
-            resp => panic!("Invalid Mesage: {:?}", resp),
+    /// Restarts the bar thread, or disables the bar after too many failures in a row.
+    fn recover(&mut self) {
+        if self
+            .restarted_at
+            .is_some_and(|t| t.elapsed() >= HEALTHY_DURATION)
+        {
+            debug!("bar {}: has run fine since the last restart", self.id);
+            self.restarts = 0;
         }
+        while self.restarts < MAX_RESTARTS {
+            self.restarts += 1;
+            info!(
+                "bar {}: restart ({}/{})",
+                self.id, self.restarts, MAX_RESTARTS
+            );
+            self.restarted_at = Some(Instant::now());
+            match self.restart() {
+                Ok(()) => return,
+                Err(reason) => error!("bar {}: {}", self.id, reason),
+            }
+        }
+        error!("bar {}: too many failures, disabled", self.id);
+        self.disabled = true;
     }
 
-    pub fn get_window_id(&mut self) -> Result<Wid> {
-        match self.send_recv(Request::GetWindowId)? {
-            Response::WindowId(wid) => Ok(wid),
-            resp => panic!("Invalid Mesage: {:?}", resp),
+    /// Returns `None` if the bar has been disabled because of repeated failures.
+    fn send_recv(&mut self, req: Request) -> Result<Option<Response>> {
+        while !self.disabled {
+            match self.try_send_recv(req.clone()) {
+                Ok(resp) => return Ok(Some(resp)),
+                Err(reason) => error!("bar {}: {}", self.id, reason),
+            }
+            self.recover();
         }
+        Ok(None)
+    }
+
+    /// Reports a response which does not answer the request.
+    fn invalid_response(&self, resp: Response) -> Error {
+        error!("bar {}: invalid response {:?}", self.id, resp);
+        Error::BrokenChannel
     }
 
     fn unit_request(&mut self, req: Request) -> Result<()> {
         match self.send_recv(req)? {
-            Response::Success => Ok(()),
-            resp => panic!("Invalid Mesage: {:?}", resp),
+            Some(Response::Success) | None => Ok(()),
+            Some(resp) => Err(self.invalid_response(resp)),
+        }
+    }
+
+    /// Moves the bar, unless it is already there.
+    pub fn configure(&mut self, geometry: Rectangle) -> Result<()> {
+        if self.geometry == Some(geometry) {
+            return Ok(());
         }
+        self.geometry = Some(geometry);
+        self.unit_request(Request::Configure { geometry })
     }
+
+    /// Redraws the bar with the content, unless it is the same as the last one.
+    pub fn update_content(&mut self, content: Content) -> Result<()> {
+        if self.content.as_ref() == Some(&content) {
+            return Ok(());
+        }
+        self.content = Some(content.clone());
+        let content = Box::new(content);
+        self.unit_request(Request::UpdateContent { content })
+    }
+
+    /// Returns the frame of the window whose title is clicked at `root_x` on `wid`.
+    pub fn task_at(&mut self, wid: Wid, root_x: i16) -> Option<Wid> {
+        let geometry = self.geometry?;
+        let content = self.content.as_ref()?;
+        if content.tasks.is_empty() || self.get_window_id().ok()? != wid {
+            return None;
+        }
+        let content = self.content.as_ref()?;
+        let x = root_x - geometry.x;
+        let scale = scale_of(geometry);
+        task_spans(content, geometry.width, scale)
+            .into_iter()
+            .zip(&content.tasks)
+            .find(|((left, width), _)| (*left..*left + *width as i16).contains(&x))
+            .map(|(_, task)| task.frame)
+    }
+
+    /// Whether the clock is clicked at `root_x` on `wid`.
+    pub fn clock_at(&mut self, wid: Wid, root_x: i16) -> bool {
+        let geometry = match self.geometry {
+            Some(geometry) => geometry,
+            None => return false,
+        };
+        if self.get_window_id().ok() != Some(wid) {
+            return false;
+        }
+        let (left, right) = clock_range(geometry.width, scale_of(geometry));
+        (left..right).contains(&(root_x - geometry.x))
+    }
+
+    pub fn get_window_id(&mut self) -> Result<Wid> {
+        match self.send_recv(Request::GetWindowId)? {
+            Some(Response::WindowId(wid)) => Ok(wid),
+            Some(resp) => Err(self.invalid_response(resp)),
+            None => Err(Error::BarDisabled),
+        }
+    }
+
     pub fn show(&mut self) -> Result<()> {
+        self.visible = true;
         self.unit_request(Request::Show)
     }
     pub fn hide(&mut self) -> Result<()> {
+        self.visible = false;
         self.unit_request(Request::Hide)
     }
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+    pub fn raise(&mut self) -> Result<()> {
+        self.unit_request(Request::Raise)
+    }
+
+    /// Destroys the bar window and waits for the thread to terminate.
+    /// Requests after this are ignored.
+    pub fn shutdown(&mut self) {
+        self.disabled = true;
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return,
+        };
+
+        debug!("bar {}: shutdown", self.id);
+        match self.try_send_recv(Request::Shutdown) {
+            Ok(Response::Success) => {}
+            Ok(resp) => {
+                self.invalid_response(resp);
+            }
+            Err(reason) => warn!("bar {}: {}", self.id, reason),
+        }
+        if thread.join().is_err() {
+            error!("bar {}: the thread panicked", self.id);
+        }
+    }
+}
+
+impl Drop for BarHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
 }
 
 fn thread_main(
     display: Option<String>,
+    style: Style,
     request_rx: Receiver<Request>,
-    response_tx: Sender<Response>,
+    response_tx: &Sender<Response>,
 ) -> Result<()> {
     let display = display.as_deref();
 
     // Use a dedicated connection for this bar.
-    let (conn, _) =
-        RustConnection::connect(display).expect("cannot establish a connection to X server");
+    let (conn, screen_num) = context::connect(display)?;
     let conn = Arc::new(conn);
 
+    let mut bar = Bar::new(conn.clone(), screen_num, style)?;
+
     // Consume X11 events and redirect it
     let (event_tx, event_rx) = unbounded();
-    spawn_named_thread("bar-x11".to_owned(), {
-        let conn = conn.clone();
-        move || loop {
-            let event = conn.wait_for_event().expect("cannot get event");
-            event_tx.send(event).expect("rx has been closed");
+    bar.event_thread = Some(spawn_named_thread("bar-x11".to_owned(), move || loop {
+        let event = conn.wait_for_event();
+        let failed = event.is_err();
+        if event_tx.send(event).is_err() || failed {
+            return;
         }
-    });
+    }));
+    // Dropping `bar` cause the "bar-x11" thread to be terminated.
 
     // To update the bar periodically
     let timer_rx = tick(std::time::Duration::from_secs(10));
 
-    let mut bar = Bar::new(conn)?;
-    // Dropping `bar` cause the "bar-x11" thread to be terminated.
-
     loop {
         select! {
             recv(request_rx) -> req => {
-                let req = req.expect("request_tx was closed");
-                let resp = bar.handle_request(req);
-                response_tx.send(resp).expect("response_rx was closed");
+                // The handle has been dropped.
+                let req = match req {
+                    Ok(Request::Shutdown) | Err(_) => break,
+                    Ok(req) => req,
+                };
+                let resp = bar.handle_request(req)?;
+                if response_tx.send(resp).is_err() {
+                    return Ok(());
+                }
             }
 
             recv(event_rx) -> event => {
-                let event = event.expect("event_rx was closed");
+                let event = event.map_err(|_| Error::BrokenChannel)??;
                 bar.handle_event(event)?;
             }
 
-            recv(timer_rx) -> _ => bar.show()?,
+            recv(timer_rx) -> _ => bar.tick()?,
         }
     }
+
+    // Reply after the window has been destroyed.
+    drop(bar);
+    let _ = response_tx.send(Response::Success);
+    Ok(())
+}
+
+/// The value of _NET_WM_STRUT_PARTIAL of the bar at `geometry`, which reserves the top of the screen:
+/// left, right, top, bottom, left_start_y, left_end_y, right_start_y, right_end_y,
+/// top_start_x, top_end_x, bottom_start_x, bottom_end_x.
+fn strut_partial(geometry: Rectangle) -> [u32; 12] {
+    let top = (geometry.y as i32 + geometry.height as i32).max(0) as u32;
+    let start = geometry.x.max(0) as u32;
+    let end = (geometry.x as i32 + geometry.width as i32 - 1).max(0) as u32;
+    [0, 0, top, 0, 0, 0, 0, 0, start, end, 0, 0]
 }
 
 struct Bar {
-    conn: Arc<RustConnection>,
+    conn: Arc<XConnection>,
+    atom: AtomCollection,
     wid: Wid,
+    /// The X screen of the bar, not 0 only in Zaphod mode.
+    screen_num: usize,
     gc: Gcontext,
     mon: Rectangle,
+    /// The scale of the monitor, which determines the font of `gc`.
+    scale: u16,
     content: Content,
+    visible: bool,
+    style: Style,
+    /// The pixmap the bar is drawn into before copied to the window, if double-buffered.
+    buffer: Option<Pixmap>,
+    event_thread: Option<JoinHandle<()>>,
 }
 
 impl Drop for Bar {
     fn drop(&mut self) {
+        // Killing our own client closes the connection, which destroys the window
+        // and wakes up the "bar-x11" thread.
         let _ = self.conn.kill_client(self.wid);
         let _ = self.conn.flush();
+        if let Some(thread) = self.event_thread.take() {
+            let _ = thread.join();
+        }
     }
 }
 
-mod color {
-    pub const MAIN: u32 = 0x4e4b61;
-    pub const LIGHT: u32 = 0x69656d;
-    pub const SHADOW: u32 = 0x1a1949;
-
-    pub const FOCUSED_CHAR1: u32 = 0xdf5b4e;
-    pub const FOCUSED_CHAR2: u32 = 0xb35349;
-    pub const STRONG_CHAR1: u32 = 0x00f080;
-    pub const STRONG_CHAR2: u32 = 0x007840;
-    pub const NORMAL_CHAR1: u32 = 0xd2ca9c;
-    pub const NORMAL_CHAR2: u32 = 0x9d9784;
-}
-
 impl Bar {
-    fn new(conn: Arc<RustConnection>) -> Result<Self> {
-        let root = conn.setup().roots[0].root;
+    fn new(conn: Arc<XConnection>, screen_num: usize, style: Style) -> Result<Self> {
+        let root = conn.setup().roots[screen_num].root;
 
         let wid = conn.generate_id()?;
         let depth = x11rb::COPY_DEPTH_FROM_PARENT;
@@ -196,15 +537,33 @@ impl Bar {
         conn.create_window(depth, wid, root, -1, -1, 1, 1, 0, class, visual, &aux)?;
         debug!("window={} created", wid);
 
+        // Let other EWMH-compliant programs know the bar is a dock.
+        let atom = AtomCollection::new(&*conn)?.reply()?;
+        conn.change_property32(
+            PropMode::REPLACE,
+            wid,
+            atom._NET_WM_WINDOW_TYPE,
+            AtomEnum::ATOM,
+            &[atom._NET_WM_WINDOW_TYPE_DOCK],
+        )?;
+
         let gc = conn.generate_id()?;
-        let aux = CreateGCAux::new();
-        conn.create_gc(gc, wid, &aux)?;
+        {
+            let font = context::open_font(&*conn, 1)?;
+
+            let aux = CreateGCAux::new().font(font);
+            conn.create_gc(gc, wid, &aux)?;
+
+            conn.close_font(font)?;
+        }
 
         conn.flush()?;
 
         Ok(Self {
             conn,
+            atom,
             wid,
+            screen_num,
             gc,
             mon: Rectangle {
                 x: -1,
@@ -212,57 +571,106 @@ impl Bar {
                 width: 1,
                 height: 1,
             },
+            scale: 1,
             content: Content::default(),
+            visible: false,
+            style,
+            buffer: None,
+            event_thread: None,
         })
     }
 
-    fn handle_request(&mut self, req: Request) -> Response {
+    fn handle_request(&mut self, req: Request) -> Result<Response> {
         match req {
-            Request::GetWindowId => Response::WindowId(self.wid),
-            Request::Configure { geometry } => self
-                .configure(geometry)
-                .map(|_| Response::Success)
-                .unwrap_or_else(|e| Response::Error {
-                    reason: e.to_string(),
-                }),
-            Request::UpdateContent { content } => self
-                .update_content(content)
-                .map(|_| Response::Success)
-                .unwrap_or_else(|e| Response::Error {
-                    reason: e.to_string(),
-                }),
-            Request::Show => {
-                self.show()
-                    .map(|_| Response::Success)
-                    .unwrap_or_else(|e| Response::Error {
-                        reason: e.to_string(),
-                    })
-            }
-            Request::Hide => {
-                self.hide()
-                    .map(|_| Response::Success)
-                    .unwrap_or_else(|e| Response::Error {
-                        reason: e.to_string(),
-                    })
-            }
+            Request::GetWindowId => return Ok(Response::WindowId(self.wid)),
+            Request::Configure { geometry } => self.configure(geometry)?,
+            Request::UpdateContent { content } => self.update_content(*content)?,
+            Request::Show => self.show()?,
+            Request::Hide => self.hide()?,
+            Request::Raise => self.raise()?,
+            Request::Shutdown => unreachable!(),
         }
+        Ok(Response::Success)
     }
 
     fn configure(&mut self, mon: Rectangle) -> Result<()> {
         debug!("configure {:?}", mon);
         self.mon = mon;
+        let scale = scale_of(mon);
+        if scale != self.scale {
+            let font = context::open_font(&*self.conn, scale)?;
+            self.conn
+                .change_gc(self.gc, &ChangeGCAux::new().font(font))?;
+            self.conn.close_font(font)?;
+            self.scale = scale;
+        }
+
         let aux = ConfigureWindowAux::new()
             .x(mon.x as i32)
             .y(mon.y as i32)
             .width(mon.width as u32)
-            .height(16) // FIXME
+            .height(mon.height as u32)
             .stack_mode(StackMode::BELOW); // Bottom of the stack
         self.conn.configure_window(self.wid, &aux)?;
+        self.set_strut(mon)?;
+        if self.style.double_buffer {
+            self.resize_buffer()?;
+        }
+        if let Some(radius) = self.style.corner_radius {
+            let radius = radius * self.scale;
+            shape::round_corners(&*self.conn, self.wid, mon.width, mon.height, 0, radius)?;
+        }
         self.conn.flush()?;
         self.draw()?;
         Ok(())
     }
 
+    /// Replaces the buffer with a new pixmap of the size of the bar.
+    fn resize_buffer(&mut self) -> Result<()> {
+        if let Some(buffer) = self.buffer.take() {
+            self.conn.free_pixmap(buffer)?;
+        }
+        let buffer = self.conn.generate_id()?;
+        let depth = self.conn.setup().roots[self.screen_num].root_depth;
+        let (width, height) = (self.mon.width.max(1), self.mon.height.max(1));
+        self.conn
+            .create_pixmap(depth, buffer, self.wid, width, height)?;
+        self.buffer = Some(buffer);
+        Ok(())
+    }
+
+    /// Copies the buffer to the window if double-buffered, and flushes the drawing.
+    fn present(&self) -> Result<()> {
+        if let Some(buffer) = self.buffer {
+            let (width, height) = (self.mon.width, self.mon.height);
+            self.conn
+                .copy_area(buffer, self.wid, self.gc, 0, 0, 0, 0, width, height)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Reserves the space of the bar with _NET_WM_STRUT(_PARTIAL).
+    fn set_strut(&self, geometry: Rectangle) -> Result<()> {
+        let partial = strut_partial(geometry);
+        let atom = &self.atom;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.wid,
+            atom._NET_WM_STRUT_PARTIAL,
+            AtomEnum::CARDINAL,
+            &partial,
+        )?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.wid,
+            atom._NET_WM_STRUT,
+            AtomEnum::CARDINAL,
+            &partial[..4],
+        )?;
+        Ok(())
+    }
+
     fn update_content(&mut self, content: Content) -> Result<()> {
         self.content = content;
         self.draw()?;
@@ -270,6 +678,7 @@ impl Bar {
     }
 
     fn show(&mut self) -> Result<()> {
+        self.visible = true;
         self.conn.map_window(self.wid)?;
         self.conn.flush()?;
         self.draw()?;
@@ -277,19 +686,51 @@ impl Bar {
     }
 
     fn hide(&mut self) -> Result<()> {
+        self.visible = false;
         self.conn.unmap_window(self.wid)?;
         self.conn.flush()?;
         Ok(())
     }
 
+    fn raise(&mut self) -> Result<()> {
+        let aux = ConfigureWindowAux::new().stack_mode(StackMode::ABOVE);
+        self.conn.configure_window(self.wid, &aux)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Redraws the clock, unless the bar is hidden.
+    fn tick(&mut self) -> Result<()> {
+        if self.visible {
+            self.draw()?;
+        }
+        Ok(())
+    }
+
     fn draw(&mut self) -> Result<()> {
         debug!("draw: mon={:?}, content={:?}", self.mon, self.content);
         let w = self.mon.width as i16;
+        let scale = self.scale;
+        let s = scale as i16;
+        let char_w = context::char_width(scale) as i16;
+        let baseline = 12 * s;
 
-        let bar = self.wid;
+        // Draw into the buffer if any, which is copied to the window at the end.
+        let bar = self.buffer.unwrap_or(self.wid);
         let gc = self.gc;
 
-        let color_bg = color::MAIN;
+        let focused = self.content.focused;
+        let colors = self.content.colors;
+        let (color_bg, color_light, color_shadow) = if focused {
+            (colors.main, colors.light, colors.shadow)
+        } else {
+            (colors.dim_main, colors.dim_light, colors.dim_shadow)
+        };
+        let (normal_char1, normal_char2) = if focused {
+            colors.normal_char
+        } else {
+            colors.dim_char
+        };
 
         // Clear background
         let aux = ChangeGCAux::new().foreground(color_bg).background(color_bg);
@@ -299,64 +740,218 @@ impl Bar {
             x: 0,
             y: 0,
             width: w as u16,
-            height: 16, // FIXME
+            height: self.mon.height,
         };
         self.conn.poly_fill_rectangle(bar, gc, &[rect])?;
 
         // Lines
-        let aux = ChangeGCAux::new().foreground(color::LIGHT);
+        let aux = ChangeGCAux::new().foreground(color_light);
         self.conn.change_gc(gc, &aux)?;
 
-        let p1 = Point { x: 0, y: 14 };
+        let h = self.mon.height as i16;
+        let p1 = Point { x: 0, y: h - 2 };
         let p2 = Point { x: 0, y: 0 };
         let p3 = Point { x: w - 2, y: 0 };
         self.conn
             .poly_line(CoordMode::ORIGIN, bar, gc, &[p1, p2, p3])?;
 
-        let aux = ChangeGCAux::new().foreground(color::SHADOW);
+        let aux = ChangeGCAux::new().foreground(color_shadow);
         self.conn.change_gc(gc, &aux)?;
 
-        let p1 = Point { x: 1, y: 15 };
-        let p2 = Point { x: w - 1, y: 15 };
+        let p1 = Point { x: 1, y: h - 1 };
+        let p2 = Point { x: w - 1, y: h - 1 };
         let p3 = Point { x: w - 1, y: 1 };
         self.conn
             .poly_line(CoordMode::ORIGIN, bar, gc, &[p1, p2, p3])?;
 
         // Digits
-        let offset_x = 2;
-        let offset_y = 5;
+        let offset_x = 2 * s;
+        let offset_y = 5 * s;
         let cont = &self.content;
         for i in 0..cont.max_screen {
             let color1;
             let color2;
             if i == cont.current_screen {
                 if cont.focused {
-                    color1 = color::FOCUSED_CHAR1;
-                    color2 = color::FOCUSED_CHAR2;
+                    (color1, color2) = colors.focused_char;
                 } else {
-                    color1 = color::STRONG_CHAR1;
-                    color2 = color::STRONG_CHAR2;
+                    (color1, color2) = colors.strong_char;
                 }
             } else {
-                color1 = color::NORMAL_CHAR1;
-                color2 = color::NORMAL_CHAR2;
+                color1 = normal_char1;
+                color2 = normal_char2;
             }
 
-            let x = offset_x + (i * 12) as i16;
+            let x = offset_x + (i * 12) as i16 * s;
+            let y = offset_y;
+            let digit = b"1234567890"[i]; // draw digit in 1-indexed, the 10th as 0
+            draw_digit(&*self.conn, bar, gc, x, y, scale, digit, color1, color2)?;
+        }
+
+        // Focus indicator: a small square next to the digits
+        if focused {
+            let x = offset_x + ((cont.max_screen * 12) as i16 + 4) * s;
             let y = offset_y;
-            let digit = b'1' + (i as u8); // draw digit in 1-indexed
-            draw_digit(&*self.conn, bar, gc, x, y, digit, color1, color2)?;
+
+            let aux = ChangeGCAux::new().foreground(colors.focused_char.1);
+            self.conn.change_gc(gc, &aux)?;
+            let outer = Rectangle {
+                x,
+                y,
+                width: 6 * scale,
+                height: 6 * scale,
+            };
+            self.conn.poly_fill_rectangle(bar, gc, &[outer])?;
+
+            let aux = ChangeGCAux::new().foreground(colors.focused_char.0);
+            self.conn.change_gc(gc, &aux)?;
+            let inner = Rectangle {
+                x: x + s,
+                y: y + s,
+                width: 4 * scale,
+                height: 4 * scale,
+            };
+            self.conn.poly_fill_rectangle(bar, gc, &[inner])?;
+        }
+
+        // The number of windows, e.g. "[3]"
+        {
+            let mut x = offset_x + ((cont.max_screen * 12) as i16 + 16) * s;
+            let y = offset_y;
+            let count = format!("[{}]", cont.window_count);
+            for &b in count.as_bytes() {
+                draw_digit(
+                    &*self.conn,
+                    bar,
+                    gc,
+                    x,
+                    y,
+                    scale,
+                    b,
+                    normal_char1,
+                    normal_char2,
+                )?;
+                x += 8 * s;
+            }
+
+            // Error indicator: a red square after the number
+            if cont.error {
+                let aux = ChangeGCAux::new().foreground(colors.error.1);
+                self.conn.change_gc(gc, &aux)?;
+                let outer = Rectangle {
+                    x: x + 4 * s,
+                    y,
+                    width: 6 * scale,
+                    height: 6 * scale,
+                };
+                self.conn.poly_fill_rectangle(bar, gc, &[outer])?;
+
+                let aux = ChangeGCAux::new().foreground(colors.error.0);
+                self.conn.change_gc(gc, &aux)?;
+                let inner = Rectangle {
+                    x: x + 5 * s,
+                    y: y + s,
+                    width: 4 * scale,
+                    height: 4 * scale,
+                };
+                self.conn.poly_fill_rectangle(bar, gc, &[inner])?;
+            }
+
+            let mut x = x + 16 * s;
+            let aux = ChangeGCAux::new()
+                .foreground(normal_char1)
+                .background(color_bg);
+            self.conn.change_gc(gc, &aux)?;
+
+            // the page of the layout, e.g. "page 2/3"
+            if let Some((page, pages)) = cont.page {
+                let text = format!("page {}/{}", page + 1, pages);
+                self.conn
+                    .image_text8(bar, gc, x, baseline, text.as_bytes())?;
+                x += char_w * text.len() as i16 + 16 * s;
+            }
+
+            // the position of the focused window in the stack, e.g. "3/7"
+            if let Some((index, count)) = cont.position {
+                let text = format!("{}/{}", index + 1, count);
+                self.conn
+                    .image_text8(bar, gc, x, baseline, text.as_bytes())?;
+                x += char_w * text.len() as i16 + 16 * s;
+            }
+
+            // media level, right to the error indicator or the page
+            if let Some(level) = &cont.level {
+                // Core fonts cannot show non-ASCII characters.
+                let text: Vec<u8> = level
+                    .chars()
+                    .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+                    .take(32)
+                    .collect();
+                self.conn.image_text8(bar, gc, x, baseline, &text)?;
+            }
+        }
+
+        // the titles of the windows, between the left items and the right ones
+        for ((x, width), task) in task_spans(cont, w as u16, scale)
+            .into_iter()
+            .zip(&cont.tasks)
+        {
+            let (bg, fg) = if task.focused {
+                (color_shadow, normal_char1)
+            } else {
+                (color_bg, normal_char2)
+            };
+            let aux = ChangeGCAux::new().foreground(bg);
+            self.conn.change_gc(gc, &aux)?;
+            let rect = Rectangle {
+                x,
+                y: 2 * s,
+                width: width.saturating_sub(4 * scale),
+                height: 12 * scale,
+            };
+            self.conn.poly_fill_rectangle(bar, gc, &[rect])?;
+
+            let max_chars = (width.saturating_sub(8 * scale) / char_w as u16).min(255) as usize;
+            let text = abbreviate(&task.title, max_chars);
+            let aux = ChangeGCAux::new().foreground(fg).background(bg);
+            self.conn.change_gc(gc, &aux)?;
+            self.conn.image_text8(bar, gc, x + 2 * s, baseline, &text)?;
+        }
+
+        // keyboard layout, left to the clock
+        let mut x = w - (136 + 8) * s;
+        if !cont.keyboard_layout.is_empty() {
+            let text = cont.keyboard_layout.as_bytes();
+            x -= char_w * text.len() as i16;
+            let aux = ChangeGCAux::new()
+                .foreground(normal_char1)
+                .background(color_bg);
+            self.conn.change_gc(gc, &aux)?;
+            self.conn.image_text8(bar, gc, x, baseline, text)?;
+            x -= 16 * s;
+        }
+
+        // the layout of the screen, left to the keyboard layout
+        {
+            let text = format!("[{}]", cont.layout_name);
+            x -= char_w * text.len() as i16;
+            let aux = ChangeGCAux::new()
+                .foreground(normal_char2)
+                .background(color_bg);
+            self.conn.change_gc(gc, &aux)?;
+            self.conn
+                .image_text8(bar, gc, x, baseline, text.as_bytes())?;
         }
 
         // clock
         use chrono::prelude::*;
-        let (color1, color2) = (color::NORMAL_CHAR1, color::NORMAL_CHAR2);
+        let (color1, color2) = (normal_char1, normal_char2);
         let now = chrono::Local::now();
         let date = now.date();
         let time = now.time();
 
-        let mut x = w - 136;
-        let y = 5;
+        let (mut x, _) = clock_range(w as u16, scale);
+        let y = 5 * s;
 
         let date_time = format!(
             "{:04}/{:02}/{:02} {:02}:{:02}",
@@ -367,35 +962,42 @@ impl Bar {
             time.minute()
         );
         for &b in date_time.as_bytes() {
-            draw_digit(&*self.conn, bar, gc, x, y, b, color1, color2)?;
-            x += 8;
+            draw_digit(&*self.conn, bar, gc, x, y, scale, b, color1, color2)?;
+            x += 8 * s;
         }
 
-        self.conn.flush()?;
+        self.present()?;
         Ok(())
     }
 }
 
 impl EventHandlerMethods for Bar {
     fn on_expose(&mut self, _e: ExposeEvent) -> Result<()> {
-        self.draw()?;
+        // The buffer still has the last content.
+        if self.buffer.is_some() {
+            self.present()?;
+        } else {
+            self.draw()?;
+        }
         Ok(())
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_digit<C: Connection>(
     conn: &C,
     wid: Drawable,
     gc: Gcontext,
     x: i16,
     y: i16,
+    scale: u16,
     ascii_digit: u8,
     color1: u32,
     color2: u32,
 ) -> Result<()> {
-    const DIGITS: [[u32; 6 * 6]; 10 + 3] = include!("digits.txt");
+    const DIGITS: [[u32; 6 * 6]; 10 + 5] = include!("digits.txt");
 
-    let digit = if (b'0'..=b'9').contains(&ascii_digit) {
+    let digit = if ascii_digit.is_ascii_digit() {
         ascii_digit - b'0'
     } else if ascii_digit == b':' {
         10
@@ -403,6 +1005,10 @@ fn draw_digit<C: Connection>(
         11
     } else if ascii_digit == b' ' {
         12
+    } else if ascii_digit == b'[' {
+        13
+    } else if ascii_digit == b']' {
+        14
     } else {
         panic!(
             "unsupported char: {}",
@@ -410,32 +1016,153 @@ fn draw_digit<C: Connection>(
         );
     };
 
+    // Each dot of the glyph is a square of `scale` pixels.
     let mut ps1 = Vec::new();
     let mut ps2 = Vec::new();
     for (p, &e) in DIGITS[digit as usize].iter().enumerate() {
         let (yi, xi) = (p / 6, p % 6);
-        let point = Point {
-            x: x + xi as i16,
-            y: y + yi as i16,
+        let dot = Rectangle {
+            x: x + (xi as u16 * scale) as i16,
+            y: y + (yi as u16 * scale) as i16,
+            width: scale,
+            height: scale,
         };
         if e == 1 {
-            ps1.push(point);
+            ps1.push(dot);
         } else if e == 2 {
-            ps2.push(point);
+            ps2.push(dot);
         }
     }
 
     if !ps1.is_empty() {
         let aux = ChangeGCAux::new().foreground(color1);
         conn.change_gc(gc, &aux)?;
-        conn.poly_point(CoordMode::ORIGIN, wid, gc, &ps1)?;
+        conn.poly_fill_rectangle(wid, gc, &ps1)?;
     }
 
     if !ps2.is_empty() {
         let aux = ChangeGCAux::new().foreground(color2);
         conn.change_gc(gc, &aux)?;
-        conn.poly_point(CoordMode::ORIGIN, wid, gc, &ps2)?;
+        conn.poly_fill_rectangle(wid, gc, &ps2)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    #[test]
+    fn shutdown_terminates_the_thread() {
+        let ctx = mock::context(&[(0, 0, 1920, 1080)]);
+        let mut bar = BarHandle::new(&ctx, 0);
+        bar.show().unwrap();
+        assert!(bar.get_window_id().is_ok());
+
+        bar.shutdown();
+        assert!(bar.thread.is_none());
+        assert!(bar.tx.send(Request::Show).is_err());
+
+        // Requests after the shutdown are ignored.
+        bar.hide().unwrap();
+        assert!(bar.get_window_id().is_err());
+    }
+
+    #[test]
+    fn failures_are_forgotten_after_running_fine() {
+        let ctx = mock::context(&[(0, 0, 1920, 1080)]);
+        let mut bar = BarHandle::new(&ctx, 0);
+        let fail = |bar: &mut BarHandle| {
+            bar.try_send_recv(Request::Shutdown).unwrap();
+            bar.recover();
+        };
+
+        // Failing again soon after the last restart counts up to the limit.
+        bar.restarts = MAX_RESTARTS - 1;
+        bar.restarted_at = Some(Instant::now());
+        fail(&mut bar);
+        assert_eq!(bar.restarts, MAX_RESTARTS);
+        assert!(!bar.disabled);
+
+        bar.restarted_at = Instant::now().checked_sub(HEALTHY_DURATION);
+        fail(&mut bar);
+        assert_eq!(bar.restarts, 1);
+        assert!(!bar.disabled);
+        assert!(bar.get_window_id().is_ok());
+    }
+
+    #[test]
+    fn clicked_task_is_found() {
+        let ctx = mock::context(&[(0, 0, 1920, 1080)]);
+        let mut bar = BarHandle::new(&ctx, 0);
+        let geometry = Rectangle {
+            x: 100,
+            y: 0,
+            width: 1820,
+            height: BAR_HEIGHT,
+        };
+        bar.configure(geometry).unwrap();
+        let task = |frame, focused| Task {
+            frame,
+            title: "xterm".to_owned(),
+            focused,
+        };
+        let content = Content {
+            max_screen: 10,
+            layout_name: "horizontal",
+            tasks: vec![task(1, false), task(2, true)],
+            ..Default::default()
+        };
+        let spans = task_spans(&content, geometry.width, 1);
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[1].0, spans[0].0 + MAX_TASK_WIDTH as i16);
+        bar.update_content(content).unwrap();
+
+        let wid = bar.get_window_id().unwrap();
+        let x = geometry.x + spans[1].0 + 4;
+        assert_eq!(bar.task_at(wid, x), Some(2));
+        assert_eq!(bar.task_at(wid, geometry.x), None);
+        assert_eq!(bar.task_at(wid + 1, x), None);
+    }
+
+    #[test]
+    fn strut_reserves_the_top_of_the_monitor() {
+        let geometry = Rectangle {
+            x: 1920,
+            y: 200,
+            width: 1280,
+            height: 16,
+        };
+        let strut = strut_partial(geometry);
+        assert_eq!(strut[..4], [0, 0, 216, 0]);
+        assert_eq!(strut[8..10], [1920, 3199]);
+    }
+
+    #[test]
+    fn clicked_clock_is_found() {
+        let ctx = mock::context(&[(0, 0, 1920, 1080)]);
+        let mut bar = BarHandle::new(&ctx, 0);
+        let geometry = Rectangle {
+            x: 100,
+            y: 0,
+            width: 1820,
+            height: BAR_HEIGHT,
+        };
+        bar.configure(geometry).unwrap();
+
+        let wid = bar.get_window_id().unwrap();
+        assert!(bar.clock_at(wid, 1920 - 8 - 4));
+        assert!(!bar.clock_at(wid, 1920 - 4));
+        assert!(!bar.clock_at(wid, geometry.x + 4));
+        assert!(!bar.clock_at(wid + 1, 1920 - 8 - 4));
+    }
+
+    #[test]
+    fn long_titles_are_abbreviated() {
+        assert_eq!(abbreviate("xterm", 8), b"xterm");
+        assert_eq!(abbreviate("a long title", 8), b"a long..");
+        assert_eq!(abbreviate("caf\u{e9}", 8), b"caf?");
+    }
+}
@@ -0,0 +1,249 @@
+use log::{debug, warn};
+
+use x11rb::connection::{Connection as _, RequestConnection as _};
+use x11rb::protocol::xfixes::{self, Barrier, ConnectionExt as _};
+use x11rb::protocol::xinput::{self, BarrierHitEvent, ConnectionExt as _};
+use x11rb::protocol::xproto::Rectangle;
+
+use crate::context::Context;
+use crate::error::Result;
+
+/// A segment of the boundary between two monitors, from (x1, y1) to (x2, y2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub x1: i16,
+    pub y1: i16,
+    pub x2: i16,
+    pub y2: i16,
+}
+
+/// Returns the overlap of the ranges [a1, a2) and [b1, b2), if any.
+fn overlap(a1: i32, a2: i32, b1: i32, b2: i32) -> Option<(i32, i32)> {
+    let (lo, hi) = (a1.max(b1), a2.min(b2));
+    if lo < hi {
+        Some((lo, hi))
+    } else {
+        None
+    }
+}
+
+/// Returns the boundaries the monitors share with each other.
+pub fn shared_edges(monitors: &[Rectangle]) -> Vec<Edge> {
+    let mut edges = Vec::new();
+    for a in monitors {
+        let (a_right, a_bottom) = (a.x as i32 + a.width as i32, a.y as i32 + a.height as i32);
+        for b in monitors {
+            let (b_right, b_bottom) = (b.x as i32 + b.width as i32, b.y as i32 + b.height as i32);
+            // `b` on the right of `a`
+            if a_right == b.x as i32 {
+                if let Some((y1, y2)) = overlap(a.y as i32, a_bottom, b.y as i32, b_bottom) {
+                    let x = b.x;
+                    edges.push(Edge {
+                        x1: x,
+                        y1: y1 as i16,
+                        x2: x,
+                        y2: (y2 - 1) as i16,
+                    });
+                }
+            }
+            // `b` below `a`
+            if a_bottom == b.y as i32 {
+                if let Some((x1, x2)) = overlap(a.x as i32, a_right, b.x as i32, b_right) {
+                    let y = b.y;
+                    edges.push(Edge {
+                        x1: x1 as i16,
+                        y1: y,
+                        x2: (x2 - 1) as i16,
+                        y2: y,
+                    });
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// Holds the pointer at the boundaries between monitors until it is pushed
+/// by `edge_resistance.threshold` pixels, using the pointer barriers of XFixes.
+pub struct EdgeResistance {
+    ctx: Context,
+    available: bool,
+    barriers: Vec<Barrier>,
+    /// The barrier the pointer is pushed against, the id of the push, and how far it has gone.
+    push: Option<(Barrier, u32, f64)>,
+}
+
+impl EdgeResistance {
+    /// Selects the barrier events if edge resistance is enabled and
+    /// XFixes 5.0 and XInput 2.3 are available.
+    pub fn new(ctx: Context) -> Result<Self> {
+        let mut resistance = Self {
+            ctx,
+            available: false,
+            barriers: Vec::new(),
+            push: None,
+        };
+        if resistance.ctx.config.edge_resistance.enabled {
+            resistance.available = resistance.init()?;
+        }
+        Ok(resistance)
+    }
+
+    fn init(&self) -> Result<bool> {
+        let conn = &self.ctx.conn;
+        for name in [xfixes::X11_EXTENSION_NAME, xinput::X11_EXTENSION_NAME] {
+            if conn.extension_information(name)?.is_none() {
+                warn!("{} is not available, edge resistance is disabled", name);
+                return Ok(false);
+            }
+        }
+
+        let xfixes = conn.xfixes_query_version(5, 0)?.reply()?;
+        let xi = conn.xinput_xi_query_version(2, 3)?.reply()?;
+        if xfixes.major_version < 5 || (xi.major_version, xi.minor_version) < (2, 3) {
+            warn!(
+                "pointer barriers are not supported (XFixes {}, XInput {}.{})",
+                xfixes.major_version, xi.major_version, xi.minor_version
+            );
+            return Ok(false);
+        }
+
+        let mask = xinput::EventMask {
+            deviceid: xinput::Device::ALL_MASTER.into(),
+            mask: vec![xinput::XIEventMask::BARRIER_HIT.into()],
+        };
+        conn.xinput_xi_select_events(self.ctx.root, &[mask])?;
+        Ok(true)
+    }
+
+    /// Puts barriers on the boundaries of the monitors, replacing the old ones.
+    pub fn update(&mut self, monitors: &[Rectangle]) -> Result<()> {
+        if !self.available {
+            return Ok(());
+        }
+        self.clear()?;
+
+        for edge in shared_edges(monitors) {
+            debug!("pointer barrier: {:?}", edge);
+            let barrier = self.ctx.conn.generate_id()?;
+            // No directions are allowed until the pointer is released by `on_hit`.
+            self.ctx.conn.xfixes_create_pointer_barrier(
+                barrier,
+                self.ctx.root,
+                edge.x1 as u16,
+                edge.y1 as u16,
+                edge.x2 as u16,
+                edge.y2 as u16,
+                0u32,
+                &[],
+            )?;
+            self.barriers.push(barrier);
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        for barrier in self.barriers.drain(..) {
+            self.ctx.conn.xfixes_delete_pointer_barrier(barrier)?;
+        }
+        self.push = None;
+        Ok(())
+    }
+
+    /// Adds the distance of a push against the barrier.
+    /// Returns true once it reaches the threshold and the pointer should pass.
+    fn push(&mut self, barrier: Barrier, eventid: u32, distance: f64) -> bool {
+        let total = match self.push {
+            Some((b, id, total)) if b == barrier && id == eventid => total + distance,
+            _ => distance,
+        };
+        if total >= self.ctx.config.edge_resistance.threshold as f64 {
+            self.push = None;
+            true
+        } else {
+            self.push = Some((barrier, eventid, total));
+            false
+        }
+    }
+
+    pub fn on_hit(&mut self, e: BarrierHitEvent) -> Result<()> {
+        let fixed = |v: xinput::Fp3232| v.integral as f64 + v.frac as f64 / 2f64.powi(32);
+        let distance = fixed(e.dx).abs() + fixed(e.dy).abs();
+        if self.push(e.barrier, e.eventid, distance) {
+            let info = xinput::BarrierReleasePointerInfo {
+                deviceid: e.deviceid,
+                barrier: e.barrier,
+                eventid: e.eventid,
+            };
+            self.ctx.conn.xinput_xi_barrier_release_pointer(&[info])?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EdgeResistance {
+    fn drop(&mut self) {
+        let _ = self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mock;
+
+    fn rect(x: i16, y: i16, width: u16, height: u16) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn edges_are_shared_by_adjacent_monitors() {
+        // A laptop below the right half of a wide monitor, and one far away.
+        let monitors = [
+            rect(0, 0, 2560, 1440),
+            rect(1280, 1440, 1920, 1080),
+            rect(8000, 0, 1920, 1080),
+        ];
+        let edges = shared_edges(&monitors);
+        assert_eq!(
+            edges,
+            vec![Edge {
+                x1: 1280,
+                y1: 1440,
+                x2: 2559,
+                y2: 1440
+            }]
+        );
+
+        let side_by_side = [rect(0, 0, 1920, 1080), rect(1920, 200, 1280, 1024)];
+        assert_eq!(
+            shared_edges(&side_by_side),
+            vec![Edge {
+                x1: 1920,
+                y1: 200,
+                x2: 1920,
+                y2: 1079
+            }]
+        );
+    }
+
+    #[test]
+    fn pointer_passes_after_the_threshold() {
+        let mut config = Config::default();
+        config.edge_resistance.threshold = 50;
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut resistance = EdgeResistance::new(ctx).unwrap();
+
+        assert!(!resistance.push(1, 1, 30.0));
+        assert!(resistance.push(1, 1, 30.0));
+        // Another push starts over.
+        assert!(!resistance.push(1, 2, 30.0));
+        assert!(!resistance.push(2, 2, 30.0));
+    }
+}
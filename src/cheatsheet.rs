@@ -0,0 +1,138 @@
+use x11rb::connection::Connection as _;
+use x11rb::protocol::xproto::{ConnectionExt as _, ModMask};
+
+use crate::context::Context;
+use crate::error::Result;
+use crate::{Command, KeybindAction};
+
+const MODIFIERS: [(ModMask, &str); 4] = [
+    (ModMask::M4, "Super"),
+    (ModMask::CONTROL, "Ctrl"),
+    (ModMask::M1, "Alt"),
+    (ModMask::SHIFT, "Shift"),
+];
+
+/// Returns the name of a keysym, or None for the ones not listed.
+fn keysym_name(sym: u32) -> Option<String> {
+    let name = match sym {
+        0x20 => "Space",
+        0x21..=0x7e => return Some((sym as u8 as char).to_ascii_uppercase().to_string()),
+        0xff08 => "BackSpace",
+        0xff09 => "Tab",
+        0xff0d => "Enter",
+        0xff1b => "Escape",
+        0xff50 => "Home",
+        0xff51 => "Left",
+        0xff52 => "Up",
+        0xff53 => "Right",
+        0xff54 => "Down",
+        0xff55 => "PageUp",
+        0xff56 => "PageDown",
+        0xff57 => "End",
+        0xff61 => "Print",
+        0xffbe..=0xffc9 => return Some(format!("F{}", sym - 0xffbe + 1)),
+        0xffeb => "Super_L",
+        0xffec => "Super_R",
+        0xffff => "Delete",
+        0x1008ff02 => "XF86MonBrightnessUp",
+        0x1008ff03 => "XF86MonBrightnessDown",
+        0x1008ff11 => "XF86AudioLowerVolume",
+        0x1008ff12 => "XF86AudioMute",
+        0x1008ff13 => "XF86AudioRaiseVolume",
+        _ => return None,
+    };
+    Some(name.to_owned())
+}
+
+/// Formats the keybindings as lines like "Super+Shift+Q    Quit", grouped by the modifiers.
+/// `keysym_of` returns the keysym of a keycode (without modifiers).
+fn format_lines<'a, I, F>(keybind: I, keysym_of: F) -> String
+where
+    I: Iterator<Item = (&'a (KeybindAction, u16, u8), &'a Command)>,
+    F: Fn(u8) -> Option<u32>,
+{
+    let mut entries: Vec<_> = keybind
+        .map(|(&(action, modmask, keycode), cmd)| {
+            let mut keys: Vec<String> = MODIFIERS
+                .iter()
+                .filter(|(m, _)| modmask & u16::from(*m) != 0)
+                .map(|(_, name)| (*name).to_owned())
+                .collect();
+            let key = keysym_of(keycode)
+                .and_then(keysym_name)
+                .unwrap_or_else(|| format!("<{}>", keycode));
+            keys.push(key);
+
+            let mut keys = keys.join("+");
+            if action == KeybindAction::Release {
+                keys.push_str(" (release)");
+            }
+            (
+                (modmask, keycode, action == KeybindAction::Release),
+                keys,
+                cmd,
+            )
+        })
+        .collect();
+    entries.sort_by_key(|(order, _, _)| *order);
+
+    let width = entries
+        .iter()
+        .map(|(_, keys, _)| keys.len())
+        .max()
+        .unwrap_or(0);
+    entries
+        .iter()
+        .map(|(_, keys, cmd)| format!("{:width$}    {:?}\n", keys, cmd, width = width))
+        .collect()
+}
+
+/// Returns the keybindings of the config in the lines to be shown by a dmenu-like command.
+pub fn lines(ctx: &Context) -> Result<String> {
+    let setup = ctx.conn.setup();
+    let (min, max) = (setup.min_keycode, setup.max_keycode);
+    let mapping = ctx.conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+    let per_keycode = mapping.keysyms_per_keycode as usize;
+
+    let keysym_of = |keycode: u8| {
+        let index = (keycode.checked_sub(min)? as usize) * per_keycode;
+        mapping
+            .keysyms
+            .get(index)
+            .copied()
+            .filter(|&sym| sym != x11rb::NO_SYMBOL)
+    };
+    Ok(format_lines(ctx.config.keybind_iter(), keysym_of))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LayoutCommand;
+    use std::collections::HashMap;
+
+    #[test]
+    fn keybindings_are_formatted_by_modifiers() {
+        let super_: u16 = ModMask::M4.into();
+        let shift: u16 = ModMask::SHIFT.into();
+        let press = KeybindAction::Press;
+        let mut keybind = HashMap::new();
+        keybind.insert((press, super_ | shift, 24), Command::Quit);
+        keybind.insert((press, super_, 65), Command::Layout(LayoutCommand::Next));
+        keybind.insert((press, 0, 123), Command::VolumeUp);
+        keybind.insert((KeybindAction::Release, super_, 133), Command::HideBorder);
+
+        let keysyms: HashMap<u8, u32> = [(24, 0x71), (65, 0x20), (133, 0xffeb)].into();
+        let lines = format_lines(keybind.iter(), |keycode| keysyms.get(&keycode).copied());
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "<123>                      VolumeUp",
+                "Super+Space                Layout(Next)",
+                "Super+Super_L (release)    HideBorder",
+                "Super+Shift+Q              Quit",
+            ]
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Commands run by the keybindings, grouped by what they act on.
+//!
+//! In the config, a grouped command is written as `{Focus: Next}` or
+//! `{Screen: {Switch: 3}}`. The flat names of the older versions
+//! (e.g. `FocusNext` or `{Screen: 3}`) are still accepted.
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::Direction;
+
+/// Where `WindowCommand::FloatSnap` puts the focused window on its monitor.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SnapPosition {
+    // halves
+    Left,
+    Right,
+    Top,
+    Bottom,
+    // quarters
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    /// Keeps the size of the window.
+    Center,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "legacy::Command")]
+pub enum Command {
+    Quit,
+    Restart,
+    ShowBorder,
+    HideBorder,
+    ShowWindowList,
+    WindowMenu,
+    /// Shows the keybindings of the config with `keybind_menu`.
+    ShowKeybindings,
+    /// Logs the properties of the focused window and shows its class, e.g. to write rules.
+    DebugWindowInfo,
+    /// Shows the metrics of the main loop every second, or stops it.
+    /// They are always published in _DAILY_METRICS of the root window.
+    ToggleMetricsHud,
+    // run the commands of `media` in the config
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    BrightnessUp,
+    BrightnessDown,
+    /// Switches to the theme of the name in `themes` of the config.
+    SetTheme(String),
+    Spawn(String),
+    /// Runs the command registered with [`Extensions::command`](crate::Extensions::command).
+    Extension(String),
+    Focus(FocusCommand),
+    Window(WindowCommand),
+    Screen(ScreenCommand),
+    Pointer(PointerCommand),
+    Layout(LayoutCommand),
+}
+
+/// Moves the input focus.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FocusCommand {
+    Next,
+    Prev,
+    NextMonitor,
+    PrevMonitor,
+    /// Focuses the monitor next to the focused one in the direction, e.g. `{Monitor: Left}`.
+    Monitor(Direction),
+    Urgent,
+    /// Cycles the focus within the group of the focused window, e.g. the stack.
+    NextInGroup,
+    PrevInGroup,
+    /// Focuses the main window of the layout.
+    Master,
+    /// Focuses the first window stacked beside the main one.
+    Stack,
+}
+
+/// Acts on the focused window, unless noted otherwise.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Deserialize, Serialize)]
+pub enum WindowCommand {
+    Close,
+    Sink,
+    /// Pins the focused tiled window where it is, or lets the layout place it again.
+    TogglePin,
+    /// Makes the focused window move along to the screen switched to on its monitor, or stop it.
+    ToggleFollow,
+    /// Moves all the floating windows to the center of their monitors, e.g. those lost off-screen.
+    Rescue,
+    /// Floats the focused window (if not yet) and moves it to the part of the monitor.
+    FloatSnap(SnapPosition),
+    ResizeMode,
+    // swap the focused tiled window with its neighbor
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+}
+
+/// Switches the screens (workspaces) shown on the focused monitor.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ScreenCommand {
+    Switch(usize),
+    /// Switches back to the screen viewed before on the monitor.
+    Back,
+    Prev,
+    Next,
+    /// Moves the focused window to the screen.
+    MoveWindow(usize),
+    /// Exchanges all the windows of the focused screen and the screen.
+    SwapWith(usize),
+}
+
+/// Drives the pointer from the keyboard.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PointerCommand {
+    MoveRel(i16, i16), // (dx, dy)
+    ClickLeft,
+    ClickRight,
+    ClickMiddle,
+    DoubleClick,
+    /// Presses the left button until `DragEnd`, moving the pointer in between drags.
+    DragStart,
+    DragEnd,
+}
+
+/// Changes the layout of the focused screen.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize)]
+pub enum LayoutCommand {
+    Next,
+    Prev,
+    /// Switches to the layout of the name (e.g. "monocle").
+    Set(String),
+    /// Passed to the active layout, e.g. "+" to grow the main area.
+    Command(String),
+}
+
+impl Command {
+    /// The screen the command refers to, if any.
+    pub fn screen(&self) -> Option<usize> {
+        match self {
+            Command::Screen(
+                ScreenCommand::Switch(id)
+                | ScreenCommand::MoveWindow(id)
+                | ScreenCommand::SwapWith(id),
+            ) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+/// The commands as written in the config, including the flat names of the older versions.
+mod legacy {
+    use super::{
+        FocusCommand, LayoutCommand, PointerCommand, ScreenCommand, SnapPosition, WindowCommand,
+    };
+    use serde::Deserialize;
+
+    /// `{Screen: 3}` of the older versions, or `{Screen: {Switch: 3}}`.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub enum Screen {
+        Id(usize),
+        Grouped(ScreenCommand),
+    }
+
+    // The names are those of the older versions, e.g. `LayoutCommand`.
+    #[allow(clippy::enum_variant_names)]
+    #[derive(Deserialize)]
+    pub enum Command {
+        Quit,
+        Restart,
+        ShowBorder,
+        HideBorder,
+        ShowWindowList,
+        WindowMenu,
+        ShowKeybindings,
+        DebugWindowInfo,
+        ToggleMetricsHud,
+        VolumeUp,
+        VolumeDown,
+        Mute,
+        BrightnessUp,
+        BrightnessDown,
+        SetTheme(String),
+        Spawn(String),
+        Extension(String),
+        Focus(FocusCommand),
+        Window(WindowCommand),
+        Screen(Screen),
+        Pointer(PointerCommand),
+        Layout(LayoutCommand),
+
+        Close,
+        Sink,
+        TogglePin,
+        ToggleFollow,
+        RescueWindows,
+        FloatSnap(SnapPosition),
+        ResizeMode,
+        MoveLeft,
+        MoveRight,
+        MoveUp,
+        MoveDown,
+        FocusNext,
+        FocusPrev,
+        FocusNextMonitor,
+        FocusPrevMonitor,
+        FocusUrgent,
+        FocusNextInGroup,
+        FocusPrevInGroup,
+        FocusMaster,
+        FocusStack,
+        NextLayout,
+        PrevLayout,
+        SetLayout(String),
+        LayoutCommand(String),
+        ScreenBack,
+        ScreenPrev,
+        ScreenNext,
+        MoveToScreen(usize),
+        SwapWithScreen(usize),
+        MovePointerRel(i16, i16),
+        MouseClickLeft,
+        MouseClickRight,
+        MouseClickMiddle,
+        MouseDoubleClick,
+        MouseDragStart,
+        MouseDragEnd,
+    }
+
+    impl From<Command> for super::Command {
+        fn from(cmd: Command) -> Self {
+            use super::Command as C;
+            match cmd {
+                Command::Quit => C::Quit,
+                Command::Restart => C::Restart,
+                Command::ShowBorder => C::ShowBorder,
+                Command::HideBorder => C::HideBorder,
+                Command::ShowWindowList => C::ShowWindowList,
+                Command::WindowMenu => C::WindowMenu,
+                Command::ShowKeybindings => C::ShowKeybindings,
+                Command::DebugWindowInfo => C::DebugWindowInfo,
+                Command::ToggleMetricsHud => C::ToggleMetricsHud,
+                Command::VolumeUp => C::VolumeUp,
+                Command::VolumeDown => C::VolumeDown,
+                Command::Mute => C::Mute,
+                Command::BrightnessUp => C::BrightnessUp,
+                Command::BrightnessDown => C::BrightnessDown,
+                Command::SetTheme(name) => C::SetTheme(name),
+                Command::Spawn(cmd) => C::Spawn(cmd),
+                Command::Extension(name) => C::Extension(name),
+                Command::Focus(cmd) => C::Focus(cmd),
+                Command::Window(cmd) => C::Window(cmd),
+                Command::Screen(Screen::Id(id)) => C::Screen(ScreenCommand::Switch(id)),
+                Command::Screen(Screen::Grouped(cmd)) => C::Screen(cmd),
+                Command::Pointer(cmd) => C::Pointer(cmd),
+                Command::Layout(cmd) => C::Layout(cmd),
+
+                Command::Close => C::Window(WindowCommand::Close),
+                Command::Sink => C::Window(WindowCommand::Sink),
+                Command::TogglePin => C::Window(WindowCommand::TogglePin),
+                Command::ToggleFollow => C::Window(WindowCommand::ToggleFollow),
+                Command::RescueWindows => C::Window(WindowCommand::Rescue),
+                Command::FloatSnap(pos) => C::Window(WindowCommand::FloatSnap(pos)),
+                Command::ResizeMode => C::Window(WindowCommand::ResizeMode),
+                Command::MoveLeft => C::Window(WindowCommand::MoveLeft),
+                Command::MoveRight => C::Window(WindowCommand::MoveRight),
+                Command::MoveUp => C::Window(WindowCommand::MoveUp),
+                Command::MoveDown => C::Window(WindowCommand::MoveDown),
+                Command::FocusNext => C::Focus(FocusCommand::Next),
+                Command::FocusPrev => C::Focus(FocusCommand::Prev),
+                Command::FocusNextMonitor => C::Focus(FocusCommand::NextMonitor),
+                Command::FocusPrevMonitor => C::Focus(FocusCommand::PrevMonitor),
+                Command::FocusUrgent => C::Focus(FocusCommand::Urgent),
+                Command::FocusNextInGroup => C::Focus(FocusCommand::NextInGroup),
+                Command::FocusPrevInGroup => C::Focus(FocusCommand::PrevInGroup),
+                Command::FocusMaster => C::Focus(FocusCommand::Master),
+                Command::FocusStack => C::Focus(FocusCommand::Stack),
+                Command::NextLayout => C::Layout(LayoutCommand::Next),
+                Command::PrevLayout => C::Layout(LayoutCommand::Prev),
+                Command::SetLayout(name) => C::Layout(LayoutCommand::Set(name)),
+                Command::LayoutCommand(cmd) => C::Layout(LayoutCommand::Command(cmd)),
+                Command::ScreenBack => C::Screen(ScreenCommand::Back),
+                Command::ScreenPrev => C::Screen(ScreenCommand::Prev),
+                Command::ScreenNext => C::Screen(ScreenCommand::Next),
+                Command::MoveToScreen(id) => C::Screen(ScreenCommand::MoveWindow(id)),
+                Command::SwapWithScreen(id) => C::Screen(ScreenCommand::SwapWith(id)),
+                Command::MovePointerRel(dx, dy) => C::Pointer(PointerCommand::MoveRel(dx, dy)),
+                Command::MouseClickLeft => C::Pointer(PointerCommand::ClickLeft),
+                Command::MouseClickRight => C::Pointer(PointerCommand::ClickRight),
+                Command::MouseClickMiddle => C::Pointer(PointerCommand::ClickMiddle),
+                Command::MouseDoubleClick => C::Pointer(PointerCommand::DoubleClick),
+                Command::MouseDragStart => C::Pointer(PointerCommand::DragStart),
+                Command::MouseDragEnd => C::Pointer(PointerCommand::DragEnd),
+            }
+        }
+    }
+}
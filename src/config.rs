@@ -1,26 +1,41 @@
+use crate::bar::BAR_HEIGHT;
 use crate::error::{Error, Result};
 use crate::{Command, KeybindAction};
 use log::info;
 use std::collections::HashMap;
 use std::convert::TryInto;
+use x11rb::protocol::xproto::Rectangle;
 
 //  KeyCode:
 //      1 = 10,
 //      2 = 11,
 //      3 = 12,
 //      ...
+//      0 = 19,
 //      Tab = 23,
+//      Escape = 9,
 //      Q = 24,
+//      W = 25,
+//      R = 27,
 //      T = 28,
+//      U = 30,
+//      I = 31,
 //      P = 33,
+//      BracketLeft = 34,
+//      BracketRight = 35,
 //      Enter = 36,
 //      S = 39,
 //      H = 43,
 //      J = 44,
 //      K = 45,
 //      L = 46,
+//      M = 58,
 //      C = 54,
+//      Slash = 61,
 //      Space = 65,
+//      Grave = 49,
+//      Comma = 59,
+//      Period = 60,
 //      SuperL = 133,
 //      SuperR = 134,
 //      AltL = 64,
@@ -29,67 +44,197 @@ use std::convert::TryInto;
 //      Down = 116,
 //      Left = 113,
 //      Right = 114,
+//      F11 = 95,
+//      F12 = 96,
+//      XF86AudioMute = 121,
+//      XF86AudioLowerVolume = 122,
+//      XF86AudioRaiseVolume = 123,
+//      XF86MonBrightnessDown = 232,
+//      XF86MonBrightnessUp = 233,
 
 const DEFAULT_CONFIG: &str = r###"
-background_color: '#343255'
 border:
     width: 1
-    color_focused: '#00f080'
-    color_regular: '#00003e'
+background_window: true
+active_theme: dark
+themes:
+    dark:
+        background: '#343255'
+        border_focused: '#00f080'
+        border_regular: '#00003e'
+        border_urgent: '#f04040'
+        title: '#ffffff'
+        bar:
+            main: '#4e4b61'
+            light: '#69656d'
+            shadow: '#1a1949'
+            focused_char: ['#df5b4e', '#b35349']
+            strong_char: ['#00f080', '#007840']
+            normal_char: ['#d2ca9c', '#9d9784']
+            dim_main: '#33313f'
+            dim_light: '#45424a'
+            dim_shadow: '#111030'
+            dim_char: ['#87826a', '#66625a']
+            error: ['#ff4040', '#a02020']
+    light:
+        background: '#d8d4c8'
+        border_focused: '#2a7fd4'
+        border_regular: '#a8a498'
+        border_urgent: '#d03030'
+        title: '#101010'
+        bar:
+            main: '#e4e0d6'
+            light: '#fbf9f4'
+            shadow: '#9a9688'
+            focused_char: ['#c8402f', '#f0a89c']
+            strong_char: ['#1f7a4a', '#9ccfb0']
+            normal_char: ['#3a3830', '#aaa698']
+            dim_main: '#ccc8bc'
+            dim_light: '#e2dfd6'
+            dim_shadow: '#8f8b7e'
+            dim_char: ['#6c685e', '#b4b0a4']
+            error: ['#d03030', '#f0a0a0']
 screens: 5
+overflow_follow: false
+screen_back_and_forth: false
+windows_per_page: 8
+activation: Focus
+monitor_source: Auto
+rules: []
+screen_keys:
+    keys: [10, 11, 12, 13, 14, 15, 16, 17, 18, 19]
+    switch_mod: [Super]
+    move_mod: [Super, Shift]
+    swap_mod: [Super, Control]
+resize_mode:
+    move_step: 32
+    resize_step: 32
+rounded_corners:
+    enabled: false
+    radius: 6
+animation:
+    enabled: false
+    duration_ms: 100
+    steps: 5
+pointer_repeat:
+    enabled: true
+    delay_ms: 200
+    interval_ms: 20
+    max_speed: 4
+mouse:
+    click_duration_ms: 10
+edge_resistance:
+    enabled: false
+    threshold: 100
+layouts:
+    horizontal:
+        default_ratio: 50
+        ratio_step: 5
+bar:
+    margin_x: 0
+    margin_y: 0
+    width: 100
+    flash_errors: true
+    taskbar: false
+    autohide: false
+    double_buffer: true
+scale:
+    default: 0
+osd:
+    enabled: true
+    timeout_ms: 1000
+window_menu:
+    command: /usr/bin/dmenu -i -l 20
+keybind_menu:
+    command: /usr/bin/dmenu -i -l 30 -p keys
+media:
+    volume_up: amixer set Master 5%+ | grep -o -m1 '[0-9]*%'
+    volume_down: amixer set Master 5%- | grep -o -m1 '[0-9]*%'
+    mute: amixer set Master toggle | grep -o -m1 '\[o[nf]*\]'
+    brightness_up: brightnessctl -m set +10% | cut -d, -f4
+    brightness_down: brightnessctl -m set 10%- | cut -d, -f4
+metrics: false
 
 keybind:
     - { action: Press,   mod: [Super],        key: 33,  command: {Spawn: /usr/bin/dmenu_run} }
     - { action: Press,   mod: [Super],        key: 28,  command: {Spawn: /usr/bin/xterm} }
 
     - { action: Press,   mod: [Super, Shift], key: 24,  command: Quit }
-    - { action: Press,   mod: [Super],        key: 54,  command: Close }
-    - { action: Press,   mod: [Super],        key: 23,  command: FocusNext }
-    - { action: Press,   mod: [Super, Shift], key: 23,  command: FocusPrev }
-    - { action: Press,   mod: [Super],        key: 44,  command: FocusNextMonitor }
-    - { action: Press,   mod: [Super],        key: 45,  command: FocusPrevMonitor }
-    - { action: Press,   mod: [Super],        key: 65,  command: NextLayout }
-    - { action: Press,   mod: [Super],        key: 39,  command: Sink }
+    - { action: Press,   mod: [Super],        key: 54,  command: {Window: Close} }
+    - { action: Press,   mod: [Super],        key: 23,  command: {Focus: Next} }
+    - { action: Press,   mod: [Super, Shift], key: 23,  command: {Focus: Prev} }
+    - { action: Press,   mod: [Super, Control], key: 23, command: {Focus: NextInGroup} }
+    - { action: Press,   mod: [Super, Control, Shift], key: 23, command: {Focus: PrevInGroup} }
+    - { action: Press,   mod: [Super, Control], key: 43, command: {Focus: Master} }
+    - { action: Press,   mod: [Super, Control], key: 46, command: {Focus: Stack} }
+    - { action: Press,   mod: [Super],        key: 44,  command: {Focus: NextMonitor} }
+    - { action: Press,   mod: [Super],        key: 45,  command: {Focus: PrevMonitor} }
+    - { action: Press,   mod: [Super],        key: 65,  command: {Layout: Next} }
+    - { action: Press,   mod: [Super, Shift], key: 65,  command: {Layout: Prev} }
+    - { action: Press,   mod: [Super],        key: 58,  command: {Layout: {Set: monocle}} }
+    - { action: Press,   mod: [Super, Shift], key: 28,  command: {Layout: {Set: horizontal-with-border}} }
+    - { action: Press,   mod: [Super],        key: 49,  command: {Screen: Back} }
+    - { action: Press,   mod: [Super],        key: 59,  command: {Screen: Prev} }
+    - { action: Press,   mod: [Super],        key: 60,  command: {Screen: Next} }
+    - { action: Press,   mod: [Super],        key: 39,  command: {Window: Sink} }
+    - { action: Press,   mod: [Super, Shift], key: 33,  command: {Window: TogglePin} }
+    - { action: Press,   mod: [Super, Shift], key: 41,  command: {Window: ToggleFollow} }
+    - { action: Press,   mod: [Super, Alt],   key: 113, command: {Window: {FloatSnap: Left}} }
+    - { action: Press,   mod: [Super, Alt],   key: 114, command: {Window: {FloatSnap: Right}} }
+    - { action: Press,   mod: [Super, Alt],   key: 111, command: {Window: {FloatSnap: Top}} }
+    - { action: Press,   mod: [Super, Alt],   key: 116, command: {Window: {FloatSnap: Bottom}} }
+    - { action: Press,   mod: [Super, Alt],   key: 54,  command: {Window: {FloatSnap: Center}} }
+    - { action: Press,   mod: [Super],        key: 27,  command: {Window: ResizeMode} }
+    - { action: Press,   mod: [Super],        key: 30,  command: {Focus: Urgent} }
+    - { action: Press,   mod: [Super, Shift], key: 43,  command: {Window: MoveLeft} }
+    - { action: Press,   mod: [Super, Shift], key: 44,  command: {Window: MoveDown} }
+    - { action: Press,   mod: [Super, Shift], key: 45,  command: {Window: MoveUp} }
+    - { action: Press,   mod: [Super, Shift], key: 46,  command: {Window: MoveRight} }
+    - { action: Press,   mod: [Super],        key: 25,  command: ShowWindowList }
+    - { action: Press,   mod: [Super, Shift], key: 25,  command: WindowMenu }
+    - { action: Press,   mod: [Super, Shift], key: 61,  command: ShowKeybindings }
+    - { action: Press,   mod: [Super, Shift], key: 31,  command: DebugWindowInfo }
 
     - { action: Press,   mod: [],             key: 133, command: ShowBorder }
     - { action: Release, mod: [Super],        key: 133, command: HideBorder }
 
-    - { action: Press,   mod: [Super],        key: 43,  command: {LayoutCommand: "-"} }
-    - { action: Press,   mod: [Super],        key: 46,  command: {LayoutCommand: "+"} }
-
-    - { action: Press,   mod: [Super],        key: 10,  command: {Screen: 0} }
-    - { action: Press,   mod: [Super],        key: 11,  command: {Screen: 1} }
-    - { action: Press,   mod: [Super],        key: 12,  command: {Screen: 2} }
-    - { action: Press,   mod: [Super],        key: 13,  command: {Screen: 3} }
-    - { action: Press,   mod: [Super],        key: 14,  command: {Screen: 4} }
-
-    - { action: Press,   mod: [Super, Shift], key: 10,  command: {MoveToScreen: 0} }
-    - { action: Press,   mod: [Super, Shift], key: 11,  command: {MoveToScreen: 1} }
-    - { action: Press,   mod: [Super, Shift], key: 12,  command: {MoveToScreen: 2} }
-    - { action: Press,   mod: [Super, Shift], key: 13,  command: {MoveToScreen: 3} }
-    - { action: Press,   mod: [Super, Shift], key: 14,  command: {MoveToScreen: 4} }
-
-    - { action: Press,   mod: [Super],        key: 111, command: {MovePointerRel: [  0, -32]} }
-    - { action: Press,   mod: [Super],        key: 116, command: {MovePointerRel: [  0,  32]} }
-    - { action: Press,   mod: [Super],        key: 113, command: {MovePointerRel: [-32,   0]} }
-    - { action: Press,   mod: [Super],        key: 114, command: {MovePointerRel: [ 32,   0]} }
-    - { action: Press,   mod: [Super, Shift], key: 111, command: {MovePointerRel: [  0,  -1]} }
-    - { action: Press,   mod: [Super, Shift], key: 116, command: {MovePointerRel: [  0,   1]} }
-    - { action: Press,   mod: [Super, Shift], key: 113, command: {MovePointerRel: [ -1,   0]} }
-    - { action: Press,   mod: [Super, Shift], key: 114, command: {MovePointerRel: [  1,   0]} }
-
-    - { action: Press,   mod: [Super],        key: 36,  command: MouseClickLeft }
+    - { action: Press,   mod: [Super],        key: 43,  command: {Layout: {Command: "-"}} }
+    - { action: Press,   mod: [Super],        key: 46,  command: {Layout: {Command: "+"}} }
+    - { action: Press,   mod: [Super],        key: 34,  command: {Layout: {Command: prev-page}} }
+    - { action: Press,   mod: [Super],        key: 35,  command: {Layout: {Command: next-page}} }
+
+    - { action: Press,   mod: [Super],        key: 111, command: {Pointer: {MoveRel: [  0, -32]}} }
+    - { action: Press,   mod: [Super],        key: 116, command: {Pointer: {MoveRel: [  0,  32]}} }
+    - { action: Press,   mod: [Super],        key: 113, command: {Pointer: {MoveRel: [-32,   0]}} }
+    - { action: Press,   mod: [Super],        key: 114, command: {Pointer: {MoveRel: [ 32,   0]}} }
+    - { action: Press,   mod: [Super, Shift], key: 111, command: {Pointer: {MoveRel: [  0,  -1]}} }
+    - { action: Press,   mod: [Super, Shift], key: 116, command: {Pointer: {MoveRel: [  0,   1]}} }
+    - { action: Press,   mod: [Super, Shift], key: 113, command: {Pointer: {MoveRel: [ -1,   0]}} }
+    - { action: Press,   mod: [Super, Shift], key: 114, command: {Pointer: {MoveRel: [  1,   0]}} }
+
+    - { action: Press,   mod: [Super],        key: 36,  command: {Pointer: ClickLeft} }
+    - { action: Press,   mod: [Super, Shift], key: 36,  command: {Pointer: ClickRight} }
+
+    - { action: Press,   mod: [],             key: 123, command: VolumeUp }
+    - { action: Press,   mod: [],             key: 122, command: VolumeDown }
+    - { action: Press,   mod: [],             key: 121, command: Mute }
+    - { action: Press,   mod: [],             key: 233, command: BrightnessUp }
+    - { action: Press,   mod: [],             key: 232, command: BrightnessDown }
+    - { action: Press,   mod: [Super],        key: 95,  command: {SetTheme: dark} }
+    - { action: Press,   mod: [Super],        key: 96,  command: {SetTheme: light} }
 "###;
 
 mod parse {
+    use super::MAX_SCREENS;
     use crate::error::{Error, Result};
-    use crate::{Command, KeybindAction};
+    use crate::{Command, KeybindAction, ScreenCommand};
+    use log::warn;
     use serde::Deserialize;
     use std::collections::HashMap;
     use std::convert::TryInto;
     use x11rb::protocol::xproto::ModMask;
 
-    use super::Config;
+    use super::{ActivationPolicy, Config, MonitorSource};
 
     #[derive(Debug, Deserialize)]
     enum Modifier {
@@ -118,80 +263,1071 @@ mod parse {
         command: Command,
     }
 
+    #[derive(Debug, Deserialize)]
+    struct ScreenKeysConfig {
+        keys: Vec<u8>,
+        switch_mod: Vec<Modifier>,
+        move_mod: Vec<Modifier>,
+        swap_mod: Vec<Modifier>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct BorderWidths {
+        width: u32,
+        width_focused: Option<u32>,
+    }
+
     #[derive(Debug, Deserialize)]
     struct BorderConfig {
         width: u32,
-        color_focused: String,
-        color_regular: String,
+        width_focused: Option<u32>,
+        outputs: Option<HashMap<String, BorderWidths>>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct BarColorsConfig {
+        main: String,
+        light: String,
+        shadow: String,
+        focused_char: [String; 2],
+        strong_char: [String; 2],
+        normal_char: [String; 2],
+        dim_main: String,
+        dim_light: String,
+        dim_shadow: String,
+        dim_char: [String; 2],
+        error: [String; 2],
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct ThemeConfig {
+        background: String,
+        border_focused: String,
+        border_regular: String,
+        border_urgent: String,
+        title: String,
+        bar: BarColorsConfig,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct ResizeModeConfig {
+        move_step: u16,
+        resize_step: u16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct RoundedCornersConfig {
+        enabled: bool,
+        radius: u16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct AnimationConfig {
+        enabled: bool,
+        duration_ms: u64,
+        steps: u16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct PointerRepeatConfig {
+        enabled: bool,
+        delay_ms: u64,
+        interval_ms: u64,
+        max_speed: i16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct EdgeResistanceConfig {
+        enabled: bool,
+        threshold: u16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct MouseConfig {
+        click_duration_ms: u32,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct HorizontalLayoutConfig {
+        default_ratio: u16,
+        ratio_step: u16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct LayoutsConfig {
+        horizontal: HorizontalLayoutConfig,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct BarConfig {
+        margin_x: u16,
+        margin_y: u16,
+        width: u16,
+        flash_errors: bool,
+        taskbar: bool,
+        autohide: bool,
+        double_buffer: bool,
+        clock_command: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct ScaleConfig {
+        default: u16,
+        outputs: Option<HashMap<String, u16>>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct WindowRule {
+        class: String,
+        screen: Option<usize>,
+        #[serde(default)]
+        follow: bool,
+        opacity: Option<f64>,
+        #[serde(default)]
+        confirm_close: bool,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct OsdConfig {
+        enabled: bool,
+        timeout_ms: u64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct WindowMenuConfig {
+        command: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct KeybindMenuConfig {
+        command: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct MediaConfig {
+        volume_up: String,
+        volume_down: String,
+        mute: String,
+        brightness_up: String,
+        brightness_down: String,
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(default)]
+    struct HooksConfig {
+        window_mapped: Option<String>,
+        window_closed: Option<String>,
+        screen_changed: Option<String>,
+        layout_changed: Option<String>,
+        monitor_changed: Option<String>,
     }
 
     #[derive(Debug, Deserialize)]
     pub struct ConfigYamlRepr {
         keybind: Vec<KeyBind>,
         border: BorderConfig,
-        background_color: String,
+        background_window: bool,
+        active_theme: String,
+        themes: HashMap<String, ThemeConfig>,
         screens: usize,
+        max_windows_per_screen: Option<usize>,
+        overflow_follow: bool,
+        screen_back_and_forth: bool,
+        windows_per_page: usize,
+        activation: ActivationPolicy,
+        monitor_source: MonitorSource,
+        screen_keys: ScreenKeysConfig,
+        rules: Option<Vec<WindowRule>>,
+        resize_mode: ResizeModeConfig,
+        rounded_corners: RoundedCornersConfig,
+        animation: AnimationConfig,
+        pointer_repeat: PointerRepeatConfig,
+        mouse: MouseConfig,
+        edge_resistance: EdgeResistanceConfig,
+        layouts: LayoutsConfig,
+        bar: BarConfig,
+        scale: ScaleConfig,
+        osd: OsdConfig,
+        window_menu: WindowMenuConfig,
+        keybind_menu: KeybindMenuConfig,
+        media: MediaConfig,
+        metrics: bool,
+        hooks: Option<HooksConfig>,
+    }
+
+    fn modmask(mods: Vec<Modifier>) -> u16 {
+        mods.into_iter().fold(0, |mask, m| mask | u16::from(m))
     }
 
-    fn parse_color(hex: &str) -> Result<u32> {
+    pub(super) fn parse_color(hex: &str) -> Result<u32> {
         let hex = hex.trim_start_matches('#');
         u32::from_str_radix(hex, 16).map_err(|_| Error::InvalidConfig {
             reason: "expect a color of \"#RRGGBB\" (in hex)".to_owned(),
         })
     }
 
-    impl std::convert::TryFrom<BorderConfig> for super::BorderConfig {
+    fn parse_color_pair(pair: &[String; 2]) -> Result<(u32, u32)> {
+        Ok((parse_color(&pair[0])?, parse_color(&pair[1])?))
+    }
+
+    impl From<BorderWidths> for super::BorderWidths {
+        fn from(yaml_repr: BorderWidths) -> Self {
+            super::BorderWidths {
+                width: yaml_repr.width,
+                width_focused: yaml_repr.width_focused.unwrap_or(yaml_repr.width),
+            }
+        }
+    }
+
+    impl From<BorderConfig> for super::BorderConfig {
+        fn from(yaml_repr: BorderConfig) -> Self {
+            let outputs = yaml_repr.outputs.unwrap_or_default();
+            super::BorderConfig {
+                width: yaml_repr.width,
+                width_focused: yaml_repr.width_focused.unwrap_or(yaml_repr.width),
+                outputs: outputs.into_iter().map(|(k, v)| (k, v.into())).collect(),
+            }
+        }
+    }
+
+    impl std::convert::TryFrom<BarColorsConfig> for super::BarColors {
+        type Error = Error;
+        fn try_from(yaml_repr: BarColorsConfig) -> Result<Self> {
+            Ok(super::BarColors {
+                main: parse_color(&yaml_repr.main)?,
+                light: parse_color(&yaml_repr.light)?,
+                shadow: parse_color(&yaml_repr.shadow)?,
+                focused_char: parse_color_pair(&yaml_repr.focused_char)?,
+                strong_char: parse_color_pair(&yaml_repr.strong_char)?,
+                normal_char: parse_color_pair(&yaml_repr.normal_char)?,
+                dim_main: parse_color(&yaml_repr.dim_main)?,
+                dim_light: parse_color(&yaml_repr.dim_light)?,
+                dim_shadow: parse_color(&yaml_repr.dim_shadow)?,
+                dim_char: parse_color_pair(&yaml_repr.dim_char)?,
+                error: parse_color_pair(&yaml_repr.error)?,
+            })
+        }
+    }
+
+    impl std::convert::TryFrom<ThemeConfig> for super::Theme {
         type Error = Error;
-        fn try_from(yaml_repr: BorderConfig) -> Result<Self> {
-            Ok(super::BorderConfig {
+        fn try_from(yaml_repr: ThemeConfig) -> Result<Self> {
+            Ok(super::Theme {
+                background: parse_color(&yaml_repr.background)?,
+                border_focused: parse_color(&yaml_repr.border_focused)?,
+                border_regular: parse_color(&yaml_repr.border_regular)?,
+                border_urgent: parse_color(&yaml_repr.border_urgent)?,
+                title: parse_color(&yaml_repr.title)?,
+                bar: yaml_repr.bar.try_into()?,
+            })
+        }
+    }
+
+    impl From<ResizeModeConfig> for super::ResizeModeConfig {
+        fn from(yaml_repr: ResizeModeConfig) -> Self {
+            super::ResizeModeConfig {
+                move_step: yaml_repr.move_step,
+                resize_step: yaml_repr.resize_step,
+            }
+        }
+    }
+
+    impl From<RoundedCornersConfig> for super::RoundedCornersConfig {
+        fn from(yaml_repr: RoundedCornersConfig) -> Self {
+            super::RoundedCornersConfig {
+                enabled: yaml_repr.enabled,
+                radius: yaml_repr.radius,
+            }
+        }
+    }
+
+    impl From<AnimationConfig> for super::AnimationConfig {
+        fn from(yaml_repr: AnimationConfig) -> Self {
+            super::AnimationConfig {
+                enabled: yaml_repr.enabled,
+                duration_ms: yaml_repr.duration_ms,
+                steps: yaml_repr.steps,
+            }
+        }
+    }
+
+    impl From<PointerRepeatConfig> for super::PointerRepeatConfig {
+        fn from(yaml_repr: PointerRepeatConfig) -> Self {
+            super::PointerRepeatConfig {
+                enabled: yaml_repr.enabled,
+                delay: std::time::Duration::from_millis(yaml_repr.delay_ms),
+                interval: std::time::Duration::from_millis(yaml_repr.interval_ms.max(1)),
+                max_speed: yaml_repr.max_speed.max(1),
+            }
+        }
+    }
+
+    impl From<EdgeResistanceConfig> for super::EdgeResistanceConfig {
+        fn from(yaml_repr: EdgeResistanceConfig) -> Self {
+            super::EdgeResistanceConfig {
+                enabled: yaml_repr.enabled,
+                threshold: yaml_repr.threshold,
+            }
+        }
+    }
+
+    impl From<MouseConfig> for super::MouseConfig {
+        fn from(yaml_repr: MouseConfig) -> Self {
+            super::MouseConfig {
+                click_duration_ms: yaml_repr.click_duration_ms,
+            }
+        }
+    }
+
+    impl std::convert::TryFrom<LayoutsConfig> for super::LayoutsConfig {
+        type Error = Error;
+        fn try_from(yaml_repr: LayoutsConfig) -> Result<Self> {
+            let horizontal = yaml_repr.horizontal;
+            if !(5..=95).contains(&horizontal.default_ratio) {
+                return Err(Error::InvalidConfig {
+                    reason: "layouts.horizontal.default_ratio should be a percentage in 5..=95"
+                        .to_owned(),
+                });
+            }
+            if horizontal.ratio_step == 0 {
+                return Err(Error::InvalidConfig {
+                    reason: "layouts.horizontal.ratio_step should be at least 1".to_owned(),
+                });
+            }
+            Ok(super::LayoutsConfig {
+                horizontal: super::HorizontalLayoutConfig {
+                    default_ratio: horizontal.default_ratio,
+                    ratio_step: horizontal.ratio_step,
+                },
+            })
+        }
+    }
+
+    impl std::convert::TryFrom<BarConfig> for super::BarConfig {
+        type Error = Error;
+        fn try_from(yaml_repr: BarConfig) -> Result<Self> {
+            if !(1..=100).contains(&yaml_repr.width) {
+                return Err(Error::InvalidConfig {
+                    reason: "bar.width should be a percentage in 1..=100".to_owned(),
+                });
+            }
+            // No X screen is as large as twice these margins.
+            let max_margin = i16::MAX as u16 / 2;
+            if yaml_repr.margin_x > max_margin || yaml_repr.margin_y > max_margin {
+                return Err(Error::InvalidConfig {
+                    reason: format!(
+                        "bar.margin_x and bar.margin_y should be up to {}",
+                        max_margin
+                    ),
+                });
+            }
+            Ok(super::BarConfig {
+                margin_x: yaml_repr.margin_x,
+                margin_y: yaml_repr.margin_y,
                 width: yaml_repr.width,
-                color_focused: parse_color(&yaml_repr.color_focused)?,
-                color_regular: parse_color(&yaml_repr.color_regular)?,
+                flash_errors: yaml_repr.flash_errors,
+                taskbar: yaml_repr.taskbar,
+                autohide: yaml_repr.autohide,
+                double_buffer: yaml_repr.double_buffer,
+                clock_command: yaml_repr.clock_command,
             })
         }
     }
 
+    impl std::convert::TryFrom<ScaleConfig> for super::ScaleConfig {
+        type Error = Error;
+        fn try_from(yaml_repr: ScaleConfig) -> Result<Self> {
+            let outputs = yaml_repr.outputs.unwrap_or_default();
+            let max = super::MAX_SCALE;
+            if yaml_repr.default > max || outputs.values().any(|&f| !(1..=max).contains(&f)) {
+                return Err(Error::InvalidConfig {
+                    reason: format!("scale factors should be in 1..={} (or 0 to detect)", max),
+                });
+            }
+            Ok(super::ScaleConfig {
+                default: yaml_repr.default,
+                outputs,
+            })
+        }
+    }
+
+    impl From<OsdConfig> for super::OsdConfig {
+        fn from(yaml_repr: OsdConfig) -> Self {
+            super::OsdConfig {
+                enabled: yaml_repr.enabled,
+                timeout_ms: yaml_repr.timeout_ms,
+            }
+        }
+    }
+
+    impl From<WindowMenuConfig> for super::WindowMenuConfig {
+        fn from(yaml_repr: WindowMenuConfig) -> Self {
+            super::WindowMenuConfig {
+                command: yaml_repr.command,
+            }
+        }
+    }
+
+    impl From<KeybindMenuConfig> for super::KeybindMenuConfig {
+        fn from(yaml_repr: KeybindMenuConfig) -> Self {
+            super::KeybindMenuConfig {
+                command: yaml_repr.command,
+            }
+        }
+    }
+
+    impl From<MediaConfig> for super::MediaConfig {
+        fn from(yaml_repr: MediaConfig) -> Self {
+            super::MediaConfig {
+                volume_up: yaml_repr.volume_up,
+                volume_down: yaml_repr.volume_down,
+                mute: yaml_repr.mute,
+                brightness_up: yaml_repr.brightness_up,
+                brightness_down: yaml_repr.brightness_down,
+            }
+        }
+    }
+
+    impl From<HooksConfig> for super::HooksConfig {
+        fn from(yaml_repr: HooksConfig) -> Self {
+            super::HooksConfig {
+                window_mapped: yaml_repr.window_mapped,
+                window_closed: yaml_repr.window_closed,
+                screen_changed: yaml_repr.screen_changed,
+                layout_changed: yaml_repr.layout_changed,
+                monitor_changed: yaml_repr.monitor_changed,
+            }
+        }
+    }
+
     impl std::convert::TryFrom<ConfigYamlRepr> for Config {
         type Error = Error;
         fn try_from(yaml_repr: ConfigYamlRepr) -> Result<Self> {
+            let screens = yaml_repr.screens;
+            if !(1..=MAX_SCREENS).contains(&screens) {
+                return Err(Error::InvalidConfig {
+                    reason: format!("`screens` should be in 1..={}", MAX_SCREENS),
+                });
+            }
+
             let mut keybind = HashMap::new();
+
+            // Generate bindings for the screens (explicit ones take precedence).
+            let screen_keys = yaml_repr.screen_keys;
+            if screen_keys.keys.len() < screens {
+                warn!(
+                    "screen_keys: only {} of {} screens have keys",
+                    screen_keys.keys.len(),
+                    screens
+                );
+            }
+            let switch_mod = modmask(screen_keys.switch_mod);
+            let move_mod = modmask(screen_keys.move_mod);
+            let swap_mod = modmask(screen_keys.swap_mod);
+            for (id, &key) in screen_keys.keys.iter().take(screens).enumerate() {
+                let press = KeybindAction::Press;
+                let mut bind =
+                    |modmask, cmd| keybind.insert((press, modmask, key), Command::Screen(cmd));
+                bind(switch_mod, ScreenCommand::Switch(id));
+                bind(move_mod, ScreenCommand::MoveWindow(id));
+                bind(swap_mod, ScreenCommand::SwapWith(id));
+            }
+
             for kb in yaml_repr.keybind {
-                let mut modmask: u16 = 0;
-                for m in kb.r#mod {
-                    modmask |= Into::<u16>::into(m);
+                if kb.command.screen().is_some_and(|id| id >= screens) {
+                    return Err(Error::InvalidConfig {
+                        reason: format!("{:?} refers to a screen out of range", kb.command),
+                    });
+                }
+                keybind.insert((kb.action, modmask(kb.r#mod), kb.key), kb.command);
+            }
+
+            let mut rules = Vec::new();
+            for rule in yaml_repr.rules.unwrap_or_default() {
+                if rule.screen.is_some_and(|id| id >= screens) {
+                    return Err(Error::InvalidConfig {
+                        reason: format!(
+                            "the rule for {:?} refers to a screen out of range",
+                            rule.class
+                        ),
+                    });
                 }
-                keybind.insert((kb.action, modmask, kb.key), kb.command);
+                if !(0.0..=1.0).contains(&rule.opacity.unwrap_or(1.0)) {
+                    return Err(Error::InvalidConfig {
+                        reason: format!(
+                            "the opacity of the rule for {:?} should be in 0.0..=1.0",
+                            rule.class
+                        ),
+                    });
+                }
+                rules.push(super::WindowRule {
+                    class: rule.class,
+                    screen: rule.screen,
+                    follow: rule.follow,
+                    opacity: rule.opacity,
+                    confirm_close: rule.confirm_close,
+                });
             }
 
-            let background_color = parse_color(&yaml_repr.background_color)?;
+            if yaml_repr.max_windows_per_screen == Some(0) {
+                return Err(Error::InvalidConfig {
+                    reason: "`max_windows_per_screen` should be at least 1".to_owned(),
+                });
+            }
+            if yaml_repr.windows_per_page == 0 {
+                return Err(Error::InvalidConfig {
+                    reason: "`windows_per_page` should be at least 1".to_owned(),
+                });
+            }
+
+            let mut themes = HashMap::new();
+            for (name, theme) in yaml_repr.themes {
+                themes.insert(name, theme.try_into()?);
+            }
+            if !themes.contains_key(&yaml_repr.active_theme) {
+                return Err(Error::InvalidConfig {
+                    reason: format!("unknown theme {:?}", yaml_repr.active_theme),
+                });
+            }
 
             Ok(Config {
                 keybind,
-                border: yaml_repr.border.try_into()?,
-                background_color,
-                screens: yaml_repr.screens,
+                border: yaml_repr.border.into(),
+                background_window: yaml_repr.background_window,
+                active_theme: yaml_repr.active_theme,
+                themes,
+                screens,
+                max_windows_per_screen: yaml_repr.max_windows_per_screen,
+                overflow_follow: yaml_repr.overflow_follow,
+                screen_back_and_forth: yaml_repr.screen_back_and_forth,
+                windows_per_page: yaml_repr.windows_per_page,
+                activation: yaml_repr.activation,
+                monitor_source: yaml_repr.monitor_source,
+                rules,
+                resize_mode: yaml_repr.resize_mode.into(),
+                rounded_corners: yaml_repr.rounded_corners.into(),
+                animation: yaml_repr.animation.into(),
+                pointer_repeat: yaml_repr.pointer_repeat.into(),
+                mouse: yaml_repr.mouse.into(),
+                edge_resistance: yaml_repr.edge_resistance.into(),
+                layouts: yaml_repr.layouts.try_into()?,
+                bar: yaml_repr.bar.try_into()?,
+                scale: yaml_repr.scale.try_into()?,
+                osd: yaml_repr.osd.into(),
+                window_menu: yaml_repr.window_menu.into(),
+                keybind_menu: yaml_repr.keybind_menu.into(),
+                media: yaml_repr.media.into(),
+                metrics: yaml_repr.metrics,
+                hooks: yaml_repr.hooks.unwrap_or_default().into(),
             })
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Conversion of the merged config into YAML for `daily --dump-config`.
+mod dump {
+    use super::parse::parse_color;
+    use super::{Config, DEFAULT_CONFIG};
+    use crate::error::{Error, Result};
+    use crate::{Command, KeybindAction};
+    use ::config::{Value, ValueKind};
+    use std::collections::HashMap;
+    use x11rb::protocol::xproto::ModMask;
+    use yaml_rust::yaml::Hash;
+    use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+    /// The names used in `mod` of the keybindings.
+    const MODIFIERS: [(ModMask, &str); 4] = [
+        (ModMask::M4, "Super"),
+        (ModMask::CONTROL, "Control"),
+        (ModMask::M1, "Alt"),
+        (ModMask::SHIFT, "Shift"),
+    ];
+
+    fn invalid(e: impl ToString) -> Error {
+        Error::InvalidConfig {
+            reason: e.to_string(),
+        }
+    }
+
+    /// Converts a value of the config crate, ordering the keys of tables as in `template`
+    /// (the defaults) and the rest alphabetically.
+    fn value_to_yaml(value: Value, template: Option<&Yaml>) -> Yaml {
+        match value.kind {
+            ValueKind::Nil => Yaml::Null,
+            ValueKind::Boolean(b) => Yaml::Boolean(b),
+            ValueKind::I64(n) => Yaml::Integer(n),
+            ValueKind::I128(n) => Yaml::Integer(n as i64),
+            ValueKind::U64(n) => Yaml::Integer(n as i64),
+            ValueKind::U128(n) => Yaml::Integer(n as i64),
+            ValueKind::Float(f) => Yaml::Real(f.to_string()),
+            ValueKind::String(s) => Yaml::String(s),
+            ValueKind::Array(values) => {
+                let template = template.and_then(|t| t.as_vec()).and_then(|t| t.first());
+                Yaml::Array(
+                    values
+                        .into_iter()
+                        .map(|v| value_to_yaml(v, template))
+                        .collect(),
+                )
+            }
+            ValueKind::Table(table) => table_to_yaml(table.into_iter(), template),
+        }
+    }
+
+    fn table_to_yaml(
+        entries: impl Iterator<Item = (String, Value)>,
+        template: Option<&Yaml>,
+    ) -> Yaml {
+        let template = template.and_then(|t| t.as_hash());
+        let position = |key: &str| {
+            let key = Yaml::String(key.to_owned());
+            template
+                .and_then(|t| t.keys().position(|k| *k == key))
+                .unwrap_or(usize::MAX)
+        };
+
+        let mut entries: Vec<_> = entries.collect();
+        entries.sort_by(|(a, _), (b, _)| (position(a), a).cmp(&(position(b), b)));
+
+        let mut hash = Hash::new();
+        for (key, value) in entries {
+            let sub_template = template.and_then(|t| t.get(&Yaml::String(key.clone())));
+            hash.insert(Yaml::String(key), value_to_yaml(value, sub_template));
+        }
+        Yaml::Hash(hash)
+    }
+
+    /// Rewrites the colors to "#rrggbb".
+    fn normalize_colors(yaml: Yaml) -> Yaml {
+        match yaml {
+            Yaml::String(s) => match parse_color(&s) {
+                Ok(color) => Yaml::String(format!("#{:06x}", color)),
+                Err(_) => Yaml::String(s),
+            },
+            Yaml::Array(values) => Yaml::Array(values.into_iter().map(normalize_colors).collect()),
+            Yaml::Hash(hash) => Yaml::Hash(
+                hash.into_iter()
+                    .map(|(k, v)| (k, normalize_colors(v)))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+
+    fn command_to_yaml(command: &Command) -> Result<Yaml> {
+        // The config crate can serialize only structs.
+        #[derive(serde::Serialize)]
+        struct Entry<'a> {
+            command: &'a Command,
+        }
+        let value: Value = ::config::Config::try_from(&Entry { command })
+            .and_then(|config| config.get("command"))
+            .map_err(invalid)?;
+        Ok(value_to_yaml(value, None))
+    }
+
+    /// Returns all the keybindings of the config, including the generated ones.
+    fn keybind_to_yaml(config: &Config) -> Result<Yaml> {
+        let mut keybind: Vec<_> = config.keybind_iter().collect();
+        keybind.sort_by_key(|&(&(action, modmask, keycode), _)| {
+            (modmask, keycode, action == KeybindAction::Release)
+        });
+
+        let mut entries = Vec::new();
+        for (&(action, modmask, keycode), command) in keybind {
+            let mods = MODIFIERS
+                .iter()
+                .filter(|(m, _)| modmask & u16::from(*m) != 0)
+                .map(|(_, name)| Yaml::String((*name).to_owned()))
+                .collect();
+
+            let mut entry = Hash::new();
+            let action = match action {
+                KeybindAction::Press => "Press",
+                KeybindAction::Release => "Release",
+            };
+            entry.insert(Yaml::from_str("action"), Yaml::from_str(action));
+            entry.insert(Yaml::from_str("mod"), Yaml::Array(mods));
+            entry.insert(Yaml::from_str("key"), Yaml::Integer(keycode.into()));
+            entry.insert(Yaml::from_str("command"), command_to_yaml(command)?);
+            entries.push(Yaml::Hash(entry));
+        }
+        Ok(Yaml::Array(entries))
+    }
+
+    /// Returns the merged config in YAML, after checking that it is valid.
+    pub(super) fn to_yaml(sources: ::config::Config) -> Result<String> {
+        let config = Config::from_sources(sources.clone())?;
+        let table: HashMap<String, Value> = sources.try_deserialize().map_err(invalid)?;
+
+        let template =
+            YamlLoader::load_from_str(DEFAULT_CONFIG).expect("ill-formed DEFAULT_CONFIG");
+        let mut yaml = table_to_yaml(table.into_iter(), template.first());
+        if let Yaml::Hash(hash) = &mut yaml {
+            hash.insert(Yaml::from_str("keybind"), keybind_to_yaml(&config)?);
+            if let Some(themes) = hash.get_mut(&Yaml::from_str("themes")) {
+                *themes = normalize_colors(themes.clone());
+            }
+        }
+
+        let mut out = String::new();
+        YamlEmitter::new(&mut out).dump(&yaml).map_err(invalid)?;
+        out.push('\n');
+        Ok(out)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct BorderConfig {
     pub width: u32,
-    pub color_focused: u32,
-    pub color_regular: u32,
+    pub width_focused: u32,
+    /// The widths in pixels by the names of outputs, which are not scaled.
+    pub outputs: HashMap<String, BorderWidths>,
+}
+
+/// The border widths of windows on an output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorderWidths {
+    pub width: u32,
+    pub width_focused: u32,
+}
+
+impl BorderConfig {
+    /// Returns the border widths on the output of the scale factor.
+    pub fn widths(&self, output: &str, scale: u16) -> BorderWidths {
+        if let Some(&widths) = self.outputs.get(output) {
+            return widths;
+        }
+        BorderWidths {
+            width: self.width * scale as u32,
+            width_focused: self.width_focused * scale as u32,
+        }
+    }
+}
+
+/// Colors of the bars. The characters are drawn with a pair of (color, shade).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BarColors {
+    pub main: u32,
+    pub light: u32,
+    pub shadow: u32,
+    pub focused_char: (u32, u32),
+    pub strong_char: (u32, u32),
+    pub normal_char: (u32, u32),
+    // for bars on unfocused monitors
+    pub dim_main: u32,
+    pub dim_light: u32,
+    pub dim_shadow: u32,
+    pub dim_char: (u32, u32),
+    pub error: (u32, u32),
+}
+
+/// A named set of colors, selected by `active_theme` or [`Command::SetTheme`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Theme {
+    /// The color of empty screens.
+    pub background: u32,
+    pub border_focused: u32,
+    pub border_regular: u32,
+    /// The border color of windows demanding attention (urgency hint).
+    pub border_urgent: u32,
+    /// The color of the window titles on frames.
+    pub title: u32,
+    pub bar: BarColors,
+}
+
+/// Steps (in pixels) used by the keyboard-driven resize mode.
+#[derive(Debug, Clone, Copy)]
+pub struct ResizeModeConfig {
+    pub move_step: u16,
+    pub resize_step: u16,
+}
+
+/// Rounded corners of frames and bars (requires the SHAPE extension).
+#[derive(Debug, Clone, Copy)]
+pub struct RoundedCornersConfig {
+    pub enabled: bool,
+    pub radius: u16,
+}
+
+impl RoundedCornersConfig {
+    /// Returns the radius if rounded corners are enabled.
+    pub fn radius(&self) -> Option<u16> {
+        if self.enabled && self.radius > 0 {
+            Some(self.radius)
+        } else {
+            None
+        }
+    }
+}
+
+/// Transitions of window geometries.
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationConfig {
+    pub enabled: bool,
+    pub duration_ms: u64,
+    pub steps: u16,
+}
+
+impl AnimationConfig {
+    /// The interval between two steps of a transition.
+    pub fn interval(&self) -> std::time::Duration {
+        let steps = std::cmp::max(self.steps, 1) as u64;
+        std::time::Duration::from_millis(self.duration_ms / steps)
+    }
+}
+
+/// The repeat of `PointerCommand::MoveRel` while its key is held, instead of the key repeat of X.
+#[derive(Debug, Clone, Copy)]
+pub struct PointerRepeatConfig {
+    pub enabled: bool,
+    /// The time the key is held before the repeat starts.
+    pub delay: std::time::Duration,
+    pub interval: std::time::Duration,
+    /// The largest multiple of the distance the pointer is moved by at once,
+    /// which it accelerates to while the key is held.
+    pub max_speed: i16,
+}
+
+/// The buttons pressed by the commands like `{Pointer: ClickLeft}`.
+#[derive(Debug, Clone, Copy)]
+pub struct MouseConfig {
+    /// How long a button is held down in a click.
+    pub click_duration_ms: u32,
+}
+
+/// Holding the pointer at the boundaries between monitors (requires XFixes 5 and XInput 2.3).
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeResistanceConfig {
+    pub enabled: bool,
+    /// How far in pixels the pointer is pushed against a boundary to pass it.
+    pub threshold: u16,
+}
+
+/// The initial parameters of the horizontally tiled layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HorizontalLayoutConfig {
+    /// The width of the main window in percent.
+    pub default_ratio: u16,
+    /// The change of the ratio by `{Layout: {Command: "+"}}` / `{Layout: {Command: "-"}}`.
+    pub ratio_step: u16,
+}
+
+/// The settings of each layout.
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutsConfig {
+    pub horizontal: HorizontalLayoutConfig,
+}
+
+/// Placement of the bar on each monitor.
+#[derive(Debug, Clone)]
+pub struct BarConfig {
+    pub margin_x: u16,
+    pub margin_y: u16,
+    /// The width in percentage of the monitor width (excluding margins).
+    pub width: u16,
+    /// Whether to show an error indicator when a spawned command fails to start or a command fails.
+    pub flash_errors: bool,
+    /// Whether to list the windows of the screen in the bar, which are focused by clicking.
+    pub taskbar: bool,
+    /// Whether to hide the bar under the full-screen layout and fullscreen windows,
+    /// until the pointer rests at the top edge of the monitor.
+    pub autohide: bool,
+    /// Whether to draw the bar into a pixmap and copy it to the window at once.
+    pub double_buffer: bool,
+    /// A shell command run when the clock is clicked, e.g. a calendar popup.
+    pub clock_command: Option<String>,
+}
+
+impl BarConfig {
+    /// Returns the geometry of the bar on the monitor of the scale factor.
+    /// The margins are narrowed on a monitor too small for them.
+    pub fn geometry(&self, mon: &Rectangle, scale: u16) -> Rectangle {
+        let height = BAR_HEIGHT * scale;
+        let margin_x = std::cmp::min(self.margin_x, mon.width / 2);
+        let margin_y = std::cmp::min(self.margin_y, mon.height.saturating_sub(height));
+        let avail = mon.width - margin_x * 2;
+        let width = std::cmp::max((avail as u32 * self.width as u32 / 100) as u16, 1);
+        Rectangle {
+            x: mon.x + (margin_x + avail.saturating_sub(width) / 2) as i16,
+            y: mon.y + margin_y as i16,
+            width,
+            height,
+        }
+    }
+
+    /// The height of the space reserved for the bar at the top of the monitor.
+    pub fn reserved_height(&self, scale: u16) -> u16 {
+        BAR_HEIGHT * scale + self.margin_y * 2
+    }
+}
+
+/// The largest scale factor of monitors.
+pub const MAX_SCALE: u16 = 4;
+
+/// The scale factors of the bar, the frames and the borders on HiDPI monitors.
+#[derive(Debug, Clone)]
+pub struct ScaleConfig {
+    /// The factor of the monitors not in `outputs`, or 0 to detect it from their physical size.
+    pub default: u16,
+    /// The factors by the names of outputs, e.g. "eDP-1".
+    pub outputs: HashMap<String, u16>,
+}
+
+impl ScaleConfig {
+    /// Returns the factor of the monitor of the output, which is `width` pixels
+    /// and `width_mm` millimeters wide.
+    pub fn factor(&self, output: &str, width: u16, width_mm: u32) -> u16 {
+        if let Some(&factor) = self.outputs.get(output) {
+            return factor;
+        }
+        if self.default != 0 {
+            return self.default;
+        }
+        // 96 dpi for the factor 1, rounded to the nearest.
+        if width_mm == 0 {
+            return 1;
+        }
+        let dpi = width as u32 * 254 / (width_mm * 10);
+        ((dpi + 48) / 96).clamp(1, MAX_SCALE as u32) as u16
+    }
+}
+
+/// Short messages shown in the middle of the focused monitor.
+#[derive(Debug, Clone, Copy)]
+pub struct OsdConfig {
+    pub enabled: bool,
+    pub timeout_ms: u64,
+}
+
+/// A dmenu-like command used by `Command::WindowMenu`.
+/// It reads the window list from stdin and prints the selected line.
+#[derive(Debug, Clone)]
+pub struct WindowMenuConfig {
+    pub command: String,
+}
+
+/// A dmenu-like command used by `Command::ShowKeybindings`.
+/// It reads the keybindings from stdin, and its output is ignored.
+#[derive(Debug, Clone)]
+pub struct KeybindMenuConfig {
+    pub command: String,
+}
+
+/// Shell commands run by the volume and brightness commands.
+/// The first line of the output (e.g. "50%") is shown in the bar for a while.
+#[derive(Debug, Clone)]
+pub struct MediaConfig {
+    pub volume_up: String,
+    pub volume_down: String,
+    pub mute: String,
+    pub brightness_up: String,
+    pub brightness_down: String,
+}
+
+/// The bar can show only single-digit screen ids.
+pub const MAX_SCREENS: usize = 10;
+
+/// Shell commands run on events of the WM.
+/// They get `DAILY_WID`, `DAILY_CLASS` and `DAILY_SCREEN` describing the subject.
+#[derive(Debug, Clone, Default)]
+pub struct HooksConfig {
+    pub window_mapped: Option<String>,
+    pub window_closed: Option<String>,
+    pub screen_changed: Option<String>,
+    pub layout_changed: Option<String>,
+    pub monitor_changed: Option<String>,
+}
+
+/// What to do when an application asks to activate a window (_NET_ACTIVE_WINDOW).
+/// Requests from pagers and taskbars are always honored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum ActivationPolicy {
+    /// Switch to the screen of the window and focus it.
+    Focus,
+    /// Only mark the window urgent, so that `FocusUrgent` can jump to it.
+    Urgent,
 }
 
+/// Where the monitors come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum MonitorSource {
+    /// RandR, or Xinerama if it has more heads than RandR has monitors.
+    Auto,
+    RandR,
+    Xinerama,
+}
+
+/// A rule applied to new windows whose WM_CLASS matches `class`.
+#[derive(Debug, Clone)]
+pub struct WindowRule {
+    pub class: String,
+    /// The screen to put the window on.
+    pub screen: Option<usize>,
+    /// Whether to switch to the screen.
+    pub follow: bool,
+    /// The opacity in 0.0..=1.0 set to the frame for compositors.
+    pub opacity: Option<f64>,
+    /// Whether `WindowCommand::Close` has to be pressed twice to close the window.
+    pub confirm_close: bool,
+}
+
+/// The configuration loaded from `$XDG_CONFIG_HOME/daily/config.yml`.
 #[derive(Debug)]
 pub struct Config {
     pub keybind: HashMap<(KeybindAction, u16, u8), Command>,
     pub border: BorderConfig,
-    pub background_color: u32,
+    /// Whether to paint empty screens with a window of the theme background.
+    /// Disabled to show wallpapers set on the root window (e.g. by feh).
+    pub background_window: bool,
+    /// The name of the theme used at startup.
+    pub active_theme: String,
+    pub themes: HashMap<String, Theme>,
     pub screens: usize,
+    /// New windows beyond this number go to the next screen with space.
+    pub max_windows_per_screen: Option<usize>,
+    /// Whether to switch to the screen a new window overflowed to.
+    pub overflow_follow: bool,
+    /// Whether switching to the focused screen goes back to the previous one (like `ScreenCommand::Back`).
+    pub screen_back_and_forth: bool,
+    /// The tiling layouts show this number of windows at once, and the rest on other pages.
+    pub windows_per_page: usize,
+    pub activation: ActivationPolicy,
+    pub monitor_source: MonitorSource,
+    pub rules: Vec<WindowRule>,
+    pub resize_mode: ResizeModeConfig,
+    pub rounded_corners: RoundedCornersConfig,
+    pub animation: AnimationConfig,
+    pub pointer_repeat: PointerRepeatConfig,
+    pub mouse: MouseConfig,
+    pub edge_resistance: EdgeResistanceConfig,
+    pub layouts: LayoutsConfig,
+    pub bar: BarConfig,
+    pub scale: ScaleConfig,
+    pub osd: OsdConfig,
+    pub window_menu: WindowMenuConfig,
+    pub keybind_menu: KeybindMenuConfig,
+    pub media: MediaConfig,
+    /// Whether to publish the metrics in _DAILY_METRICS every second,
+    /// not only while `Command::ToggleMetricsHud` shows them.
+    pub metrics: bool,
+    pub hooks: HooksConfig,
 }
 
 impl Config {
-    pub fn load() -> Result<Self> {
+    /// Returns the first rule matching the WM_CLASS.
+    pub fn rule_for(&self, class: &str) -> Option<&WindowRule> {
+        self.rules.iter().find(|rule| rule.class == class)
+    }
+
+    /// Returns the theme selected by `active_theme`.
+    pub fn theme(&self) -> Theme {
+        self.themes[&self.active_theme]
+    }
+
+    /// Merges the default config and the user config files.
+    fn sources() -> Result<::config::Config> {
         use ::config::{File, FileFormat};
         use std::{env, path::PathBuf};
 
@@ -206,7 +1342,7 @@ impl Config {
         xdg_config.push("daily");
         xdg_config.push("config.yml");
 
-        let config = ::config::Config::builder()
+        ::config::Config::builder()
             // Default
             .add_source(File::from_str(DEFAULT_CONFIG, FileFormat::Yaml).required(true))
             // config.yml can be localted on the current working directory.
@@ -222,8 +1358,10 @@ impl Config {
             .build()
             .map_err(|e| Error::InvalidConfig {
                 reason: e.to_string(),
-            })?;
+            })
+    }
 
+    fn from_sources(config: ::config::Config) -> Result<Self> {
         let yaml_repr: parse::ConfigYamlRepr =
             config.try_deserialize().map_err(|e| Error::InvalidConfig {
                 reason: e.to_string(),
@@ -231,6 +1369,18 @@ impl Config {
         yaml_repr.try_into()
     }
 
+    /// Loads the user config, falling back to the defaults for missing entries.
+    pub fn load() -> Result<Self> {
+        Self::from_sources(Self::sources()?)
+    }
+
+    /// Returns the effective config (the defaults merged with the user config) in YAML.
+    /// `keybind` includes the bindings generated from `screen_keys`.
+    pub fn dump() -> Result<String> {
+        dump::to_yaml(Self::sources()?)
+    }
+
+    /// Returns the command bound to the key.
     pub fn keybind_match(&self, on: KeybindAction, modifier: u16, keycode: u8) -> Option<Command> {
         self.keybind.get(&(on, modifier, keycode)).cloned()
     }
@@ -256,3 +1406,170 @@ impl Default for Config {
         yaml_repr.try_into().expect("ill-formed DEFAULT_CONFIG")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FocusCommand, PointerCommand, ScreenCommand};
+    use ::config::{File, FileFormat};
+    use x11rb::protocol::xproto::ModMask;
+
+    /// Parses the user config on top of the default one.
+    fn parse(user_config: &str) -> Result<Config> {
+        let config = ::config::Config::builder()
+            .add_source(File::from_str(DEFAULT_CONFIG, FileFormat::Yaml))
+            .add_source(File::from_str(user_config, FileFormat::Yaml))
+            .build()
+            .unwrap();
+        let yaml_repr: parse::ConfigYamlRepr = config.try_deserialize().unwrap();
+        yaml_repr.try_into()
+    }
+
+    #[test]
+    fn bundled_config_is_valid() {
+        let config = parse(include_str!("../config.yml")).unwrap();
+        assert!(config.hooks.window_mapped.is_none());
+    }
+
+    #[test]
+    fn clock_command_is_optional() {
+        assert!(parse("").unwrap().bar.clock_command.is_none());
+        let config = parse("bar: { clock_command: gsimplecal }").unwrap();
+        assert_eq!(config.bar.clock_command.as_deref(), Some("gsimplecal"));
+    }
+
+    #[test]
+    fn screen_bindings_are_generated() {
+        let config = parse("screens: 7").unwrap();
+        let super_: u16 = ModMask::M4.into();
+        let shift: u16 = ModMask::SHIFT.into();
+        let press = KeybindAction::Press;
+        assert_eq!(
+            config.keybind_match(press, super_, 16),
+            Some(Command::Screen(ScreenCommand::Switch(6)))
+        );
+        assert_eq!(
+            config.keybind_match(press, super_ | shift, 16),
+            Some(Command::Screen(ScreenCommand::MoveWindow(6)))
+        );
+        assert_eq!(config.keybind_match(press, super_, 17), None);
+    }
+
+    #[test]
+    fn flat_command_names_are_still_accepted() {
+        let keybind = "keybind: [
+            { action: Press, mod: [], key: 10, command: FocusNext },
+            { action: Press, mod: [], key: 11, command: {Focus: Next} },
+            { action: Press, mod: [], key: 12, command: {Screen: 3} },
+            { action: Press, mod: [], key: 13, command: {Screen: {Switch: 3}} },
+            { action: Press, mod: [], key: 14, command: {MovePointerRel: [-1, 0]} },
+            { action: Press, mod: [], key: 15, command: {Pointer: {MoveRel: [-1, 0]}} },
+        ]";
+        let config = parse(keybind).unwrap();
+        let command = |key| config.keybind_match(KeybindAction::Press, 0, key).unwrap();
+        assert_eq!(command(10), Command::Focus(FocusCommand::Next));
+        assert_eq!(command(12), Command::Screen(ScreenCommand::Switch(3)));
+        assert_eq!(
+            command(14),
+            Command::Pointer(PointerCommand::MoveRel(-1, 0))
+        );
+        for key in [10, 12, 14] {
+            assert_eq!(command(key), command(key + 1));
+        }
+    }
+
+    #[test]
+    fn out_of_range_screens_are_rejected() {
+        assert!(parse("screens: 0").is_err());
+        assert!(parse("screens: 11").is_err());
+
+        let keybind = "keybind: [{ action: Press, mod: [Super], key: 10, command: {Screen: 5} }]";
+        assert!(parse(keybind).is_err());
+
+        assert!(parse("rules: [{ class: Gimp, screen: 4 }]").is_ok());
+        assert!(parse("rules: [{ class: Gimp, screen: 5 }]").is_err());
+    }
+
+    #[test]
+    fn themes_can_be_overridden_partially() {
+        let config =
+            parse("active_theme: light\nthemes: { dark: { background: '#000000' } }").unwrap();
+        assert_eq!(config.theme(), config.themes["light"]);
+        assert_eq!(config.themes["dark"].background, 0x000000);
+        assert_eq!(config.themes["dark"].border_focused, 0x00f080);
+
+        assert!(parse("active_theme: solarized").is_err());
+    }
+
+    #[test]
+    fn bar_fits_in_small_monitors() {
+        let config = parse("bar: { margin_x: 100, margin_y: 50, width: 50 }").unwrap();
+        let mon = |width, height| Rectangle {
+            x: 1920,
+            y: 0,
+            width,
+            height,
+        };
+        let geometry = config.bar.geometry(&mon(1200, 800), 1);
+        assert_eq!(
+            (geometry.x, geometry.y, geometry.width),
+            (1920 + 350, 50, 500)
+        );
+
+        let geometry = config.bar.geometry(&mon(150, 40), 1);
+        assert_eq!((geometry.x, geometry.y, geometry.width), (1920 + 75, 24, 1));
+        let geometry = config.bar.geometry(&mon(0, 0), 2);
+        assert_eq!((geometry.x, geometry.y, geometry.width), (1920, 0, 1));
+
+        assert!(parse("bar: { margin_x: 16384 }").is_err());
+        assert!(parse("bar: { margin_y: 16384 }").is_err());
+    }
+
+    #[test]
+    fn layout_settings_are_validated() {
+        let config = parse("layouts: { horizontal: { default_ratio: 60 } }").unwrap();
+        assert_eq!(config.layouts.horizontal.default_ratio, 60);
+        assert_eq!(config.layouts.horizontal.ratio_step, 5);
+
+        assert!(parse("layouts: { horizontal: { default_ratio: 100 } }").is_err());
+        assert!(parse("layouts: { horizontal: { ratio_step: 0 } }").is_err());
+    }
+
+    #[test]
+    fn scale_is_detected_unless_configured() {
+        let config = parse("scale: { outputs: { eDP-1: 3 } }").unwrap();
+        // 1920 pixels in 508 mm is 96 dpi, and 3840 pixels in 344 mm is 283 dpi.
+        assert_eq!(config.scale.factor("HDMI-1", 1920, 508), 1);
+        assert_eq!(config.scale.factor("DP-1", 3840, 344), 3);
+        assert_eq!(config.scale.factor("DP-1", 1024, 0), 1);
+        assert_eq!(config.scale.factor("eDP-1", 1920, 508), 3);
+
+        let config = parse("scale: { default: 2 }").unwrap();
+        assert_eq!(config.scale.factor("HDMI-1", 1920, 508), 2);
+
+        assert!(parse("scale: { default: 5 }").is_err());
+        assert!(parse("scale: { outputs: { eDP-1: 0 } }").is_err());
+    }
+
+    #[test]
+    fn dumped_config_is_loaded_as_is() {
+        let user_config = "themes: { dark: { border_focused: '#00F080' } }";
+        let sources = ::config::Config::builder()
+            .add_source(File::from_str(DEFAULT_CONFIG, FileFormat::Yaml))
+            .add_source(File::from_str(user_config, FileFormat::Yaml))
+            .build()
+            .unwrap();
+        let yaml = dump::to_yaml(sources).unwrap();
+        assert!(yaml.contains("border_focused: \"#00f080\""));
+        assert!(yaml.contains("SwapWith: 0"));
+
+        let dumped = ::config::Config::builder()
+            .add_source(File::from_str(&yaml, FileFormat::Yaml))
+            .build()
+            .unwrap();
+        let dumped = Config::from_sources(dumped).unwrap();
+        let config = parse(user_config).unwrap();
+        assert_eq!(dumped.keybind, config.keybind);
+        assert_eq!(dumped.themes, config.themes);
+    }
+}
@@ -1,67 +1,230 @@
-use log::debug;
-use std::sync::Arc;
+use log::{debug, error, info, warn};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
 
 use crate::atom::AtomCollection;
-use crate::config::Config;
+use crate::config::{Config, Theme};
 use crate::error::{Error, Result};
+use crate::extension::Extensions;
 
 use x11rb::connection::Connection;
-use x11rb::protocol::xproto::{ConnectionExt as _, InputFocus, Window as Wid};
-use x11rb::rust_connection::RustConnection;
+use x11rb::protocol::xproto::{
+    ConnectionExt as _, CreateGCAux, Font, Gcontext, InputFocus, Screen, Window as Wid,
+};
+use x11rb::protocol::Event;
+
+/// The connection type used to talk with the X server.
+#[cfg(all(not(test), not(feature = "xcb")))]
+pub type XConnection = x11rb::rust_connection::RustConnection;
+/// The connection through libxcb, whose `xcb_connection_t` can be handed to other libraries
+/// with `get_raw_xcb_connection`.
+#[cfg(all(not(test), feature = "xcb"))]
+pub type XConnection = x11rb::xcb_ffi::XCBConnection;
+/// Unit tests run against a fake connection.
+#[cfg(test)]
+pub type XConnection = crate::mock::MockConnection;
 
 pub type Context = Arc<ContextInner>;
 
-pub fn init<S>(display_name: S) -> Result<Context>
+/// The value of `focus` meaning it has to be asked to the server.
+/// It is never a window since the top three bits of resource IDs are zero.
+const FOCUS_UNKNOWN: u32 = u32::MAX;
+
+/// The core font for the text on scaled monitors, which is preferred over "fixed".
+const LARGE_FONT: &[u8] = b"12x24";
+
+/// Returns the width of the characters of the font for the scale of a monitor.
+pub fn char_width(scale: u16) -> u16 {
+    if scale >= 2 {
+        12
+    } else {
+        6
+    }
+}
+
+/// Opens the font for the text on a monitor of the scale.
+/// "fixed" is used instead if the large one is not installed.
+pub fn open_font<C: Connection>(conn: &C, scale: u16) -> Result<Font> {
+    let font = conn.generate_id()?;
+    if scale >= 2 {
+        if conn.open_font(font, LARGE_FONT)?.check().is_ok() {
+            return Ok(font);
+        }
+        warn!(
+            "font {:?} is not found",
+            String::from_utf8_lossy(LARGE_FONT)
+        );
+    }
+    conn.open_font(font, b"fixed")?.check()?;
+    Ok(font)
+}
+
+/// Opens a new connection with the X server, e.g. for a thread of its own.
+/// Returns the X screen of the display name (e.g. 1 of ":0.1") as well.
+pub fn connect(display_name: Option<&str>) -> Result<(XConnection, usize)> {
+    #[cfg(all(not(test), feature = "xcb"))]
+    let display_name = display_name
+        .map(std::ffi::CString::new)
+        .transpose()
+        .map_err(|_| Error::ConnectionFailed)?;
+    #[cfg(all(not(test), feature = "xcb"))]
+    let display_name = display_name.as_deref();
+
+    XConnection::connect(display_name).map_err(|_| Error::ConnectionFailed)
+}
+
+/// Returns the name of the X screen on the display, e.g. ":0.1" for `(":0", 1)`.
+fn display_for_screen(display: &str, screen_num: usize) -> String {
+    let host_len = display.rfind(':').map_or(0, |colon| colon + 1);
+    let number = display[host_len..].split('.').next().unwrap_or("");
+    format!("{}{}.{}", &display[..host_len], number, screen_num)
+}
+
+/// Makes a context for each X screen of the display, which has more than one in Zaphod mode.
+/// Each of them has a connection of its own, which gets only the events of its root window.
+pub fn init<S>(display_name: S, extensions: Extensions) -> Result<Vec<Context>>
 where
     S: Into<Option<&'static str>>,
 {
-    let inner = ContextInner::new(display_name)?;
-    Ok(Arc::new(inner))
+    let display_name = display_name.into();
+    let screens = connect(display_name)?.0.setup().roots.len();
+    let base = display_name
+        .map(str::to_owned)
+        .or_else(|| std::env::var("DISPLAY").ok());
+    let displays: Vec<Option<String>> = match base {
+        Some(base) if screens > 1 => {
+            info!("managing {} X screens of {}", screens, base);
+            (0..screens)
+                .map(|n| Some(display_for_screen(&base, n)))
+                .collect()
+        }
+        _ => vec![display_name.map(str::to_owned)],
+    };
+
+    let extensions = Arc::new(extensions);
+    displays
+        .iter()
+        .map(|display| {
+            let mut inner = ContextInner::new(display.as_deref())?;
+            inner.extensions = extensions.clone();
+            Ok(Arc::new(inner))
+        })
+        .collect()
 }
 
 #[derive(Debug)]
 pub struct ContextInner {
-    pub conn: RustConnection,
+    pub conn: XConnection,
     pub config: Config,
+    /// The X screen of `root`, which is not 0 only in Zaphod mode.
+    pub screen_num: usize,
     pub root: Wid,
+    /// The name of the display, including the X screen in Zaphod mode.
     pub display: Option<String>,
     pub atom: AtomCollection,
+    /// The GC with the "fixed" font shared by the frames and the tab strips,
+    /// whose colors are set before each drawing.
+    pub gc: Gcontext,
+    /// The same as `gc` but with the large font, for the monitors scaled up.
+    pub large_gc: Gcontext,
+    /// Shared by the contexts of all the X screens.
+    pub extensions: Arc<Extensions>,
+    /// Why the user config was rejected, if the defaults are used instead.
+    pub config_error: Option<String>,
+    /// The current theme, which can be switched at runtime.
+    theme: RwLock<Theme>,
+    /// The input focus last set or seen, which saves the round trips of `get_focused_window`.
+    focus: AtomicU32,
 }
 
 impl ContextInner {
-    fn new<S>(display_name: S) -> Result<Self>
-    where
-        S: Into<Option<&'static str>>,
-    {
-        let config = Config::load()?;
+    fn new(display_name: Option<&str>) -> Result<Self> {
+        // A broken config should not prevent the WM from starting (or restarting).
+        let (config, config_error) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(err) => {
+                error!("{}", err);
+                (Config::default(), Some(err.to_string()))
+            }
+        };
 
         // Connect with the X server
-        let display_name = display_name.into();
         let display = display_name.map(str::to_owned);
-        let conn = RustConnection::connect(display_name)
-            .map_err(|_| Error::ConnectionFailed)?
-            .0;
+        let (conn, screen_num) = connect(display_name)?;
 
-        // Get a root window on the first screen.
-        let screen = conn.setup().roots.get(0).ok_or(Error::NoScreen)?;
+        let mut inner = Self::with_connection(conn, screen_num, config, display)?;
+        inner.config_error = config_error;
+        Ok(inner)
+    }
+
+    pub fn with_connection(
+        conn: XConnection,
+        screen_num: usize,
+        config: Config,
+        display: Option<String>,
+    ) -> Result<Self> {
+        let screen = conn.setup().roots.get(screen_num).ok_or(Error::NoScreen)?;
         let root = screen.root;
         debug!("root = {:08X}", root);
 
         let atom = AtomCollection::new(&conn)?.reply()?;
 
+        let mut gcs = [0; 2];
+        for (gc, scale) in gcs.iter_mut().zip([1, 2]) {
+            *gc = conn.generate_id()?;
+            let font = open_font(&conn, scale)?;
+
+            let aux = CreateGCAux::new().font(font);
+            conn.create_gc(*gc, root, &aux)?;
+
+            conn.close_font(font)?;
+        }
+        let [gc, large_gc] = gcs;
+
+        let theme = RwLock::new(config.theme());
         Ok(Self {
             conn,
             config,
+            screen_num,
             root,
             display,
             atom,
+            gc,
+            large_gc,
+            extensions: Arc::default(),
+            config_error: None,
+            theme,
+            focus: AtomicU32::new(FOCUS_UNKNOWN),
         })
     }
 
+    /// The X screen managed with this context.
+    pub fn screen(&self) -> &Screen {
+        &self.conn.setup().roots[self.screen_num]
+    }
+
+    pub fn theme(&self) -> Theme {
+        *self.theme.read().unwrap()
+    }
+
+    pub fn set_theme(&self, theme: Theme) {
+        *self.theme.write().unwrap() = theme;
+    }
+
+    /// Returns the GC whose font fits the scale of a monitor.
+    pub fn gc_for(&self, scale: u16) -> Gcontext {
+        if scale >= 2 {
+            self.large_gc
+        } else {
+            self.gc
+        }
+    }
+
     pub fn focus_window(&self, win: Wid) -> Result<()> {
         debug!("set_input_focus --> {:08X}", win);
         self.conn
             .set_input_focus(InputFocus::POINTER_ROOT, win, x11rb::CURRENT_TIME)?;
+        self.focus.store(win, Ordering::Relaxed);
         Ok(())
     }
 
@@ -70,7 +233,71 @@ impl ContextInner {
             wid != InputFocus::POINTER_ROOT.into() && wid != InputFocus::NONE.into()
         }
 
-        let focus = self.conn.get_input_focus()?.reply()?.focus;
+        let mut focus = self.focus.load(Ordering::Relaxed);
+        if focus == FOCUS_UNKNOWN {
+            focus = self.conn.get_input_focus()?.reply()?.focus;
+            self.focus.store(focus, Ordering::Relaxed);
+        }
         Ok(if is_window(focus) { Some(focus) } else { None })
     }
+
+    /// Forgets the cached focus on events telling that it may have changed,
+    /// e.g. by clients, by the focused window going away, or a failed `focus_window`.
+    pub fn observe_event(&self, event: &Event) {
+        if matches!(
+            event,
+            Event::FocusIn(_)
+                | Event::FocusOut(_)
+                | Event::UnmapNotify(_)
+                | Event::DestroyNotify(_)
+                | Event::Error(_)
+        ) {
+            self.focus.store(FOCUS_UNKNOWN, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for ContextInner {
+    fn drop(&mut self) {
+        let _ = self.conn.free_gc(self.gc);
+        let _ = self.conn.free_gc(self.large_gc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use x11rb::protocol::xproto::{FocusInEvent, NotifyDetail, NotifyMode, FOCUS_IN_EVENT};
+
+    #[test]
+    fn focus_is_cached_until_an_event_tells_otherwise() {
+        let ctx = mock::context(&[]);
+        let a = ctx.conn.create_client(10, 10);
+        let b = ctx.conn.create_client(10, 10);
+        ctx.focus_window(a).unwrap();
+
+        // A client takes the focus.
+        ctx.conn
+            .set_input_focus(InputFocus::PARENT, b, x11rb::CURRENT_TIME)
+            .unwrap();
+        assert_eq!(ctx.get_focused_window().unwrap(), Some(a));
+
+        let focus_in = FocusInEvent {
+            response_type: FOCUS_IN_EVENT,
+            sequence: 0,
+            detail: NotifyDetail::NONLINEAR_VIRTUAL,
+            event: b,
+            mode: NotifyMode::NORMAL,
+        };
+        ctx.observe_event(&Event::FocusIn(focus_in));
+        assert_eq!(ctx.get_focused_window().unwrap(), Some(b));
+    }
+
+    #[test]
+    fn display_names_of_the_x_screens() {
+        assert_eq!(display_for_screen(":0", 1), ":0.1");
+        assert_eq!(display_for_screen(":0.1", 0), ":0.0");
+        assert_eq!(display_for_screen("localhost:10.0", 2), "localhost:10.2");
+    }
 }
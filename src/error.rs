@@ -11,12 +11,16 @@ pub enum Error {
 
     #[error("Cannot connect with the X server.")]
     ConnectionFailed,
-    #[error("Another window manager already exists.")]
-    WmAlreadyExists,
+    #[error("The connection with the X server has been lost: {0}")]
+    ConnectionLost(x11rb::errors::ConnectionError),
+    #[error("Another window manager already exists: {owner}")]
+    WmAlreadyExists { owner: String },
     #[error("Another client has already grabbed the key we want to use.")]
     KeyAlreadyGrabbed,
     #[error("Another client has already grabbed the button we want to use.")]
     ButtonAlreadyGrabbed,
+    #[error("Another client has already grabbed the keyboard.")]
+    KeyboardAlreadyGrabbed,
 
     #[error("No screen available.")]
     NoScreen,
@@ -28,6 +32,8 @@ pub enum Error {
 
     #[error("The other side of the stream was already closed.")]
     BrokenChannel,
+    #[error("The bar has been disabled because of repeated failures.")]
+    BarDisabled,
 
     #[error("Invalid config: {reason}")]
     InvalidConfig { reason: String },
@@ -36,6 +42,14 @@ pub enum Error {
 pub type Result<T> = std::result::Result<T, Error>;
 
 impl Error {
+    /// Whether the WM has to stop (or restart), unlike on the failures of a command.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Error::Quit | Error::Restart | Error::ConnectionFailed | Error::ConnectionLost(_)
+        )
+    }
+
     pub fn x11_error_kind(&self) -> Option<ErrorKind> {
         match self {
             Error::X11(ReplyOrIdError::X11Error(err)) => Some(err.error_kind),
@@ -46,6 +60,25 @@ impl Error {
 
 impl<T: Into<ReplyOrIdError>> From<T> for Error {
     fn from(x: T) -> Error {
-        Error::X11(Into::<ReplyOrIdError>::into(x))
+        // Requests fail this way once the X server has gone.
+        match Into::<ReplyOrIdError>::into(x) {
+            ReplyOrIdError::ConnectionError(err) => Error::ConnectionLost(err),
+            err => Error::X11(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::errors::ConnectionError;
+
+    #[test]
+    fn connection_errors_mean_the_server_has_gone() {
+        let err = Error::from(ConnectionError::UnknownError);
+        assert!(matches!(err, Error::ConnectionLost(_)));
+        assert_eq!(err.x11_error_kind(), None);
+        assert!(err.is_fatal());
+        assert!(!Error::NoMonitor.is_fatal());
     }
 }
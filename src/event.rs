@@ -1,6 +1,6 @@
 use crate::error::Result;
 use log::{trace, warn};
-use x11rb::protocol::{randr, xproto::*, Event};
+use x11rb::protocol::{randr, xinput, xkb, xproto::*, Event};
 
 pub trait EventHandler {
     fn handle_event(&mut self, event: Event) -> Result<()>;
@@ -21,6 +21,7 @@ pub trait EventHandlerMethods {
     event_handler_ignore!(on_button_press, ButtonPressEvent);
     event_handler_ignore!(on_button_release, ButtonReleaseEvent);
     event_handler_ignore!(on_motion_notify, MotionNotifyEvent);
+    event_handler_ignore!(on_enter_notify, EnterNotifyEvent);
     event_handler_ignore!(on_map_request, MapRequestEvent);
     event_handler_ignore!(on_map_notify, MapNotifyEvent);
     event_handler_ignore!(on_unmap_notify, UnmapNotifyEvent);
@@ -32,7 +33,10 @@ pub trait EventHandlerMethods {
     event_handler_ignore!(on_focus_in, FocusInEvent);
     event_handler_ignore!(on_focus_out, FocusInEvent);
     event_handler_ignore!(on_client_message, ClientMessageEvent);
+    event_handler_ignore!(on_property_notify, PropertyNotifyEvent);
     event_handler_ignore!(on_randr_notify, randr::NotifyEvent);
+    event_handler_ignore!(on_xkb_state_notify, xkb::StateNotifyEvent);
+    event_handler_ignore!(on_barrier_hit, xinput::BarrierHitEvent);
 }
 
 impl<T: EventHandlerMethods> EventHandler for T {
@@ -44,6 +48,7 @@ impl<T: EventHandlerMethods> EventHandler for T {
             Event::ButtonPress(e) => self.on_button_press(e),
             Event::ButtonRelease(e) => self.on_button_release(e),
             Event::MotionNotify(e) => self.on_motion_notify(e),
+            Event::EnterNotify(e) => self.on_enter_notify(e),
             Event::MapRequest(e) => self.on_map_request(e),
             Event::MapNotify(e) => self.on_map_notify(e),
             Event::UnmapNotify(e) => self.on_unmap_notify(e),
@@ -55,7 +60,10 @@ impl<T: EventHandlerMethods> EventHandler for T {
             Event::FocusIn(e) => self.on_focus_in(e),
             Event::FocusOut(e) => self.on_focus_out(e),
             Event::ClientMessage(e) => self.on_client_message(e),
+            Event::PropertyNotify(e) => self.on_property_notify(e),
             Event::RandrNotify(e) => self.on_randr_notify(e),
+            Event::XkbStateNotify(e) => self.on_xkb_state_notify(e),
+            Event::XinputBarrierHit(e) => self.on_barrier_hit(e),
             e => {
                 warn!("unhandled event: {:?}", e);
                 Ok(())
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::layout::Layout;
+use crate::winman::WinMan;
+
+/// Creates a layout for each screen.
+pub type LayoutFactory = Box<dyn Fn() -> Box<dyn Layout> + Send + Sync>;
+
+/// Runs a command invoked by `Command::Extension(name)`.
+pub type CommandHandler = Box<dyn Fn(&mut WinMan) -> Result<()> + Send + Sync>;
+
+/// Layouts and commands compiled into a custom binary.
+#[derive(Default)]
+pub struct Extensions {
+    layouts: Vec<LayoutFactory>,
+    commands: HashMap<String, CommandHandler>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a layout next to the built-in ones.
+    pub fn layout<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> Box<dyn Layout> + Send + Sync + 'static,
+    {
+        self.layouts.push(Box::new(factory));
+        self
+    }
+
+    /// Registers a command which can be bound as `{Extension: <name>}` in the config.
+    pub fn command<F>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(&mut WinMan) -> Result<()> + Send + Sync + 'static,
+    {
+        self.commands.insert(name.to_owned(), Box::new(handler));
+        self
+    }
+
+    pub(crate) fn layouts(&self) -> impl Iterator<Item = Box<dyn Layout>> + '_ {
+        self.layouts.iter().map(|factory| factory())
+    }
+
+    pub(crate) fn command_handler(&self, name: &str) -> Option<&CommandHandler> {
+        self.commands.get(name)
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut commands: Vec<_> = self.commands.keys().collect();
+        commands.sort_unstable();
+        f.debug_struct("Extensions")
+            .field("layouts", &self.layouts.len())
+            .field("commands", &commands)
+            .finish()
+    }
+}
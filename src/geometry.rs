@@ -0,0 +1,191 @@
+//! Rectangles on the root window and the arithmetic on them.
+
+use x11rb::protocol::xproto::{GetGeometryReply, Rectangle};
+
+/// A rectangle on the root window, with room for the arithmetic
+/// which would overflow the i16 / u16 of [`Rectangle`].
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub const fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The x coordinate just past the right edge.
+    pub fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    /// The y coordinate just past the bottom edge.
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        (
+            self.x + (self.width / 2) as i32,
+            self.y + (self.height / 2) as i32,
+        )
+    }
+
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        (self.x..self.right()).contains(&x) && (self.y..self.bottom()).contains(&y)
+    }
+
+    /// Returns the part shared with `other`, if any.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let (right, bottom) = (
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        );
+        (x < right && y < bottom).then(|| Rect::new(x, y, (right - x) as u32, (bottom - y) as u32))
+    }
+
+    pub fn offset(&self, dx: i32, dy: i32) -> Rect {
+        Rect {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..*self
+        }
+    }
+
+    /// Returns a rectangle of the size (at most that of `self`) at the center of `self`.
+    pub fn centered(&self, width: u32, height: u32) -> Rect {
+        let (width, height) = (width.min(self.width), height.min(self.height));
+        Rect {
+            x: self.x + ((self.width - width) / 2) as i32,
+            y: self.y + ((self.height - height) / 2) as i32,
+            width,
+            height,
+        }
+    }
+
+    /// Moves the rectangle into `bounds`, shrinking it to `bounds` if it is larger.
+    pub fn clamp(&self, bounds: &Rect) -> Rect {
+        let (width, height) = (self.width.min(bounds.width), self.height.min(bounds.height));
+        Rect {
+            x: self.x.min(bounds.right() - width as i32).max(bounds.x),
+            y: self.y.min(bounds.bottom() - height as i32).max(bounds.y),
+            width,
+            height,
+        }
+    }
+
+    /// Shrinks the rectangle by `n` pixels on each side, keeping at least 1 pixel.
+    pub fn shrink(&self, n: u32) -> Rect {
+        Rect {
+            x: self.x + n as i32,
+            y: self.y + n as i32,
+            width: self.width.saturating_sub(n * 2).max(1),
+            height: self.height.saturating_sub(n * 2).max(1),
+        }
+    }
+
+    /// Splits the rectangle into the left `width` pixels (at most all of it) and the rest.
+    pub fn split_x(&self, width: u32) -> (Rect, Rect) {
+        let width = width.min(self.width);
+        let left = Rect { width, ..*self };
+        let right = Rect {
+            x: self.x + width as i32,
+            width: self.width - width,
+            ..*self
+        };
+        (left, right)
+    }
+
+    /// Splits the rectangle into the top `height` pixels (at most all of it) and the rest.
+    pub fn split_y(&self, height: u32) -> (Rect, Rect) {
+        let height = height.min(self.height);
+        let top = Rect { height, ..*self };
+        let bottom = Rect {
+            y: self.y + height as i32,
+            height: self.height - height,
+            ..*self
+        };
+        (top, bottom)
+    }
+}
+
+impl From<Rectangle> for Rect {
+    fn from(rect: Rectangle) -> Self {
+        Rect::new(
+            rect.x as i32,
+            rect.y as i32,
+            rect.width as u32,
+            rect.height as u32,
+        )
+    }
+}
+
+/// The geometry of a window without its border.
+impl From<GetGeometryReply> for Rect {
+    fn from(geo: GetGeometryReply) -> Self {
+        Rect::new(
+            geo.x as i32,
+            geo.y as i32,
+            geo.width as u32,
+            geo.height as u32,
+        )
+    }
+}
+
+/// Saturates the values which do not fit in the protocol.
+impl From<Rect> for Rectangle {
+    fn from(rect: Rect) -> Self {
+        let pos = |v: i32| v.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        let size = |v: u32| v.min(u16::MAX as u32) as u16;
+        Rectangle {
+            x: pos(rect.x),
+            y: pos(rect.y),
+            width: size(rect.width),
+            height: size(rect.height),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersection_and_points() {
+        let a = Rect::new(0, 0, 100, 50);
+        let b = Rect::new(80, 40, 100, 100);
+        assert_eq!(a.intersection(&b), Some(Rect::new(80, 40, 20, 10)));
+        assert_eq!(a.intersection(&Rect::new(100, 0, 10, 10)), None);
+
+        assert!(a.contains_point(0, 49));
+        assert!(!a.contains_point(100, 0));
+        assert_eq!(b.center(), (130, 90));
+    }
+
+    #[test]
+    fn clamp_and_split() {
+        let bounds = Rect::new(1920, 0, 1280, 1024);
+        let off = Rect::new(3000, -100, 640, 2000);
+        assert_eq!(off.clamp(&bounds), Rect::new(2560, 0, 640, 1024));
+        assert_eq!(bounds.centered(640, 480), Rect::new(2240, 272, 640, 480));
+
+        let (left, right) = bounds.split_x(1000);
+        assert_eq!((left.width, right.x, right.width), (1000, 2920, 280));
+        let (top, bottom) = bounds.split_y(2000);
+        assert_eq!((top, bottom.height), (bounds, 0));
+        assert_eq!(Rect::new(0, 0, 3, 100).shrink(2), Rect::new(2, 2, 1, 96));
+
+        let huge = Rect::new(-40000, 0, 70000, 10);
+        assert_eq!(Rectangle::from(huge).x, i16::MIN);
+        assert_eq!(Rectangle::from(huge).width, u16::MAX);
+    }
+}
@@ -0,0 +1,49 @@
+use log::debug;
+
+use crate::config::HooksConfig;
+use crate::error::Result;
+use crate::spawn_process;
+use crate::window::Window;
+
+/// WM events which run the commands in the `hooks` section of the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    WindowMapped,
+    WindowClosed,
+    ScreenChanged,
+    LayoutChanged,
+    MonitorChanged,
+}
+
+impl Hook {
+    fn command(self, config: &HooksConfig) -> Option<&str> {
+        let cmd = match self {
+            Hook::WindowMapped => &config.window_mapped,
+            Hook::WindowClosed => &config.window_closed,
+            Hook::ScreenChanged => &config.screen_changed,
+            Hook::LayoutChanged => &config.layout_changed,
+            Hook::MonitorChanged => &config.monitor_changed,
+        };
+        cmd.as_deref()
+    }
+}
+
+pub type Envs = Vec<(&'static str, String)>;
+
+/// Environment variables describing the window on the screen.
+pub fn window_envs(win: &Window, screen: usize) -> Envs {
+    vec![
+        ("DAILY_WID", format!("0x{:08x}", win.inner())),
+        ("DAILY_CLASS", win.class().to_owned()),
+        ("DAILY_SCREEN", screen.to_string()),
+    ]
+}
+
+/// Runs the command configured for the hook in the background, if any.
+pub fn run(config: &HooksConfig, hook: Hook, envs: &[(&str, String)]) -> Result<()> {
+    if let Some(cmd) = hook.command(config) {
+        debug!("hook {:?}: {:?}, envs = {:?}", hook, cmd, envs);
+        spawn_process(cmd, envs, || ());
+    }
+    Ok(())
+}
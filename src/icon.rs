@@ -0,0 +1,232 @@
+//! Icons of windows from _NET_WM_ICON, drawn in the titles of frames and tabs.
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Window as Wid, *};
+
+use crate::context::Context;
+use crate::error::Result;
+
+/// The width and height of the icons, which fit in the title bars.
+pub const ICON_SIZE: u16 = 16;
+
+/// Picks the smallest icon not smaller than ICON_SIZE (or the largest one) from the value of
+/// _NET_WM_ICON, which is a list of (width, height, ARGB pixels), and scales it to
+/// ICON_SIZE x ICON_SIZE ARGB pixels.
+pub fn scale(data: &[u32]) -> Option<Vec<u32>> {
+    let size = ICON_SIZE as usize;
+    let mut icons = Vec::new();
+    let mut rest = data;
+    while let [width, height, pixels @ ..] = rest {
+        let (width, height) = (*width as usize, *height as usize);
+        let len = width.checked_mul(height)?;
+        if width == 0 || height == 0 || pixels.len() < len {
+            break;
+        }
+        icons.push((width, height, &pixels[..len]));
+        rest = &pixels[len..];
+    }
+    let &(width, height, pixels) = icons
+        .iter()
+        .filter(|(w, h, _)| *w.min(h) >= size)
+        .min_by_key(|(w, h, _)| w * h)
+        .or_else(|| icons.iter().max_by_key(|(w, h, _)| w * h))?;
+
+    // The source range covered by the `i`-th of the scaled pixels, at least one pixel.
+    let span = |i: usize, len: usize| {
+        let start = i * len / size;
+        (start, ((i + 1) * len / size).max(start + 1))
+    };
+
+    // Each pixel is the average of the source pixels it covers, weighted by their alpha.
+    let mut scaled = Vec::with_capacity(size * size);
+    for ty in 0..size {
+        let (y0, y1) = span(ty, height);
+        for tx in 0..size {
+            let (x0, x1) = span(tx, width);
+            let mut sum = [0u64; 4]; // alpha, premultiplied red, green and blue
+            for y in y0..y1 {
+                for &argb in &pixels[y * width + x0..y * width + x1] {
+                    let alpha = (argb >> 24) as u64;
+                    sum[0] += alpha;
+                    for (i, shift) in [16, 8, 0].into_iter().enumerate() {
+                        sum[i + 1] += ((argb >> shift) & 0xFF) as u64 * alpha;
+                    }
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u64;
+            let mut argb = ((sum[0] / count) as u32) << 24;
+            for (i, shift) in [16, 8, 0].into_iter().enumerate() {
+                argb |= (sum[i + 1].checked_div(sum[0]).unwrap_or(0) as u32) << shift;
+            }
+            scaled.push(argb);
+        }
+    }
+    Some(scaled)
+}
+
+/// Blends the ARGB pixels with the background into the bytes of 32-bit RGB pixels.
+fn blend(pixels: &[u32], background: u32, lsb_first: bool) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|&argb| {
+            let alpha = argb >> 24;
+            let mut rgb = 0;
+            for shift in [16, 8, 0] {
+                let fg = (argb >> shift) & 0xFF;
+                let bg = (background >> shift) & 0xFF;
+                rgb |= ((fg * alpha + bg * (255 - alpha)) / 255) << shift;
+            }
+            if lsb_first {
+                rgb.to_le_bytes()
+            } else {
+                rgb.to_be_bytes()
+            }
+        })
+        .collect()
+}
+
+/// Puts the scaled icon on `drawable` over the background color.
+/// Nothing is drawn unless the pixels of the root depth are 32 bits.
+pub fn put(
+    ctx: &Context,
+    drawable: Drawable,
+    x: i16,
+    y: i16,
+    pixels: &[u32],
+    background: u32,
+) -> Result<()> {
+    let setup = ctx.conn.setup();
+    let depth = ctx.screen().root_depth;
+    let supported = setup
+        .pixmap_formats
+        .iter()
+        .any(|format| format.depth == depth && format.bits_per_pixel == 32);
+    if !supported {
+        return Ok(());
+    }
+
+    let lsb_first = setup.image_byte_order == ImageOrder::LSB_FIRST;
+    let data = blend(pixels, background, lsb_first);
+    ctx.conn.put_image(
+        ImageFormat::Z_PIXMAP,
+        drawable,
+        ctx.gc,
+        ICON_SIZE,
+        ICON_SIZE,
+        x,
+        y,
+        0,
+        depth,
+        &data,
+    )?;
+    Ok(())
+}
+
+/// The icon of a window, rendered on a pixmap again only when the background is changed.
+pub struct Icon {
+    ctx: Context,
+    pixels: Vec<u32>,
+    pixmap: Pixmap,
+    background: Option<u32>,
+}
+
+impl Icon {
+    /// Reads _NET_WM_ICON of the window, if any.
+    pub fn fetch(ctx: &Context, wid: Wid) -> Result<Option<Self>> {
+        let net_wm_icon = ctx.atom._NET_WM_ICON;
+        let reply = ctx
+            .conn
+            .get_property(false, wid, net_wm_icon, AtomEnum::CARDINAL, 0, u32::MAX)?
+            .reply()?;
+        let data: Vec<u32> = match reply.value32() {
+            Some(iter) => iter.collect(),
+            None => return Ok(None),
+        };
+        let pixels = match scale(&data) {
+            Some(pixels) => pixels,
+            None => return Ok(None),
+        };
+
+        let pixmap = ctx.conn.generate_id()?;
+        let depth = ctx.screen().root_depth;
+        ctx.conn
+            .create_pixmap(depth, pixmap, ctx.root, ICON_SIZE, ICON_SIZE)?;
+        Ok(Some(Self {
+            ctx: ctx.clone(),
+            pixels,
+            pixmap,
+            background: None,
+        }))
+    }
+
+    /// The ICON_SIZE x ICON_SIZE ARGB pixels.
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    /// Copies the icon to `drawable` over the background color.
+    pub fn draw(&mut self, drawable: Drawable, x: i16, y: i16, background: u32) -> Result<()> {
+        if self.background != Some(background) {
+            put(&self.ctx, self.pixmap, 0, 0, &self.pixels, background)?;
+            self.background = Some(background);
+        }
+        self.ctx.conn.copy_area(
+            self.pixmap,
+            drawable,
+            self.ctx.gc,
+            0,
+            0,
+            x,
+            y,
+            ICON_SIZE,
+            ICON_SIZE,
+        )?;
+        Ok(())
+    }
+}
+
+impl Drop for Icon {
+    fn drop(&mut self) {
+        let _ = self.ctx.conn.free_pixmap(self.pixmap);
+    }
+}
+
+impl std::fmt::Debug for Icon {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Icon {{ pixmap: {:08X} }}", self.pixmap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icon_is_picked_and_scaled() {
+        let size = ICON_SIZE as u32;
+        // A 8x8 icon and a 32x32 one whose left half is opaque red and right half transparent.
+        let mut data = vec![8, 8];
+        data.extend(std::iter::repeat_n(0xFF00FF00, 64));
+        data.extend([size * 2, size * 2]);
+        for _ in 0..size * 2 {
+            data.extend(std::iter::repeat_n(0xFFFF0000, size as usize));
+            data.extend(std::iter::repeat_n(0x00000000, size as usize));
+        }
+
+        let pixels = scale(&data).unwrap();
+        assert_eq!(pixels.len(), (size * size) as usize);
+        assert_eq!(pixels[0], 0xFFFF0000);
+        assert_eq!(pixels[size as usize - 1] >> 24, 0);
+
+        // Only the smaller one is scaled up.
+        let pixels = scale(&data[..66]).unwrap();
+        assert!(pixels.iter().all(|&p| p == 0xFF00FF00));
+
+        // Broken values are ignored.
+        assert_eq!(scale(&[16, 16, 0]), None);
+        assert_eq!(
+            blend(&[0x80FFFFFF], 0x000000, true),
+            vec![0x80, 0x80, 0x80, 0]
+        );
+    }
+}
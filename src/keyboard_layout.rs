@@ -0,0 +1,126 @@
+use log::{debug, warn};
+
+use x11rb::connection::RequestConnection as _;
+use x11rb::protocol::xkb::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::ConnectionExt as _;
+
+use crate::context::Context;
+use crate::error::Result;
+
+// Components of the XKB symbols name which are not layouts, e.g. "inet(evdev)".
+const NON_LAYOUTS: &[&str] = &[
+    "pc",
+    "inet",
+    "group",
+    "compose",
+    "ctrl",
+    "caps",
+    "capslock",
+    "altwin",
+    "level3",
+    "level5",
+    "lv3",
+    "terminate",
+    "keypad",
+    "kpdl",
+    "nbsp",
+    "shift",
+    "eurosign",
+    "srvr_ctrl",
+];
+
+/// Extracts the layouts from the XKB symbols name, e.g. ["us", "de"] from
+/// "pc+us+de:2+inet(evdev)". Variants are dropped.
+fn layout_names(symbols: &str) -> Vec<String> {
+    symbols
+        .split('+')
+        .map(|part| {
+            let end = part.find(['(', ':']).unwrap_or(part.len());
+            &part[..end]
+        })
+        .filter(|name| !name.is_empty() && !NON_LAYOUTS.contains(name))
+        .map(|name| name.to_owned())
+        .collect()
+}
+
+/// Enables the XKB extension and selects notifications of group changes.
+/// Returns false if the extension is not available.
+pub fn init(ctx: &Context) -> Result<bool> {
+    if ctx
+        .conn
+        .extension_information(xkb::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        warn!("XKB is not available");
+        return Ok(false);
+    }
+    let reply = ctx
+        .conn
+        .xkb_use_extension(xkb::X11_XML_VERSION.0 as u16, xkb::X11_XML_VERSION.1 as u16)?
+        .reply()?;
+    if !reply.supported {
+        warn!(
+            "XKB {}.{} is not supported",
+            reply.server_major, reply.server_minor
+        );
+        return Ok(false);
+    }
+
+    let group_state = u16::from(xkb::StatePart::GROUP_STATE);
+    let details = xkb::SelectEventsAux::new().bitcase2(xkb::SelectEventsAuxBitcase2 {
+        affect_state: group_state,
+        state_details: group_state,
+    });
+    ctx.conn.xkb_select_events(
+        xkb::ID::USE_CORE_KBD.into(),
+        0u16,
+        0u16,
+        0u16,
+        0u16,
+        &details,
+    )?;
+    Ok(true)
+}
+
+/// Returns the name of the layout of the group of the core keyboard.
+pub fn layout_name(ctx: &Context, group: usize) -> Result<String> {
+    let names = ctx
+        .conn
+        .xkb_get_names(xkb::ID::USE_CORE_KBD.into(), xkb::NameDetail::SYMBOLS)?
+        .reply()?;
+    let symbols = match names.value_list.symbols_name {
+        Some(atom) if atom != x11rb::NONE => ctx.conn.get_atom_name(atom)?.reply()?.name,
+        _ => return Ok(String::new()),
+    };
+    let symbols = String::from_utf8_lossy(&symbols);
+    debug!("XKB symbols: {}, group = {}", symbols, group);
+
+    Ok(layout_names(&symbols)
+        .into_iter()
+        .nth(group)
+        .unwrap_or_default())
+}
+
+/// Returns the current group of the core keyboard.
+pub fn current_group(ctx: &Context) -> Result<usize> {
+    let state = ctx
+        .conn
+        .xkb_get_state(xkb::ID::USE_CORE_KBD.into())?
+        .reply()?;
+    Ok(u8::from(state.group) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts_are_extracted_from_symbols() {
+        assert_eq!(layout_names("pc+us+inet(evdev)"), vec!["us"]);
+        assert_eq!(
+            layout_names("pc+us(dvorak)+de:2+inet(evdev)+group(alt_shift_toggle)"),
+            vec!["us", "de"]
+        );
+        assert!(layout_names("").is_empty());
+    }
+}
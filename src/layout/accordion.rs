@@ -0,0 +1,110 @@
+#![allow(dead_code)]
+
+use x11rb::protocol::xproto::{Rectangle, Window as Wid};
+
+use super::{Layout, LayoutWindow, Placement};
+use crate::geometry::Rect;
+
+/// Expands the focused window and collapses the others into their title bars,
+/// stacked above and below it.
+#[derive(Debug, Default)]
+pub struct Accordion {
+    /// The window expanded last, which stays so while no window is focused.
+    expanded: Option<Wid>,
+}
+
+impl Accordion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Layout for Accordion {
+    fn name(&self) -> &'static str {
+        "accordion"
+    }
+
+    fn uses_title_bars(&self) -> bool {
+        true
+    }
+
+    fn layout(
+        &mut self,
+        area: &Rectangle,
+        windows: &[LayoutWindow],
+        border_visible: bool,
+    ) -> Vec<Placement> {
+        if windows.is_empty() {
+            return Vec::new();
+        }
+
+        if let Some(focused) = windows.iter().find(|win| win.focused) {
+            self.expanded = Some(focused.wid);
+        }
+        let expanded = windows
+            .iter()
+            .position(|win| Some(win.wid) == self.expanded)
+            .unwrap_or(0);
+
+        let border_width = |win: &LayoutWindow| if border_visible { win.border_width } else { 0 };
+        let collapsed_height = |win: &LayoutWindow| win.title_height + border_width(win) * 2;
+        let collapsed_total: u32 = windows
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != expanded)
+            .map(|(_, win)| collapsed_height(win))
+            .sum();
+        let expanded_height = (area.height as u32).saturating_sub(collapsed_total);
+
+        let mut placements = Vec::with_capacity(windows.len());
+        let mut rest = Rect::from(*area);
+        for (i, win) in windows.iter().enumerate() {
+            let outer_height = if i == expanded {
+                expanded_height
+            } else {
+                collapsed_height(win)
+            };
+            // Not cut by `split_y`, so that the title bars overflowing the area keep their height.
+            let slot = Rect {
+                height: outer_height,
+                ..rest
+            };
+            placements.push(Placement::in_slot(win, slot, border_width(win), false));
+            rest = rest.offset(0, outer_height as i32);
+        }
+        placements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focused_window_expands_between_title_bars() {
+        let area = Rectangle {
+            x: 0,
+            y: 16,
+            width: 800,
+            height: 600,
+        };
+        let mut windows: Vec<LayoutWindow> = (0..4)
+            .map(|wid| LayoutWindow {
+                wid,
+                border_width: 1,
+                title_height: 16,
+                focused: wid == 1,
+            })
+            .collect();
+
+        let mut layout = Accordion::new();
+        let placements = layout.layout(&area, &windows, true);
+        let rows: Vec<(i32, u32)> = placements.iter().map(|p| (p.y, p.height)).collect();
+        assert_eq!(rows, vec![(16, 16), (34, 544), (580, 16), (598, 16)]);
+
+        // The last expanded window stays so while the focus is elsewhere.
+        windows[1].focused = false;
+        let placements = layout.layout(&area, &windows, true);
+        assert_eq!(placements[1].height, 544);
+    }
+}
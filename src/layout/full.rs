@@ -1,20 +1,16 @@
 #![allow(dead_code)]
 
-use x11rb::protocol::{randr::MonitorInfo, xproto::*};
+use x11rb::protocol::xproto::Rectangle;
 
-use super::Layout;
-use crate::context::Context;
-use crate::error::Result;
-use crate::window::Window;
+use super::{Layout, LayoutWindow, Placement};
+use crate::geometry::Rect;
 
-#[derive(Debug)]
-pub struct FullScreen {
-    ctx: Context,
-}
+#[derive(Debug, Default)]
+pub struct FullScreen {}
 
 impl FullScreen {
-    pub fn new(ctx: Context) -> Self {
-        Self { ctx }
+    pub fn new() -> Self {
+        Self {}
     }
 }
 
@@ -23,42 +19,20 @@ impl Layout for FullScreen {
         "full-screen"
     }
 
+    fn uses_whole_monitor(&self) -> bool {
+        true
+    }
+
     fn layout(
         &mut self,
-        mon: &MonitorInfo,
-        windows: &mut [&mut Window],
+        area: &Rectangle,
+        windows: &[LayoutWindow],
         _border_visible: bool,
-    ) -> Result<()> {
-        if windows.is_empty() {
-            return Ok(());
-        }
-
-        let x = mon.x as i32;
-        let y = mon.y as i32;
-        let w = mon.width as u32;
-        let h = mon.height as u32;
-
-        let base_conf = ConfigureWindowAux::new()
-            .x(x)
-            .y(y)
-            .border_width(0)
-            .width(w)
-            .height(h);
-
-        let focus = self
-            .ctx
-            .get_focused_window()?
-            .unwrap_or_else(|| InputFocus::NONE.into());
-
-        for win in windows.iter_mut() {
-            let conf = if win.contains(focus) {
-                base_conf.stack_mode(StackMode::ABOVE) // Top-most
-            } else {
-                base_conf
-            };
-            win.configure(&conf)?;
-        }
-
-        Ok(())
+    ) -> Vec<Placement> {
+        let area = Rect::from(*area);
+        windows
+            .iter()
+            .map(|win| Placement::in_slot(win, area, 0, win.focused)) // Top-most
+            .collect()
     }
 }
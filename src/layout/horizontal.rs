@@ -1,21 +1,41 @@
 #![allow(dead_code)]
 
-use x11rb::protocol::{randr::MonitorInfo, xproto::*};
+use x11rb::protocol::xproto::Rectangle;
 
-use super::Layout;
-use crate::context::Context;
+use super::{split, Layout, LayoutWindow, Pager, Placement, MAIN_GROUP, STACK_GROUP};
+use crate::config::HorizontalLayoutConfig;
 use crate::error::Result;
-use crate::window::Window;
+use crate::geometry::Rect;
 
 #[derive(Debug)]
 pub struct Horizontal {
-    ctx: Context,
     ratio: u16,
+    ratio_step: u16,
+    pager: Pager,
 }
 
 impl Horizontal {
-    pub fn new(ctx: Context) -> Self {
-        Self { ctx, ratio: 50 }
+    pub fn new() -> Self {
+        Self {
+            ratio: 50,
+            ratio_step: 5,
+            pager: Pager::default(),
+        }
+    }
+
+    /// Starts with the configured ratio and shows only `per_page` windows at once.
+    pub fn with_config(config: HorizontalLayoutConfig, per_page: usize) -> Self {
+        Self {
+            ratio: config.default_ratio,
+            ratio_step: config.ratio_step,
+            pager: Pager::new(per_page),
+        }
+    }
+}
+
+impl Default for Horizontal {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -26,87 +46,95 @@ impl Layout for Horizontal {
 
     fn layout(
         &mut self,
-        mon: &MonitorInfo,
-        windows: &mut [&mut Window],
+        area: &Rectangle,
+        windows: &[LayoutWindow],
         border_visible: bool,
-    ) -> Result<()> {
+    ) -> Vec<Placement> {
+        let windows = self.pager.page(windows);
         if windows.is_empty() {
-            return Ok(());
+            return Vec::new();
         }
 
-        let offset_x = mon.x as i32;
-        let offset_y = mon.y as i32;
-        let h = mon.height as u32;
-
-        let border_conf = self.ctx.config.border;
-        let border_width = if border_visible { border_conf.width } else { 0 };
-
-        let main_w;
-        let w;
-        if windows.len() > 1 {
-            main_w = mon.width as u32 * self.ratio as u32 / 100;
-            w = (mon.width as u32 - main_w) / (windows.len() as u32 - 1);
+        let area = Rect::from(*area);
+        let main_w = if windows.len() > 1 {
+            area.width * self.ratio as u32 / 100
         } else {
-            main_w = mon.width as u32;
-            w = 0;
-        }
-        let mut x = 0;
-
-        // main area
-        {
-            let conf = ConfigureWindowAux::new()
-                .x(offset_x + x)
-                .y(offset_y)
-                .border_width(border_width)
-                .width(main_w - border_width * 2)
-                .height(h - border_width * 2);
-            windows[0].configure(&conf)?;
-            x += main_w as i32;
-        }
-
-        for win in windows[1..].iter_mut() {
-            let conf = ConfigureWindowAux::new()
-                .x(offset_x + x)
-                .y(offset_y)
-                .border_width(border_width)
-                .width(w - border_width * 2)
-                .height(h - border_width * 2);
-            win.configure(&conf)?;
-            x += w as i32;
+            area.width
+        };
+        let (main, rest) = area.split_x(main_w);
+        let stack = split(rest.width, windows.len() - 1);
+
+        let border_width = |win: &LayoutWindow| if border_visible { win.border_width } else { 0 };
+        let mut placements = Vec::with_capacity(windows.len());
+        placements.push(Placement::in_slot(
+            &windows[0],
+            main,
+            border_width(&windows[0]),
+            false,
+        ));
+
+        for (win, &(x, w)) in windows[1..].iter().zip(&stack) {
+            let slot = rest.offset(x as i32, 0).split_x(w).0;
+            let shared = stack.iter().filter(|&&(other, _)| other == x).count() > 1;
+            placements.push(Placement::in_slot(
+                win,
+                slot,
+                border_width(win),
+                shared && win.focused,
+            ));
         }
 
-        Ok(())
+        placements
     }
 
     fn process_command(&mut self, cmd: String) -> Result<()> {
+        if self.pager.process_command(&cmd) {
+            return Ok(());
+        }
         match cmd.as_str() {
             "+" => {
-                if self.ratio < 95 {
-                    self.ratio += 5;
-                }
+                self.ratio = (self.ratio + self.ratio_step).min(95);
             }
 
             "-" => {
-                if self.ratio > 5 {
-                    self.ratio -= 5;
-                }
+                self.ratio = self.ratio.saturating_sub(self.ratio_step).max(5);
             }
 
             _ => {}
         }
         Ok(())
     }
+
+    fn page(&self) -> Option<(usize, usize)> {
+        self.pager.position()
+    }
+
+    fn group(&self, index: usize) -> usize {
+        // The first window of the page is in the main area.
+        if index == 0 {
+            MAIN_GROUP
+        } else {
+            STACK_GROUP
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct HorizontalWithBorder {
     base: Horizontal,
 }
 
 impl HorizontalWithBorder {
-    pub fn new(ctx: Context) -> Self {
+    pub fn new() -> Self {
+        Self {
+            base: Horizontal::new(),
+        }
+    }
+
+    /// Starts with the configured ratio and shows only `per_page` windows at once.
+    pub fn with_config(config: HorizontalLayoutConfig, per_page: usize) -> Self {
         Self {
-            base: Horizontal::new(ctx),
+            base: Horizontal::with_config(config, per_page),
         }
     }
 }
@@ -116,11 +144,109 @@ impl Layout for HorizontalWithBorder {
         "horizontal-with-border"
     }
 
-    fn layout(&mut self, mon: &MonitorInfo, windows: &mut [&mut Window], _: bool) -> Result<()> {
-        self.base.layout(mon, windows, true)
+    fn layout(&mut self, area: &Rectangle, windows: &[LayoutWindow], _: bool) -> Vec<Placement> {
+        self.base.layout(area, windows, true)
     }
 
     fn process_command(&mut self, cmd: String) -> Result<()> {
         self.base.process_command(cmd)
     }
+
+    fn page(&self) -> Option<(usize, usize)> {
+        self.base.page()
+    }
+
+    fn group(&self, index: usize) -> usize {
+        self.base.group(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows(n: u32) -> Vec<LayoutWindow> {
+        (0..n)
+            .map(|i| LayoutWindow {
+                wid: i,
+                border_width: 1,
+                title_height: 16,
+                focused: i == 0,
+            })
+            .collect()
+    }
+
+    const AREA: Rectangle = Rectangle {
+        x: 100,
+        y: 16,
+        width: 1000,
+        height: 500,
+    };
+
+    #[test]
+    fn single_window_fills_area() {
+        let mut layout = Horizontal::new();
+        let placements = layout.layout(&AREA, &windows(1), false);
+        assert_eq!(placements.len(), 1);
+        let p = placements[0];
+        assert_eq!((p.x, p.y, p.width, p.height), (100, 16, 1000, 500));
+        assert_eq!(p.border_width, 0);
+    }
+
+    #[test]
+    fn main_area_follows_ratio() {
+        let mut layout = Horizontal::new();
+        layout.process_command("+".to_owned()).unwrap();
+        let placements = layout.layout(&AREA, &windows(3), true);
+
+        // 55% for the main window and the rest is split evenly.
+        let xs: Vec<i32> = placements.iter().map(|p| p.x).collect();
+        assert_eq!(xs, vec![100, 650, 875]);
+        let widths: Vec<u32> = placements.iter().map(|p| p.width).collect();
+        assert_eq!(widths, vec![548, 223, 223]);
+        assert!(placements.iter().all(|p| p.height == 498));
+    }
+
+    #[test]
+    fn ratio_is_configurable() {
+        let config = HorizontalLayoutConfig {
+            default_ratio: 60,
+            ratio_step: 10,
+        };
+        let mut layout = Horizontal::with_config(config, 8);
+        layout.process_command("+".to_owned()).unwrap();
+        let placements = layout.layout(&AREA, &windows(2), false);
+        assert_eq!(placements[1].x, 800);
+
+        // The ratio is clamped.
+        for _ in 0..3 {
+            layout.process_command("+".to_owned()).unwrap();
+        }
+        let placements = layout.layout(&AREA, &windows(2), false);
+        assert_eq!(placements[1].x, 1050);
+    }
+
+    #[test]
+    fn windows_which_do_not_fit_are_stacked() {
+        let area = Rectangle {
+            x: 0,
+            y: 0,
+            width: 200,
+            height: 6,
+        };
+        let mut windows = windows(5);
+        windows[4].focused = true;
+        windows.iter_mut().for_each(|w| w.border_width = 4);
+        let placements = Horizontal::new().layout(&area, &windows, true);
+
+        // The stack of 100 pixels has 3 slots of `MIN_SIZE`.
+        let xs: Vec<i32> = placements.iter().map(|p| p.x).collect();
+        assert_eq!(xs, vec![0, 100, 133, 166, 166]);
+        let raised: Vec<bool> = placements.iter().map(|p| p.raise).collect();
+        assert_eq!(raised, vec![false, false, false, false, true]);
+        // Borders are thinned not to exceed the height.
+        assert!(placements
+            .iter()
+            .all(|p| p.height == 2 && p.border_width == 2));
+    }
 }
@@ -1,27 +1,326 @@
+mod accordion;
 mod full;
 mod horizontal;
+mod monocle;
+mod tabbed;
 mod vertical;
 
+pub use accordion::*;
 pub use full::*;
 pub use horizontal::*;
+pub use monocle::*;
+pub use tabbed::*;
 pub use vertical::*;
 
-use x11rb::protocol::randr::MonitorInfo;
+use x11rb::protocol::xproto::{Rectangle, Window as Wid};
 
 use crate::error::Result;
-use crate::window::Window;
+use crate::geometry::Rect;
 
+/// A window to be arranged by a layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutWindow {
+    pub wid: Wid,
+    pub border_width: u32,
+    /// The height of the title bar on the monitor, which is also that of the tab strip.
+    pub title_height: u32,
+    pub focused: bool,
+}
+
+/// The geometry of a window decided by a layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub wid: Wid,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub border_width: u32,
+    /// Put the window at the top of the window stack.
+    pub raise: bool,
+}
+
+impl Placement {
+    /// Places the window in `slot` including its border, thinned by `fit` if needed.
+    pub fn in_slot(win: &LayoutWindow, slot: Rect, border_width: u32, raise: bool) -> Self {
+        let (width, height, border_width) = fit(slot.width, slot.height, border_width);
+        Self {
+            wid: win.wid,
+            x: slot.x,
+            y: slot.y,
+            width,
+            height,
+            border_width,
+            raise,
+        }
+    }
+
+    /// The geometry of the window without its border.
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+/// The group of the main window, e.g. the left one in the horizontal layout.
+pub const MAIN_GROUP: usize = 0;
+/// The group of the windows stacked beside the main one.
+pub const STACK_GROUP: usize = 1;
+
+/// The smallest width or height of a slot a layout gives to a window.
+/// Windows which do not fit share the last slot instead (see `split`).
+pub const MIN_SIZE: u32 = 32;
+
+/// Splits `length` into `count` slots evenly and returns their (offset, size).
+/// When the slots would be smaller than `MIN_SIZE`, only as many slots as fit are made
+/// and the overflowing windows are stacked in the last one.
+pub fn split(length: u32, count: usize) -> Vec<(u32, u32)> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let slots = (count as u32).min(length / MIN_SIZE).max(1);
+    let size = length / slots;
+    (0..count as u32)
+        .map(|i| (i.min(slots - 1) * size, size))
+        .collect()
+}
+
+/// Returns the (width, height, border width) of a window occupying `width` x `height`
+/// pixels with the border, thinning the border so that the window gets at least 1 pixel.
+pub fn fit(width: u32, height: u32, border_width: u32) -> (u32, u32, u32) {
+    let border_width = border_width.min(width.min(height).saturating_sub(1) / 2);
+    (
+        (width - border_width * 2).max(1),
+        (height - border_width * 2).max(1),
+        border_width,
+    )
+}
+
+/// Shows only a page of `per_page` windows, turned by "next-page" / "prev-page".
+/// The page follows the focus when another window is focused.
+#[derive(Debug, Clone)]
+pub struct Pager {
+    per_page: usize,
+    page: usize,
+    pages: usize,
+    last_focused: Option<Wid>,
+}
+
+impl Pager {
+    pub fn new(per_page: usize) -> Self {
+        assert!(per_page > 0);
+        Self {
+            per_page,
+            page: 0,
+            pages: 1,
+            last_focused: None,
+        }
+    }
+
+    /// Returns the windows on the current page.
+    pub fn page<'a>(&mut self, windows: &'a [LayoutWindow]) -> &'a [LayoutWindow] {
+        self.pages = windows.len().div_ceil(self.per_page).max(1);
+
+        let focused = windows.iter().position(|win| win.focused);
+        if let Some(index) = focused {
+            if self.last_focused != Some(windows[index].wid) {
+                self.page = index / self.per_page;
+            }
+        }
+        self.last_focused = focused.map(|index| windows[index].wid);
+
+        self.page = self.page.min(self.pages - 1);
+        let start = (self.page * self.per_page).min(windows.len());
+        let end = start.saturating_add(self.per_page).min(windows.len());
+        &windows[start..end]
+    }
+
+    /// Handles the commands turning the page. Returns false for other commands.
+    pub fn process_command(&mut self, cmd: &str) -> bool {
+        match cmd {
+            "next-page" => self.page = (self.page + 1) % self.pages,
+            "prev-page" => self.page = (self.page + self.pages - 1) % self.pages,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The current page and the number of pages, if there are more than one page.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        (self.pages > 1).then_some((self.page, self.pages))
+    }
+}
+
+impl Default for Pager {
+    fn default() -> Self {
+        Self::new(usize::MAX)
+    }
+}
+
+/// A direction on the screen.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Finds the nearest window side by side with `wid` in the direction among `placements`.
+pub fn neighbor(placements: &[Placement], wid: Wid, dir: Direction) -> Option<Wid> {
+    let from = placements.iter().find(|p| p.wid == wid)?.rect();
+    let (fx, fy) = from.center();
+
+    placements
+        .iter()
+        .filter(|p| p.wid != wid)
+        .filter_map(|p| {
+            let rect = p.rect();
+            let (x, y) = rect.center();
+            let overlap_x = rect.x < from.right() && from.x < rect.right();
+            let overlap_y = rect.y < from.bottom() && from.y < rect.bottom();
+            // (distance along the direction, distance across it, whether they are side by side)
+            let (along, across, side_by_side) = match dir {
+                Direction::Left => (fx - x, y - fy, overlap_y),
+                Direction::Right => (x - fx, y - fy, overlap_y),
+                Direction::Up => (fy - y, x - fx, overlap_x),
+                Direction::Down => (y - fy, x - fx, overlap_x),
+            };
+            (side_by_side && along > 0).then(|| ((along, across.abs()), p.wid))
+        })
+        .min()
+        .map(|(_, wid)| wid)
+}
+
+/// Arranges tiled windows of a screen.
 pub trait Layout {
+    /// Computes the geometries of `windows` arranged in `area`.
     fn layout(
         &mut self,
-        mon: &MonitorInfo,
-        windows: &mut [&mut Window],
+        area: &Rectangle,
+        windows: &[LayoutWindow],
         border_visible: bool,
-    ) -> Result<()>;
+    ) -> Vec<Placement>;
 
+    /// The name shown in logs.
     fn name(&self) -> &'static str;
 
+    /// Whether the layout covers the whole monitor including the space for the bar.
+    fn uses_whole_monitor(&self) -> bool {
+        false
+    }
+
+    /// Whether the screen shows a strip of tabs of the windows at the top of the area.
+    /// The layout leaves `title_height` pixels of the windows for it.
+    fn uses_tab_strip(&self) -> bool {
+        false
+    }
+
+    /// Whether the tiled windows show their title bars, like floating ones.
+    fn uses_title_bars(&self) -> bool {
+        false
+    }
+
+    /// Handles `LayoutCommand::Command` while the layout is active.
     fn process_command(&mut self, _cmd: String) -> Result<()> {
         Ok(())
     }
+
+    /// The current page and the number of pages, if some windows are on other pages.
+    /// The screen hides the windows for which `layout` returns no placement.
+    fn page(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// The position of the focused window among the windows stacked on each other,
+    /// and their number.
+    fn position(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// The group of the window at `index` of the placements returned last,
+    /// `MAIN_GROUP` or `STACK_GROUP` if the layout has a main area.
+    /// `FocusCommand::NextInGroup` cycles within the group of the focused window.
+    fn group(&self, _index: usize) -> usize {
+        MAIN_GROUP
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(wid: Wid, x: i32, y: i32, width: u32, height: u32) -> Placement {
+        Placement {
+            wid,
+            x,
+            y,
+            width,
+            height,
+            border_width: 0,
+            raise: false,
+        }
+    }
+
+    #[test]
+    fn overflowing_windows_share_the_last_slot() {
+        assert_eq!(split(300, 3), vec![(0, 100), (100, 100), (200, 100)]);
+        // Only 2 slots of `MIN_SIZE` fit in 70 pixels.
+        assert_eq!(split(70, 4), vec![(0, 35), (35, 35), (35, 35), (35, 35)]);
+        assert_eq!(split(10, 2), vec![(0, 10), (0, 10)]);
+
+        assert_eq!(fit(100, 50, 2), (96, 46, 2));
+        assert_eq!(fit(100, 10, 8), (92, 2, 4));
+        assert_eq!(fit(0, 10, 8), (1, 10, 0));
+    }
+
+    #[test]
+    fn pager_turns_pages_and_follows_the_focus() {
+        let windows: Vec<LayoutWindow> = (0..5)
+            .map(|wid| LayoutWindow {
+                wid,
+                border_width: 0,
+                title_height: 16,
+                focused: wid == 0,
+            })
+            .collect();
+        let wids = |page: &[LayoutWindow]| page.iter().map(|win| win.wid).collect::<Vec<_>>();
+
+        let mut pager = Pager::new(2);
+        assert_eq!(wids(pager.page(&windows)), vec![0, 1]);
+        assert_eq!(pager.position(), Some((0, 3)));
+
+        assert!(pager.process_command("prev-page"));
+        assert_eq!(wids(pager.page(&windows)), vec![4]);
+        assert!(pager.process_command("next-page"));
+        assert!(pager.process_command("next-page"));
+        assert_eq!(wids(pager.page(&windows)), vec![2, 3]);
+
+        // Focusing a window on another page turns to it.
+        let mut windows = windows;
+        windows[0].focused = false;
+        windows[4].focused = true;
+        assert_eq!(wids(pager.page(&windows)), vec![4]);
+        assert_eq!(pager.position(), Some((2, 3)));
+
+        assert!(!pager.process_command("+"));
+        assert_eq!(pager.page(&windows[..2]).len(), 2);
+        assert_eq!(pager.position(), None);
+    }
+
+    #[test]
+    fn neighbor_is_side_by_side() {
+        // 0 on the left half, 1 and 2 stacked on the right half, 3 below 2.
+        let placements = [
+            placement(0, 0, 0, 500, 600),
+            placement(1, 500, 0, 500, 200),
+            placement(2, 500, 200, 500, 200),
+            placement(3, 500, 400, 500, 200),
+        ];
+        assert_eq!(neighbor(&placements, 1, Direction::Left), Some(0));
+        assert_eq!(neighbor(&placements, 0, Direction::Right), Some(2));
+        assert_eq!(neighbor(&placements, 3, Direction::Up), Some(2));
+        assert_eq!(neighbor(&placements, 1, Direction::Down), Some(2));
+        assert_eq!(neighbor(&placements, 0, Direction::Left), None);
+        assert_eq!(neighbor(&placements, 0, Direction::Up), None);
+    }
 }
@@ -0,0 +1,82 @@
+#![allow(dead_code)]
+
+use x11rb::protocol::xproto::Rectangle;
+
+use super::{Layout, LayoutWindow, Placement};
+use crate::geometry::Rect;
+
+/// Maximizes every window in the area below the bar and raises the focused one.
+#[derive(Debug, Default)]
+pub struct Monocle {
+    position: Option<(usize, usize)>,
+}
+
+impl Monocle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Layout for Monocle {
+    fn name(&self) -> &'static str {
+        "monocle"
+    }
+
+    fn layout(
+        &mut self,
+        area: &Rectangle,
+        windows: &[LayoutWindow],
+        border_visible: bool,
+    ) -> Vec<Placement> {
+        self.position = windows
+            .iter()
+            .position(|win| win.focused)
+            .map(|index| (index, windows.len()));
+
+        let area = Rect::from(*area);
+        windows
+            .iter()
+            .map(|win| {
+                let border_width = if border_visible { win.border_width } else { 0 };
+                // Top-most
+                Placement::in_slot(win, area, border_width, win.focused)
+            })
+            .collect()
+    }
+
+    fn position(&self) -> Option<(usize, usize)> {
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focused_window_is_raised_and_counted() {
+        let area = Rectangle {
+            x: 0,
+            y: 16,
+            width: 800,
+            height: 600,
+        };
+        let windows: Vec<LayoutWindow> = (0..3)
+            .map(|wid| LayoutWindow {
+                wid,
+                border_width: 2,
+                title_height: 16,
+                focused: wid == 1,
+            })
+            .collect();
+
+        let mut layout = Monocle::new();
+        let placements = layout.layout(&area, &windows, true);
+        assert!(placements
+            .iter()
+            .all(|p| (p.x, p.y, p.width, p.height) == (0, 16, 796, 596)));
+        let raised: Vec<bool> = placements.iter().map(|p| p.raise).collect();
+        assert_eq!(raised, vec![false, true, false]);
+        assert_eq!(layout.position(), Some((1, 3)));
+    }
+}
@@ -0,0 +1,49 @@
+#![allow(dead_code)]
+
+use x11rb::protocol::xproto::Rectangle;
+
+use super::{Layout, LayoutWindow, Placement};
+use crate::geometry::Rect;
+
+/// The height of the strip of tabs on monitors which are not scaled,
+/// which the screen draws at the top of the area.
+pub const TAB_STRIP_HEIGHT: u16 = 16;
+
+/// Maximizes every window below a strip of tabs and raises the focused one.
+#[derive(Debug, Default)]
+pub struct Tabbed {}
+
+impl Tabbed {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Layout for Tabbed {
+    fn name(&self) -> &'static str {
+        "tabbed"
+    }
+
+    fn uses_tab_strip(&self) -> bool {
+        true
+    }
+
+    fn layout(
+        &mut self,
+        area: &Rectangle,
+        windows: &[LayoutWindow],
+        border_visible: bool,
+    ) -> Vec<Placement> {
+        // The strip is as high as the title bars.
+        let strip_height = windows.first().map_or(0, |win| win.title_height);
+        let (_, below) = Rect::from(*area).split_y(strip_height);
+        windows
+            .iter()
+            .map(|win| {
+                let border_width = if border_visible { win.border_width } else { 0 };
+                // Top-most
+                Placement::in_slot(win, below, border_width, win.focused)
+            })
+            .collect()
+    }
+}
@@ -1,20 +1,26 @@
 #![allow(dead_code)]
 
-use x11rb::protocol::{randr::MonitorInfo, xproto::*};
+use x11rb::protocol::xproto::Rectangle;
 
-use super::Layout;
-use crate::context::Context;
+use super::{split, Layout, LayoutWindow, Pager, Placement};
 use crate::error::Result;
-use crate::window::Window;
+use crate::geometry::Rect;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Vertical {
-    ctx: Context,
+    pager: Pager,
 }
 
 impl Vertical {
-    pub fn new(ctx: Context) -> Self {
-        Self { ctx }
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shows only `per_page` windows at once.
+    pub fn with_page_size(per_page: usize) -> Self {
+        Self {
+            pager: Pager::new(per_page),
+        }
     }
 }
 
@@ -25,48 +31,60 @@ impl Layout for Vertical {
 
     fn layout(
         &mut self,
-        mon: &MonitorInfo,
-        windows: &mut [&mut Window],
+        area: &Rectangle,
+        windows: &[LayoutWindow],
         border_visible: bool,
-    ) -> Result<()> {
+    ) -> Vec<Placement> {
+        let windows = self.pager.page(windows);
         if windows.is_empty() {
-            return Ok(());
+            return Vec::new();
         }
 
-        let count = windows.len();
-        let w = mon.width as u32;
-        let h = (mon.height / count as u16) as u32;
-        let offset_x = mon.x as i32;
-        let offset_y = mon.y as i32;
-        let mut y = 0;
-
-        for win in windows.iter_mut() {
-            let border_conf = self.ctx.config.border;
-            let border_width = if border_visible { border_conf.width } else { 0 };
-
-            let conf = ConfigureWindowAux::new()
-                .x(offset_x)
-                .y(offset_y + y)
-                .border_width(border_width)
-                .width(w - border_width * 2)
-                .height(h - border_width * 2);
-            win.configure(&conf)?;
-            y += h as i32;
+        let area = Rect::from(*area);
+        let slots = split(area.height, windows.len());
+
+        let mut placements = Vec::with_capacity(windows.len());
+        for (win, &(y, h)) in windows.iter().zip(&slots) {
+            let border_width = if border_visible { win.border_width } else { 0 };
+            let slot = area.offset(0, y as i32).split_y(h).0;
+            let shared = slots.iter().filter(|&&(other, _)| other == y).count() > 1;
+            placements.push(Placement::in_slot(
+                win,
+                slot,
+                border_width,
+                shared && win.focused,
+            ));
         }
 
+        placements
+    }
+
+    fn process_command(&mut self, cmd: String) -> Result<()> {
+        self.pager.process_command(&cmd);
         Ok(())
     }
+
+    fn page(&self) -> Option<(usize, usize)> {
+        self.pager.position()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct VerticalWithBorder {
     base: Vertical,
 }
 
 impl VerticalWithBorder {
-    pub fn new(ctx: Context) -> Self {
+    pub fn new() -> Self {
         Self {
-            base: Vertical::new(ctx),
+            base: Vertical::new(),
+        }
+    }
+
+    /// Shows only `per_page` windows at once.
+    pub fn with_page_size(per_page: usize) -> Self {
+        Self {
+            base: Vertical::with_page_size(per_page),
         }
     }
 }
@@ -76,7 +94,15 @@ impl Layout for VerticalWithBorder {
         "vertical-with-border"
     }
 
-    fn layout(&mut self, mon: &MonitorInfo, windows: &mut [&mut Window], _: bool) -> Result<()> {
-        self.base.layout(mon, windows, true)
+    fn layout(&mut self, area: &Rectangle, windows: &[LayoutWindow], _: bool) -> Vec<Placement> {
+        self.base.layout(area, windows, true)
+    }
+
+    fn process_command(&mut self, cmd: String) -> Result<()> {
+        self.base.process_command(cmd)
+    }
+
+    fn page(&self) -> Option<(usize, usize)> {
+        self.base.page()
     }
 }
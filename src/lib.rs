@@ -0,0 +1,375 @@
+//! Daily, a tiling window manager for X11.
+//!
+//! The `daily` binary is a thin wrapper around [`start`]. A custom binary can
+//! compile its own layouts and commands in through [`Extensions`]:
+//!
+//! ```no_run
+//! use daily::layout::{Layout, LayoutWindow, Placement};
+//! use daily::{Command, Extensions, ScreenCommand};
+//!
+//! struct Monocle;
+//!
+//! impl Layout for Monocle {
+//!     fn layout(
+//!         &mut self,
+//!         area: &x11rb::protocol::xproto::Rectangle,
+//!         windows: &[LayoutWindow],
+//!         _border_visible: bool,
+//!     ) -> Vec<Placement> {
+//!         windows
+//!             .iter()
+//!             .map(|win| Placement {
+//!                 wid: win.wid,
+//!                 x: area.x as i32,
+//!                 y: area.y as i32,
+//!                 width: area.width as u32,
+//!                 height: area.height as u32,
+//!                 border_width: 0,
+//!                 raise: win.focused,
+//!             })
+//!             .collect()
+//!     }
+//!
+//!     fn name(&self) -> &'static str {
+//!         "Monocle"
+//!     }
+//! }
+//!
+//! let extensions = Extensions::new()
+//!     .layout(|| Box::new(Monocle))
+//!     .command("first-screen", |wm| {
+//!         wm.process_command(Command::Screen(ScreenCommand::Switch(0)))
+//!     });
+//! daily::start_with_extensions(None, extensions).unwrap();
+//! ```
+
+mod animation;
+mod atom;
+mod bar;
+mod barrier;
+mod cheatsheet;
+mod command;
+pub mod config;
+mod context;
+pub mod error;
+mod event;
+mod extension;
+pub mod geometry;
+mod hook;
+mod icon;
+mod keyboard_layout;
+pub mod layout;
+mod metrics;
+#[cfg(test)]
+mod mock;
+mod monitor;
+mod osd;
+pub mod screen;
+mod shape;
+pub mod signal;
+mod stacking;
+mod tab_strip;
+mod window;
+mod window_list;
+pub mod winman;
+
+pub use command::{
+    Command, FocusCommand, LayoutCommand, PointerCommand, ScreenCommand, SnapPosition,
+    WindowCommand,
+};
+pub use context::XConnection;
+pub use extension::{CommandHandler, Extensions, LayoutFactory};
+
+/// A wrapper for `std::thread::spawn` to give a name to the thread.
+pub fn spawn_named_thread<F, T>(name: String, body: F) -> std::thread::JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    std::thread::Builder::new()
+        .name(name)
+        .spawn(body)
+        .expect("failed to spawn thread")
+}
+
+/// Runs the shell command in the background with additional environment variables.
+///
+/// The stderr of the command is inherited so that its errors end up in the log of Daily.
+/// If the command cannot be executed (e.g. the program is not found), `on_failure`
+/// is called from another thread. Other exit statuses are only logged.
+fn spawn_process<F>(cmd: &str, envs: &[(&str, String)], on_failure: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    use std::process::{Command, Stdio};
+
+    // The exit statuses of the shell for a command not executable and not found.
+    const NOT_EXECUTABLE: i32 = 126;
+    const NOT_FOUND: i32 = 127;
+
+    let sh = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .envs(envs.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn();
+    let mut sh = match sh {
+        Ok(sh) => sh,
+        Err(e) => {
+            error!("spawn: cannot run sh: {}", e);
+            on_failure();
+            return;
+        }
+    };
+
+    // The thread reaps the command when it exits.
+    let cmd = cmd.to_owned();
+    spawn_named_thread("spawn".to_owned(), move || match sh.wait() {
+        Ok(status) if matches!(status.code(), Some(NOT_EXECUTABLE | NOT_FOUND)) => {
+            error!("spawn: cannot execute {:?} ({})", cmd, status);
+            on_failure();
+        }
+        Ok(status) if status.success() => {}
+        Ok(status) => info!("spawn: {:?} exited ({})", cmd, status),
+        Err(e) => error!("spawn: {:?}: {}", cmd, e),
+    });
+}
+
+/// Runs the shell command with `input` given to its stdin, and returns its stdout.
+/// This blocks until the command exits.
+fn run_with_input(cmd: &str, input: &str) -> std::io::Result<String> {
+    use std::io::Write as _;
+    use std::process::{Command, Stdio};
+    let mut sh = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Dropping stdin lets the command see EOF.
+    sh.stdin.take().unwrap().write_all(input.as_bytes())?;
+    let output = sh.wait_with_output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, serde::Deserialize)]
+pub enum KeybindAction {
+    Press,
+    Release,
+}
+
+use error::Result;
+use log::{debug, error, info};
+
+/// Runs the window manager on the display until it is terminated.
+pub fn start<S>(display_name: S) -> Result<()>
+where
+    S: Into<Option<&'static str>>,
+{
+    start_with_extensions(display_name, Extensions::default())
+}
+
+/// Same as [`start`], with additional layouts and commands.
+pub fn start_with_extensions<S>(display_name: S, extensions: Extensions) -> Result<()>
+where
+    S: Into<Option<&'static str>>,
+{
+    use event::EventHandler;
+    use std::time::Instant;
+    use x11rb::connection::Connection;
+
+    // One window manager for each X screen (more than one in Zaphod mode).
+    let contexts = context::init(display_name, extensions)?;
+    let mut wms = contexts
+        .iter()
+        .map(|ctx| winman::WinMan::new(ctx.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    // SIGTERM and SIGINT terminate the WM, SIGHUP restarts it.
+    let signal_rx = match signal::receive(&[libc::SIGTERM, libc::SIGINT, libc::SIGHUP]) {
+        Ok(rx) => rx,
+        Err(e) => {
+            error!("cannot handle signals: {}", e);
+            crossbeam_channel::never()
+        }
+    };
+    debug!("WinMan initialized");
+
+    // The events and the tasks are routed to the WM of their X screen by its index.
+    let (event_tx, event_rx) = crossbeam_channel::unbounded();
+    let (task_tx, task_rx) = crossbeam_channel::unbounded();
+    for (i, (ctx, wm)) in contexts.iter().zip(&wms).enumerate() {
+        // a thread to consume X11 events.
+        spawn_named_thread("main-x11".to_owned(), {
+            let ctx = ctx.clone();
+            let event_tx = event_tx.clone();
+            move || loop {
+                // The error is the last one once the connection is lost.
+                let event = ctx.conn.wait_for_event();
+                let failed = event.is_err();
+                if event_tx.send((i, event)).is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        let rx = wm.task_receiver();
+        let task_tx = task_tx.clone();
+        spawn_named_thread("main-tasks".to_owned(), move || {
+            while let Ok(task) = rx.recv() {
+                if task_tx.send((i, task)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    let flush_all = || -> Result<()> {
+        for ctx in contexts.iter() {
+            ctx.conn.flush()?;
+        }
+        Ok(())
+    };
+
+    let timer_rx = crossbeam_channel::tick(std::time::Duration::from_secs(10));
+    let metrics_tick = crossbeam_channel::tick(std::time::Duration::from_secs(1));
+
+    // main thread: processes events gathered from the others.
+    loop {
+        // Wake up for the next step only while windows are moving.
+        let animation_deadline = wms
+            .iter_mut()
+            .filter_map(|wm| wm.animation_deadline())
+            .min();
+        let animation_rx = match animation_deadline {
+            Some(deadline) => {
+                crossbeam_channel::after(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => crossbeam_channel::never(),
+        };
+        let pointer_deadline = wms
+            .iter()
+            .filter_map(|wm| wm.pointer_repeat_deadline())
+            .min();
+        let pointer_rx = match pointer_deadline {
+            Some(deadline) => {
+                crossbeam_channel::after(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => crossbeam_channel::never(),
+        };
+
+        let bar_deadline = wms.iter().filter_map(|wm| wm.bar_deadline()).min();
+        let bar_rx = match bar_deadline {
+            Some(deadline) => {
+                crossbeam_channel::after(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => crossbeam_channel::never(),
+        };
+
+        // The metrics are summarized only while someone looks at them.
+        let metrics_rx = if wms.iter().any(|wm| wm.reports_metrics()) {
+            metrics_tick.clone()
+        } else {
+            crossbeam_channel::never()
+        };
+
+        crossbeam_channel::select! {
+            recv(event_rx) -> event => {
+                let (i, event) = event.expect("event_tx has been closed.");
+                let event = event?;
+                let (ctx, wm) = (&contexts[i], &mut wms[i]);
+                ctx.observe_event(&event);
+                wm.count_event(event_rx.len());
+                let res = wm.handle_event(event);
+
+                // Ignore WINDOW errors ...
+                //     because WINDOW errors occur during processing a event
+                //     which was generated on a already destroyed window at the time.
+                use x11rb::protocol::ErrorKind;
+                if let Err(err) = res {
+                    if err.x11_error_kind() == Some(ErrorKind::Window) {
+                        debug!("Ignored WINDOW error: {:?}", err);
+                    } else {
+                        return Err(err);
+                    }
+                }
+
+                ctx.conn.flush()?;
+            }
+            recv(task_rx) -> task => {
+                let (i, task) = task.expect("task_tx has been closed.");
+                wms[i].process_task(task)?;
+                contexts[i].conn.flush()?;
+            }
+            recv(timer_rx) -> _ => {
+                for wm in wms.iter_mut() {
+                    wm.alarm()?;
+                }
+                flush_all()?;
+            }
+            recv(animation_rx) -> _ => {
+                let now = Instant::now();
+                for wm in wms.iter_mut() {
+                    if wm.animation_deadline().is_some_and(|deadline| deadline <= now) {
+                        wm.animate()?;
+                    }
+                }
+                flush_all()?;
+            }
+            recv(pointer_rx) -> _ => {
+                let now = Instant::now();
+                for wm in wms.iter_mut() {
+                    if wm.pointer_repeat_deadline().is_some_and(|deadline| deadline <= now) {
+                        wm.repeat_pointer()?;
+                    }
+                }
+                flush_all()?;
+            }
+            recv(bar_rx) -> _ => {
+                for wm in wms.iter_mut() {
+                    wm.check_bars()?;
+                }
+                flush_all()?;
+            }
+            recv(metrics_rx) -> _ => {
+                for wm in wms.iter_mut().filter(|wm| wm.reports_metrics()) {
+                    wm.report_metrics()?;
+                }
+                flush_all()?;
+            }
+            recv(signal_rx) -> signum => {
+                // Dropping `wms` gives the clients back to the root windows and closes the bars.
+                if signum == Ok(libc::SIGHUP) {
+                    info!("restarted by SIGHUP");
+                    for wm in wms.iter() {
+                        wm.save_state()?;
+                    }
+                    return Err(error::Error::Restart);
+                }
+                info!("terminated by signal {:?}", signum);
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn spawn_fails(cmd: &str) -> bool {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        spawn_process(cmd, &[], move || tx.send(()).unwrap());
+        rx.recv_timeout(Duration::from_secs(2)).is_ok()
+    }
+
+    #[test]
+    fn spawn_reports_only_commands_failing_to_start() {
+        assert!(spawn_fails("daily-no-such-command"));
+        assert!(!spawn_fails("exit 3"));
+        assert!(!spawn_fails("true"));
+        assert!(!spawn_fails("sleep 1"));
+    }
+}
@@ -0,0 +1,121 @@
+//! Counters of the main loop, to find out where the WM is sluggish.
+
+use std::time::{Duration, Instant};
+
+/// The durations of a kind of work in the current period.
+#[derive(Debug, Default, Clone, Copy)]
+struct Timing {
+    count: u32,
+    total: Duration,
+    max: Duration,
+}
+
+impl Timing {
+    fn record(&mut self, elapsed: Duration) {
+        self.count = self.count.saturating_add(1);
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+
+    fn average(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.count
+        }
+    }
+}
+
+/// Counts the events, and times the commands and the layout refreshes,
+/// summarized once a period (see `report`).
+#[derive(Debug)]
+pub struct Metrics {
+    since: Instant,
+    events: u32,
+    /// The most events waiting in the queue at once.
+    max_queued: usize,
+    command: Timing,
+    layout: Timing,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            events: 0,
+            max_queued: 0,
+            command: Timing::default(),
+            layout: Timing::default(),
+        }
+    }
+
+    /// Counts an event, with the number of the others still waiting.
+    pub fn record_event(&mut self, queued: usize) {
+        // A period lasts until the next report, which may be long without the HUD.
+        self.events = self.events.saturating_add(1);
+        self.max_queued = self.max_queued.max(queued);
+    }
+
+    pub fn record_command(&mut self, elapsed: Duration) {
+        self.command.record(elapsed);
+    }
+
+    pub fn record_layout(&mut self, elapsed: Duration) {
+        self.layout.record(elapsed);
+    }
+
+    /// Summarizes the period since the last report and starts a new one,
+    /// e.g. "events 12.0/s, queue 3, command 0.5/1.2ms, layout 0.3/0.9ms (avg/max)",
+    /// which fits in the OSD.
+    pub fn report(&mut self, now: Instant) -> String {
+        let secs = now.duration_since(self.since).as_secs_f64().max(0.001);
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let summary = format!(
+            "events {:.1}/s, queue {}, command {:.1}/{:.1}ms, layout {:.1}/{:.1}ms (avg/max)",
+            self.events as f64 / secs,
+            self.max_queued,
+            ms(self.command.average()),
+            ms(self.command.max),
+            ms(self.layout.average()),
+            ms(self.layout.max),
+        );
+        *self = Self {
+            since: now,
+            ..Self::new()
+        };
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_summarizes_the_period() {
+        let mut metrics = Metrics::new();
+        let start = metrics.since;
+        for queued in [0, 3, 1] {
+            metrics.record_event(queued);
+        }
+        metrics.record_command(Duration::from_millis(1));
+        metrics.record_command(Duration::from_millis(3));
+        metrics.record_layout(Duration::from_micros(500));
+
+        let report = metrics.report(start + Duration::from_secs(2));
+        assert_eq!(
+            report,
+            "events 1.5/s, queue 3, command 2.0/3.0ms, layout 0.5/0.5ms (avg/max)"
+        );
+
+        // The next period starts over.
+        let report = metrics.report(start + Duration::from_secs(3));
+        assert!(report.starts_with("events 0.0/s, queue 0, command 0.0/0.0ms"));
+    }
+}
@@ -0,0 +1,958 @@
+//! A fake X connection used in unit tests.
+//!
+//! It keeps a tiny model of the server state (window tree, geometry, map state,
+//! properties, atoms and the input focus), which is enough to drive WinMan, Screen
+//! and Window without a live X server. Requests it doesn't know about are
+//! answered with an all-zero reply.
+
+use std::collections::HashMap;
+use std::io::IoSlice;
+use std::sync::{Condvar, Mutex};
+
+use x11rb::connection::{
+    BufWithFds, Connection, DiscardMode, ReplyOrError, RequestConnection, RequestKind,
+    SequenceNumber,
+};
+use x11rb::cookie::{Cookie, CookieWithFds, VoidCookie};
+use x11rb::errors::{ConnectError, ConnectionError, ParseError, ReplyOrIdError};
+use x11rb::protocol::randr::MonitorInfo;
+use x11rb::protocol::xproto::{self, BackingStore, ImageOrder, Screen, Setup, Window as Wid};
+use x11rb::protocol::Event;
+use x11rb::utils::RawFdContainer;
+use x11rb::x11_utils::{
+    ExtInfoProvider, ExtensionInformation, Serialize, TryParse, TryParseFd, X11Error,
+};
+
+use crate::config::Config;
+use crate::context::{Context, ContextInner};
+use crate::extension::Extensions;
+
+pub const ROOT: Wid = 0x0000_0100;
+/// The root window of the X screen added by `add_screen`.
+pub const SECOND_ROOT: Wid = 0x0000_0200;
+
+const ROOT_WIDTH: u16 = 1920;
+const ROOT_HEIGHT: u16 = 1080;
+
+const EXTENSIONS: &[&str] = &["RANDR", "SHAPE", "XFIXES", "XTEST", "XKEYBOARD", "XINERAMA"];
+const FIRST_EXTENSION_OPCODE: u8 = 128;
+
+#[derive(Debug, Clone, Default)]
+pub struct MockWindow {
+    pub parent: Wid,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub border_width: u16,
+    pub mapped: bool,
+    pub input_only: bool,
+    pub override_redirect: bool,
+    pub properties: HashMap<u32, (u32, u8, Vec<u8>)>, // atom -> (type, format, data)
+}
+
+#[derive(Debug, Default)]
+struct State {
+    sequence: SequenceNumber,
+    next_id: u32,
+    windows: HashMap<Wid, MockWindow>,
+    /// All the windows from the bottom of the stack, including destroyed ones.
+    stack: Vec<Wid>,
+    atoms: Vec<String>,
+    focus: Wid,
+    /// The owners of selections.
+    selections: HashMap<u32, Wid>,
+    /// The position of the pointer moved by WarpPointer.
+    pointer: (i16, i16),
+    monitors: Vec<MonitorInfo>,
+    /// The heads of Xinerama, which is inactive without them.
+    xinerama_screens: Vec<(i16, i16, u16, u16)>,
+    /// The extensions of `EXTENSIONS` taken away by `remove_extension`.
+    removed_extensions: Vec<&'static str>,
+    /// The buttons pressed (true) and released by XTEST FakeInput.
+    fake_buttons: Vec<(u8, bool)>,
+    /// The passive grabs of GrabButton as (window, button, modifiers).
+    button_grabs: Vec<(Wid, u8, u16)>,
+    /// The passive grabs of GrabKey as (window, keycode, modifiers).
+    key_grabs: Vec<(Wid, u8, u16)>,
+    /// Whether another client has grabbed the keyboard, which fails GrabKeyboard.
+    keyboard_grabbed: bool,
+    /// The windows given to SHAPE Rectangles, once for each request.
+    shaped: Vec<Wid>,
+    /// The windows given to SetInputFocus while not viewable,
+    /// which a real server refuses with BadMatch.
+    unviewable_focus: Vec<Wid>,
+    replies: HashMap<SequenceNumber, Vec<u8>>,
+    /// The errors of the requests on windows which do not exist.
+    errors: HashMap<SequenceNumber, Vec<u8>>,
+    closed: bool,
+}
+
+impl State {
+    /// Whether the window and all its ancestors are mapped.
+    fn is_viewable(&self, mut wid: Wid) -> bool {
+        while wid != ROOT && wid != SECOND_ROOT {
+            match self.windows.get(&wid) {
+                Some(win) if win.mapped => wid = win.parent,
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct MockConnection {
+    setup: Setup,
+    state: Mutex<State>,
+    closed: Condvar,
+}
+
+fn u16_at(buf: &[u8], at: usize) -> u16 {
+    u16::from_ne_bytes([buf[at], buf[at + 1]])
+}
+fn i16_at(buf: &[u8], at: usize) -> i16 {
+    u16_at(buf, at) as i16
+}
+fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_ne_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+/// Builds the BadWindow error of the request of `seq`.
+fn bad_window(seq: SequenceNumber, wid: Wid, major_opcode: u8) -> Vec<u8> {
+    let mut error = vec![0; 32];
+    error[1] = 3; // Window
+    error[2..4].copy_from_slice(&(seq as u16).to_ne_bytes());
+    error[4..8].copy_from_slice(&wid.to_ne_bytes());
+    error[10] = major_opcode;
+    error
+}
+
+/// Builds a reply whose fixed part is `fixed` (at least 32 bytes) followed by `extra`.
+fn make_reply(seq: SequenceNumber, mut fixed: Vec<u8>, extra: &[u8]) -> Vec<u8> {
+    fixed.resize(std::cmp::max(fixed.len(), 32), 0);
+    fixed.extend_from_slice(extra);
+    while !fixed.len().is_multiple_of(4) {
+        fixed.push(0);
+    }
+    fixed[0] = 1;
+    fixed[2..4].copy_from_slice(&(seq as u16).to_ne_bytes());
+    let length = ((fixed.len() - 32) / 4) as u32;
+    fixed[4..8].copy_from_slice(&length.to_ne_bytes());
+    fixed
+}
+
+struct NoExtensions;
+
+impl ExtInfoProvider for NoExtensions {
+    fn get_from_major_opcode(&self, _: u8) -> Option<(&str, ExtensionInformation)> {
+        None
+    }
+    fn get_from_event_code(&self, _: u8) -> Option<(&str, ExtensionInformation)> {
+        None
+    }
+    fn get_from_error_code(&self, _: u8) -> Option<(&str, ExtensionInformation)> {
+        None
+    }
+}
+
+impl MockConnection {
+    pub fn new() -> Self {
+        let screen = Screen {
+            root: ROOT,
+            default_colormap: 0,
+            white_pixel: 0xFFFFFF,
+            black_pixel: 0,
+            current_input_masks: 0,
+            width_in_pixels: ROOT_WIDTH,
+            height_in_pixels: ROOT_HEIGHT,
+            width_in_millimeters: 508,
+            height_in_millimeters: 286,
+            min_installed_maps: 1,
+            max_installed_maps: 1,
+            root_visual: 0x21,
+            backing_stores: BackingStore::NOT_USEFUL,
+            save_unders: false,
+            root_depth: 24,
+            allowed_depths: Vec::new(),
+        };
+        let setup = Setup {
+            status: 1,
+            protocol_major_version: 11,
+            protocol_minor_version: 0,
+            length: 0,
+            release_number: 0,
+            resource_id_base: 0x0040_0000,
+            resource_id_mask: 0x001F_FFFF,
+            motion_buffer_size: 0,
+            maximum_request_length: u16::MAX,
+            image_byte_order: ImageOrder::LSB_FIRST,
+            bitmap_format_bit_order: ImageOrder::LSB_FIRST,
+            bitmap_format_scanline_unit: 32,
+            bitmap_format_scanline_pad: 32,
+            min_keycode: 8,
+            max_keycode: 255,
+            vendor: b"daily-mock".to_vec(),
+            pixmap_formats: Vec::new(),
+            roots: vec![screen],
+        };
+
+        let mut state = State {
+            next_id: 0x0040_0000,
+            focus: xproto::InputFocus::POINTER_ROOT.into(),
+            ..Default::default()
+        };
+        state.windows.insert(
+            ROOT,
+            MockWindow {
+                width: ROOT_WIDTH,
+                height: ROOT_HEIGHT,
+                mapped: true,
+                ..Default::default()
+            },
+        );
+
+        Self {
+            setup,
+            state: Mutex::new(state),
+            closed: Condvar::new(),
+        }
+    }
+
+    /// Mimics `RustConnection::connect`.
+    pub fn connect(_dpy_name: Option<&str>) -> Result<(Self, usize), ConnectError> {
+        Ok((Self::new(), 0))
+    }
+
+    /// Adds the second X screen of the same size, as a server in Zaphod mode has.
+    pub fn add_screen(&mut self) {
+        let mut screen = self.setup.roots[0].clone();
+        screen.root = SECOND_ROOT;
+        self.setup.roots.push(screen);
+        let mut state = self.state.lock().unwrap();
+        let root = state.windows[&ROOT].clone();
+        state.windows.insert(SECOND_ROOT, root);
+    }
+
+    /// Adds a monitor reported by RRGetMonitors.
+    pub fn add_monitor(&self, x: i16, y: i16, width: u16, height: u16) {
+        let mut state = self.state.lock().unwrap();
+        let primary = state.monitors.is_empty();
+        state.monitors.push(MonitorInfo {
+            name: 0,
+            primary,
+            automatic: true,
+            x,
+            y,
+            width,
+            height,
+            width_in_millimeters: 0,
+            height_in_millimeters: 0,
+            outputs: Vec::new(),
+        });
+    }
+
+    /// Creates a (not yet mapped) top-level window as if a client did.
+    pub fn create_client(&self, width: u16, height: u16) -> Wid {
+        let mut state = self.state.lock().unwrap();
+        let wid = state.next_id | 0x0100_0000; // a different client's id space
+        state.next_id += 1;
+        state.windows.insert(
+            wid,
+            MockWindow {
+                parent: ROOT,
+                width,
+                height,
+                ..Default::default()
+            },
+        );
+        state.stack.push(wid);
+        wid
+    }
+
+    /// Unmaps a window as if its client did.
+    pub fn unmap_client(&self, wid: Wid) {
+        if let Some(win) = self.state.lock().unwrap().windows.get_mut(&wid) {
+            win.mapped = false;
+        }
+    }
+
+    /// Simulates the destruction of a window by its client.
+    pub fn destroy_client(&self, wid: Wid) {
+        self.state.lock().unwrap().windows.remove(&wid);
+    }
+
+    /// Sets a property of a window as if its client did.
+    pub fn set_property(&self, wid: Wid, atom: u32, type_: u32, format: u8, data: Vec<u8>) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(win) = state.windows.get_mut(&wid) {
+            win.properties.insert(atom, (type_, format, data));
+        }
+    }
+
+    pub fn window(&self, wid: Wid) -> Option<MockWindow> {
+        self.state.lock().unwrap().windows.get(&wid).cloned()
+    }
+
+    /// All the windows created so far, in the order of their IDs.
+    pub fn windows(&self) -> Vec<(Wid, MockWindow)> {
+        let state = self.state.lock().unwrap();
+        let mut windows: Vec<_> = state
+            .windows
+            .iter()
+            .map(|(&wid, win)| (wid, win.clone()))
+            .collect();
+        windows.sort_by_key(|&(wid, _)| wid);
+        windows
+    }
+
+    /// Whether the window and all of its ancestors are mapped.
+    pub fn is_viewable(&self, wid: Wid) -> bool {
+        self.state.lock().unwrap().is_viewable(wid)
+    }
+
+    pub fn focus(&self) -> Wid {
+        self.state.lock().unwrap().focus
+    }
+
+    pub fn pointer(&self) -> (i16, i16) {
+        self.state.lock().unwrap().pointer
+    }
+
+    /// Sets the heads reported by Xinerama, which may differ from the RandR monitors.
+    pub fn set_xinerama_screens(&self, screens: &[(i16, i16, u16, u16)]) {
+        self.state.lock().unwrap().xinerama_screens = screens.to_vec();
+    }
+
+    /// Resizes the root window, as a server without RandR may do.
+    pub fn resize_root(&self, width: u16, height: u16) {
+        let mut state = self.state.lock().unwrap();
+        let root = state.windows.get_mut(&ROOT).unwrap();
+        root.width = width;
+        root.height = height;
+    }
+
+    /// Makes the extension unavailable, e.g. to test the fallbacks without it.
+    pub fn remove_extension(&self, name: &'static str) {
+        self.state.lock().unwrap().removed_extensions.push(name);
+    }
+
+    pub fn fake_buttons(&self) -> Vec<(u8, bool)> {
+        self.state.lock().unwrap().fake_buttons.clone()
+    }
+
+    /// The buttons grabbed on the window as (button, modifiers).
+    pub fn button_grabs(&self, wid: Wid) -> Vec<(u8, u16)> {
+        let state = self.state.lock().unwrap();
+        state
+            .button_grabs
+            .iter()
+            .filter(|grab| grab.0 == wid)
+            .map(|&(_, button, modifiers)| (button, modifiers))
+            .collect()
+    }
+
+    /// The keys grabbed on the window as (keycode, modifiers).
+    pub fn key_grabs(&self, wid: Wid) -> Vec<(u8, u16)> {
+        let state = self.state.lock().unwrap();
+        state
+            .key_grabs
+            .iter()
+            .filter(|grab| grab.0 == wid)
+            .map(|&(_, keycode, modifiers)| (keycode, modifiers))
+            .collect()
+    }
+
+    /// How many times the shape of the window has been set.
+    pub fn shape_count(&self, wid: Wid) -> usize {
+        let state = self.state.lock().unwrap();
+        state.shaped.iter().filter(|&&w| w == wid).count()
+    }
+
+    /// The windows focused while not viewable, which fails on a real server.
+    pub fn unviewable_focus(&self) -> Vec<Wid> {
+        self.state.lock().unwrap().unviewable_focus.clone()
+    }
+
+    /// Lets another client grab the keyboard.
+    pub fn grab_keyboard_elsewhere(&self) {
+        self.state.lock().unwrap().keyboard_grabbed = true;
+    }
+
+    /// Moves the pointer as the user would, without a WarpPointer.
+    pub fn set_pointer(&self, x: i16, y: i16) {
+        self.state.lock().unwrap().pointer = (x, y);
+    }
+
+    fn intern_atom(state: &mut State, name: &str) -> u32 {
+        // Predefined atoms occupy 1..=68.
+        const LAST_PREDEFINED_ATOM: u32 = 68;
+        match state.atoms.iter().position(|a| a == name) {
+            Some(i) => LAST_PREDEFINED_ATOM + 1 + i as u32,
+            None => {
+                state.atoms.push(name.to_owned());
+                LAST_PREDEFINED_ATOM + state.atoms.len() as u32
+            }
+        }
+    }
+
+    fn atom_name(state: &State, atom: u32) -> Vec<u8> {
+        state
+            .atoms
+            .get((atom as usize).wrapping_sub(69))
+            .map(|name| name.as_bytes().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Applies a request to the model. Returns its reply if the request has one.
+    fn process(&self, req: &[u8]) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        state.sequence += 1;
+        let seq = state.sequence;
+
+        match req[0] {
+            // CreateWindow
+            1 => {
+                let wid = u32_at(req, 4);
+                // The values follow the mask in the order of the bits.
+                let value_mask = u32_at(req, 28);
+                let override_redirect = value_mask & 0x200 != 0 && {
+                    let index = (value_mask & 0x1ff).count_ones() as usize;
+                    u32_at(req, 32 + index * 4) != 0
+                };
+                let win = MockWindow {
+                    parent: u32_at(req, 8),
+                    x: i16_at(req, 12),
+                    y: i16_at(req, 14),
+                    width: u16_at(req, 16),
+                    height: u16_at(req, 18),
+                    border_width: u16_at(req, 20),
+                    input_only: u16_at(req, 22) == u16::from(xproto::WindowClass::INPUT_ONLY),
+                    override_redirect,
+                    ..Default::default()
+                };
+                state.windows.insert(wid, win);
+                state.stack.push(wid);
+                None
+            }
+            // GetWindowAttributes
+            3 => {
+                let wid = u32_at(req, 4);
+                let win = match state.windows.get(&wid) {
+                    Some(win) => win.clone(),
+                    None => {
+                        state.errors.insert(seq, bad_window(seq, wid, req[0]));
+                        return None;
+                    }
+                };
+                let mut fixed = vec![0; 44];
+                let class = if win.input_only {
+                    xproto::WindowClass::INPUT_ONLY
+                } else {
+                    xproto::WindowClass::INPUT_OUTPUT
+                };
+                fixed[12..14].copy_from_slice(&u16::from(class).to_ne_bytes());
+                let map_state = if win.mapped {
+                    xproto::MapState::VIEWABLE
+                } else {
+                    xproto::MapState::UNMAPPED
+                };
+                fixed[26] = u8::from(map_state);
+                fixed[27] = win.override_redirect as u8;
+                Some(make_reply(seq, fixed, &[]))
+            }
+            // DestroyWindow
+            4 => {
+                let wid = u32_at(req, 4);
+                if state.windows.remove(&wid).is_none() {
+                    state.errors.insert(seq, bad_window(seq, wid, req[0]));
+                }
+                state.windows.retain(|_, win| win.parent != wid);
+                state.selections.retain(|_, owner| *owner != wid);
+                None
+            }
+            // ReparentWindow
+            7 => {
+                let wid = u32_at(req, 4);
+                let parent = u32_at(req, 8);
+                if let Some(win) = state.windows.get_mut(&wid) {
+                    win.parent = parent;
+                    win.x = i16_at(req, 12);
+                    win.y = i16_at(req, 14);
+                }
+                // A reparented window goes to the top of its new siblings.
+                state.stack.retain(|&w| w != wid);
+                state.stack.push(wid);
+                None
+            }
+            // MapWindow / UnmapWindow
+            8 | 10 => {
+                if let Some(win) = state.windows.get_mut(&u32_at(req, 4)) {
+                    win.mapped = req[0] == 8;
+                }
+                None
+            }
+            // ConfigureWindow
+            12 => {
+                let wid = u32_at(req, 4);
+                let mask = u16_at(req, 8);
+                let mut at = 12;
+                let mut next = || {
+                    let v = u32_at(req, at);
+                    at += 4;
+                    v
+                };
+                if let Some(win) = state.windows.get_mut(&wid) {
+                    if mask & 0x01 != 0 {
+                        win.x = next() as i16;
+                    }
+                    if mask & 0x02 != 0 {
+                        win.y = next() as i16;
+                    }
+                    if mask & 0x04 != 0 {
+                        win.width = next() as u16;
+                    }
+                    if mask & 0x08 != 0 {
+                        win.height = next() as u16;
+                    }
+                    if mask & 0x10 != 0 {
+                        win.border_width = next() as u16;
+                    }
+                    let sibling = (mask & 0x20 != 0).then(&mut next);
+                    if mask & 0x40 != 0 {
+                        let above = next() == u32::from(xproto::StackMode::ABOVE);
+                        state.stack.retain(|&w| w != wid);
+                        let index =
+                            match sibling.and_then(|s| state.stack.iter().position(|&w| w == s)) {
+                                Some(index) if above => index + 1,
+                                Some(index) => index,
+                                None if above => state.stack.len(),
+                                None => 0,
+                            };
+                        state.stack.insert(index, wid);
+                    }
+                }
+                None
+            }
+            // GetGeometry
+            14 => {
+                let win = state
+                    .windows
+                    .get(&u32_at(req, 4))
+                    .cloned()
+                    .unwrap_or_default();
+                let mut fixed = vec![0; 32];
+                fixed[1] = 24;
+                fixed[8..12].copy_from_slice(&ROOT.to_ne_bytes());
+                fixed[12..14].copy_from_slice(&win.x.to_ne_bytes());
+                fixed[14..16].copy_from_slice(&win.y.to_ne_bytes());
+                fixed[16..18].copy_from_slice(&win.width.to_ne_bytes());
+                fixed[18..20].copy_from_slice(&win.height.to_ne_bytes());
+                fixed[20..22].copy_from_slice(&win.border_width.to_ne_bytes());
+                Some(make_reply(seq, fixed, &[]))
+            }
+            // QueryTree
+            15 => {
+                let wid = u32_at(req, 4);
+                // from the bottom of the stack
+                let children: Vec<Wid> = state
+                    .stack
+                    .iter()
+                    .copied()
+                    .filter(|child| state.windows.get(child).map(|win| win.parent) == Some(wid))
+                    .collect();
+                let mut fixed = vec![0; 32];
+                fixed[8..12].copy_from_slice(&ROOT.to_ne_bytes());
+                fixed[16..18].copy_from_slice(&(children.len() as u16).to_ne_bytes());
+                let extra: Vec<u8> = children.iter().flat_map(|c| c.to_ne_bytes()).collect();
+                Some(make_reply(seq, fixed, &extra))
+            }
+            // InternAtom
+            16 => {
+                let len = u16_at(req, 4) as usize;
+                let name = String::from_utf8_lossy(&req[8..8 + len]).into_owned();
+                let atom = Self::intern_atom(&mut state, &name);
+                let mut fixed = vec![0; 32];
+                fixed[8..12].copy_from_slice(&atom.to_ne_bytes());
+                Some(make_reply(seq, fixed, &[]))
+            }
+            // GetAtomName
+            17 => {
+                let name = Self::atom_name(&state, u32_at(req, 4));
+                let mut fixed = vec![0; 32];
+                fixed[8..10].copy_from_slice(&(name.len() as u16).to_ne_bytes());
+                Some(make_reply(seq, fixed, &name))
+            }
+            // ChangeProperty (only PropMode::REPLACE is modeled)
+            18 => {
+                let wid = u32_at(req, 4);
+                let property = u32_at(req, 8);
+                let type_ = u32_at(req, 12);
+                let format = req[16];
+                let len = u32_at(req, 20) as usize * (format as usize / 8);
+                let data = req[24..24 + len].to_vec();
+                if let Some(win) = state.windows.get_mut(&wid) {
+                    win.properties.insert(property, (type_, format, data));
+                }
+                None
+            }
+            // DeleteProperty
+            19 => {
+                let wid = u32_at(req, 4);
+                let property = u32_at(req, 8);
+                if let Some(win) = state.windows.get_mut(&wid) {
+                    win.properties.remove(&property);
+                }
+                None
+            }
+            // GetProperty
+            20 => {
+                let wid = u32_at(req, 4);
+                let property = u32_at(req, 8);
+                let value = state
+                    .windows
+                    .get(&wid)
+                    .and_then(|win| win.properties.get(&property))
+                    .cloned();
+                let mut fixed = vec![0; 32];
+                let extra = match value {
+                    Some((type_, format, data)) => {
+                        fixed[1] = format;
+                        fixed[8..12].copy_from_slice(&type_.to_ne_bytes());
+                        let units = (data.len() / (format as usize / 8)) as u32;
+                        fixed[16..20].copy_from_slice(&units.to_ne_bytes());
+                        data
+                    }
+                    None => Vec::new(),
+                };
+                Some(make_reply(seq, fixed, &extra))
+            }
+            // SetSelectionOwner
+            22 => {
+                let owner = u32_at(req, 4);
+                let selection = u32_at(req, 8);
+                state.selections.insert(selection, owner);
+                None
+            }
+            // GetSelectionOwner
+            23 => {
+                let owner = state.selections.get(&u32_at(req, 4)).copied();
+                let mut fixed = vec![0; 32];
+                fixed[8..12].copy_from_slice(&owner.unwrap_or(0).to_ne_bytes());
+                Some(make_reply(seq, fixed, &[]))
+            }
+            // WarpPointer (only relative moves)
+            41 => {
+                let (x, y) = state.pointer;
+                state.pointer = (x + i16_at(req, 20), y + i16_at(req, 22));
+                None
+            }
+            // QueryPointer
+            38 => {
+                let mut fixed = vec![0; 32];
+                fixed[1] = 1; // same-screen
+                fixed[8..12].copy_from_slice(&ROOT.to_ne_bytes());
+                fixed[16..18].copy_from_slice(&state.pointer.0.to_ne_bytes());
+                fixed[18..20].copy_from_slice(&state.pointer.1.to_ne_bytes());
+                Some(make_reply(seq, fixed, &[]))
+            }
+            // SetInputFocus
+            42 => {
+                let wid = u32_at(req, 4);
+                let is_window = wid != u32::from(xproto::InputFocus::NONE)
+                    && wid != u32::from(xproto::InputFocus::POINTER_ROOT);
+                if is_window && !state.is_viewable(wid) {
+                    state.unviewable_focus.push(wid);
+                }
+                state.focus = wid;
+                None
+            }
+            // GetInputFocus
+            43 => {
+                let mut fixed = vec![0; 32];
+                fixed[8..12].copy_from_slice(&state.focus.to_ne_bytes());
+                Some(make_reply(seq, fixed, &[]))
+            }
+            // RRGetMonitors
+            op if op == FIRST_EXTENSION_OPCODE && req[1] == 42 => {
+                let mut fixed = vec![0; 32];
+                fixed[12..16].copy_from_slice(&(state.monitors.len() as u32).to_ne_bytes());
+                let extra: Vec<u8> = state
+                    .monitors
+                    .iter()
+                    .flat_map(|mon| mon.serialize())
+                    .collect();
+                Some(make_reply(seq, fixed, &extra))
+            }
+            // ShapeRectangles
+            op if op == FIRST_EXTENSION_OPCODE + 1 && req[1] == 1 => {
+                state.shaped.push(u32_at(req, 8));
+                None
+            }
+            // XineramaIsActive
+            op if op == FIRST_EXTENSION_OPCODE + 5 && req[1] == 4 => {
+                let mut fixed = vec![0; 32];
+                let active = !state.xinerama_screens.is_empty() as u32;
+                fixed[8..12].copy_from_slice(&active.to_ne_bytes());
+                Some(make_reply(seq, fixed, &[]))
+            }
+            // XineramaQueryScreens
+            op if op == FIRST_EXTENSION_OPCODE + 5 && req[1] == 5 => {
+                let mut fixed = vec![0; 32];
+                let number = state.xinerama_screens.len() as u32;
+                fixed[8..12].copy_from_slice(&number.to_ne_bytes());
+                let extra: Vec<u8> = state
+                    .xinerama_screens
+                    .iter()
+                    .flat_map(|&(x, y, width, height)| {
+                        [
+                            x.to_ne_bytes(),
+                            y.to_ne_bytes(),
+                            width.to_ne_bytes(),
+                            height.to_ne_bytes(),
+                        ]
+                    })
+                    .flatten()
+                    .collect();
+                Some(make_reply(seq, fixed, &extra))
+            }
+            // XTestFakeInput
+            op if op == FIRST_EXTENSION_OPCODE + 3 && req[1] == 2 => {
+                let press = req[4] == xproto::BUTTON_PRESS_EVENT;
+                if press || req[4] == xproto::BUTTON_RELEASE_EVENT {
+                    state.fake_buttons.push((req[5], press));
+                }
+                None
+            }
+            // GrabButton
+            28 => {
+                let grab = (u32_at(req, 4), req[20], u16_at(req, 22));
+                state.button_grabs.retain(|g| *g != grab);
+                state.button_grabs.push(grab);
+                None
+            }
+            // GrabKey
+            33 => {
+                let grab = (u32_at(req, 4), req[10], u16_at(req, 8));
+                state.key_grabs.retain(|g| *g != grab);
+                state.key_grabs.push(grab);
+                None
+            }
+            // UngrabKey
+            34 => {
+                let grab = (u32_at(req, 4), req[1], u16_at(req, 8));
+                state.key_grabs.retain(|g| *g != grab);
+                None
+            }
+            // GrabKeyboard
+            31 => {
+                let mut reply = vec![0; 32];
+                if state.keyboard_grabbed {
+                    reply[1] = xproto::GrabStatus::ALREADY_GRABBED.into();
+                }
+                Some(make_reply(seq, reply, &[]))
+            }
+            // UngrabButton (only AnyButton with AnyModifier)
+            29 => {
+                let wid = u32_at(req, 4);
+                state.button_grabs.retain(|g| g.0 != wid);
+                None
+            }
+            // KillClient
+            113 => {
+                // Killing a resource of our own closes the connection.
+                let resource = u32_at(req, 4);
+                if resource & !self.setup.resource_id_mask == self.setup.resource_id_base {
+                    state.closed = true;
+                    self.closed.notify_all();
+                }
+                None
+            }
+            _ => Some(make_reply(seq, vec![0; 64], &[])),
+        }
+    }
+
+    fn send(&self, bufs: &[IoSlice<'_>]) -> (SequenceNumber, Option<Vec<u8>>) {
+        let req: Vec<u8> = bufs.iter().flat_map(|buf| buf.iter().copied()).collect();
+        let reply = self.process(&req);
+        let seq = self.state.lock().unwrap().sequence;
+        (seq, reply)
+    }
+}
+
+impl RequestConnection for MockConnection {
+    type Buf = Vec<u8>;
+
+    fn send_request_with_reply<R>(
+        &self,
+        bufs: &[IoSlice<'_>],
+        _fds: Vec<RawFdContainer>,
+    ) -> Result<Cookie<'_, Self, R>, ConnectionError>
+    where
+        R: TryParse,
+    {
+        let (seq, reply) = self.send(bufs);
+        let mut state = self.state.lock().unwrap();
+        if !state.errors.contains_key(&seq) {
+            let reply = reply.unwrap_or_else(|| make_reply(seq, vec![0; 64], &[]));
+            state.replies.insert(seq, reply);
+        }
+        Ok(Cookie::new(self, seq))
+    }
+
+    fn send_request_with_reply_with_fds<R>(
+        &self,
+        _bufs: &[IoSlice<'_>],
+        _fds: Vec<RawFdContainer>,
+    ) -> Result<CookieWithFds<'_, Self, R>, ConnectionError>
+    where
+        R: TryParseFd,
+    {
+        Err(ConnectionError::FdPassingFailed)
+    }
+
+    fn send_request_without_reply(
+        &self,
+        bufs: &[IoSlice<'_>],
+        _fds: Vec<RawFdContainer>,
+    ) -> Result<VoidCookie<'_, Self>, ConnectionError> {
+        let (seq, _) = self.send(bufs);
+        Ok(VoidCookie::new(self, seq))
+    }
+
+    fn discard_reply(&self, sequence: SequenceNumber, _kind: RequestKind, _mode: DiscardMode) {
+        let mut state = self.state.lock().unwrap();
+        state.replies.remove(&sequence);
+        state.errors.remove(&sequence);
+    }
+
+    fn prefetch_extension_information(&self, _: &'static str) -> Result<(), ConnectionError> {
+        Ok(())
+    }
+
+    fn extension_information(
+        &self,
+        extension_name: &'static str,
+    ) -> Result<Option<ExtensionInformation>, ConnectionError> {
+        let removed = self.state.lock().unwrap().removed_extensions.clone();
+        Ok(EXTENSIONS
+            .iter()
+            .position(|&name| name == extension_name && !removed.contains(&name))
+            .map(|i| ExtensionInformation {
+                major_opcode: FIRST_EXTENSION_OPCODE + i as u8,
+                first_event: 64 + 8 * i as u8,
+                first_error: 128 + 8 * i as u8,
+            }))
+    }
+
+    fn wait_for_reply_or_raw_error(
+        &self,
+        sequence: SequenceNumber,
+    ) -> Result<ReplyOrError<Vec<u8>>, ConnectionError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(error) = state.errors.remove(&sequence) {
+            return Ok(ReplyOrError::Error(error));
+        }
+        let reply = state.replies.remove(&sequence);
+        Ok(ReplyOrError::Reply(
+            reply.unwrap_or_else(|| make_reply(sequence, vec![0; 64], &[])),
+        ))
+    }
+
+    fn wait_for_reply(&self, sequence: SequenceNumber) -> Result<Option<Vec<u8>>, ConnectionError> {
+        Ok(self.state.lock().unwrap().replies.remove(&sequence))
+    }
+
+    fn wait_for_reply_with_fds_raw(
+        &self,
+        _sequence: SequenceNumber,
+    ) -> Result<ReplyOrError<BufWithFds<Vec<u8>>, Vec<u8>>, ConnectionError> {
+        Err(ConnectionError::FdPassingFailed)
+    }
+
+    fn check_for_raw_error(
+        &self,
+        sequence: SequenceNumber,
+    ) -> Result<Option<Vec<u8>>, ConnectionError> {
+        Ok(self.state.lock().unwrap().errors.remove(&sequence))
+    }
+
+    fn prefetch_maximum_request_bytes(&self) {}
+
+    fn maximum_request_bytes(&self) -> usize {
+        u16::MAX as usize * 4
+    }
+
+    fn parse_error(&self, error: &[u8]) -> Result<X11Error, ParseError> {
+        X11Error::try_parse(error, &NoExtensions)
+    }
+
+    fn parse_event(&self, event: &[u8]) -> Result<Event, ParseError> {
+        Event::parse(event, &NoExtensions)
+    }
+}
+
+impl Connection for MockConnection {
+    fn wait_for_raw_event_with_sequence(
+        &self,
+    ) -> Result<x11rb::connection::RawEventAndSeqNumber<Vec<u8>>, ConnectionError> {
+        // No events are ever generated, so just wait for the connection to be closed.
+        let mut state = self.state.lock().unwrap();
+        while !state.closed {
+            state = self.closed.wait(state).unwrap();
+        }
+        Err(ConnectionError::UnknownError)
+    }
+
+    fn poll_for_raw_event_with_sequence(
+        &self,
+    ) -> Result<Option<x11rb::connection::RawEventAndSeqNumber<Vec<u8>>>, ConnectionError> {
+        Ok(None)
+    }
+
+    fn flush(&self) -> Result<(), ConnectionError> {
+        Ok(())
+    }
+
+    fn setup(&self) -> &Setup {
+        &self.setup
+    }
+
+    fn generate_id(&self) -> Result<u32, ReplyOrIdError> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        Ok(id)
+    }
+}
+
+/// Creates a context backed by a fake connection having the given monitors.
+pub fn context(monitors: &[(i16, i16, u16, u16)]) -> Context {
+    context_with_extensions(monitors, Extensions::default())
+}
+
+pub fn context_with_extensions(
+    monitors: &[(i16, i16, u16, u16)],
+    extensions: Extensions,
+) -> Context {
+    build_context(monitors, Config::default(), extensions)
+}
+
+pub fn context_with_config(monitors: &[(i16, i16, u16, u16)], config: Config) -> Context {
+    build_context(monitors, config, Extensions::default())
+}
+
+fn build_context(
+    monitors: &[(i16, i16, u16, u16)],
+    config: Config,
+    extensions: Extensions,
+) -> Context {
+    let conn = MockConnection::new();
+    for &(x, y, width, height) in monitors {
+        conn.add_monitor(x, y, width, height);
+    }
+    let mut inner = ContextInner::with_connection(conn, 0, config, None)
+        .expect("failed to initialize a mock context");
+    inner.extensions = std::sync::Arc::new(extensions);
+    std::sync::Arc::new(inner)
+}
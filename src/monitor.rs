@@ -1,20 +1,154 @@
+use x11rb::connection::{Connection as _, RequestConnection as _};
 use x11rb::protocol::randr::MonitorInfo;
+use x11rb::protocol::xinerama::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{Window as Wid, *};
 
 use crate::bar::BarHandle;
 use crate::context::Context;
+use crate::error::Result;
+use crate::window::FrameMetrics;
 
 #[derive(Debug)]
 pub struct Monitor {
     pub id: usize,
     pub info: MonitorInfo,
+    /// The factor of the sizes of the bar, the title bars and the borders.
+    pub scale: u16,
+    /// The sizes of the frames of the windows on the monitor.
+    pub frame: FrameMetrics,
     pub bar: BarHandle,
+    /// An invisible window at the top edge, which reveals the bar hidden by `bar.autohide`.
+    pub edge: Wid,
+    /// Whether the bar hidden by `bar.autohide` is shown for the pointer at the top edge.
+    pub bar_revealed: bool,
 }
 
 impl Monitor {
-    pub fn new(ctx: &Context, id: usize, info: MonitorInfo) -> Self {
+    pub fn new(ctx: &Context, id: usize, info: MonitorInfo) -> Result<Self> {
+        // The monitors made up without RandR have no name.
+        let name = if info.name == x11rb::NONE {
+            Vec::new()
+        } else {
+            ctx.conn.get_atom_name(info.name)?.reply()?.name
+        };
+        let name = String::from_utf8_lossy(&name);
+        let scale = ctx
+            .config
+            .scale
+            .factor(&name, info.width, info.width_in_millimeters);
+        let frame = FrameMetrics::new(&ctx.config.border, &name, scale);
+
         let mut bar = BarHandle::new(ctx, id);
-        bar.show().expect("TODO: bar.show");
+        bar.show()?;
+
+        let edge = ctx.conn.generate_id()?;
+        let aux = CreateWindowAux::new()
+            .event_mask(EventMask::ENTER_WINDOW)
+            .override_redirect(1);
+        ctx.conn.create_window(
+            0,
+            edge,
+            ctx.root,
+            info.x,
+            info.y,
+            info.width,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &aux,
+        )?;
+
+        Ok(Self {
+            id,
+            info,
+            scale,
+            frame,
+            bar,
+            edge,
+            bar_revealed: false,
+        })
+    }
 
-        Self { id, info, bar }
+    /// Destroys the bar of the monitor which is no longer in use.
+    pub fn shutdown(mut self, ctx: &Context) -> Result<()> {
+        self.bar.shutdown();
+        ctx.conn.destroy_window(self.edge)?;
+        Ok(())
+    }
+
+    /// Returns the geometry of the monitor.
+    pub fn rect(&self) -> Rectangle {
+        Rectangle {
+            x: self.info.x,
+            y: self.info.y,
+            width: self.info.width,
+            height: self.info.height,
+        }
+    }
+}
+
+/// Makes up a monitor without a name, whose size in millimeters is the part of the root window's.
+fn unnamed_monitor(ctx: &Context, rect: Rectangle, primary: bool) -> MonitorInfo {
+    let root = ctx.screen();
+    let mm = |px: u16, root_px: u16, root_mm: u16| {
+        (px as u32 * root_mm as u32) / (root_px as u32).max(1)
+    };
+    MonitorInfo {
+        name: x11rb::NONE,
+        primary,
+        automatic: true,
+        x: rect.x,
+        y: rect.y,
+        width: rect.width,
+        height: rect.height,
+        width_in_millimeters: mm(rect.width, root.width_in_pixels, root.width_in_millimeters),
+        height_in_millimeters: mm(
+            rect.height,
+            root.height_in_pixels,
+            root.height_in_millimeters,
+        ),
+        outputs: Vec::new(),
+    }
+}
+
+/// The whole root window of the size as the only monitor, e.g. without RandR.
+pub fn root_monitor(ctx: &Context, width: u16, height: u16) -> MonitorInfo {
+    let rect = Rectangle {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+    unnamed_monitor(ctx, rect, true)
+}
+
+/// Returns the heads of Xinerama as monitors, or `None` if it is not active.
+pub fn xinerama_monitors(ctx: &Context) -> Result<Option<Vec<MonitorInfo>>> {
+    if ctx
+        .conn
+        .extension_information(xinerama::X11_EXTENSION_NAME)?
+        .is_none()
+        || ctx.conn.xinerama_is_active()?.reply()?.state == 0
+    {
+        return Ok(None);
+    }
+    let heads = ctx.conn.xinerama_query_screens()?.reply()?.screen_info;
+    if heads.is_empty() {
+        return Ok(None);
     }
+    let monitors = heads
+        .iter()
+        .enumerate()
+        .map(|(i, head)| {
+            let rect = Rectangle {
+                x: head.x_org,
+                y: head.y_org,
+                width: head.width,
+                height: head.height,
+            };
+            unnamed_monitor(ctx, rect, i == 0)
+        })
+        .collect();
+    Ok(Some(monitors))
 }
@@ -0,0 +1,294 @@
+use crossbeam_channel::{after, never, select, unbounded, Receiver, Sender};
+use log::{debug, error};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Window as Wid, *};
+use x11rb::protocol::Event;
+
+use crate::context::{self, Context, XConnection};
+use crate::error::{Error, Result};
+use crate::spawn_named_thread;
+
+const HEIGHT: u16 = 32;
+const PADDING: u16 = 16;
+/// The size of a character of the "fixed" font.
+const CHAR_WIDTH: u16 = 6;
+const MAX_CHARS: usize = 80;
+
+const COLOR_BG: u32 = 0x1a1949;
+const COLOR_FG: u32 = 0xd2ca9c;
+
+/// Returns the geometry of the window showing `len` characters in the middle of `area`.
+fn geometry(area: Rectangle, len: usize) -> Rectangle {
+    let width = (CHAR_WIDTH * len as u16 + PADDING * 2).min(area.width);
+    let height = HEIGHT.min(area.height);
+    Rectangle {
+        x: area.x + ((area.width - width) / 2) as i16,
+        y: area.y + ((area.height - height) / 2) as i16,
+        width,
+        height,
+    }
+}
+
+#[derive(Debug)]
+enum Message {
+    /// Shows the text in the middle of `area` until the timeout.
+    Show {
+        area: Rectangle,
+        text: String,
+    },
+    Shutdown,
+}
+
+/// A handle of the thread showing short messages (e.g. "Screen 3") over everything.
+/// Like the bars, the thread has its own connection.
+#[derive(Debug)]
+pub struct OsdHandle {
+    tx: Option<Sender<Message>>,
+    thread: Option<JoinHandle<()>>,
+    /// The window of the thread, or 0 until it is created.
+    wid: Arc<AtomicU32>,
+}
+
+impl OsdHandle {
+    pub fn new(ctx: &Context) -> Self {
+        let config = &ctx.config.osd;
+        let wid = Arc::new(AtomicU32::new(0));
+        if !config.enabled {
+            return Self {
+                tx: None,
+                thread: None,
+                wid,
+            };
+        }
+
+        let display = ctx.display.clone();
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let (tx, rx) = unbounded();
+        let thread_wid = wid.clone();
+        let thread = spawn_named_thread("osd".to_owned(), move || {
+            if let Err(err) = thread_main(display, timeout, rx, &thread_wid) {
+                error!("osd: {}", err);
+            }
+        });
+
+        Self {
+            tx: Some(tx),
+            thread: Some(thread),
+            wid,
+        }
+    }
+
+    /// Whether `wid` is the window showing the messages.
+    pub fn is_osd(&self, wid: Wid) -> bool {
+        wid != x11rb::NONE && self.wid.load(Ordering::Relaxed) == wid
+    }
+
+    /// Shows the text in the middle of `area` for a while.
+    pub fn show(&self, area: Rectangle, text: &str) {
+        if let Some(tx) = &self.tx {
+            // The thread has already reported its failure.
+            let _ = tx.send(Message::Show {
+                area,
+                text: text.to_owned(),
+            });
+        }
+    }
+}
+
+impl Drop for OsdHandle {
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(Message::Shutdown);
+        }
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("osd: the thread panicked");
+            }
+        }
+    }
+}
+
+fn thread_main(
+    display: Option<String>,
+    timeout: Duration,
+    rx: Receiver<Message>,
+    wid: &AtomicU32,
+) -> Result<()> {
+    let (conn, screen_num) = context::connect(display.as_deref())?;
+    let conn = Arc::new(conn);
+
+    let mut osd = Osd::new(conn.clone(), screen_num)?;
+    wid.store(osd.wid, Ordering::Relaxed);
+
+    let (event_tx, event_rx) = unbounded();
+    osd.event_thread = Some(spawn_named_thread("osd-x11".to_owned(), move || loop {
+        let event = conn.wait_for_event();
+        let failed = event.is_err();
+        if event_tx.send(event).is_err() || failed {
+            return;
+        }
+    }));
+    // Dropping `osd` cause the "osd-x11" thread to be terminated.
+
+    let mut deadline: Option<Instant> = None;
+    loop {
+        let timer = match deadline {
+            Some(deadline) => after(deadline.saturating_duration_since(Instant::now())),
+            None => never(),
+        };
+
+        select! {
+            recv(rx) -> msg => match msg {
+                Ok(Message::Show { area, text }) => {
+                    osd.show(area, &text)?;
+                    deadline = Some(Instant::now() + timeout);
+                }
+                Ok(Message::Shutdown) | Err(_) => break,
+            },
+
+            recv(event_rx) -> event => {
+                let event = event.map_err(|_| Error::BrokenChannel)??;
+                if let Event::Expose(_) = event {
+                    osd.draw()?;
+                }
+            }
+
+            recv(timer) -> _ => {
+                osd.hide()?;
+                deadline = None;
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Osd {
+    conn: Arc<XConnection>,
+    wid: Wid,
+    gc: Gcontext,
+    text: Vec<u8>,
+    event_thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Osd {
+    fn drop(&mut self) {
+        // Killing our own client closes the connection, which destroys the window
+        // and wakes up the "osd-x11" thread.
+        let _ = self.conn.kill_client(self.wid);
+        let _ = self.conn.flush();
+        if let Some(thread) = self.event_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Osd {
+    fn new(conn: Arc<XConnection>, screen_num: usize) -> Result<Self> {
+        let root = conn.setup().roots[screen_num].root;
+
+        let wid = conn.generate_id()?;
+        let aux = CreateWindowAux::new()
+            .background_pixel(COLOR_BG)
+            .event_mask(EventMask::EXPOSURE)
+            .override_redirect(1);
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            wid,
+            root,
+            -1,
+            -1,
+            1,
+            1,
+            1,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &aux,
+        )?;
+
+        let gc = conn.generate_id()?;
+        {
+            let font = conn.generate_id()?;
+            conn.open_font(font, b"fixed")?;
+
+            let aux = CreateGCAux::new()
+                .font(font)
+                .foreground(COLOR_FG)
+                .background(COLOR_BG);
+            conn.create_gc(gc, wid, &aux)?;
+
+            conn.close_font(font)?;
+        }
+        conn.flush()?;
+
+        Ok(Self {
+            conn,
+            wid,
+            gc,
+            text: Vec::new(),
+            event_thread: None,
+        })
+    }
+
+    fn show(&mut self, area: Rectangle, text: &str) -> Result<()> {
+        debug!("osd: {:?}", text);
+        // Core fonts cannot show non-ASCII characters.
+        self.text = text
+            .chars()
+            .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+            .take(MAX_CHARS)
+            .collect();
+
+        let rect = geometry(area, self.text.len());
+        let aux = ConfigureWindowAux::new()
+            .x(rect.x as i32)
+            .y(rect.y as i32)
+            .width(rect.width as u32)
+            .height(rect.height as u32)
+            .stack_mode(StackMode::ABOVE);
+        self.conn.configure_window(self.wid, &aux)?;
+        self.conn.map_window(self.wid)?;
+        self.conn.clear_area(false, self.wid, 0, 0, 0, 0)?;
+        self.draw()
+    }
+
+    fn hide(&mut self) -> Result<()> {
+        self.conn.unmap_window(self.wid)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn draw(&self) -> Result<()> {
+        let y = ((HEIGHT + 10) / 2) as i16;
+        self.conn
+            .image_text8(self.wid, self.gc, PADDING as i16, y, &self.text)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osd_is_centered_on_the_monitor() {
+        let area = Rectangle {
+            x: 1920,
+            y: 0,
+            width: 1280,
+            height: 1024,
+        };
+        let rect = geometry(area, 8);
+        assert_eq!((rect.width, rect.height), (80, HEIGHT));
+        assert_eq!((rect.x, rect.y), (1920 + 600, 496));
+
+        // Long messages are clipped by the monitor.
+        assert_eq!(geometry(area, MAX_CHARS * 4).width, 1280);
+        assert_eq!(geometry(area, MAX_CHARS * 4).x, 1920);
+    }
+}
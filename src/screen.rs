@@ -1,26 +1,48 @@
-use log::debug;
+use log::{debug, warn};
 use std::collections::{BTreeMap, VecDeque};
 
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{Window as Wid, *};
 
-use crate::bar::Content;
+use crate::animation::Animator;
+use crate::bar::{Content, Task};
 use crate::context::Context;
 use crate::error::Result;
 use crate::event::EventHandlerMethods;
-use crate::layout::{self, Layout};
+use crate::geometry::Rect;
+use crate::layout::{self, Direction, Layout, LayoutWindow, Placement};
 use crate::monitor::Monitor;
-use crate::window::{Window, WindowState};
+use crate::stacking::{self, Layer};
+use crate::tab_strip::{Tab, TabStrip};
+use crate::window::{FrameMetrics, Window, WindowMode, WindowState};
+
+fn with_geometry(aux: ConfigureWindowAux, rect: Rectangle) -> ConfigureWindowAux {
+    aux.x(rect.x as i32)
+        .y(rect.y as i32)
+        .width(rect.width as u32)
+        .height(rect.height as u32)
+}
 
+/// A set of windows shown on a monitor at a time.
 #[derive()]
 pub struct Screen {
     ctx: Context,
     pub id: usize,
     monitor: Option<Monitor>,
     wins: BTreeMap<Wid, Window>,
-    background: Window,
+    order: Vec<Wid>,            // frames in the order to be tiled
+    float_stack: Vec<Wid>,      // floating frames from the bottom of the stack
+    placements: Vec<Placement>, // the last result of the layout
+    last_focused: Option<Wid>,  // the frame focused last, which gets the focus back
+    background: Option<Window>,
+    focus_holder: Wid,
+    tab_strip: TabStrip,
     layouts: VecDeque<Box<dyn Layout>>,
     border_visible: bool,
+    animator: Animator,
+    error: bool,
+    keyboard_layout: String,
+    level: Option<String>,
 }
 
 impl std::fmt::Debug for Screen {
@@ -37,46 +59,92 @@ impl std::fmt::Debug for Screen {
 
 impl Screen {
     pub fn new(ctx: Context, id: usize) -> Result<Self> {
-        let background = {
+        let depth = x11rb::COPY_DEPTH_FROM_PARENT;
+        let visual = x11rb::COPY_FROM_PARENT;
+
+        let background = if ctx.config.background_window {
             let wid = ctx.conn.generate_id()?;
-            let depth = x11rb::COPY_DEPTH_FROM_PARENT;
             let class = WindowClass::INPUT_OUTPUT;
-            let visual = x11rb::COPY_FROM_PARENT;
-            let aux = CreateWindowAux::new()
-                .background_pixel(ctx.config.background_color)
-                .event_mask(EventMask::FOCUS_CHANGE);
+            let aux = CreateWindowAux::new().background_pixel(ctx.theme().background);
             ctx.conn
                 .create_window(depth, wid, ctx.root, 0, 0, 16, 16, 0, class, visual, &aux)?;
-            Window::new(ctx.clone(), wid, WindowState::Unmapped, 0)?
+            let metrics = FrameMetrics::borderless();
+            Some(Window::new(
+                ctx.clone(),
+                wid,
+                WindowState::Unmapped,
+                metrics,
+            )?)
+        } else {
+            None
+        };
+
+        // An invisible window out of sight, which holds the focus while no client is focused.
+        let focus_holder = {
+            let wid = ctx.conn.generate_id()?;
+            let class = WindowClass::INPUT_ONLY;
+            let aux = CreateWindowAux::new()
+                .event_mask(EventMask::FOCUS_CHANGE)
+                .override_redirect(1);
+            ctx.conn
+                .create_window(depth, wid, ctx.root, -1, -1, 1, 1, 0, class, visual, &aux)?;
+            wid
         };
 
+        let tab_strip = TabStrip::new(ctx.clone())?;
+
         let mut layouts: VecDeque<Box<dyn Layout>> = VecDeque::new();
 
-        // let horizontal = layout::Horizontal::new(ctx.clone());
+        // let horizontal = layout::Horizontal::new();
         // layouts.push_back(Box::new(horizontal));
 
-        let horizontal = layout::HorizontalWithBorder::new(ctx.clone());
+        let per_page = ctx.config.windows_per_page;
+        let horizontal =
+            layout::HorizontalWithBorder::with_config(ctx.config.layouts.horizontal, per_page);
         layouts.push_back(Box::new(horizontal));
 
-        // let vertical = layout::Vertical::new(ctx.clone());
+        // let vertical = layout::Vertical::new();
         // layouts.push_back(Box::new(vertical));
 
-        let vertical = layout::VerticalWithBorder::new(ctx.clone());
+        let vertical = layout::VerticalWithBorder::with_page_size(per_page);
         layouts.push_back(Box::new(vertical));
 
-        let full = layout::FullScreen::new(ctx.clone());
+        let monocle = layout::Monocle::new();
+        layouts.push_back(Box::new(monocle));
+
+        let tabbed = layout::Tabbed::new();
+        layouts.push_back(Box::new(tabbed));
+
+        let accordion = layout::Accordion::new();
+        layouts.push_back(Box::new(accordion));
+
+        let full = layout::FullScreen::new();
         layouts.push_back(Box::new(full));
 
+        layouts.extend(ctx.extensions.layouts());
+
         assert!(!layouts.is_empty());
 
+        let animator = Animator::new(ctx.config.animation);
+
         Ok(Self {
             ctx,
             id,
             monitor: None,
             wins: Default::default(),
             background,
+            focus_holder,
+            tab_strip,
             layouts,
             border_visible: false,
+            last_focused: None,
+            animator,
+            error: false,
+            keyboard_layout: String::new(),
+            level: None,
+            order: Vec::new(),
+            float_stack: Vec::new(),
+            placements: Vec::new(),
         })
     }
 
@@ -89,12 +157,18 @@ impl Screen {
         self.monitor = Some(monitor);
         self.update()?;
 
-        self.background.map()?;
+        if let Some(background) = &mut self.background {
+            background.map()?;
+        }
+        self.ctx.conn.map_window(self.focus_holder)?;
+        // The layout shows the tiled windows except those on other pages.
         for win in self.wins.values_mut() {
-            win.show()?;
+            if !win.is_mapped() || win.mode() != WindowMode::Tiled || win.is_pinned() {
+                win.show()?;
+            }
         }
 
-        Ok(())
+        self.refresh_layout()
     }
 
     pub fn detach(&mut self) -> Result<Option<Monitor>> {
@@ -107,18 +181,27 @@ impl Screen {
             self.id, self.background, self.monitor, self.wins
         );
 
-        self.background.unmap()?;
+        if let Some(background) = &mut self.background {
+            background.unmap()?;
+        }
+        self.ctx.conn.unmap_window(self.focus_holder)?;
+        self.tab_strip.hide()?;
+        // The tiled windows on other pages are hidden already.
         for w in self.wins.values_mut() {
-            w.hide()?;
+            if !w.is_hidden() {
+                w.hide()?;
+            }
         }
+        self.animator.cancel_all();
 
         Ok(self.monitor.take())
     }
 
     pub fn swap_monitors(a: &mut Self, b: &mut Self) -> Result<()> {
         std::mem::swap(&mut a.monitor, &mut b.monitor);
-        a.update()?;
-        b.update()?;
+        // Fit the windows to the new monitors, which may differ in size.
+        a.refresh_layout()?;
+        b.refresh_layout()?;
         Ok(())
     }
 
@@ -128,29 +211,150 @@ impl Screen {
             .get_focused_window()?
             .unwrap_or_else(|| InputFocus::NONE.into());
         let focused = self.contains(focused_window);
+        let window_count = self.window_count();
+        let page = self.layout_page();
+        let layout_name = self.layout_name();
+        let position = self.layouts.front().expect("no layout").position();
+        let tasks = if self.ctx.config.bar.taskbar {
+            self.order
+                .iter()
+                .filter_map(|frame| self.wins.get(frame))
+                .filter(|win| win.is_mapped())
+                .map(|win| {
+                    Ok(Task {
+                        frame: win.frame(),
+                        title: win.title()?,
+                        focused: win.contains(focused_window),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            Vec::new()
+        };
+
+        let autohide = self.ctx.config.bar.autohide && self.covers_bar();
 
         // update the bar
         let mon = self.monitor.as_mut().expect("monitor is not attached");
-        mon.bar
-            .configure(mon.info.x, mon.info.y, mon.info.width, 16)
-            .expect("TODO: bar.configure");
-        mon.bar
-            .update_content(Content {
-                max_screen: self.ctx.config.screens,
-                current_screen: self.id,
-                focused,
-            })
-            .expect("TODO: bar.update_content");
+        let bar_geometry = self.ctx.config.bar.geometry(&mon.rect(), mon.scale);
+        mon.bar.configure(bar_geometry)?;
+        let hidden = autohide && !mon.bar_revealed;
+        if hidden && mon.bar.is_visible() {
+            mon.bar.hide()?;
+        } else if !hidden && !mon.bar.is_visible() {
+            mon.bar.show()?;
+        }
+        if autohide && mon.bar_revealed {
+            mon.bar.raise()?;
+        }
+        if hidden {
+            // Above the windows covering the monitor.
+            let aux = ConfigureWindowAux::new().stack_mode(StackMode::ABOVE);
+            self.ctx.conn.configure_window(mon.edge, &aux)?;
+            self.ctx.conn.map_window(mon.edge)?;
+        } else {
+            self.ctx.conn.unmap_window(mon.edge)?;
+        }
+        mon.bar.update_content(Content {
+            max_screen: self.ctx.config.screens,
+            current_screen: self.id,
+            focused,
+            window_count,
+            error: self.error,
+            keyboard_layout: self.keyboard_layout.clone(),
+            layout_name,
+            level: self.level.clone(),
+            page,
+            position,
+            tasks,
+            colors: self.ctx.theme().bar,
+        })?;
 
         // update the background
-        let aux = ConfigureWindowAux::new()
-            .x(mon.info.x as i32)
-            .y(mon.info.y as i32)
-            .width(mon.info.width as u32)
-            .height(mon.info.height as u32)
-            .stack_mode(StackMode::BELOW);
-        self.background.configure(&aux)?;
+        if let Some(background) = &mut self.background {
+            let aux =
+                with_geometry(ConfigureWindowAux::new(), mon.rect()).stack_mode(StackMode::BELOW);
+            background.configure(&aux)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether windows cover the whole monitor including the bar,
+    /// i.e. the full-screen layout or a fullscreen window.
+    fn covers_bar(&self) -> bool {
+        let layout = self.layouts.front().expect("no layout");
+        layout.uses_whole_monitor()
+            || self
+                .wins
+                .values()
+                .any(|win| win.is_mapped() && win.layer() == Layer::Fullscreen)
+    }
+
+    /// Shows the bar hidden by `bar.autohide` while the pointer is at the top edge or on the bar.
+    pub fn reveal_bar(&mut self, revealed: bool) -> Result<()> {
+        match self.monitor.as_mut() {
+            Some(mon) if mon.bar_revealed != revealed => mon.bar_revealed = revealed,
+            _ => return Ok(()),
+        }
+        self.update()
+    }
+
+    /// Moves the floating windows back to the center of the monitor.
+    pub fn center_floating_windows(&mut self) -> Result<()> {
+        for win in self.wins.values_mut() {
+            win.center_float_geometry();
+        }
+        self.refresh_layout()
+    }
+
+    /// Turns on or off the error indicator in the bar.
+    pub fn set_error(&mut self, error: bool) -> Result<()> {
+        self.error = error;
+        if self.monitor.is_some() {
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    pub fn level(&self) -> Option<&str> {
+        self.level.as_deref()
+    }
+
+    /// Shows or hides the output of a media command (e.g. "vol 50%") in the bar.
+    pub fn set_level(&mut self, level: Option<&str>) -> Result<()> {
+        self.level = level.map(|level| level.to_owned());
+        if self.monitor.is_some() {
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    /// Sets the keyboard layout shown in the bar.
+    pub fn set_keyboard_layout(&mut self, layout: &str) -> Result<()> {
+        self.keyboard_layout = layout.to_owned();
+        if self.monitor.is_some() {
+            self.update()?;
+        }
+        Ok(())
+    }
 
+    /// Repaints the background, the windows and the bar with the current theme.
+    pub fn apply_theme(&mut self) -> Result<()> {
+        if let Some(background) = &self.background {
+            let background = background.inner();
+            let aux =
+                ChangeWindowAttributesAux::new().background_pixel(self.ctx.theme().background);
+            self.ctx.conn.change_window_attributes(background, &aux)?;
+            self.ctx.conn.clear_area(true, background, 0, 0, 0, 0)?;
+        }
+
+        for win in self.wins.values_mut() {
+            win.update_ornament()?;
+        }
+        if self.monitor.is_some() {
+            self.update()?;
+        }
         Ok(())
     }
 
@@ -164,14 +368,22 @@ impl Screen {
         }
 
         debug!("add_window: win={:?}", win);
+        win.set_screen(self.id)?;
 
-        if self.monitor.is_none() && win.is_mapped() {
-            win.hide()?;
+        // Unmapped windows are hidden as well, as `detach` does, so that `attach` can show them.
+        // They come from the windows adopted at startup (e.g. after a restart) with
+        // _NET_WM_DESKTOP of a screen not shown, and `show` expects a hidden window.
+        if self.monitor.is_none() {
+            if !win.is_hidden() {
+                win.hide()?;
+            }
+        } else if win.is_hidden() {
+            win.show()?;
         }
 
         // Float the window if it is a dialog
         let type_dialog = self.ctx.atom._NET_WM_WINDOW_TYPE_DIALOG;
-        if win.net_wm_type()? == Some(type_dialog) {
+        if win.net_wm_type() == Some(type_dialog) {
             let geo = self.ctx.conn.get_geometry(win.frame())?.reply()?;
 
             let x;
@@ -192,13 +404,33 @@ impl Screen {
             })?;
         }
 
+        self.order.push(win.frame());
         self.wins.insert(win.frame(), win);
         self.refresh_layout()?;
         Ok(())
     }
 
-    pub fn forget_window(&mut self, wid: Wid) -> Result<Window> {
+    /// Forgets all the windows, in the order to be tiled.
+    pub fn take_windows(&mut self) -> Result<Vec<Window>> {
+        let frames = self.order.clone();
+        let mut wins = Vec::new();
+        for frame in frames {
+            wins.extend(self.forget_window(frame)?);
+        }
+        Ok(wins)
+    }
+
+    /// Forgets the window, or returns None if it is not on the screen,
+    /// e.g. already forgotten on a DestroyNotify racing with a command.
+    pub fn forget_window(&mut self, wid: Wid) -> Result<Option<Window>> {
         debug!("screen.forget_window: id={}, wid={:08X}", self.id, wid);
+        let wid = match self.window(wid) {
+            Some(win) => win.frame(),
+            None => {
+                warn!("screen {}: unknown window {:08X}", self.id, wid);
+                return Ok(None);
+            }
+        };
 
         let mut need_focus_change = false;
         if let Some(focused) = self.ctx.get_focused_window()? {
@@ -207,15 +439,36 @@ impl Screen {
             }
         }
 
-        let wid = self.window_mut(wid).expect("unknown window").frame();
-        let win = self.wins.remove(&wid).expect("unknown window");
+        let win = self.wins.remove(&wid).expect("frames are the keys");
+        self.order.retain(|&frame| frame != wid);
+        self.animator.cancel(wid);
 
         if need_focus_change {
             self.focus_next()?;
         }
 
         self.refresh_layout()?;
-        Ok(win)
+        Ok(Some(win))
+    }
+
+    pub fn layout_name(&self) -> &'static str {
+        self.layouts.front().unwrap().name()
+    }
+
+    /// The current page of the layout and the number of pages, if the windows are paged.
+    pub fn layout_page(&self) -> Option<(usize, usize)> {
+        self.layouts.front().unwrap().page()
+    }
+
+    /// Selects the layout by its name. Returns false if there is no such layout.
+    pub fn select_layout(&mut self, name: &str) -> bool {
+        match self.layouts.iter().position(|layout| layout.name() == name) {
+            Some(pos) => {
+                self.layouts.rotate_left(pos);
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn next_layout(&mut self) -> Result<()> {
@@ -223,35 +476,157 @@ impl Screen {
         self.refresh_layout()
     }
 
+    pub fn prev_layout(&mut self) -> Result<()> {
+        self.layouts.rotate_right(1);
+        self.refresh_layout()
+    }
+
     pub fn refresh_layout(&mut self) -> Result<()> {
         if self.monitor.is_none() {
             return Ok(());
         }
 
+        let focused = self
+            .ctx
+            .get_focused_window()?
+            .unwrap_or_else(|| InputFocus::NONE.into());
+        self.refresh_layout_for(focused)
+    }
+
+    /// Turns the layout to the page of the window so that it can be focused.
+    pub fn turn_to(&mut self, wid: Wid) -> Result<()> {
+        match self.window(wid) {
+            Some(win) if win.is_hidden() => self.refresh_layout_for(wid),
+            _ => Ok(()),
+        }
+    }
+
+    /// Lays out the windows as if `focused` had the focus.
+    fn refresh_layout_for(&mut self, focused: Wid) -> Result<()> {
+        if self.monitor.is_none() {
+            return Ok(());
+        }
+
         debug!("screen.refresh_layout: id={}", self.id);
 
         let mon = self.monitor.as_ref().unwrap();
+        let mut lost_focus = false;
+
+        // update highlight and frame metrics of the monitor
+        // NOTE: this must precede the layout since border widths depend on them.
+        {
+            for win in self.wins.values_mut() {
+                if !win.is_mapped() {
+                    continue;
+                }
+
+                let highlight = win.contains(focused);
+                win.set_highlight(highlight)?;
+                if highlight {
+                    self.last_focused = Some(win.frame());
+                }
+                win.set_metrics(mon.frame)?;
+            }
+        }
 
         // for normal mapped windows
         {
             let mut wins: Vec<&mut Window> = self
                 .wins
                 .values_mut()
-                .filter(|win| win.is_mapped() && !win.is_floating())
+                .filter(|win| {
+                    win.is_mapped() && win.mode() == WindowMode::Tiled && !win.is_pinned()
+                })
                 .collect();
-            wins.sort_unstable_by_key(|w| w.frame());
-
-            let mut mon_info = mon.info.clone();
+            let order = &self.order;
+            wins.sort_by_key(|w| order.iter().position(|&frame| frame == w.frame()));
 
             let layout = self.layouts.front_mut().expect("no layout");
 
+            let mut area = Rect::from(mon.rect());
+
             // make a space for the bar
-            if layout.name() != "full-screen" {
-                mon_info.y += 16;
-                mon_info.height -= 16;
+            if !layout.uses_whole_monitor() {
+                let reserved = self.ctx.config.bar.reserved_height(mon.scale);
+                (_, area) = area.split_y(reserved as u32);
             }
 
-            layout.layout(&mon_info, &mut wins, self.border_visible)?;
+            for win in wins.iter_mut() {
+                win.set_title_visible(layout.uses_title_bars())?;
+            }
+
+            let layout_wins: Vec<LayoutWindow> = wins
+                .iter()
+                .map(|win| LayoutWindow {
+                    wid: win.frame(),
+                    border_width: win.border_width(),
+                    title_height: win.full_title_height() as u32,
+                    focused: win.is_highlighted(),
+                })
+                .collect();
+
+            let placements = layout.layout(&area.into(), &layout_wins, self.border_visible);
+            self.placements = placements.clone();
+
+            // Hide the windows the layout has left out (e.g. on other pages).
+            for win in wins.iter_mut() {
+                let placed = placements.iter().any(|p| p.wid == win.frame());
+                if !placed && !win.is_hidden() {
+                    if win.contains(focused) {
+                        lost_focus = true;
+                    }
+                    self.animator.cancel(win.frame());
+                    win.hide()?;
+                }
+            }
+
+            for p in placements {
+                let win = match wins.iter_mut().find(|win| win.frame() == p.wid) {
+                    Some(win) => win,
+                    None => continue,
+                };
+
+                let target = Rectangle {
+                    x: p.x as i16,
+                    y: p.y as i16,
+                    width: p.width as u16,
+                    height: p.height as u16,
+                };
+                let mut aux = ConfigureWindowAux::new().border_width(p.border_width);
+                if p.raise {
+                    aux = aux.stack_mode(StackMode::ABOVE);
+                }
+                if self.animator.enabled() && win.is_viewable() {
+                    self.animator
+                        .start(win.frame(), win.frame_geometry(), target);
+                    win.configure(&aux)?;
+                } else {
+                    self.animator.cancel(win.frame());
+                    win.configure(&with_geometry(aux, target))?;
+                }
+                if win.is_hidden() {
+                    win.show()?;
+                }
+            }
+
+            if layout.uses_tab_strip() && !wins.is_empty() {
+                let strip_height = layout::TAB_STRIP_HEIGHT * mon.scale;
+                let geometry = area.split_y(strip_height as u32).0.into();
+                let tabs = wins
+                    .iter()
+                    .map(|win| {
+                        Ok(Tab {
+                            frame: win.frame(),
+                            title: win.title()?,
+                            focused: win.is_highlighted(),
+                            icon: win.icon_pixels().map(<[u32]>::to_vec),
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                self.tab_strip.show(geometry, tabs, mon.scale)?;
+            } else {
+                self.tab_strip.hide()?;
+            }
         }
 
         // for floating windows
@@ -259,94 +634,266 @@ impl Screen {
             for win in self
                 .wins
                 .values_mut()
-                .filter(|win| win.is_mapped() && win.is_floating())
+                .filter(|win| win.is_mapped() && win.mode() == WindowMode::Floating)
             {
-                let geo = win.get_float_geometry().unwrap();
-                let aux = ConfigureWindowAux::new()
-                    .x((mon.info.x + geo.x) as i32)
-                    .y((mon.info.y + geo.y) as i32)
-                    .width(geo.width as u32)
-                    .height(geo.height as u32);
-                win.configure(&aux)?;
+                win.fit_float_geometry(mon.info.width, mon.info.height);
+                let geo = Rect::from(win.get_float_geometry().unwrap());
+                let target = geo.offset(mon.info.x as i32, mon.info.y as i32).into();
+                if self.animator.enabled() && win.is_viewable() {
+                    self.animator
+                        .start(win.frame(), win.frame_geometry(), target);
+                } else {
+                    self.animator.cancel(win.frame());
+                    win.configure(&with_geometry(ConfigureWindowAux::new(), target))?;
+                }
             }
         }
 
-        // update highlight
-        {
-            let focused = self
-                .ctx
-                .get_focused_window()?
-                .unwrap_or_else(|| InputFocus::NONE.into());
+        // for fullscreen windows, which cover the whole monitor they are on,
+        // and maximized ones, which leave the bar
+        let reserved = self.ctx.config.bar.reserved_height(mon.scale);
+        let (_, work_area) = Rect::from(mon.rect()).split_y(reserved as u32);
+        for win in self.wins.values_mut().filter(|win| win.is_mapped()) {
+            let rect = match win.mode() {
+                WindowMode::Fullscreen => mon.rect(),
+                WindowMode::Maximized => work_area.into(),
+                WindowMode::Floating | WindowMode::Tiled => continue,
+            };
+            self.animator.cancel(win.frame());
+            win.cover(rect)?;
+            if win.is_hidden() {
+                win.show()?;
+            }
+        }
 
-            for win in self.wins.values_mut() {
-                if !win.is_mapped() {
-                    continue;
-                }
+        self.restack()?;
+        self.update()?;
 
-                let highlight = win.contains(focused);
-                win.set_highlight(highlight)?;
+        if lost_focus {
+            self.focus_any()?;
+        }
+        Ok(())
+    }
+
+    /// Puts the windows in the order of their layers.
+    /// Floating windows keep the order of `float_stack`, even after the screen is detached.
+    fn restack(&mut self) -> Result<()> {
+        let wins = &self.wins;
+        self.float_stack
+            .retain(|frame| wins.get(frame).is_some_and(|win| win.is_floating()));
+        for win in self.wins.values().filter(|win| win.is_floating()) {
+            if !self.float_stack.contains(&win.frame()) {
+                self.float_stack.push(win.frame());
             }
         }
 
-        self.update()?;
+        let mut windows: Vec<_> = self
+            .background
+            .iter()
+            .map(|background| (background.frame(), Layer::Desktop))
+            .collect();
+        if self.tab_strip.is_visible() {
+            windows.push((self.tab_strip.wid(), Layer::Tiled));
+        }
+        windows.extend(
+            self.wins
+                .values()
+                .filter(|win| win.is_mapped())
+                .map(|win| (win.frame(), win.layer())),
+        );
+        stacking::restack(&self.ctx, &windows, &self.float_stack)
+    }
+
+    /// Puts the floating window above the other floating ones.
+    pub fn raise(&mut self, wid: Wid) -> Result<()> {
+        let frame = match self.window(wid).filter(|win| win.is_floating()) {
+            Some(win) => win.frame(),
+            None => return Ok(()),
+        };
+        self.float_stack.retain(|&w| w != frame);
+        self.float_stack.push(frame);
+        self.restack()
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.animator.is_active()
+    }
 
+    pub fn animate(&mut self) -> Result<()> {
+        for (wid, rect) in self.animator.step() {
+            if let Some(win) = self.wins.get_mut(&wid) {
+                win.move_resize(rect)?;
+            }
+        }
         Ok(())
     }
 
-    pub fn background(&self) -> &Window {
-        &self.background
+    pub fn focus_holder(&self) -> Wid {
+        self.focus_holder
+    }
+
+    /// Whether `wid` is one of the windows of the screen itself, not a client.
+    pub fn is_background(&self, wid: Wid) -> bool {
+        wid == self.focus_holder
+            || wid == self.tab_strip.wid()
+            || self.background.iter().any(|bg| bg.contains(wid))
+    }
+
+    /// Returns the frame of the window whose title is clicked at `root_x` on `wid`,
+    /// which is the bar of the monitor.
+    pub fn task_at(&mut self, wid: Wid, root_x: i16) -> Option<Wid> {
+        self.monitor.as_mut()?.bar.task_at(wid, root_x)
+    }
+
+    /// Whether the clock in the bar of the monitor is clicked at `root_x` on `wid`.
+    pub fn clock_at(&mut self, wid: Wid, root_x: i16) -> bool {
+        match self.monitor.as_mut() {
+            Some(mon) => mon.bar.clock_at(wid, root_x),
+            None => false,
+        }
+    }
+
+    /// Whether `wid` is the bar on the monitor of the screen.
+    pub fn is_bar(&mut self, wid: Wid) -> bool {
+        match self.monitor.as_mut() {
+            Some(mon) => mon.bar.get_window_id().ok() == Some(wid),
+            None => false,
+        }
+    }
+
+    /// Returns the frame of the window whose tab is clicked at `root_x` on `wid`.
+    pub fn tab_at(&self, wid: Wid, root_x: i16) -> Option<Wid> {
+        if wid != self.tab_strip.wid() || !self.tab_strip.is_visible() {
+            return None;
+        }
+        self.tab_strip.tab_at(root_x)
     }
 
     pub fn contains(&self, wid: Wid) -> bool {
-        self.background.contains(wid)
+        self.is_background(wid)
             || self.wins.contains_key(&wid)
             || self.wins.values().any(|win| win.contains(wid))
     }
 
+    /// The number of mapped windows on the screen.
+    pub fn window_count(&self) -> usize {
+        self.wins.values().filter(|win| win.is_mapped()).count()
+    }
+
+    /// Windows managed on the screen (excluding the background).
+    pub fn windows(&self) -> impl Iterator<Item = &Window> {
+        self.wins.values()
+    }
+
+    /// The client containing `wid`.
+    pub fn window(&self, wid: Wid) -> Option<&Window> {
+        self.wins.values().find(|win| win.contains(wid))
+    }
+
     pub fn window_mut(&mut self, wid: Wid) -> Option<&mut Window> {
-        if self.background.contains(wid) {
-            Some(&mut self.background)
-        } else {
-            self.wins.values_mut().find(|win| win.contains(wid))
-        }
+        self.wins.values_mut().find(|win| win.contains(wid))
     }
 
     pub fn focus_any(&mut self) -> Result<()> {
         debug!("screen {}: focus_any", self.id);
-        match self.wins.values_mut().find(|win| win.is_mapped()) {
+        // Windows hidden on an attached screen are on other pages of the layout.
+        let attached = self.monitor.is_some();
+        match self
+            .wins
+            .values_mut()
+            .find(|win| win.is_mapped() && !(attached && win.is_hidden()))
+        {
             Some(first) => {
                 first.focus()?;
             }
             None => {
-                debug!("screen {}: focus background", self.id);
-                self.background.focus()?;
+                debug!("screen {}: focus the focus holder", self.id);
+                self.ctx.focus_window(self.focus_holder)?;
             }
         }
         Ok(())
     }
 
+    /// Focuses the window focused last on the screen, or any window if it has gone.
+    pub fn focus_last(&mut self) -> Result<()> {
+        let attached = self.monitor.is_some();
+        let last = self
+            .last_focused
+            .and_then(|frame| self.wins.get_mut(&frame))
+            .filter(|win| win.is_mapped() && !(attached && win.is_hidden()));
+        match last {
+            Some(win) => win.focus(),
+            None => self.focus_any(),
+        }
+    }
+
+    /// The frames the layout has put in the group, in the order of the placements.
+    fn group_members(&self, group: usize) -> Vec<Wid> {
+        let layout = self.layouts.front().expect("no layout");
+        self.placements
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| layout.group(i) == group)
+            .map(|(_, p)| p.wid)
+            .collect()
+    }
+
+    /// Focuses the next (or previous if `offset` is negative) window
+    /// in the group of the focused window.
+    pub fn focus_in_group(&mut self, offset: isize) -> Result<()> {
+        let focused = self.ctx.get_focused_window()?;
+        let frame = focused.and_then(|wid| self.window_mut(wid).map(|win| win.frame()));
+        let index = frame.and_then(|frame| self.placements.iter().position(|p| p.wid == frame));
+        let index = match index {
+            Some(index) => index,
+            None => return self.focus_any(),
+        };
+
+        let layout = self.layouts.front().expect("no layout");
+        let members = self.group_members(layout.group(index));
+        let pos = members.iter().position(|&wid| Some(wid) == frame).unwrap() as isize;
+        let next = members[(pos + offset).rem_euclid(members.len() as isize) as usize];
+        match self.wins.get_mut(&next) {
+            Some(win) => win.focus(),
+            None => Ok(()),
+        }
+    }
+
+    /// Focuses the first window in the group, e.g. `layout::MAIN_GROUP`.
+    pub fn focus_group(&mut self, group: usize) -> Result<()> {
+        let first = self.group_members(group).first().copied();
+        match first.and_then(|frame| self.wins.get_mut(&frame)) {
+            Some(win) => win.focus(),
+            None => Ok(()),
+        }
+    }
+
     pub fn focus_next(&mut self) -> Result<()> {
         let old = self
             .ctx
             .get_focused_window()?
             .unwrap_or_else(|| InputFocus::NONE.into());
 
-        if !self.contains(old) || self.background.contains(old) {
+        if !self.contains(old) || self.is_background(old) {
             return self.focus_any();
         }
 
         let old = self.window_mut(old).unwrap().frame();
 
-        let next = self
+        let attached = self.monitor.is_some();
+        let candidates: Vec<Wid> = self
             .wins
             .iter()
-            .filter(|(_, win)| win.is_mapped())
+            .filter(|(_, win)| win.is_mapped() && !(attached && win.is_hidden()))
             .map(|(wid, _)| wid)
             .copied()
-            .cycle()
-            .skip_while(|&wid| wid != old)
-            .nth(1)
-            .unwrap();
+            .collect();
+        // The focused window itself may be on another page.
+        let pos = match candidates.iter().position(|&wid| wid == old) {
+            Some(pos) => pos,
+            None => return self.focus_any(),
+        };
+        let next = candidates[(pos + 1) % candidates.len()];
 
         if let Some(win) = self.wins.get_mut(&next) {
             debug!("focus_next: next={:?}", win);
@@ -355,6 +902,29 @@ impl Screen {
         Ok(())
     }
 
+    /// Swaps the focused tiled window with its neighbor in the direction.
+    pub fn move_focused(&mut self, dir: Direction) -> Result<()> {
+        let focused = self
+            .ctx
+            .get_focused_window()?
+            .unwrap_or_else(|| InputFocus::NONE.into());
+        let frame = match self.wins.values().find(|win| win.contains(focused)) {
+            Some(win) if !win.is_floating() => win.frame(),
+            _ => return Ok(()),
+        };
+
+        let other = match layout::neighbor(&self.placements, frame, dir) {
+            Some(other) => other,
+            None => return Ok(()),
+        };
+        debug!("move_focused: {:08X} <-> {:08X}", frame, other);
+
+        let a = self.order.iter().position(|&w| w == frame).unwrap();
+        let b = self.order.iter().position(|&w| w == other).unwrap();
+        self.order.swap(a, b);
+        self.refresh_layout()
+    }
+
     pub fn show_border(&mut self) {
         self.border_visible = true;
     }
@@ -362,7 +932,19 @@ impl Screen {
         self.border_visible = false;
     }
 
+    /// Polls the titles of the windows shown, which may change without notice,
+    /// and updates the tab strip and the bar if any has changed.
     pub fn alarm(&mut self) -> Result<()> {
+        if self.monitor.is_none() {
+            return Ok(());
+        }
+        let mut changed = false;
+        for win in self.wins.values_mut().filter(|win| win.is_viewable()) {
+            changed |= win.refresh_title()?;
+        }
+        if changed {
+            self.refresh_layout()?;
+        }
         Ok(())
     }
 
@@ -382,9 +964,102 @@ impl EventHandlerMethods for Screen {
 
         let wid = ev.window;
         assert!(self.contains(wid));
+        if wid == self.tab_strip.wid() {
+            return self.tab_strip.draw();
+        }
         if let Some(win) = self.window_mut(wid) {
             win.on_expose(ev)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mock;
+
+    fn new_window(ctx: &Context) -> Window {
+        let wid = ctx.conn.create_client(320, 240);
+        Window::new(
+            ctx.clone(),
+            wid,
+            WindowState::Mapped,
+            FrameMetrics::unscaled(&ctx.config.border),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn add_and_forget_window() {
+        let ctx = mock::context(&[]);
+        let mut screen = Screen::new(ctx.clone(), 0).unwrap();
+
+        let win = new_window(&ctx);
+        let frame = win.frame();
+        screen.add_window(win).unwrap();
+        assert!(screen.contains(frame));
+
+        let win = screen.forget_window(frame).unwrap().unwrap();
+        assert_eq!(win.frame(), frame);
+        assert!(!screen.contains(frame));
+        assert!(screen.forget_window(frame).unwrap().is_none());
+    }
+
+    #[test]
+    fn focus_any_falls_back_to_focus_holder() {
+        let ctx = mock::context(&[]);
+        let mut screen = Screen::new(ctx.clone(), 0).unwrap();
+
+        screen.focus_any().unwrap();
+        assert_eq!(ctx.conn.focus(), screen.focus_holder());
+        assert!(ctx.conn.window(screen.focus_holder()).unwrap().input_only);
+        assert!(screen.window(screen.focus_holder()).is_none());
+
+        let win = new_window(&ctx);
+        let frame = win.frame();
+        screen.add_window(win).unwrap();
+        screen.focus_any().unwrap();
+        assert!(screen.window_mut(ctx.conn.focus()).unwrap().contains(frame));
+    }
+
+    #[test]
+    fn background_window_can_be_disabled() {
+        let config = Config {
+            background_window: false,
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[], config);
+        let mut screen = Screen::new(ctx.clone(), 0).unwrap();
+
+        // The root window is not covered, but empty screens still can be focused.
+        assert!(screen.background.is_none());
+        screen.focus_any().unwrap();
+        assert_eq!(ctx.conn.focus(), screen.focus_holder());
+    }
+
+    #[test]
+    fn focus_next_wraps_around() {
+        let ctx = mock::context(&[]);
+        let mut screen = Screen::new(ctx.clone(), 0).unwrap();
+
+        let a = new_window(&ctx);
+        let b = new_window(&ctx);
+        let (a_frame, b_frame) = (a.frame(), b.frame());
+        screen.add_window(a).unwrap();
+        screen.add_window(b).unwrap();
+
+        screen.focus_any().unwrap();
+        let first = screen.window_mut(ctx.conn.focus()).unwrap().frame();
+        screen.focus_next().unwrap();
+        let second = screen.window_mut(ctx.conn.focus()).unwrap().frame();
+        screen.focus_next().unwrap();
+        let third = screen.window_mut(ctx.conn.focus()).unwrap().frame();
+
+        assert_ne!(first, second);
+        assert_eq!(first, third);
+        assert!([a_frame, b_frame].contains(&first));
+        assert!([a_frame, b_frame].contains(&second));
+    }
+}
@@ -0,0 +1,83 @@
+use log::warn;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::shape::{self, ConnectionExt as _, SK, SO};
+use x11rb::protocol::xproto::{ClipOrdering, Rectangle, Window as Wid};
+
+use crate::error::Result;
+
+/// Computes rectangles covering a rounded rectangle of the given size.
+fn rounded_rectangles(width: u16, height: u16, radius: u16) -> Vec<Rectangle> {
+    let radius = radius.min(width / 2).min(height / 2);
+    if radius == 0 {
+        return vec![Rectangle {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }];
+    }
+
+    let mut rects = Vec::with_capacity(radius as usize * 2 + 1);
+    let r = radius as f64;
+    for i in 0..radius {
+        // horizontal inset of the i-th row from the top (and the bottom)
+        let dy = r - i as f64 - 0.5;
+        let inset = (r - (r * r - dy * dy).sqrt()).round() as u16;
+        let row_width = width - inset * 2;
+        rects.push(Rectangle {
+            x: inset as i16,
+            y: i as i16,
+            width: row_width,
+            height: 1,
+        });
+        rects.push(Rectangle {
+            x: inset as i16,
+            y: (height - 1 - i) as i16,
+            width: row_width,
+            height: 1,
+        });
+    }
+    rects.push(Rectangle {
+        x: 0,
+        y: radius as i16,
+        width,
+        height: height - radius * 2,
+    });
+    rects
+}
+
+/// Sets the bounding shape of the window to a rounded rectangle.
+/// `width` and `height` are the size of the window excluding its border.
+pub fn round_corners<C: Connection>(
+    conn: &C,
+    wid: Wid,
+    width: u16,
+    height: u16,
+    border_width: u16,
+    radius: u16,
+) -> Result<()> {
+    if conn
+        .extension_information(shape::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        warn!("SHAPE extension is not available");
+        return Ok(());
+    }
+
+    // The bounding region includes the border.
+    let outer_w = width + border_width * 2;
+    let outer_h = height + border_width * 2;
+    let rects = rounded_rectangles(outer_w, outer_h, radius);
+    let offset = -(border_width as i16);
+    conn.shape_rectangles(
+        SO::SET,
+        SK::BOUNDING,
+        ClipOrdering::UNSORTED,
+        wid,
+        offset,
+        offset,
+        &rects,
+    )?;
+    Ok(())
+}
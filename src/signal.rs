@@ -0,0 +1,86 @@
+//! Delivery of Unix signals to a channel, used by both daily and daily-supervisor.
+
+use crossbeam_channel::{unbounded, Receiver};
+use log::{debug, error};
+use std::fs::File;
+use std::io::{self, Read as _};
+use std::os::unix::io::FromRawFd as _;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use crate::spawn_named_thread;
+
+/// The write end of the pipe the handler reports signals to.
+static PIPE_WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_signal(signum: libc::c_int) {
+    // Only async-signal-safe functions can be used here.
+    let fd = PIPE_WRITE_FD.load(Ordering::Relaxed);
+    let byte = signum as u8;
+    unsafe {
+        libc::write(fd, &byte as *const u8 as *const libc::c_void, 1);
+    }
+}
+
+/// Installs handlers of `signals` and returns the channel receiving them.
+/// The signals are passed through a pipe to a thread, which forwards them to the channel.
+/// This should be called only once in a process.
+pub fn receive(signals: &[libc::c_int]) -> io::Result<Receiver<libc::c_int>> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let [read_fd, write_fd] = fds;
+    PIPE_WRITE_FD.store(write_fd, Ordering::Relaxed);
+
+    for &signum in signals {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            let handler: extern "C" fn(libc::c_int) = on_signal;
+            action.sa_sigaction = handler as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signum, &action, std::ptr::null_mut()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+
+    let (tx, rx) = unbounded();
+    let mut pipe = unsafe { File::from_raw_fd(read_fd) };
+    spawn_named_thread("signal".to_owned(), move || {
+        let mut buf = [0u8; 1];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(1) => {
+                    debug!("signal: {}", buf[0]);
+                    if tx.send(buf[0] as libc::c_int).is_err() {
+                        return;
+                    }
+                }
+                Ok(_) => return,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    error!("signal: {}", e);
+                    return;
+                }
+            }
+        }
+    });
+    Ok(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn signals_are_forwarded_to_the_channel() {
+        let rx = receive(&[libc::SIGTERM]).unwrap();
+        unsafe {
+            libc::raise(libc::SIGTERM);
+        }
+        let signum = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(signum, libc::SIGTERM);
+    }
+}
@@ -0,0 +1,135 @@
+use x11rb::protocol::xproto::{Window as Wid, *};
+
+use crate::context::Context;
+use crate::error::Result;
+
+/// Strata of the window stack, from the bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    /// The background of a screen.
+    Desktop,
+    /// Windows with _NET_WM_STATE_BELOW.
+    Below,
+    Tiled,
+    /// Floating windows, and maximized ones which cover the tiled windows.
+    Floating,
+    /// Windows with _NET_WM_STATE_ABOVE.
+    Above,
+    /// Windows with _NET_WM_STATE_FULLSCREEN.
+    Fullscreen,
+    /// Docks and notifications.
+    Dock,
+}
+
+/// Sorts `windows` by their layers, keeping the relative order of `current` (bottom to top)
+/// within each layer. Windows missing in `current` go above the others of their layers,
+/// and those in `tracked` are put in its order above all of them.
+fn stacking_order(current: &[Wid], tracked: &[Wid], windows: &[(Wid, Layer)]) -> Vec<Wid> {
+    let mut windows = windows.to_vec();
+    windows.sort_by_key(|&(wid, layer)| {
+        let pos = match tracked.iter().position(|&w| w == wid) {
+            Some(pos) => (1, pos),
+            None => (
+                0,
+                current.iter().position(|&w| w == wid).unwrap_or(usize::MAX),
+            ),
+        };
+        (layer, pos)
+    });
+    windows.into_iter().map(|(wid, _)| wid).collect()
+}
+
+/// Returns the moves which bring `order` to the bottom of the `current` stack,
+/// each as a window and the one to put it right above (`None` for the bottom).
+/// Windows already in their places are left alone.
+fn restack_moves(current: &[Wid], order: &[Wid]) -> Vec<(Wid, Option<Wid>)> {
+    let mut stack = current.to_vec();
+    let mut moves = Vec::new();
+    for (i, &wid) in order.iter().enumerate() {
+        if stack.get(i) == Some(&wid) {
+            continue;
+        }
+        stack.retain(|&w| w != wid);
+        stack.insert(i.min(stack.len()), wid);
+        moves.push((wid, i.checked_sub(1).map(|below| order[below])));
+    }
+    moves
+}
+
+/// Restacks the top-level `windows` by their layers, with those in `tracked` in its order.
+/// The first window of the order goes to the bottom of the stack,
+/// and each of the others is put right above the previous one.
+pub fn restack(ctx: &Context, windows: &[(Wid, Layer)], tracked: &[Wid]) -> Result<()> {
+    let current = ctx.conn.query_tree(ctx.root)?.reply()?.children;
+    let order = stacking_order(&current, tracked, windows);
+
+    for (wid, below) in restack_moves(&current, &order) {
+        let aux = match below {
+            None => ConfigureWindowAux::new().stack_mode(StackMode::BELOW),
+            Some(sibling) => ConfigureWindowAux::new()
+                .sibling(sibling)
+                .stack_mode(StackMode::ABOVE),
+        };
+        ctx.conn.configure_window(wid, &aux)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_come_first() {
+        let current = [1, 2, 3, 4, 5];
+        let windows = [
+            (5, Layer::Tiled),
+            (4, Layer::Desktop),
+            (3, Layer::Tiled),
+            (2, Layer::Floating),
+            (1, Layer::Above),
+        ];
+        assert_eq!(stacking_order(&current, &[], &windows), vec![4, 3, 5, 2, 1]);
+    }
+
+    #[test]
+    fn relative_order_is_kept_within_layer() {
+        // 1 has been raised above 2 and 3 is new.
+        let current = [2, 1];
+        let windows = [
+            (1, Layer::Floating),
+            (2, Layer::Floating),
+            (3, Layer::Floating),
+        ];
+        assert_eq!(stacking_order(&current, &[], &windows), vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn only_windows_out_of_place_are_moved() {
+        // 9 is a window not restacked here, e.g. a bar.
+        let order = [1, 2, 3, 4];
+        assert_eq!(restack_moves(&[1, 2, 3, 4, 9], &order), vec![]);
+        assert_eq!(restack_moves(&[1, 3, 2, 4, 9], &order), vec![(2, Some(1))]);
+        assert_eq!(
+            restack_moves(&[9, 1, 2, 3, 4], &order),
+            vec![(1, None), (2, Some(1)), (3, Some(2)), (4, Some(3))]
+        );
+        // A new window not in the stack yet.
+        assert_eq!(restack_moves(&[1, 2, 4], &order), vec![(3, Some(2))]);
+    }
+
+    #[test]
+    fn tracked_order_overrides_current_one() {
+        let current = [1, 2, 3, 4];
+        let windows = [
+            (1, Layer::Floating),
+            (2, Layer::Floating),
+            (3, Layer::Floating),
+            (4, Layer::Tiled),
+        ];
+        assert_eq!(
+            stacking_order(&current, &[3, 1], &windows),
+            vec![4, 2, 3, 1]
+        );
+    }
+}
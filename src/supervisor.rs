@@ -1,25 +1,91 @@
-use log::{error, info};
-use std::process::Command;
+use crossbeam_channel::{bounded, select};
+use log::{error, info, warn};
+use std::process::{Command, ExitStatus};
 use std::time::Instant;
 
+use daily::signal;
+
 const DAILY_BIN_NAME: &str = "daily";
+/// The exit status of daily when the X server has gone.
+const EXIT_CONNECTION_LOST: i32 = 3;
+
+/// Why the supervisor has signaled the running daily.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    None,
+    Terminate,
+    /// SIGHUP (forwarded) or SIGUSR2 (upgrade): daily saves its state and exits,
+    /// then the newest binary in PATH is launched.
+    Restart,
+}
+
+/// Waits for the child to exit, forwarding signals to it.
+fn wait_forwarding_signals(
+    mut ch: std::process::Child,
+    signal_rx: &crossbeam_channel::Receiver<libc::c_int>,
+) -> (ExitStatus, Request) {
+    let pid = ch.id() as libc::pid_t;
+    let (status_tx, status_rx) = bounded(1);
+    std::thread::spawn(move || {
+        let _ = status_tx.send(ch.wait().unwrap());
+    });
+
+    let mut request = Request::None;
+    loop {
+        select! {
+            recv(status_rx) -> status => return (status.unwrap(), request),
+            recv(signal_rx) -> signum => {
+                let signum = signum.unwrap();
+                let forwarded = match signum {
+                    libc::SIGUSR2 => {
+                        info!("Upgrade Daily.");
+                        request = Request::Restart;
+                        libc::SIGHUP
+                    }
+                    libc::SIGHUP => {
+                        request = Request::Restart;
+                        libc::SIGHUP
+                    }
+                    _ => {
+                        request = Request::Terminate;
+                        libc::SIGTERM
+                    }
+                };
+                if unsafe { libc::kill(pid, forwarded) } != 0 {
+                    warn!("Cannot send signal {} to Daily.", forwarded);
+                }
+            }
+        }
+    }
+}
 
 fn main() {
     env_logger::init();
 
+    let signals = [libc::SIGTERM, libc::SIGINT, libc::SIGHUP, libc::SIGUSR2];
+    let signal_rx = signal::receive(&signals).unwrap();
+
     let mut retries = 0;
     loop {
         info!("Try to launch a Daily process.");
-        let mut ch = Command::new(DAILY_BIN_NAME).spawn().unwrap();
+        let ch = Command::new(DAILY_BIN_NAME).spawn().unwrap();
         let started_time = Instant::now();
 
+        let (status, request) = wait_forwarding_signals(ch, &signal_rx);
+
         // Exit caused by user.
-        if ch.wait().unwrap().success() {
+        if status.success() || request == Request::Terminate {
             info!("Daily has gracefully exited.");
             break;
         }
 
-        if started_time.elapsed().as_millis() < 3000 {
+        // The X server has gone.
+        if status.code() == Some(EXIT_CONNECTION_LOST) {
+            info!("Daily has lost the connection with the X server.");
+            break;
+        }
+
+        if request != Request::Restart && started_time.elapsed().as_millis() < 3000 {
             retries += 1;
 
             // Maybe something is wrong in launching process.
@@ -0,0 +1,184 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Window as Wid, *};
+
+use crate::context::{self, Context};
+use crate::error::Result;
+use crate::icon::{self, ICON_SIZE};
+
+/// A tab of a window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tab {
+    pub frame: Wid,
+    pub title: String,
+    pub focused: bool,
+    /// The pixels of the icon, see `icon::scale`.
+    pub icon: Option<Vec<u32>>,
+}
+
+/// The strip of tabs a screen shows above the windows in the tabbed layout.
+#[derive(Debug)]
+pub struct TabStrip {
+    ctx: Context,
+    wid: Wid,
+    geometry: Rectangle,
+    tabs: Vec<Tab>,
+    /// The scale factor of the monitor, which determines the font.
+    scale: u16,
+    visible: bool,
+}
+
+impl TabStrip {
+    pub fn new(ctx: Context) -> Result<Self> {
+        let wid = ctx.conn.generate_id()?;
+        let aux = CreateWindowAux::new()
+            .background_pixel(ctx.theme().border_regular)
+            .event_mask(EventMask::EXPOSURE)
+            .override_redirect(1);
+        ctx.conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            wid,
+            ctx.root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &aux,
+        )?;
+
+        Ok(Self {
+            ctx,
+            wid,
+            geometry: Rectangle {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            },
+            tabs: Vec::new(),
+            scale: 1,
+            visible: false,
+        })
+    }
+
+    pub fn wid(&self) -> Wid {
+        self.wid
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows the tabs at `geometry` on a monitor of the scale factor.
+    pub fn show(&mut self, geometry: Rectangle, tabs: Vec<Tab>, scale: u16) -> Result<()> {
+        if !self.visible || self.geometry != geometry {
+            let aux = ConfigureWindowAux::new()
+                .x(geometry.x as i32)
+                .y(geometry.y as i32)
+                .width(geometry.width as u32)
+                .height(geometry.height as u32);
+            self.ctx.conn.configure_window(self.wid, &aux)?;
+        }
+        self.geometry = geometry;
+        self.tabs = tabs;
+        self.scale = scale;
+
+        if !self.visible {
+            self.visible = true;
+            self.ctx.conn.map_window(self.wid)?;
+        }
+        self.draw()
+    }
+
+    pub fn hide(&mut self) -> Result<()> {
+        if self.visible {
+            self.visible = false;
+            self.ctx.conn.unmap_window(self.wid)?;
+        }
+        Ok(())
+    }
+
+    /// The width of each tab.
+    fn tab_width(&self) -> u16 {
+        self.geometry.width / self.tabs.len().max(1) as u16
+    }
+
+    /// Returns the frame of the window whose tab is at `root_x`.
+    pub fn tab_at(&self, root_x: i16) -> Option<Wid> {
+        let x = root_x.checked_sub(self.geometry.x)?;
+        let index = x as usize / self.tab_width().max(1) as usize;
+        self.tabs
+            .get(index.min(self.tabs.len().saturating_sub(1)))
+            .map(|tab| tab.frame)
+    }
+
+    pub fn draw(&self) -> Result<()> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let conn = &self.ctx.conn;
+        let theme = self.ctx.theme();
+        let tab_width = self.tab_width();
+        let gc = self.ctx.gc_for(self.scale);
+        let s = self.scale as i16;
+
+        // The gaps between the tabs
+        let aux = ChangeGCAux::new().foreground(theme.border_regular);
+        conn.change_gc(gc, &aux)?;
+        let rect = Rectangle {
+            x: 0,
+            y: 0,
+            width: self.geometry.width,
+            height: self.geometry.height,
+        };
+        conn.poly_fill_rectangle(self.wid, gc, &[rect])?;
+
+        for (i, tab) in self.tabs.iter().enumerate() {
+            let color = if tab.focused {
+                theme.border_focused
+            } else {
+                theme.border_regular
+            };
+            let x = tab_width as i16 * i as i16;
+            let aux = ChangeGCAux::new().foreground(color);
+            conn.change_gc(gc, &aux)?;
+            let rect = Rectangle {
+                x,
+                y: 0,
+                width: tab_width.saturating_sub(1),
+                height: self.geometry.height,
+            };
+            conn.poly_fill_rectangle(self.wid, gc, &[rect])?;
+
+            let mut text_x = x + 4 * s;
+            let mut text_width = tab_width.saturating_sub(8 * self.scale);
+            if let Some(pixels) = &tab.icon {
+                icon::put(&self.ctx, self.wid, x + 2, 0, pixels, color)?;
+                text_x += ICON_SIZE as i16;
+                text_width = text_width.saturating_sub(ICON_SIZE);
+            }
+
+            // Core fonts cannot show non-ASCII characters, and the title is clipped by the tab.
+            let max_chars = (text_width / context::char_width(self.scale)) as usize;
+            let title: Vec<u8> = tab
+                .title
+                .chars()
+                .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+                .take(max_chars.min(255))
+                .collect();
+            let aux = ChangeGCAux::new().foreground(theme.title).background(color);
+            conn.change_gc(gc, &aux)?;
+            conn.image_text8(self.wid, gc, text_x, 13 * s, &title)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TabStrip {
+    fn drop(&mut self) {
+        let _ = self.ctx.conn.destroy_window(self.wid);
+    }
+}
@@ -1,84 +1,365 @@
-use log::debug;
+use log::{debug, log_enabled, Level};
 
+use x11rb::properties::{WmClass, WmHints};
 use x11rb::protocol::xproto::{Window as Wid, *};
+use x11rb::protocol::ErrorKind;
+use x11rb::wrapper::ConnectionExt as _;
 
+use crate::config::BorderConfig;
 use crate::context::Context;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::event::EventHandlerMethods;
+use crate::geometry::Rect;
+use crate::icon::{Icon, ICON_SIZE};
+use crate::shape;
+use crate::stacking::Layer;
 
-fn get_wm_protocols(ctx: &Context, wid: Wid) -> Result<Vec<Atom>> {
+// The properties read by `Window::new` are requested at once and parsed from the replies,
+// which saves the round trips of adopting many windows at startup.
+
+/// The reply of GetProperty to come.
+type PropertyCookie<'c> = x11rb::cookie::Cookie<'c, crate::context::XConnection, GetPropertyReply>;
+
+fn request_wm_protocols<'c>(ctx: &'c Context, wid: Wid) -> Result<PropertyCookie<'c>> {
     // NOTE: https://www.x.org/releases/X11R7.7/doc/xorg-docs/icccm/icccm.html#WM_PROTOCOLS_Property
 
     let wm_protocols = ctx.atom.WM_PROTOCOLS;
-    let res = ctx
+    Ok(ctx
         .conn
-        .get_property(false, wid, wm_protocols, AtomEnum::ATOM, 0, std::u32::MAX)?
-        .reply()?;
+        .get_property(false, wid, wm_protocols, AtomEnum::ATOM, 0, u32::MAX)?)
+}
+
+fn parse_wm_protocols(res: GetPropertyReply) -> Vec<Atom> {
+    if res.type_ == x11rb::NONE || !res.value.len().is_multiple_of(4) {
+        return Vec::new();
+    }
+
+    res.value32().map(|iter| iter.collect()).unwrap_or_default()
+}
+
+fn get_wm_protocols(ctx: &Context, wid: Wid) -> Result<Vec<Atom>> {
+    Ok(parse_wm_protocols(request_wm_protocols(ctx, wid)?.reply()?))
+}
+
+fn request_wm_class<'c>(ctx: &'c Context, wid: Wid) -> Result<PropertyCookie<'c>> {
+    // NOTE: https://www.x.org/releases/X11R7.7/doc/xorg-docs/icccm/icccm.html#WM_CLASS_Property
+
+    let wm_class = AtomEnum::WM_CLASS;
+    Ok(ctx
+        .conn
+        .get_property(false, wid, wm_class, AtomEnum::STRING, 0, u32::MAX)?)
+}
+
+fn parse_wm_class(reply: GetPropertyReply) -> String {
+    WmClass::from_reply(reply)
+        .map(|wm_class| String::from_utf8_lossy(wm_class.class()).into_owned())
+        .unwrap_or_default()
+}
+
+fn request_wm_hints<'c>(ctx: &'c Context, wid: Wid) -> Result<PropertyCookie<'c>> {
+    // NOTE: https://www.x.org/releases/X11R7.7/doc/xorg-docs/icccm/icccm.html#WM_HINTS_Property
+
+    let wm_hints = AtomEnum::WM_HINTS;
+    Ok(ctx
+        .conn
+        .get_property(false, wid, wm_hints, wm_hints, 0, 9)?)
+}
+
+fn get_wm_hints(ctx: &Context, wid: Wid) -> Result<Option<WmHints>> {
+    let reply = request_wm_hints(ctx, wid)?.reply()?;
+    Ok(WmHints::from_reply(&reply).ok())
+}
+
+fn parse_urgent(reply: GetPropertyReply) -> bool {
+    // A window without WM_HINTS cannot be urgent.
+    WmHints::from_reply(&reply).is_ok_and(|hints| hints.urgent)
+}
+
+fn is_urgent(ctx: &Context, wid: Wid) -> Result<bool> {
+    Ok(parse_urgent(request_wm_hints(ctx, wid)?.reply()?))
+}
+
+fn request_net_wm_window_type<'c>(ctx: &'c Context, wid: Wid) -> Result<PropertyCookie<'c>> {
+    let net_wm_type = ctx.atom._NET_WM_WINDOW_TYPE;
+    Ok(ctx
+        .conn
+        .get_property(false, wid, net_wm_type, AtomEnum::ATOM, 0, 1)?)
+}
 
-    if res.type_ == x11rb::NONE || res.value.len() % 4 != 0 {
-        return Ok(Vec::new());
+fn parse_net_wm_window_type(reply: GetPropertyReply) -> Option<Atom> {
+    let value = reply.value;
+    if value.len() < 4 {
+        return None;
     }
 
-    let protocols = res
+    value[..].try_into().map(Atom::from_ne_bytes).ok()
+}
+
+fn request_net_wm_state<'c>(ctx: &'c Context, wid: Wid) -> Result<PropertyCookie<'c>> {
+    // NOTE: https://specifications.freedesktop.org/wm-spec/latest/ar01s05.html
+
+    let net_wm_state = ctx.atom._NET_WM_STATE;
+    Ok(ctx
+        .conn
+        .get_property(false, wid, net_wm_state, AtomEnum::ATOM, 0, u32::MAX)?)
+}
+
+fn parse_net_wm_state(reply: GetPropertyReply) -> Vec<Atom> {
+    reply
         .value32()
         .map(|iter| iter.collect())
-        .unwrap_or_else(|| Vec::new());
+        .unwrap_or_default()
+}
 
-    Ok(protocols)
+fn get_net_wm_desktop(ctx: &Context, wid: Wid) -> Result<Option<u32>> {
+    let net_wm_desktop = ctx.atom._NET_WM_DESKTOP;
+    let reply = ctx
+        .conn
+        .get_property(false, wid, net_wm_desktop, AtomEnum::CARDINAL, 0, 1)?
+        .reply()?;
+    Ok(reply.value32().and_then(|mut iter| iter.next()))
 }
 
+// values of WM_STATE
+const WM_STATE_WITHDRAWN: u32 = 0;
+const WM_STATE_NORMAL: u32 = 1;
+const WM_STATE_ICONIC: u32 = 3;
+
+fn set_wm_state(ctx: &Context, wid: Wid, state: u32) -> Result<()> {
+    // NOTE: https://www.x.org/releases/X11R7.7/doc/xorg-docs/icccm/icccm.html#WM_STATE_Property
+
+    // The icon window is None since no icons are shown.
+    let wm_state = ctx.atom.WM_STATE;
+    let data = [state, x11rb::NONE];
+    ctx.conn
+        .change_property32(PropMode::REPLACE, wid, wm_state, wm_state, &data)?;
+    Ok(())
+}
+
+/// The part of a floating window kept on its monitor at least, so that it can be grabbed.
+const FLOAT_MIN_VISIBLE: i32 = 32;
+
+/// The value of _NET_WM_DESKTOP meaning all desktops.
+const ALL_DESKTOPS: u32 = 0xFFFFFFFF;
+
+/// The height of the title bar of frames of floating windows (and tiled ones in some layouts)
+/// on monitors which are not scaled.
+pub(crate) const TITLE_HEIGHT: u16 = 16;
+
+/// The sizes of the frames on a monitor, which differ among monitors of different scales.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameMetrics {
+    /// The scale factor of the monitor, which also selects the font of the title.
+    pub scale: u16,
+    pub title_height: u16,
+    pub border_width: u32,
+    pub border_width_focused: u32,
+}
+
+impl FrameMetrics {
+    /// Returns the metrics on the output of the scale factor.
+    pub fn new(config: &BorderConfig, output: &str, scale: u16) -> Self {
+        let widths = config.widths(output, scale);
+        Self {
+            scale,
+            title_height: TITLE_HEIGHT * scale,
+            border_width: widths.width,
+            border_width_focused: widths.width_focused,
+        }
+    }
+
+    /// The metrics of windows not on any monitor yet, which are updated by the screen.
+    pub fn unscaled(config: &BorderConfig) -> Self {
+        Self::new(config, "", 1)
+    }
+
+    /// The metrics of frames without borders, e.g. the background windows.
+    pub fn borderless() -> Self {
+        Self {
+            scale: 1,
+            title_height: TITLE_HEIGHT,
+            border_width: 0,
+            border_width_focused: 0,
+        }
+    }
+}
+
+// actions of _NET_WM_STATE client messages
+const NET_WM_STATE_REMOVE: u32 = 0;
+const NET_WM_STATE_ADD: u32 = 1;
+const NET_WM_STATE_TOGGLE: u32 = 2;
+
+/// Whether the client has mapped the window.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum WindowState {
+    /// Managed, but not mapped by the client yet.
     Created,
     Mapped,
     Unmapped,
 }
 
+/// The mapping of the window by the client (`WindowState`), and by the WM,
+/// which hides the windows of the screens without monitor.
+/// It changes only by the transitions below, which tell the window what to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Lifecycle {
+    state: WindowState,
+    hidden: bool,
+}
+
+impl Lifecycle {
+    fn new(state: WindowState) -> Self {
+        Self {
+            state,
+            hidden: false,
+        }
+    }
+
+    /// The client maps the window. Returns whether it is the first time.
+    fn map(&mut self) -> bool {
+        debug_assert_ne!(self.state, WindowState::Mapped, "mapped twice");
+        let first = self.state == WindowState::Created;
+        self.state = WindowState::Mapped;
+        first
+    }
+
+    /// The client unmaps the window.
+    fn unmap(&mut self) {
+        debug_assert_eq!(self.state, WindowState::Mapped, "unmapped while not mapped");
+        self.state = WindowState::Unmapped;
+    }
+
+    /// The WM hides the window. Returns false if it is hidden already.
+    fn hide(&mut self) -> bool {
+        debug_assert!(!self.hidden, "hidden twice");
+        !std::mem::replace(&mut self.hidden, true)
+    }
+
+    /// The WM shows the window again. Returns false if it is shown already.
+    fn show(&mut self) -> bool {
+        debug_assert!(self.hidden, "shown twice");
+        std::mem::replace(&mut self.hidden, false)
+    }
+
+    fn is_mapped(&self) -> bool {
+        self.state == WindowState::Mapped
+    }
+
+    fn is_viewable(&self) -> bool {
+        self.is_mapped() && !self.hidden
+    }
+}
+
+/// How the screen places the window, from the one taking precedence.
+/// The mode follows the state of the window, so it is kept through
+/// the layout switches and the moves to other screens or monitors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WindowMode {
+    /// Covers the whole monitor (_NET_WM_STATE_FULLSCREEN).
+    Fullscreen,
+    /// Covers the monitor except the bar (_NET_WM_STATE_MAXIMIZED_VERT and _HORZ).
+    Maximized,
+    /// At the floating geometry.
+    Floating,
+    /// Placed by the layout, unless pinned.
+    Tiled,
+}
+
 #[derive()]
 pub struct Window {
     ctx: Context,
     frame: Wid,
     inner: Wid,
-    state: WindowState,
-    hidden: bool,
+    class: String,
+    window_type: Option<Atom>,
+    lifecycle: Lifecycle,
     float_geometry: Option<Rectangle>,
+    /// The size of the monitor `float_geometry` was given on.
+    float_area: Option<(u16, u16)>,
+    pinned: bool,
+    following: bool,
     frame_visible: bool,
     highlighted: bool,
-    border_width: u32,
-    gc: Gcontext,
+    urgent: bool,
+    icon: Option<Icon>,
+    net_wm_state: Vec<Atom>,
+    /// The title last seen, to find changes by `refresh_title`.
+    known_title: String,
+    metrics: FrameMetrics,
+    frame_geometry: Rectangle,
+    /// The width, height and border width the frame was last shaped for.
+    shaped_size: Option<(u16, u16, u16)>,
     is_wm_delete_compliant: bool,
 }
 
+/// Grabs the clicks in the frame, which are replayed to the client after focusing it.
+/// The focused window lets them through (see `Window::set_click_through`).
+fn grab_clicks(ctx: &Context, frame: Wid) -> Result<()> {
+    ctx.conn.grab_button(
+        false,
+        frame,
+        u32::from(EventMask::BUTTON_PRESS) as u16,
+        GrabMode::SYNC,  // pointer
+        GrabMode::ASYNC, // keyboard
+        x11rb::NONE,
+        x11rb::NONE,
+        ButtonIndex::ANY,
+        ModMask::ANY,
+    )?;
+    Ok(())
+}
+
 impl Window {
-    pub fn new(ctx: Context, inner: Wid, state: WindowState, border_width: u32) -> Result<Self> {
+    pub fn new(
+        ctx: Context,
+        inner: Wid,
+        state: WindowState,
+        metrics: FrameMetrics,
+    ) -> Result<Self> {
         use x11rb::connection::Connection as _;
 
-        let mut is_wm_delete_compliant = false;
+        // Track changes of WM_HINTS
+        let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+        ctx.conn.change_window_attributes(inner, &aux)?;
+
+        // Send the queries first, then wait for all the replies.
+        let protocols = request_wm_protocols(&ctx, inner)?;
+        let hints = request_wm_hints(&ctx, inner)?;
+        let class = request_wm_class(&ctx, inner)?;
+        let net_wm_state = request_net_wm_state(&ctx, inner)?;
+        let window_type = request_net_wm_window_type(&ctx, inner)?;
+        let geo = ctx.conn.get_geometry(inner)?;
+
+        let wm_protocols = parse_wm_protocols(protocols.reply()?);
+        let urgent = parse_urgent(hints.reply()?);
+        let class = parse_wm_class(class.reply()?);
+        let net_wm_state = parse_net_wm_state(net_wm_state.reply()?);
+        let window_type = parse_net_wm_window_type(window_type.reply()?);
+        let geo = geo.reply()?;
 
         // Examine WM_PROTOCOLS
-        {
-            let wm_protocols = get_wm_protocols(&ctx, inner)?;
-
-            debug!("WM_PROTOCOLS of {:08X}: {:?}", inner, wm_protocols);
-            for proto in wm_protocols {
+        debug!("WM_PROTOCOLS of {:08X}: {:?}", inner, wm_protocols);
+        let is_wm_delete_compliant = wm_protocols.contains(&ctx.atom.WM_DELETE_WINDOW);
+        // The names are looked up only for the log, which costs a round trip each.
+        if log_enabled!(Level::Debug) {
+            for &proto in &wm_protocols {
                 let name_bytes = ctx.conn.get_atom_name(proto)?.reply()?.name;
                 let name =
                     String::from_utf8(name_bytes).unwrap_or_else(|e| format!("{:?}", e.as_bytes()));
                 debug!("WM_PROTOCOLS: {}", name);
-
-                if proto == ctx.atom.WM_DELETE_WINDOW {
-                    is_wm_delete_compliant = true;
-                }
             }
         }
 
+        let icon = Icon::fetch(&ctx, inner)?;
+
         // Reparent
-        let geo = ctx.conn.get_geometry(inner)?.reply()?;
         let frame = {
             let frame = ctx.conn.generate_id()?;
+            // FOCUS_CHANGE tells the focus cache of the context when clients take the focus.
             let mask = EventMask::SUBSTRUCTURE_NOTIFY
                 | EventMask::SUBSTRUCTURE_REDIRECT
-                | EventMask::EXPOSURE;
+                | EventMask::EXPOSURE
+                | EventMask::FOCUS_CHANGE;
             let aux = CreateWindowAux::new().event_mask(mask).override_redirect(1);
             ctx.conn.create_window(
                 x11rb::COPY_DEPTH_FROM_PARENT,
@@ -88,19 +369,14 @@ impl Window {
                 geo.y,
                 geo.width,
                 geo.height,
-                border_width as u16,
+                metrics.border_width as u16,
                 WindowClass::INPUT_OUTPUT,
                 x11rb::COPY_FROM_PARENT,
                 &aux,
             )?;
+            grab_clicks(&ctx, frame)?;
 
-            // WM_STATE
-            let wm_state = ctx.atom.WM_STATE;
-            let mut data = Vec::new();
-            data.extend_from_slice(&1u32.to_ne_bytes());
-            data.extend_from_slice(&x11rb::NONE.to_ne_bytes());
-            ctx.conn
-                .change_property(PropMode::REPLACE, inner, wm_state, wm_state, 32, 2, &data)?;
+            set_wm_state(&ctx, inner, WM_STATE_NORMAL)?;
 
             ctx.conn.reparent_window(inner, frame, 0, 0)?;
 
@@ -112,45 +388,86 @@ impl Window {
             ctx.conn.map_window(inner)?;
         }
 
-        let gc = ctx.conn.generate_id()?;
-        {
-            let font = ctx.conn.generate_id()?;
-            ctx.conn.open_font(font, b"fixed")?.check()?;
-
-            let aux = CreateGCAux::new().font(font);
-            ctx.conn.create_gc(gc, frame, &aux)?;
-
-            ctx.conn.close_font(font)?;
-        }
-
-        Ok(Self {
+        let mut win = Self {
             ctx,
             frame,
             inner,
-            state,
-            hidden: false,
+            class,
+            window_type,
+            lifecycle: Lifecycle::new(state),
             float_geometry: None,
+            float_area: None,
+            pinned: false,
+            following: false,
             frame_visible: false,
             highlighted: false,
-            border_width,
-            gc,
+            urgent,
+            icon,
+            net_wm_state,
+            known_title: String::new(),
+            metrics,
+            frame_geometry: Rectangle {
+                x: geo.x,
+                y: geo.y,
+                width: geo.width,
+                height: geo.height,
+            },
+            shaped_size: None,
             is_wm_delete_compliant,
-        })
+        };
+        win.update_bypass_compositor()?;
+        win.known_title = win.title()?;
+        Ok(win)
     }
 
-    pub fn net_wm_type(&self) -> Result<Option<Atom>> {
-        let net_wm_type = self.ctx.atom._NET_WM_WINDOW_TYPE;
-        let value = self
-            .ctx
-            .conn
-            .get_property(false, self.inner, net_wm_type, AtomEnum::ATOM, 0, 1)?
-            .reply()?
-            .value;
-        if value.len() < 4 {
-            return Ok(None);
+    pub fn net_wm_type(&self) -> Option<Atom> {
+        self.window_type
+    }
+
+    /// The stratum of the window stack the window belongs to.
+    pub fn layer(&self) -> Layer {
+        let atom = &self.ctx.atom;
+        let dock_types = [
+            atom._NET_WM_WINDOW_TYPE_DOCK,
+            atom._NET_WM_WINDOW_TYPE_NOTIFICATION,
+        ];
+        if self.window_type.is_some_and(|t| dock_types.contains(&t)) {
+            Layer::Dock
+        } else if self.is_fullscreen() {
+            Layer::Fullscreen
+        } else if self.is_above() {
+            Layer::Above
+        } else if self.is_below() {
+            Layer::Below
+        } else if self.mode() == WindowMode::Tiled {
+            Layer::Tiled
+        } else {
+            Layer::Floating
+        }
+    }
+
+    pub fn mode(&self) -> WindowMode {
+        let atom = &self.ctx.atom;
+        let maximized = [
+            atom._NET_WM_STATE_MAXIMIZED_VERT,
+            atom._NET_WM_STATE_MAXIMIZED_HORZ,
+        ]
+        .iter()
+        .all(|state| self.net_wm_state.contains(state));
+        if self.is_fullscreen() {
+            WindowMode::Fullscreen
+        } else if maximized {
+            WindowMode::Maximized
+        } else if self.is_floating() {
+            WindowMode::Floating
+        } else {
+            WindowMode::Tiled
         }
+    }
 
-        Ok(value[..].try_into().map(Atom::from_ne_bytes).ok())
+    pub fn is_fullscreen(&self) -> bool {
+        self.net_wm_state
+            .contains(&self.ctx.atom._NET_WM_STATE_FULLSCREEN)
     }
 
     pub fn close(self) -> Result<()> {
@@ -165,74 +482,230 @@ impl Window {
             self.ctx.conn.send_event(false, self.inner, 0_u32, event)?;
         } else {
             debug!("destroy window {:08X}", self.inner);
-            self.ctx.conn.destroy_window(self.inner)?.check()?;
+            let res = self.ctx.conn.destroy_window(self.inner)?.check();
+            match res.map_err(Error::from) {
+                // The client has gone by itself, before its DestroyNotify comes.
+                Err(err) if err.x11_error_kind() == Some(ErrorKind::Window) => {
+                    debug!("window {:08X} is already destroyed", self.inner);
+                }
+                res => res?,
+            }
         }
         Ok(())
     }
 
     pub fn is_mapped(&self) -> bool {
-        self.state == WindowState::Mapped
+        self.lifecycle.is_mapped()
     }
 
     pub fn is_viewable(&self) -> bool {
-        !self.hidden && self.state == WindowState::Mapped
+        self.lifecycle.is_viewable()
     }
 
     pub fn frame(&self) -> Wid {
         self.frame
     }
 
+    /// Lets the clicks go straight to the client without the WM, e.g. while it is focused,
+    /// or grabs them again to focus the window on the first click.
+    pub fn set_click_through(&self, click_through: bool) -> Result<()> {
+        if click_through {
+            self.ctx
+                .conn
+                .ungrab_button(ButtonIndex::ANY, self.frame, ModMask::ANY)?;
+            Ok(())
+        } else {
+            grab_clicks(&self.ctx, self.frame)
+        }
+    }
+
+    pub fn inner(&self) -> Wid {
+        self.inner
+    }
+
+    /// WM_NAME of the window.
+    pub fn title(&self) -> Result<String> {
+        let reply = self
+            .ctx
+            .conn
+            .get_property(
+                false,
+                self.inner,
+                AtomEnum::WM_NAME,
+                AtomEnum::STRING,
+                0,
+                u32::MAX,
+            )?
+            .reply();
+        let win_name = reply
+            .map(|reply| reply.value)
+            .unwrap_or_else(|_| b"(unknown)".to_vec());
+        Ok(String::from_utf8_lossy(&win_name).into_owned())
+    }
+
+    /// Fetches the title and redraws the frame if it has changed since last seen.
+    /// Returns whether it has changed.
+    pub fn refresh_title(&mut self) -> Result<bool> {
+        let title = self.title()?;
+        if title == self.known_title {
+            return Ok(false);
+        }
+        self.known_title = title;
+        self.update_ornament()?;
+        Ok(true)
+    }
+
+    /// The class part of WM_CLASS (empty if not set).
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
     pub fn contains(&self, wid: Wid) -> bool {
         self.inner == wid || self.frame == wid
     }
 
+    /// Returns the geometry of the frame (excluding its border) last configured.
+    pub fn frame_geometry(&self) -> Rectangle {
+        self.frame_geometry
+    }
+
     pub fn is_floating(&self) -> bool {
         self.float_geometry.is_some()
     }
 
+    /// Whether the layouts leave the tiled window at its geometry (see `set_pinned`).
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    /// Pins the tiled window where it is: the layouts skip it,
+    /// but unlike floating windows it has no frame and stays among the tiled ones.
+    pub fn set_pinned(&mut self, pinned: bool) -> Result<()> {
+        self.pinned = pinned;
+        if pinned {
+            self.remove_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Whether the window moves to the screen switched to on its monitor.
+    pub fn is_following(&self) -> bool {
+        self.following
+    }
+
+    pub fn set_following(&mut self, following: bool) {
+        self.following = following;
+    }
+
     pub fn set_float_geometry(&mut self, rect: Rectangle) {
         assert!(self.is_floating());
         self.float_geometry = Some(rect);
+        self.float_area = None;
     }
     pub fn get_float_geometry(&self) -> Option<Rectangle> {
         self.float_geometry
     }
 
+    /// Scales the floating geometry to a monitor of `width` x `height`,
+    /// so that the window keeps its proportion to the monitor it was placed on.
+    /// The window is also moved to stay on the monitor by `FLOAT_MIN_VISIBLE` pixels.
+    pub fn fit_float_geometry(&mut self, width: u16, height: u16) {
+        let geo = match self.float_geometry.as_mut() {
+            Some(geo) => geo,
+            None => return,
+        };
+        if let Some((old_width, old_height)) = self.float_area {
+            if (old_width, old_height) != (width, height) {
+                let scale = |v: i32, new: u16, old: u16| v * new as i32 / (old as i32).max(1);
+                geo.x = scale(geo.x as i32, width, old_width) as i16;
+                geo.y = scale(geo.y as i32, height, old_height) as i16;
+                geo.width = scale(geo.width as i32, width, old_width).max(1) as u16;
+                geo.height = scale(geo.height as i32, height, old_height).max(1) as u16;
+            }
+        }
+        let keep = |v: i16, size: u16, area: u16| {
+            let min = FLOAT_MIN_VISIBLE - size as i32;
+            let max = area as i32 - FLOAT_MIN_VISIBLE;
+            (v as i32).min(max).max(min) as i16
+        };
+        geo.x = keep(geo.x, geo.width, width);
+        geo.y = keep(geo.y, geo.height, height);
+        self.float_area = Some((width, height));
+    }
+
+    /// Moves the floating window to the center of the monitor it was last shown on,
+    /// shrinking it to the monitor if needed.
+    pub fn center_float_geometry(&mut self) {
+        let (geo, (width, height)) = match (self.float_geometry.as_mut(), self.float_area) {
+            (Some(geo), Some(area)) => (geo, area),
+            _ => return,
+        };
+        let area = Rect::new(0, 0, width as u32, height as u32);
+        *geo = area.centered(geo.width as u32, geo.height as u32).into();
+    }
+
     pub fn map(&mut self) -> Result<()> {
-        if !self.hidden {
+        let first = self.lifecycle.map();
+        if self.lifecycle.hidden {
+            set_wm_state(&self.ctx, self.inner, WM_STATE_ICONIC)?;
+        } else {
+            set_wm_state(&self.ctx, self.inner, WM_STATE_NORMAL)?;
             self.ctx.conn.map_window(self.frame)?;
             self.ctx.conn.map_window(self.inner)?;
         }
 
         // Focus this window if it's a newly mapped one
-        if self.state == WindowState::Created {
+        if first {
             debug!("focus newly mapped window: win={:?}", self);
             self.focus()?;
         }
-        self.state = WindowState::Mapped;
         Ok(())
     }
 
     pub fn unmap(&mut self) -> Result<()> {
-        self.state = WindowState::Unmapped;
+        self.lifecycle.unmap();
         self.ctx.conn.unmap_window(self.frame)?;
         Ok(())
     }
 
-    /// Map the window without changing its state.
+    /// Gives up the window withdrawn by the client, which is reparented back
+    /// to the root when dropped (ICCCM 4.1.4).
+    pub fn withdraw(mut self) -> Result<()> {
+        debug!("withdraw: win={:?}", self);
+        if self.lifecycle.is_mapped() {
+            self.lifecycle.unmap();
+        }
+        set_wm_state(&self.ctx, self.inner, WM_STATE_WITHDRAWN)?;
+        // The properties maintained by the WM are removed (EWMH).
+        let atom = &self.ctx.atom;
+        for property in [atom._NET_WM_STATE, atom._NET_WM_DESKTOP] {
+            self.ctx.conn.delete_property(self.inner, property)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the window is unmapped by `hide`, i.e. on a screen without monitor.
+    pub fn is_hidden(&self) -> bool {
+        self.lifecycle.hidden
+    }
+
+    /// Map the window without changing its state, which makes WM_STATE Normal again.
     pub fn show(&mut self) -> Result<()> {
-        assert!(self.hidden);
-        self.hidden = false;
-        if self.state == WindowState::Mapped {
+        if self.lifecycle.show() && self.lifecycle.is_mapped() {
+            set_wm_state(&self.ctx, self.inner, WM_STATE_NORMAL)?;
             self.ctx.conn.map_window(self.frame)?;
         }
         Ok(())
     }
 
-    /// Unmap the window without changing its state.
+    /// Unmap the window without changing its state, which makes WM_STATE Iconic.
     pub fn hide(&mut self) -> Result<()> {
-        assert!(!self.hidden);
-        self.hidden = true;
+        if !self.lifecycle.hide() {
+            return Ok(());
+        }
+        if self.lifecycle.is_mapped() {
+            set_wm_state(&self.ctx, self.inner, WM_STATE_ICONIC)?;
+        }
         self.ctx.conn.unmap_window(self.frame)?;
         Ok(())
     }
@@ -241,6 +714,7 @@ impl Window {
         self.ctx.focus_window(self.inner)
     }
 
+    /// Floats the window at `rect`, which is the position of the frame and the size of the client.
     pub fn float(&mut self, mut rect: Rectangle) -> Result<()> {
         self.add_frame()?;
 
@@ -248,10 +722,12 @@ impl Window {
         let aux = ConfigureWindowAux::new().stack_mode(StackMode::ABOVE);
         self.ctx.conn.configure_window(self.frame, &aux)?;
 
-        // add space for the frame
-        rect.height += 16; // FIXME
+        // add space for the title
+        rect.height += self.title_height();
 
         self.float_geometry = Some(rect);
+        self.float_area = None;
+        self.pinned = false;
         Ok(())
     }
 
@@ -262,6 +738,162 @@ impl Window {
         Ok(())
     }
 
+    /// The height of the title bar, which is zero while the frame is hidden
+    /// or the window is fullscreen.
+    pub fn title_height(&self) -> u16 {
+        if self.frame_visible && !self.is_fullscreen() {
+            self.full_title_height()
+        } else {
+            0
+        }
+    }
+
+    /// The height of the title bar on the monitor of the window, even if it is hidden.
+    pub fn full_title_height(&self) -> u16 {
+        self.metrics.title_height
+    }
+
+    /// Returns the border width for the current highlight state.
+    pub fn border_width(&self) -> u32 {
+        if self.highlighted {
+            self.metrics.border_width_focused
+        } else {
+            self.metrics.border_width
+        }
+    }
+
+    /// Sets the sizes of the frame on the monitor showing the window.
+    pub fn set_metrics(&mut self, metrics: FrameMetrics) -> Result<()> {
+        if self.metrics == metrics {
+            return Ok(());
+        }
+        self.metrics = metrics;
+        let aux = ConfigureWindowAux::new();
+        self.configure(&aux)?;
+        self.update_ornament()?;
+        Ok(())
+    }
+
+    pub fn is_highlighted(&self) -> bool {
+        self.highlighted
+    }
+
+    /// The pixels of the icon scaled to ICON_SIZE, if the client has set one.
+    pub fn icon_pixels(&self) -> Option<&[u32]> {
+        self.icon.as_ref().map(|icon| icon.pixels())
+    }
+
+    pub fn is_urgent(&self) -> bool {
+        self.urgent
+    }
+
+    /// Marks the window urgent until the client updates its WM_HINTS.
+    pub fn set_urgent(&mut self) -> Result<()> {
+        self.urgent = true;
+        self.update_ornament()
+    }
+
+    /// Whether the client asks to be kept above other windows (_NET_WM_STATE_ABOVE).
+    pub fn is_above(&self) -> bool {
+        self.net_wm_state
+            .contains(&self.ctx.atom._NET_WM_STATE_ABOVE)
+    }
+
+    /// Whether the client asks to be kept below other windows (_NET_WM_STATE_BELOW).
+    pub fn is_below(&self) -> bool {
+        self.net_wm_state
+            .contains(&self.ctx.atom._NET_WM_STATE_BELOW)
+    }
+
+    /// Describes the properties of the window for `Command::DebugWindowInfo`.
+    pub fn debug_info(&self) -> Result<String> {
+        let protocols = get_wm_protocols(&self.ctx, self.inner)?
+            .into_iter()
+            .map(|proto| {
+                let name = self.ctx.conn.get_atom_name(proto)?.reply()?.name;
+                Ok(String::from_utf8_lossy(&name).into_owned())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let hints = get_wm_hints(&self.ctx, self.inner)?;
+        Ok(format!(
+            "window=0x{:08X}, frame=0x{:08X}, class={:?}, name={:?}, protocols={:?}, hints={:?}, floating={}, pinned={}",
+            self.inner,
+            self.frame,
+            self.class,
+            self.title()?,
+            protocols,
+            hints,
+            self.is_floating(),
+            self.pinned,
+        ))
+    }
+
+    /// The screen the client asks to be put on with _NET_WM_DESKTOP, if any.
+    pub fn requested_screen(&self) -> Result<Option<usize>> {
+        let desktop = get_net_wm_desktop(&self.ctx, self.inner)?;
+        Ok(desktop
+            .filter(|&desktop| desktop != ALL_DESKTOPS)
+            .map(|desktop| desktop as usize))
+    }
+
+    /// Sets _NET_WM_WINDOW_OPACITY of the frame, which a compositor applies to the window.
+    pub fn set_opacity(&self, opacity: f64) -> Result<()> {
+        let value = (opacity.clamp(0.0, 1.0) * u32::MAX as f64) as u32;
+        self.ctx.conn.change_property32(
+            PropMode::REPLACE,
+            self.frame,
+            self.ctx.atom._NET_WM_WINDOW_OPACITY,
+            AtomEnum::CARDINAL,
+            &[value],
+        )?;
+        Ok(())
+    }
+
+    /// Asks compositors to unredirect the fullscreen window with _NET_WM_BYPASS_COMPOSITOR,
+    /// e.g. for smooth video playback.
+    fn update_bypass_compositor(&self) -> Result<()> {
+        let bypass = self.ctx.atom._NET_WM_BYPASS_COMPOSITOR;
+        if self.is_fullscreen() {
+            self.ctx.conn.change_property32(
+                PropMode::REPLACE,
+                self.frame,
+                bypass,
+                AtomEnum::CARDINAL,
+                &[1],
+            )?;
+        } else {
+            self.ctx.conn.delete_property(self.frame, bypass)?;
+        }
+        Ok(())
+    }
+
+    /// Publishes the screen of the window as _NET_WM_DESKTOP.
+    pub fn set_screen(&self, id: usize) -> Result<()> {
+        self.ctx.conn.change_property32(
+            PropMode::REPLACE,
+            self.inner,
+            self.ctx.atom._NET_WM_DESKTOP,
+            AtomEnum::CARDINAL,
+            &[id as u32],
+        )?;
+        Ok(())
+    }
+
+    fn change_net_wm_state(&mut self, action: u32, state: Atom) {
+        let present = self.net_wm_state.contains(&state);
+        let wanted = match action {
+            NET_WM_STATE_REMOVE => false,
+            NET_WM_STATE_ADD => true,
+            NET_WM_STATE_TOGGLE => !present,
+            _ => return,
+        };
+        if wanted && !present {
+            self.net_wm_state.push(state);
+        } else if !wanted {
+            self.net_wm_state.retain(|&s| s != state);
+        }
+    }
+
     pub fn set_highlight(&mut self, highlight: bool) -> Result<()> {
         self.highlighted = highlight;
         self.update_ornament()?;
@@ -269,11 +901,6 @@ impl Window {
     }
 
     pub fn configure(&mut self, aux: &ConfigureWindowAux) -> Result<()> {
-        // Use self.border_width if border_width is not specified.
-        let bw = aux.border_width.unwrap_or(self.border_width);
-        let aux = aux.border_width(bw);
-        self.ctx.conn.configure_window(self.frame, &aux)?;
-
         if self.is_floating() {
             let mut outer_rect = self.float_geometry.unwrap();
             if let Some(width) = aux.width {
@@ -284,6 +911,59 @@ impl Window {
             }
             self.float_geometry = Some(outer_rect);
         }
+        self.configure_frame(aux)
+    }
+
+    /// Moves and resizes the frame without changing the floating geometry.
+    /// This is used for intermediate steps of animations.
+    pub fn move_resize(&mut self, rect: Rectangle) -> Result<()> {
+        let aux = ConfigureWindowAux::new()
+            .x(rect.x as i32)
+            .y(rect.y as i32)
+            .width(rect.width as u32)
+            .height(rect.height as u32);
+        self.configure_frame(&aux)
+    }
+
+    /// Makes the frame cover `rect` without the border, leaving the floating geometry as it is.
+    pub fn cover(&mut self, rect: Rectangle) -> Result<()> {
+        let aux = ConfigureWindowAux::new()
+            .x(rect.x as i32)
+            .y(rect.y as i32)
+            .width(rect.width as u32)
+            .height(rect.height as u32)
+            .border_width(0);
+        self.configure_frame(&aux)
+    }
+
+    fn configure_frame(&mut self, aux: &ConfigureWindowAux) -> Result<()> {
+        // Use the current border width if border_width is not specified.
+        let bw = aux.border_width.unwrap_or_else(|| self.border_width());
+        let aux = aux.border_width(bw);
+        self.ctx.conn.configure_window(self.frame, &aux)?;
+
+        let geo = &mut self.frame_geometry;
+        if let Some(x) = aux.x {
+            geo.x = x as i16;
+        }
+        if let Some(y) = aux.y {
+            geo.y = y as i16;
+        }
+        if let Some(width) = aux.width {
+            geo.width = width as u16;
+        }
+        if let Some(height) = aux.height {
+            geo.height = height as u16;
+        }
+        // Moves keep the shape, which is relative to the frame.
+        let size = (geo.width, geo.height, bw as u16);
+        if let Some(radius) = self.ctx.config.rounded_corners.radius() {
+            if self.shaped_size != Some(size) {
+                let (width, height, bw) = size;
+                shape::round_corners(&self.ctx.conn, self.frame, width, height, bw, radius)?;
+                self.shaped_size = Some(size);
+            }
+        }
 
         // dummy request
         // Ensure delivery of ConfigureNotify at the following `configure_window`
@@ -291,23 +971,32 @@ impl Window {
         let dummy_aux = ConfigureWindowAux::new().x(1);
         self.ctx.conn.configure_window(self.inner, &dummy_aux)?;
 
+        let title_height = self.title_height() as u32;
         let mut inner_aux = ConfigureWindowAux::new()
             .x(0)
-            .y(if self.frame_visible { 16 } else { 0 }) // FIXME
+            .y(title_height as i32)
             .border_width(0);
 
         if let Some(w) = aux.width {
             inner_aux = inner_aux.width(w);
         }
         if let Some(h) = aux.height {
-            let y = inner_aux.y.unwrap();
-            inner_aux = inner_aux.height(h - y as u32);
+            inner_aux = inner_aux.height(h.saturating_sub(title_height).max(1));
         }
 
         self.ctx.conn.configure_window(self.inner, &inner_aux)?;
         Ok(())
     }
 
+    /// Shows or hides the title bar of a tiled window. Floating windows always have it.
+    pub fn set_title_visible(&mut self, visible: bool) -> Result<()> {
+        match (visible, self.is_floating()) {
+            (true, _) => self.add_frame(),
+            (false, false) => self.remove_frame(),
+            (false, true) => Ok(()),
+        }
+    }
+
     fn add_frame(&mut self) -> Result<()> {
         if self.frame_visible {
             return Ok(());
@@ -339,62 +1028,58 @@ impl Window {
 
         // Fetch window info
         let geo = conn.get_geometry(self.frame)?.reply()?;
-        let reply = self
-            .ctx
-            .conn
-            .get_property(
-                false,
-                self.inner,
-                AtomEnum::WM_NAME,
-                AtomEnum::STRING,
-                0,
-                std::u32::MAX,
-            )?
-            .reply();
-        let win_name = reply
-            .map(|reply| reply.value)
-            .unwrap_or_else(|_| b"(unknown)".to_vec());
-        let win_name = String::from_utf8_lossy(&win_name);
+        let win_name = self.title()?;
+        self.known_title = win_name.clone();
 
         // Clear
+        let theme = self.ctx.theme();
         let color = if self.highlighted {
-            self.ctx.config.border.color_focused
+            theme.border_focused
         } else {
-            self.ctx.config.border.color_regular
+            theme.border_regular
         };
+        let gc = self.ctx.gc_for(self.metrics.scale);
         let aux = ChangeGCAux::new().foreground(color).background(color);
-        conn.change_gc(self.gc, &aux)?;
+        conn.change_gc(gc, &aux)?;
         conn.poly_fill_rectangle(
             self.frame,
-            self.gc,
+            gc,
             &[Rectangle {
                 x: 0,
                 y: 0,
                 width: geo.width,
-                height: 16,
+                height: self.full_title_height(),
             }],
         )?;
 
-        // Window ID and name
+        // Icon, window ID and name
+        let mut x = 4;
+        if let Some(icon) = &mut self.icon {
+            icon.draw(self.frame, 2, 0, color)?;
+            x += ICON_SIZE as i16;
+        }
         let title = format!("0x{:07X} -- {}", self.inner, win_name);
         let title = title.as_bytes();
-        let aux = ChangeGCAux::new().foreground(0xFFFFFF);
-        conn.change_gc(self.gc, &aux)?;
-        conn.image_text8(self.frame, self.gc, 4, 13, title)?;
+        let aux = ChangeGCAux::new().foreground(theme.title);
+        conn.change_gc(gc, &aux)?;
+        conn.image_text8(self.frame, gc, x, 13 * self.metrics.scale as i16, title)?;
 
         Ok(())
     }
 
-    fn update_ornament(&mut self) -> Result<()> {
+    /// Redraws the frame and the border, e.g. after the theme is changed.
+    pub fn update_ornament(&mut self) -> Result<()> {
         if self.frame_visible {
             self.draw_frame()?;
         }
 
-        let border = self.ctx.config.border;
+        let theme = self.ctx.theme();
         let color = if self.highlighted {
-            border.color_focused
+            theme.border_focused
+        } else if self.urgent {
+            theme.border_urgent
         } else {
-            border.color_regular
+            theme.border_regular
         };
         let aux = ChangeWindowAttributesAux::new().border_pixel(color);
         self.ctx.conn.change_window_attributes(self.frame, &aux)?;
@@ -412,17 +1097,6 @@ impl EventHandlerMethods for Window {
         Ok(())
     }
 
-    fn on_unmap_notify(&mut self, notif: UnmapNotifyEvent) -> Result<()> {
-        if !self.contains(notif.window) {
-            return Ok(());
-        }
-
-        assert!(notif.window == self.inner);
-        self.unmap()?;
-
-        Ok(())
-    }
-
     fn on_expose(&mut self, ev: ExposeEvent) -> Result<()> {
         assert!(ev.window == self.frame);
         if self.is_viewable() {
@@ -432,13 +1106,63 @@ impl EventHandlerMethods for Window {
     }
 
     fn on_configure_request(&mut self, req: ConfigureRequestEvent) -> Result<()> {
+        // The client asks for its own size, which excludes the title.
         let mut aux = ConfigureWindowAux::from_configure_request(&req);
         if let Some(height) = aux.height {
-            aux.height = Some(height + 16); // FIXME
+            aux.height = Some(height + self.title_height() as u32);
         }
         self.configure(&aux)?;
         Ok(())
     }
+
+    fn on_client_message(&mut self, ev: ClientMessageEvent) -> Result<()> {
+        if ev.window != self.inner || ev.type_ != self.ctx.atom._NET_WM_STATE || ev.format != 32 {
+            return Ok(());
+        }
+
+        let [action, first, second, ..] = ev.data.as_data32();
+        for state in [first, second] {
+            if state != x11rb::NONE {
+                self.change_net_wm_state(action, state);
+            }
+        }
+        debug!(
+            "_NET_WM_STATE of {:08X}: {:?}",
+            self.inner, self.net_wm_state
+        );
+
+        // The WM maintains the property once the window is managed.
+        self.ctx.conn.change_property32(
+            PropMode::REPLACE,
+            self.inner,
+            self.ctx.atom._NET_WM_STATE,
+            AtomEnum::ATOM,
+            &self.net_wm_state,
+        )?;
+        self.update_bypass_compositor()?;
+        Ok(())
+    }
+
+    fn on_property_notify(&mut self, notif: PropertyNotifyEvent) -> Result<()> {
+        if notif.window != self.inner {
+            return Ok(());
+        }
+        if notif.atom == self.ctx.atom._NET_WM_ICON {
+            self.icon = Icon::fetch(&self.ctx, self.inner)?;
+            return self.update_ornament();
+        }
+        if notif.atom != AtomEnum::WM_HINTS.into() {
+            return Ok(());
+        }
+
+        let urgent = is_urgent(&self.ctx, self.inner)?;
+        if urgent != self.urgent {
+            debug!("urgency of {:08X}: {}", self.inner, urgent);
+            self.urgent = urgent;
+            self.update_ornament()?;
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Window {
@@ -460,8 +1184,369 @@ impl std::fmt::Debug for Window {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Window {{ inner: {:08X}, frame: {:08X}, state: {:?} }}",
-            self.inner, self.frame, self.state
+            "Window {{ inner: {:08X}, frame: {:08X}, {:?} }}",
+            self.inner, self.frame, self.lifecycle
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    fn client_size(ctx: &Context, wid: Wid) -> (u16, u16) {
+        let win = ctx.conn.window(wid).unwrap();
+        (win.width, win.height)
+    }
+
+    /// Applies the floating geometry as `Screen::refresh_layout` does.
+    fn apply_float_geometry(win: &mut Window) {
+        let geo = win.get_float_geometry().unwrap();
+        let aux = ConfigureWindowAux::new()
+            .x(geo.x as i32)
+            .y(geo.y as i32)
+            .width(geo.width as u32)
+            .height(geo.height as u32);
+        win.configure(&aux).unwrap();
+    }
+
+    #[test]
+    fn float_and_sink_preserve_client_size() {
+        let ctx = mock::context(&[]);
+        let wid = ctx.conn.create_client(320, 240);
+        let mut win = Window::new(
+            ctx.clone(),
+            wid,
+            WindowState::Mapped,
+            FrameMetrics::unscaled(&ctx.config.border),
+        )
+        .unwrap();
+        let rect = Rectangle {
+            x: 10,
+            y: 20,
+            width: 320,
+            height: 240,
+        };
+
+        for _ in 0..2 {
+            win.float(rect).unwrap();
+            apply_float_geometry(&mut win);
+            assert_eq!(client_size(&ctx, wid), (320, 240));
+            assert_eq!(win.frame_geometry().height, 240 + TITLE_HEIGHT);
+
+            win.sink().unwrap();
+            assert_eq!(win.title_height(), 0);
+        }
+    }
+
+    #[test]
+    fn float_geometry_scales_with_the_monitor() {
+        let ctx = mock::context(&[]);
+        let wid = ctx.conn.create_client(320, 240);
+        let metrics = FrameMetrics::unscaled(&ctx.config.border);
+        let mut win = Window::new(ctx.clone(), wid, WindowState::Mapped, metrics).unwrap();
+        let rect = Rectangle {
+            x: 960,
+            y: 540,
+            width: 640,
+            height: 480 - TITLE_HEIGHT,
+        };
+        win.float(rect).unwrap();
+        win.fit_float_geometry(1920, 1080);
+        let framed = Rectangle {
+            height: 480,
+            ..rect
+        };
+        assert_eq!(win.get_float_geometry(), Some(framed));
+
+        win.fit_float_geometry(960, 540);
+        let expected = Rectangle {
+            x: 480,
+            y: 270,
+            width: 320,
+            height: 240,
+        };
+        assert_eq!(win.get_float_geometry(), Some(expected));
+
+        // A geometry given on the current monitor is kept.
+        let moved = Rectangle {
+            x: 100,
+            y: 50,
+            ..rect
+        };
+        win.set_float_geometry(moved);
+        win.fit_float_geometry(960, 540);
+        assert_eq!(win.get_float_geometry(), Some(moved));
+    }
+
+    #[test]
+    fn float_geometry_stays_on_the_monitor() {
+        let ctx = mock::context(&[]);
+        let wid = ctx.conn.create_client(320, 240);
+        let metrics = FrameMetrics::unscaled(&ctx.config.border);
+        let mut win = Window::new(ctx.clone(), wid, WindowState::Mapped, metrics).unwrap();
+        let rect = Rectangle {
+            x: -5000,
+            y: 3000,
+            width: 320,
+            height: 240,
+        };
+        win.float(rect).unwrap();
+        win.fit_float_geometry(1920, 1080);
+        let geo = win.get_float_geometry().unwrap();
+        assert_eq!((geo.x, geo.y), (32 - 320, 1080 - 32));
+
+        win.center_float_geometry();
+        let geo = win.get_float_geometry().unwrap();
+        assert_eq!((geo.x, geo.y), (800, 420 - TITLE_HEIGHT as i16 / 2));
+    }
+
+    #[test]
+    fn changed_title_is_found() {
+        let ctx = mock::context(&[]);
+        let wid = ctx.conn.create_client(320, 240);
+        let metrics = FrameMetrics::unscaled(&ctx.config.border);
+        let mut win = Window::new(ctx.clone(), wid, WindowState::Mapped, metrics).unwrap();
+        assert!(!win.refresh_title().unwrap());
+
+        ctx.conn
+            .change_property8(
+                PropMode::REPLACE,
+                wid,
+                AtomEnum::WM_NAME,
+                AtomEnum::STRING,
+                b"vim",
+            )
+            .unwrap();
+        assert!(win.refresh_title().unwrap());
+        assert!(!win.refresh_title().unwrap());
+    }
+
+    #[test]
+    fn frame_follows_the_metrics_of_the_monitor() {
+        let ctx = mock::context(&[]);
+        let wid = ctx.conn.create_client(320, 240);
+        let metrics = FrameMetrics::unscaled(&ctx.config.border);
+        let mut win = Window::new(ctx.clone(), wid, WindowState::Mapped, metrics).unwrap();
+        let rect = Rectangle {
+            x: 10,
+            y: 20,
+            width: 320,
+            height: 240,
+        };
+        win.float(rect).unwrap();
+        apply_float_geometry(&mut win);
+
+        win.set_metrics(FrameMetrics::new(&ctx.config.border, "eDP-1", 2))
+            .unwrap();
+        assert_eq!(win.title_height(), 2 * TITLE_HEIGHT);
+        assert_eq!(win.border_width(), 2);
+        assert_eq!(ctx.conn.window(win.frame()).unwrap().border_width, 2);
+        let client = ctx.conn.window(wid).unwrap();
+        assert_eq!(client.y, 2 * TITLE_HEIGHT as i16);
+    }
+
+    #[test]
+    fn frame_is_shaped_again_only_when_resized() {
+        let config = crate::config::Config {
+            rounded_corners: crate::config::RoundedCornersConfig {
+                enabled: true,
+                radius: 6,
+            },
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[], config);
+        let wid = ctx.conn.create_client(320, 240);
+        let metrics = FrameMetrics::unscaled(&ctx.config.border);
+        let mut win = Window::new(ctx.clone(), wid, WindowState::Mapped, metrics).unwrap();
+        let mut rect = Rectangle {
+            x: 10,
+            y: 20,
+            width: 320,
+            height: 240,
+        };
+        win.move_resize(rect).unwrap();
+        let shaped = ctx.conn.shape_count(win.frame());
+        assert!(shaped > 0);
+
+        // e.g. the steps of an animation moving the window
+        for x in [20, 30, 40] {
+            rect.x = x;
+            win.move_resize(rect).unwrap();
+        }
+        assert_eq!(ctx.conn.shape_count(win.frame()), shaped);
+
+        rect.width = 400;
+        win.move_resize(rect).unwrap();
+        assert_eq!(ctx.conn.shape_count(win.frame()), shaped + 1);
+    }
+
+    #[test]
+    fn debug_info_describes_the_window() {
+        let ctx = mock::context(&[]);
+        let wid = ctx.conn.create_client(320, 240);
+        let metrics = FrameMetrics::unscaled(&ctx.config.border);
+        let win = Window::new(ctx.clone(), wid, WindowState::Mapped, metrics).unwrap();
+
+        let info = win.debug_info().unwrap();
+        assert!(info.contains(&format!("window=0x{:08X}", wid)));
+        assert!(info.contains(&format!("frame=0x{:08X}", win.frame())));
+        assert!(info.contains("protocols=[]"));
+        assert!(info.ends_with("floating=false, pinned=false"));
+    }
+
+    #[test]
+    fn wm_state_is_iconic_while_hidden() {
+        let ctx = mock::context(&[]);
+        let wid = ctx.conn.create_client(320, 240);
+        let mut win = Window::new(
+            ctx.clone(),
+            wid,
+            WindowState::Mapped,
+            FrameMetrics::unscaled(&ctx.config.border),
         )
+        .unwrap();
+        let wm_state = |ctx: &Context| {
+            let data = ctx.conn.window(wid).unwrap().properties[&ctx.atom.WM_STATE]
+                .2
+                .clone();
+            data.chunks(4)
+                .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+                .collect::<Vec<u32>>()
+        };
+        assert_eq!(wm_state(&ctx), vec![WM_STATE_NORMAL, x11rb::NONE]);
+
+        win.hide().unwrap();
+        assert_eq!(wm_state(&ctx), vec![WM_STATE_ICONIC, x11rb::NONE]);
+        win.show().unwrap();
+        assert_eq!(wm_state(&ctx), vec![WM_STATE_NORMAL, x11rb::NONE]);
+    }
+
+    #[test]
+    fn window_hidden_before_mapped_is_iconic() {
+        let ctx = mock::context(&[]);
+        let wid = ctx.conn.create_client(320, 240);
+        let metrics = FrameMetrics::unscaled(&ctx.config.border);
+        let mut win = Window::new(ctx.clone(), wid, WindowState::Created, metrics).unwrap();
+        let frame_mapped = |win: &Window| ctx.conn.window(win.frame()).unwrap().mapped;
+
+        win.hide().unwrap();
+        win.map().unwrap();
+        assert!(win.is_mapped() && !win.is_viewable());
+        assert!(!frame_mapped(&win));
+        let wm_state = &ctx.conn.window(wid).unwrap().properties[&ctx.atom.WM_STATE].2;
+        assert_eq!(wm_state[..4], WM_STATE_ICONIC.to_ne_bytes());
+
+        win.show().unwrap();
+        assert!(win.is_viewable());
+        assert!(frame_mapped(&win));
+    }
+
+    #[test]
+    fn lifecycle_transitions() {
+        let mut life = Lifecycle::new(WindowState::Created);
+        assert!(!life.is_mapped());
+        assert!(life.map());
+        assert!(life.is_viewable());
+
+        // The client unmaps and maps the window again, which is not the first time.
+        life.unmap();
+        assert!(!life.is_mapped());
+        assert!(!life.map());
+        assert!(life.is_viewable());
+
+        // The WM hides and shows it regardless of the client.
+        assert!(life.hide());
+        assert!(life.is_mapped() && !life.is_viewable());
+        life.unmap();
+        assert!(!life.map());
+        assert!(!life.is_viewable());
+        assert!(life.show());
+        assert!(life.is_viewable());
+
+        // Windows adopted at startup are mapped already.
+        let mut life = Lifecycle::new(WindowState::Mapped);
+        assert!(life.is_viewable());
+        life.unmap();
+        assert!(life.hide());
+        assert!(life.show());
+        assert!(!life.is_mapped());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "mapped twice")]
+    fn mapping_a_mapped_window_is_a_bug() {
+        Lifecycle::new(WindowState::Mapped).map();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "unmapped while not mapped")]
+    fn unmapping_a_created_window_is_a_bug() {
+        Lifecycle::new(WindowState::Created).unmap();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "hidden twice")]
+    fn hiding_a_hidden_window_is_a_bug() {
+        let mut life = Lifecycle::new(WindowState::Mapped);
+        life.hide();
+        life.hide();
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "shown twice")]
+    fn showing_a_shown_window_is_a_bug() {
+        Lifecycle::new(WindowState::Mapped).show();
+    }
+
+    #[test]
+    fn configure_request_sets_client_size() {
+        let ctx = mock::context(&[]);
+        let wid = ctx.conn.create_client(320, 240);
+        let mut win = Window::new(
+            ctx.clone(),
+            wid,
+            WindowState::Mapped,
+            FrameMetrics::unscaled(&ctx.config.border),
+        )
+        .unwrap();
+        let request = |win: &mut Window| {
+            let req = ConfigureRequestEvent {
+                response_type: CONFIGURE_REQUEST_EVENT,
+                stack_mode: StackMode::ABOVE,
+                sequence: 0,
+                parent: win.frame(),
+                window: wid,
+                sibling: x11rb::NONE,
+                x: 0,
+                y: 0,
+                width: 400,
+                height: 300,
+                border_width: 0,
+                value_mask: u16::from(ConfigWindow::WIDTH | ConfigWindow::HEIGHT),
+            };
+            win.on_configure_request(req).unwrap();
+        };
+
+        // Without the frame, the client fills the frame.
+        request(&mut win);
+        assert_eq!(client_size(&ctx, wid), (400, 300));
+        assert_eq!(win.frame_geometry().height, 300);
+
+        win.float(Rectangle {
+            x: 0,
+            y: 0,
+            width: 400,
+            height: 300,
+        })
+        .unwrap();
+        request(&mut win);
+        assert_eq!(client_size(&ctx, wid), (400, 300));
+        assert_eq!(win.frame_geometry().height, 300 + TITLE_HEIGHT);
     }
 }
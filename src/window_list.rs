@@ -0,0 +1,150 @@
+use log::debug;
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Window as Wid, *};
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+
+const LINE_HEIGHT: u16 = 16;
+const WIDTH: u16 = 480;
+const PADDING: u16 = 4;
+
+const COLOR_BG: u32 = 0x1a1949;
+const COLOR_FG: u32 = 0xd2ca9c;
+
+/// Keycodes of 1, 2, ..., 9, 0 selecting the entries.
+const NUMBER_KEYS: [u8; 10] = [10, 11, 12, 13, 14, 15, 16, 17, 18, 19];
+pub const MAX_ENTRIES: usize = NUMBER_KEYS.len();
+
+/// An entry of the list.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub wid: Wid,
+    pub class: String,
+    pub title: String,
+}
+
+/// A popup listing windows, which grabs the keyboard while it is shown.
+#[derive(Debug)]
+pub struct WindowList {
+    ctx: Context,
+    wid: Wid,
+    gc: Gcontext,
+    entries: Vec<Entry>,
+}
+
+impl WindowList {
+    /// Shows the list in the middle of `area`.
+    pub fn show(ctx: Context, area: Rectangle, mut entries: Vec<Entry>) -> Result<Self> {
+        entries.truncate(MAX_ENTRIES);
+
+        let width = WIDTH.min(area.width);
+        let height = LINE_HEIGHT * entries.len().max(1) as u16 + PADDING * 2;
+        let x = area.x + (area.width.saturating_sub(width) / 2) as i16;
+        let y = area.y + (area.height.saturating_sub(height) / 2) as i16;
+
+        let wid = ctx.conn.generate_id()?;
+        let aux = CreateWindowAux::new()
+            .background_pixel(COLOR_BG)
+            .event_mask(EventMask::EXPOSURE)
+            .override_redirect(1);
+        ctx.conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            wid,
+            ctx.root,
+            x,
+            y,
+            width,
+            height,
+            1,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &aux,
+        )?;
+
+        let gc = ctx.conn.generate_id()?;
+        {
+            let font = ctx.conn.generate_id()?;
+            ctx.conn.open_font(font, b"fixed")?.check()?;
+
+            let aux = CreateGCAux::new()
+                .font(font)
+                .foreground(COLOR_FG)
+                .background(COLOR_BG);
+            ctx.conn.create_gc(gc, wid, &aux)?;
+
+            ctx.conn.close_font(font)?;
+        }
+
+        ctx.conn.map_window(wid)?;
+        debug!("window list: {:?}", entries);
+
+        // Dropping the list takes the popup away if it cannot have the keys.
+        let list = Self {
+            ctx,
+            wid,
+            gc,
+            entries,
+        };
+        let status = list
+            .ctx
+            .conn
+            .grab_keyboard(
+                false,
+                list.ctx.root,
+                x11rb::CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?
+            .reply()?
+            .status;
+        if status != GrabStatus::SUCCESS {
+            return Err(Error::KeyboardAlreadyGrabbed);
+        }
+        list.draw()?;
+        Ok(list)
+    }
+
+    pub fn wid(&self) -> Wid {
+        self.wid
+    }
+
+    /// Returns the window selected by the key.
+    pub fn select(&self, keycode: u8) -> Option<Wid> {
+        let index = NUMBER_KEYS.iter().position(|&key| key == keycode)?;
+        self.entries.get(index).map(|entry| entry.wid)
+    }
+
+    pub fn draw(&self) -> Result<()> {
+        if self.entries.is_empty() {
+            let y = (PADDING + LINE_HEIGHT - 3) as i16;
+            self.ctx
+                .conn
+                .image_text8(self.wid, self.gc, 8, y, b"(no windows)")?;
+        }
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let number = (i + 1) % 10;
+            let line = format!("{}  {:<16} {}", number, entry.class, entry.title);
+            // Core fonts cannot show non-ASCII characters.
+            let line: Vec<u8> = line
+                .chars()
+                .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+                .take(255)
+                .collect();
+
+            let y = (PADDING + LINE_HEIGHT * (i as u16 + 1) - 3) as i16;
+            self.ctx.conn.image_text8(self.wid, self.gc, 8, y, &line)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for WindowList {
+    fn drop(&mut self) {
+        let _ = self.ctx.conn.ungrab_keyboard(x11rb::CURRENT_TIME);
+        let _ = self.ctx.conn.free_gc(self.gc);
+        let _ = self.ctx.conn.destroy_window(self.wid);
+    }
+}
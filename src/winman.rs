@@ -1,19 +1,47 @@
-use log::{debug, error, info, warn};
+use crossbeam_channel::{Receiver, Sender};
+use log::{debug, error, info, trace, warn};
+use std::collections::HashMap;
+use std::time::Instant;
 
-use x11rb::connection::Connection;
+use x11rb::connection::{Connection, RequestConnection as _};
 use x11rb::protocol::{
-    randr::{self, ConnectionExt as _},
+    randr::{self, ConnectionExt as _, MonitorInfo},
+    xinput::BarrierHitEvent,
+    xkb,
     xproto::{Window as Wid, *},
-    xtest::ConnectionExt as _,
+    xtest::{self, ConnectionExt as _},
 };
+use x11rb::wrapper::ConnectionExt as _;
 
-use crate::context::Context;
+use crate::barrier::EdgeResistance;
+use crate::cheatsheet;
+use crate::config::{ActivationPolicy, MonitorSource};
+use crate::context::{Context, XConnection};
 use crate::error::{Error, Result};
 use crate::event::EventHandlerMethods;
-use crate::monitor::Monitor;
+use crate::geometry::Rect;
+use crate::hook::{self, Hook};
+use crate::keyboard_layout;
+use crate::layout::{self, Direction};
+use crate::metrics::Metrics;
+use crate::monitor::{self, Monitor};
+use crate::osd::OsdHandle;
 use crate::screen::Screen;
-use crate::window::{Window, WindowState};
-use crate::{Command, KeybindAction};
+use crate::window::{FrameMetrics, Window, WindowState};
+use crate::window_list::{self, WindowList};
+use crate::{
+    run_with_input, spawn_named_thread, spawn_process, Command, FocusCommand, KeybindAction,
+    LayoutCommand, PointerCommand, ScreenCommand, SnapPosition, WindowCommand,
+};
+
+macro_rules! unwrap_or_return {
+    ( $e:expr ) => {
+        match $e {
+            Some(x) => x,
+            None => return Ok(()),
+        }
+    };
+}
 
 fn get_mut_pair<T>(slice: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
     assert!(a != b && a < slice.len() && b < slice.len());
@@ -38,21 +66,66 @@ fn get_mut_pair<T>(slice: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
     }
 }
 
-fn spawn_process(cmd: &str) -> Result<()> {
-    use std::process::{Command, Stdio};
-    let mut cmd = cmd.to_owned();
-    cmd.push_str(" &");
-    if let Ok(mut sh) = Command::new("sh")
-        .arg("-c")
-        .arg(cmd)
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-    {
-        let _ = sh.wait();
+/// Returns the float geometry (the frame without its border) of a window snapped to `pos` of `area`.
+/// `size` is the current size of the frame, which is kept for `SnapPosition::Center`.
+fn snap_geometry(
+    area: Rectangle,
+    pos: SnapPosition,
+    size: (u16, u16),
+    border_width: u16,
+) -> Rectangle {
+    use SnapPosition::*;
+    let area = Rect::from(area);
+    let (left, right) = area.split_x(area.width / 2);
+    let (top, bottom) = area.split_y(area.height / 2);
+    let columns = match pos {
+        Left | TopLeft | BottomLeft => left,
+        Right | TopRight | BottomRight => right,
+        Top | Bottom | Center => area,
+    };
+    let rows = match pos {
+        Top | TopLeft | TopRight => top,
+        Bottom | BottomLeft | BottomRight => bottom,
+        Left | Right | Center => area,
+    };
+    let cell = Rect {
+        x: columns.x,
+        width: columns.width,
+        ..rows
+    };
+
+    // The frame without its border, which is not shifted by it.
+    let inner = Rect {
+        x: cell.x,
+        y: cell.y,
+        ..cell.shrink(border_width as u32)
+    };
+    if pos == Center {
+        return inner.centered(size.0 as u32, size.1 as u32).into();
+    }
+    inner.into()
+}
+
+/// Describes the owner of the selection (e.g. WM_S0) with its WM_NAME if any,
+/// e.g. `window 0x00A00001 ("openbox") owns WM_S0`.
+fn describe_client(ctx: &Context, selection: &str, wid: Wid) -> Result<String> {
+    if wid == x11rb::NONE {
+        return Ok(format!("the owner of {} has gone", selection));
+    }
+    let name = ctx
+        .conn
+        .get_property(false, wid, AtomEnum::WM_NAME, AtomEnum::ANY, 0, 256)?
+        .reply()?
+        .value;
+    if name.is_empty() {
+        Ok(format!("window 0x{:08X} owns {}", wid, selection))
+    } else {
+        let name = String::from_utf8_lossy(&name);
+        Ok(format!(
+            "window 0x{:08X} ({:?}) owns {}",
+            wid, name, selection
+        ))
     }
-    Ok(())
 }
 
 fn move_pointer<C: Connection>(conn: &C, dx: i16, dy: i16) -> Result<()> {
@@ -60,43 +133,172 @@ fn move_pointer<C: Connection>(conn: &C, dx: i16, dy: i16) -> Result<()> {
     Ok(())
 }
 
+/// Presses or releases the button after `delay_ms`, through the XTEST extension.
+fn simulate_button<C: Connection>(conn: &C, press: bool, button: u8, delay_ms: u32) -> Result<()> {
+    let event = if press {
+        BUTTON_PRESS_EVENT
+    } else {
+        BUTTON_RELEASE_EVENT
+    };
+    conn.xtest_fake_input(event, button, delay_ms, x11rb::NONE, 0, 0, 0)?;
+    Ok(())
+}
+
 fn simulate_click<C: Connection>(conn: &C, button: u8, duration_ms: u32) -> Result<()> {
-    // button down
-    conn.xtest_fake_input(
-        BUTTON_PRESS_EVENT,
-        button,
-        x11rb::CURRENT_TIME,
-        x11rb::NONE,
-        0,
-        0,
-        0,
-    )?;
-
-    // button up
-    conn.xtest_fake_input(
-        BUTTON_RELEASE_EVENT,
-        button,
-        duration_ms,
-        x11rb::NONE,
-        0,
-        0,
-        0,
-    )?;
+    simulate_button(conn, true, button, x11rb::CURRENT_TIME)?;
+    simulate_button(conn, false, button, duration_ms)?;
+    Ok(())
+}
 
+fn simulate_double_click<C: Connection>(conn: &C, button: u8, duration_ms: u32) -> Result<()> {
+    simulate_click(conn, button, duration_ms)?;
+    // The delays are relative to the previous events.
+    simulate_button(conn, true, button, duration_ms)?;
+    simulate_button(conn, false, button, duration_ms)?;
     Ok(())
 }
 
+// buttons pressed by the Mouse* commands
+const BUTTON_LEFT: u8 = 1;
+const BUTTON_MIDDLE: u8 = 2;
+const BUTTON_RIGHT: u8 = 3;
+
+// KeyCodes handled in the resize mode
+const KEY_ESCAPE: u8 = 9;
+const KEY_ENTER: u8 = 36;
+const KEY_UP: u8 = 111;
+const KEY_DOWN: u8 = 116;
+const KEY_LEFT: u8 = 113;
+const KEY_RIGHT: u8 = 114;
+
+const RESIZE_MODE_KEYS: [u8; 6] = [KEY_ESCAPE, KEY_ENTER, KEY_UP, KEY_DOWN, KEY_LEFT, KEY_RIGHT];
+
+/// The number of screens remembered for each monitor by `ScreenCommand::Back`.
+const SCREEN_HISTORY_LEN: usize = 16;
+
+/// What dragging the pointer does to the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragMode {
+    Move,
+    /// Resizing by the edges, each of `dx` and `dy` is -1 (left/top), 0 or 1 (right/bottom).
+    Resize {
+        dx: i16,
+        dy: i16,
+    },
+}
+
+// directions of _NET_WM_MOVERESIZE
+const MOVERESIZE_SIZE_TOPLEFT: u32 = 0;
+const MOVERESIZE_SIZE_LEFT: u32 = 7;
+const MOVERESIZE_MOVE: u32 = 8;
+const MOVERESIZE_CANCEL: u32 = 11;
+
+impl DragMode {
+    fn from_moveresize_direction(direction: u32) -> Option<Self> {
+        // clockwise from the top-left corner
+        const EDGES: [(i16, i16); 8] = [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+        ];
+        match direction {
+            MOVERESIZE_SIZE_TOPLEFT..=MOVERESIZE_SIZE_LEFT => {
+                let (dx, dy) = EDGES[direction as usize];
+                Some(DragMode::Resize { dx, dy })
+            }
+            MOVERESIZE_MOVE => Some(DragMode::Move),
+            // Moving or resizing by the keyboard is not supported.
+            _ => None,
+        }
+    }
+
+    /// Returns the geometry of `window` dragged by (`dx`, `dy`).
+    fn apply(self, window: Rect, dx: i32, dy: i32) -> Rect {
+        match self {
+            DragMode::Move => window.offset(dx, dy),
+            DragMode::Resize {
+                dx: edge_x,
+                dy: edge_y,
+            } => {
+                // Moving the left or top edge also moves the window.
+                let resize = |pos: i32, size: u32, delta: i32, edge: i16| {
+                    let new_size = (size as i32 + delta * edge as i32).max(1);
+                    let new_pos = if edge < 0 {
+                        pos + size as i32 - new_size
+                    } else {
+                        pos
+                    };
+                    (new_pos, new_size as u32)
+                };
+                let (x, width) = resize(window.x, window.width, dx, edge_x);
+                let (y, height) = resize(window.y, window.height, dy, edge_y);
+                Rect::new(x, y, width, height)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct MouseDrag {
     wid: Wid,
-    start_x: i16,
-    start_y: i16,
-    window_x: i16,
-    window_y: i16,
-    window_w: u16,
-    window_h: u16,
+    mode: DragMode,
+    /// Whether the pointer is grabbed actively for _NET_WM_MOVERESIZE,
+    /// instead of the Alt + button grab.
+    pointer_grabbed: bool,
+    start_x: i32,
+    start_y: i32,
+    /// The geometry of the frame when the drag started.
+    window: Rect,
+}
+
+/// `PointerCommand::MoveRel` repeated while its key is held.
+#[derive(Debug, Clone, Copy)]
+struct PointerRepeat {
+    keycode: u8,
+    dx: i16,
+    dy: i16,
+    /// The number of moves so far, which speed up the pointer.
+    steps: i16,
+    /// The time of the next move.
+    next: Instant,
+    /// The time the key was released at; the key repeat of X sends a KeyRelease
+    /// followed by a KeyPress of the same time, which does not stop the repeat.
+    released: Option<Timestamp>,
+}
+
+/// Results of work done outside the main thread, fed back through `WinMan::task_receiver`.
+#[derive(Debug)]
+pub enum Task {
+    /// The line chosen in the window menu, if any.
+    WindowMenu(Option<String>),
+    /// A spawned command could not be started.
+    SpawnFailed,
+    /// Turns off the error indicator turned on by `SpawnFailed`.
+    ClearError,
+    /// The output of a media command, labeled with what it controls.
+    MediaLevel(&'static str, Option<String>),
+    /// Hides the level shown by `MediaLevel`.
+    ClearLevel,
 }
 
+/// How long the bars show the error indicator.
+const ERROR_FLASH_DURATION: std::time::Duration = std::time::Duration::from_secs(3);
+/// How long the bars show the output of a media command.
+const LEVEL_DURATION: std::time::Duration = std::time::Duration::from_secs(2);
+/// How long the pointer rests at the top edge before the hidden bar is shown.
+const BAR_REVEAL_DELAY: std::time::Duration = std::time::Duration::from_millis(300);
+/// How often the revealed bar checks if the pointer has left it.
+const BAR_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// How long a window of a `confirm_close` rule waits for the second Close.
+const CONFIRM_CLOSE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Manages all screens and dispatches X events and commands to them.
 #[derive()]
 pub struct WinMan {
     ctx: Context,
@@ -104,22 +306,138 @@ pub struct WinMan {
     monitor_num: usize,
     drag: Option<MouseDrag>,
     last_focused_screen: usize,
+    screen_history: HashMap<usize, Vec<usize>>, // monitor -> screens viewed on it, the latest last
+    resize_mode: Option<Wid>, // the window moved by the keys of the resize mode while it is active
+    urgent_history: Vec<Wid>, // frames, the most recently urgent last
+    window_list: Option<WindowList>,
+    window_menu: Option<Vec<Wid>>, // frames listed in the pending window menu
+    task_tx: Sender<Task>,
+    task_rx: Receiver<Task>,
+    error_flashes: usize, // the number of errors being indicated
+    level_shows: usize,   // the number of media levels being shown
+    xkb_enabled: bool,
+    randr_enabled: bool,   // otherwise the monitors come from elsewhere
+    root_size: (u16, u16), // polled without RandR
+    xtest_enabled: bool,   // the clicks of the Pointer commands need XTEST
+    osd: OsdHandle,
+    current_desktop: Option<usize>, // the last _NET_CURRENT_DESKTOP published
+    /// The window owning the WM_Sn selection.
+    selection_owner: Wid,
+    /// Override-redirect windows mapped (e.g. dmenu), each with the managed window
+    /// focused before it, which gets the focus back when the popup goes away.
+    popups: Vec<(Wid, Option<Wid>)>,
+    /// The focused window, whose frame lets the clicks through to the client.
+    click_through: Option<Wid>,
+    /// The window asked to close once, which a `confirm_close` rule keeps open until asked again.
+    close_pending: Option<(Wid, Instant)>,
+    /// The time of the next step of the running transitions.
+    next_animation: Option<Instant>,
+    /// The times to check the bars hidden by `bar.autohide`, by the ids of their monitors.
+    bar_checks: HashMap<usize, Instant>,
+    pointer_repeat: Option<PointerRepeat>,
+    edge_resistance: EdgeResistance,
+    metrics: Metrics,
+    metrics_hud: bool,
 }
 
 impl WinMan {
     pub fn new(ctx: Context) -> Result<Self> {
+        let (task_tx, task_rx) = crossbeam_channel::unbounded();
+        let osd = OsdHandle::new(&ctx);
+        let edge_resistance = EdgeResistance::new(ctx.clone())?;
         let mut wm = Self {
             ctx,
             screens: Vec::new(),
             monitor_num: 0,
             drag: None,
             last_focused_screen: 0,
+            screen_history: HashMap::new(),
+            resize_mode: None,
+            urgent_history: Vec::new(),
+            window_list: None,
+            window_menu: None,
+            task_tx,
+            task_rx,
+            error_flashes: 0,
+            level_shows: 0,
+            xkb_enabled: false,
+            randr_enabled: false,
+            root_size: (0, 0),
+            xtest_enabled: false,
+            osd,
+            current_desktop: None,
+            selection_owner: x11rb::NONE,
+            popups: Vec::new(),
+            click_through: None,
+            close_pending: None,
+            next_animation: None,
+            bar_checks: HashMap::new(),
+            pointer_repeat: None,
+            edge_resistance,
+            metrics: Metrics::new(),
+            metrics_hud: false,
         };
         wm.init()?;
         Ok(wm)
     }
 
+    /// Takes the WM_Sn selection of the X screen n, which tells other window managers
+    /// that the screen is managed.
+    /// NOTE: https://www.x.org/releases/X11R7.7/doc/xorg-docs/icccm/icccm.html#Communication_with_the_Window_Manager_by_Means_of_Selections
+    fn acquire_wm_selection(&mut self) -> Result<()> {
+        let conn = &self.ctx.conn;
+        let name = format!("WM_S{}", self.ctx.screen_num);
+        let selection = conn.intern_atom(false, name.as_bytes())?.reply()?.atom;
+
+        let owner = conn.get_selection_owner(selection)?.reply()?.owner;
+        if owner != x11rb::NONE {
+            let owner = describe_client(&self.ctx, &name, owner)?;
+            return Err(Error::WmAlreadyExists { owner });
+        }
+
+        // An invisible window owns the selection, named so that others can tell who we are.
+        let wid = conn.generate_id()?;
+        let aux = CreateWindowAux::new().override_redirect(1);
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            wid,
+            self.ctx.root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            x11rb::COPY_FROM_PARENT,
+            &aux,
+        )?;
+        conn.change_property8(
+            PropMode::REPLACE,
+            wid,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            b"daily",
+        )?;
+        conn.set_selection_owner(wid, selection, x11rb::CURRENT_TIME)?;
+
+        // Another window manager may have taken it in the meantime.
+        let owner = conn.get_selection_owner(selection)?.reply()?.owner;
+        if owner != wid {
+            let owner = describe_client(&self.ctx, &name, owner)?;
+            return Err(Error::WmAlreadyExists { owner });
+        }
+        self.selection_owner = wid;
+
+        // Announce the new manager to the clients.
+        let data = [x11rb::CURRENT_TIME, selection, wid, 0, 0];
+        let event = ClientMessageEvent::new(32, self.ctx.root, self.ctx.atom.MANAGER, data);
+        conn.send_event(false, self.ctx.root, EventMask::STRUCTURE_NOTIFY, event)?;
+        Ok(())
+    }
+
     fn init(&mut self) -> Result<()> {
+        self.acquire_wm_selection()?;
+
         // Become a window manager of the root window.
         let mask = EventMask::SUBSTRUCTURE_NOTIFY
             | EventMask::SUBSTRUCTURE_REDIRECT
@@ -132,105 +450,251 @@ impl WinMan {
             .conn
             .change_window_attributes(self.ctx.root, &aux)?
             .check()
-            .map_err(|_| Error::WmAlreadyExists)?;
+            .map_err(|_| Error::WmAlreadyExists {
+                owner: "a client which does not own WM_Sn has redirected the root window"
+                    .to_owned(),
+            })?;
 
         // Grab keys
         for (&(_, modif, keycode), _) in self.ctx.config.keybind_iter() {
-            self.ctx
-                .conn
-                .grab_key(
-                    true,
-                    self.ctx.root,
-                    modif,
-                    keycode,
-                    GrabMode::ASYNC,
-                    GrabMode::ASYNC,
-                )?
-                .check()
-                .map_err(|_| Error::KeyAlreadyGrabbed)?;
-        }
-
-        // Grab mouse buttons
+            self.grab_key(modif, keycode)?;
+        }
+
+        // Grab mouse buttons to drag the windows with Alt.
+        // The plain clicks are grabbed on the frames of the unfocused windows instead.
         let event_mask: u32 =
             (EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::BUTTON_MOTION).into();
-        // Mouse left and right button
+        // Mouse left and right button, also with CapsLock (Lock) and NumLock (Mod2)
+        let lock = ModMask::LOCK | ModMask::M2;
         for button in [ButtonIndex::M1, ButtonIndex::M3] {
-            self.ctx
-                .conn
-                .grab_button(
-                    false,
-                    self.ctx.root,
-                    event_mask as u16,
-                    GrabMode::SYNC,  // pointer
-                    GrabMode::ASYNC, // keyboard
-                    self.ctx.root,
-                    x11rb::NONE,
-                    button,
-                    ModMask::ANY,
-                )?
-                .check()
-                .map_err(|_| Error::ButtonAlreadyGrabbed)?;
+            for modifiers in [ModMask::from(0u16), ModMask::LOCK, ModMask::M2, lock] {
+                self.ctx
+                    .conn
+                    .grab_button(
+                        false,
+                        self.ctx.root,
+                        event_mask as u16,
+                        GrabMode::ASYNC, // pointer
+                        GrabMode::ASYNC, // keyboard
+                        self.ctx.root,
+                        x11rb::NONE,
+                        button,
+                        ModMask::M1 | modifiers,
+                    )?
+                    .check()
+                    .map_err(|_| Error::ButtonAlreadyGrabbed)?;
+            }
         }
 
         // Receive RROutputChangeNotify / RRCrtcChangeNotify
-        self.ctx.conn.randr_select_input(
-            self.ctx.root,
-            randr::NotifyMask::OUTPUT_CHANGE | randr::NotifyMask::CRTC_CHANGE,
-        )?;
+        self.randr_enabled = self
+            .ctx
+            .conn
+            .extension_information(randr::X11_EXTENSION_NAME)?
+            .is_some();
+        if self.randr_enabled {
+            self.ctx.conn.randr_select_input(
+                self.ctx.root,
+                randr::NotifyMask::OUTPUT_CHANGE | randr::NotifyMask::CRTC_CHANGE,
+            )?;
+        } else {
+            warn!("RandR is not available, the monitors come from Xinerama or the root window");
+        }
+
+        // Receive XkbStateNotify for the keyboard layout indicator
+        self.xkb_enabled = keyboard_layout::init(&self.ctx)?;
+
+        self.xtest_enabled = self
+            .ctx
+            .conn
+            .extension_information(xtest::X11_EXTENSION_NAME)?
+            .is_some();
+        if !self.xtest_enabled {
+            warn!("XTEST is not available, the clicks of the Pointer commands are disabled");
+        }
+        self.publish_capabilities()?;
 
         // Setup screens and attach monitors
         self.setup_monitor()?;
+        if self.xkb_enabled {
+            let group = keyboard_layout::current_group(&self.ctx)?;
+            self.update_keyboard_layout(group)?;
+        }
 
-        // Put all pre-existing windows on the first screen.
+        // Put pre-existing windows on the screens in their _NET_WM_DESKTOP
+        // (kept across restarts), or on the first screen.
         let preexist = self.ctx.conn.query_tree(self.ctx.root)?.reply()?.children;
         info!("preexist windows = {:08X?}", &preexist);
-        let first = &mut self.screens[0];
-        for &wid in preexist.iter() {
-            let attr = self.ctx.conn.get_window_attributes(wid)?.reply()?;
+        // Ask the attributes of all the windows before waiting for the replies.
+        let cookies = preexist
+            .iter()
+            .map(|&wid| Ok((wid, self.ctx.conn.get_window_attributes(wid)?)))
+            .collect::<Result<Vec<_>>>()?;
+        for (wid, cookie) in cookies {
+            // The window may have gone in the meantime.
+            let attr = match cookie.reply() {
+                Ok(attr) => attr,
+                Err(err) => {
+                    warn!("failed to get the attributes of {:08X}: {}", wid, err);
+                    continue;
+                }
+            };
 
             // Ignore uninteresting windows
             if attr.override_redirect || attr.class == WindowClass::INPUT_ONLY {
                 continue;
             }
 
-            let state = if attr.map_state == MapState::VIEWABLE {
-                WindowState::Mapped
+            let (state, shown_on) = if attr.map_state == MapState::VIEWABLE {
+                (WindowState::Mapped, self.monitor_of_window(wid)?)
             } else {
-                WindowState::Unmapped
+                (WindowState::Unmapped, None)
             };
 
-            let border_width = self.ctx.config.border.width;
-            let win = Window::new(self.ctx.clone(), wid, state, border_width)?;
-            first.add_window(win)?;
+            let metrics = FrameMetrics::unscaled(&self.ctx.config.border);
+            let win = Window::new(self.ctx.clone(), wid, state, metrics)?;
+            // The rules may have changed since the window was mapped.
+            let rule = self.ctx.config.rule_for(win.class());
+            if let Some(opacity) = rule.and_then(|rule| rule.opacity) {
+                win.set_opacity(opacity)?;
+            }
+            // Otherwise the window stays on the monitor it is shown on.
+            let id = match win
+                .requested_screen()?
+                .filter(|&id| id < self.screens.len())
+            {
+                Some(id) => id,
+                None => self
+                    .screens
+                    .iter()
+                    .find(|screen| screen.monitor().is_some_and(|mon| Some(mon.id) == shown_on))
+                    .map_or(0, |screen| screen.id),
+            };
+            self.screens[id].add_window(win)?;
         }
 
         // Focus the first monitor
-        first.focus_any()?;
+        self.screens[0].focus_any()?;
+        self.restore_state()?;
 
         for (id, screen) in self.screens.iter().enumerate() {
             debug!("[{}]: screen {}: {:#?}", id, screen.id, screen);
         }
 
         self.refresh_layout()?;
+        self.update_current_desktop()?;
+
+        if let Some(reason) = self.ctx.config_error.clone() {
+            self.show_osd(&format!("config error: {}", reason))?;
+        }
 
         self.ctx.conn.flush()?;
         Ok(())
     }
 
-    fn setup_monitor(&mut self) -> Result<()> {
-        self.ctx.focus_window(self.ctx.root)?; // HACK
+    /// Publishes the optional features available on this server in _DAILY_CAPABILITIES
+    /// of the root window, one name per line (e.g. "xtest"), for scripts to query.
+    fn publish_capabilities(&self) -> Result<()> {
+        let features = [
+            (self.randr_enabled, "randr"),
+            (self.xkb_enabled, "xkb"),
+            (self.xtest_enabled, "xtest"),
+        ];
+        let capabilities: Vec<&str> = features
+            .into_iter()
+            .filter_map(|(enabled, name)| enabled.then_some(name))
+            .collect();
+        self.ctx.conn.change_property8(
+            PropMode::REPLACE,
+            self.ctx.root,
+            self.ctx.atom._DAILY_CAPABILITIES,
+            AtomEnum::STRING,
+            capabilities.join("\n").as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Leaves the state not kept by the windows themselves (in _NET_WM_DESKTOP)
+    /// on the root window for the next instance after a restart.
+    pub fn save_state(&self) -> Result<()> {
+        let layouts: Vec<&str> = self.screens.iter().map(|sc| sc.layout_name()).collect();
+        self.ctx.conn.change_property8(
+            PropMode::REPLACE,
+            self.ctx.root,
+            self.ctx.atom._DAILY_LAYOUTS,
+            AtomEnum::STRING,
+            layouts.join("\n").as_bytes(),
+        )?;
+        self.ctx.conn.flush()?;
+        Ok(())
+    }
+
+    /// Restores the state left by `save_state` and the screen shown before the restart.
+    fn restore_state(&mut self) -> Result<()> {
+        let root = self.ctx.root;
+        let layouts = self
+            .ctx
+            .conn
+            .get_property(
+                true,
+                root,
+                self.ctx.atom._DAILY_LAYOUTS,
+                AtomEnum::STRING,
+                0,
+                u32::MAX,
+            )?
+            .reply()?
+            .value;
+        let layouts = String::from_utf8_lossy(&layouts);
+        for (screen, name) in self.screens.iter_mut().zip(layouts.split('\n')) {
+            if !name.is_empty() && !screen.select_layout(name) {
+                warn!("restore_state: no layout named {:?}", name);
+            }
+        }
 
-        // Request monitor info
-        let monitors_reply = self
+        let current = self
             .ctx
             .conn
-            .randr_get_monitors(self.ctx.root, true)?
-            .reply()?;
-        self.monitor_num = monitors_reply.monitors.len();
+            .get_property(
+                false,
+                root,
+                self.ctx.atom._NET_CURRENT_DESKTOP,
+                AtomEnum::CARDINAL,
+                0,
+                1,
+            )?
+            .reply()?
+            .value32()
+            .and_then(|mut iter| iter.next());
+        match current {
+            Some(id) if id != 0 && (id as usize) < self.screens.len() => {
+                info!("restore_state: screen {}", id);
+                let id = id as usize;
+                if self.screens[id].monitor().is_none() {
+                    self.switch_screen(id)
+                } else {
+                    // Already shown on another monitor.
+                    self.screens[id].focus_any()?;
+                    self.focus_changed()
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn setup_monitor(&mut self) -> Result<()> {
+        self.ctx.focus_window(self.ctx.root)?; // HACK
+
+        let root = self.ctx.conn.get_geometry(self.ctx.root)?.reply()?;
+        self.root_size = (root.width, root.height);
+        let monitors = self.query_monitors()?;
+        self.monitor_num = monitors.len();
 
         // Detach all monitors
         for screen in self.screens.iter_mut() {
-            let _old = screen.detach()?;
+            if let Some(old) = screen.detach()? {
+                old.shutdown(&self.ctx)?;
+            }
         }
 
         // Fill self.screens
@@ -242,24 +706,58 @@ impl WinMan {
         }
 
         // Attach monitors
-        for (id, info) in monitors_reply.monitors.into_iter().enumerate() {
-            let new = Monitor::new(&self.ctx, id, info);
+        let mut rects = Vec::new();
+        for (id, info) in monitors.into_iter().enumerate() {
+            let new = Monitor::new(&self.ctx, id, info)?;
+            rects.push(new.rect());
             self.screens[id].attach(new)?;
         }
+        self.edge_resistance.update(&rects)?;
 
+        self.ctx.conn.change_property32(
+            PropMode::REPLACE,
+            self.ctx.root,
+            self.ctx.atom._NET_NUMBER_OF_DESKTOPS,
+            AtomEnum::CARDINAL,
+            &[self.screens.len() as u32],
+        )?;
         Ok(())
     }
 
+    /// Returns the monitors from the source of the config,
+    /// or the whole root window as the only monitor without RandR and Xinerama.
+    fn query_monitors(&self) -> Result<Vec<MonitorInfo>> {
+        let source = self.ctx.config.monitor_source;
+        let randr = if self.randr_enabled {
+            self.ctx
+                .conn
+                .randr_get_monitors(self.ctx.root, true)?
+                .reply()?
+                .monitors
+        } else {
+            Vec::new()
+        };
+        let xinerama = match source {
+            MonitorSource::RandR => None,
+            // Some setups have the heads only in Xinerama, and a single giant monitor in RandR.
+            MonitorSource::Auto if randr.len() > 1 => None,
+            _ => monitor::xinerama_monitors(&self.ctx)?,
+        };
+        Ok(match xinerama {
+            Some(heads) if source == MonitorSource::Xinerama || heads.len() > randr.len() => heads,
+            _ if !randr.is_empty() => randr,
+            _ => {
+                let (width, height) = self.root_size;
+                vec![monitor::root_monitor(&self.ctx, width, height)]
+            }
+        })
+    }
+
     fn find_screen_mut<P>(&mut self, pred: P) -> Option<&mut Screen>
     where
         P: Fn(&Screen) -> bool,
     {
-        for screen in self.screens.iter_mut() {
-            if pred(screen) {
-                return Some(screen);
-            }
-        }
-        None
+        self.screens.iter_mut().find(|screen| pred(screen))
     }
 
     fn container_of_mut(&mut self, wid: Wid) -> Option<&mut Screen> {
@@ -282,24 +780,276 @@ impl WinMan {
         .expect("Monitor lost")
     }
 
-    fn focused_screen_mut(&mut self) -> Result<&mut Screen> {
+    fn monitors(&self) -> impl Iterator<Item = &Monitor> {
+        self.screens.iter().filter_map(|screen| screen.monitor())
+    }
+
+    /// Returns the id of the monitor containing the point on the root window, if any.
+    fn monitor_at(&self, x: i32, y: i32) -> Option<usize> {
+        self.monitors()
+            .find(|mon| Rect::from(mon.rect()).contains_point(x, y))
+            .map(|mon| mon.id)
+    }
+
+    /// Returns the id of the monitor showing the largest part of the rectangle, if any.
+    fn monitor_of_rect(&self, rect: Rect) -> Option<usize> {
+        self.monitors()
+            .filter_map(|mon| Some((Rect::from(mon.rect()).intersection(&rect)?, mon.id)))
+            .max_by_key(|(shared, _)| shared.width as u64 * shared.height as u64)
+            .map(|(_, id)| id)
+    }
+
+    /// Returns the id of the monitor showing the largest part of the window, if any.
+    fn monitor_of_window(&self, wid: Wid) -> Result<Option<usize>> {
+        let geo = self.ctx.conn.get_geometry(wid)?.reply()?;
+        Ok(self.monitor_of_rect(Rect::from(geo)))
+    }
+
+    /// Returns the screen containing the focused window,
+    /// or the one on the monitor under the pointer if no window is focused.
+    pub fn focused_screen_mut(&mut self) -> Result<&mut Screen> {
         let mut id = None;
         if let Some(wid) = self.ctx.get_focused_window()? {
             id = self.container_of_mut(wid).map(|sc| sc.id);
         };
-        let id = id.unwrap_or_else(|| self.screen_mut_by_mon(0).id);
+        let id = match id {
+            Some(id) => id,
+            None => {
+                let pointer = self.ctx.conn.query_pointer(self.ctx.root)?.reply()?;
+                let mon_id = self
+                    .monitor_at(pointer.root_x as i32, pointer.root_y as i32)
+                    .unwrap_or(0);
+                self.screen_mut_by_mon(mon_id).id
+            }
+        };
         Ok(&mut self.screens[id])
     }
 
+    /// Returns the first screen from `id` which can hold one more window
+    /// under `max_windows_per_screen`, or `id` if every screen is full.
+    fn screen_with_space(&self, id: usize) -> usize {
+        let max = match self.ctx.config.max_windows_per_screen {
+            Some(max) => max,
+            None => return id,
+        };
+        let n = self.screens.len();
+        (0..n)
+            .map(|i| (id + i) % n)
+            .find(|&i| self.screens[i].window_count() < max)
+            .unwrap_or(id)
+    }
+
     fn refresh_layout(&mut self) -> Result<()> {
+        let started = Instant::now();
         for screen in self.screens.iter_mut() {
             screen.refresh_layout()?;
         }
+        self.metrics.record_layout(started.elapsed());
+        Ok(())
+    }
+
+    /// The environment of the processes run by the WM, e.g. DISPLAY=:0.1 on the X screen 1
+    /// in Zaphod mode, so that the windows open on the same X screen.
+    fn process_envs(&self) -> Vec<(&'static str, String)> {
+        match &self.ctx.display {
+            Some(display) => vec![("DISPLAY", display.clone())],
+            None => Vec::new(),
+        }
+    }
+
+    fn run_hook(&self, hook: Hook, envs: &[(&str, String)]) -> Result<()> {
+        let mut all = self.process_envs();
+        all.extend_from_slice(envs);
+        hook::run(&self.ctx.config.hooks, hook, &all)
+    }
+
+    fn click(&self, button: u8) -> Result<()> {
+        let duration = self.ctx.config.mouse.click_duration_ms;
+        simulate_click(&self.ctx.conn, button, duration)
+    }
+
+    fn spawn(&self, cmd: &str) {
+        let tx = self.task_tx.clone();
+        spawn_process(cmd, &self.process_envs(), move || {
+            let _ = tx.send(Task::SpawnFailed);
+        });
+    }
+
+    /// Shows the error indicator in the bars for a while.
+    fn flash_error(&mut self) -> Result<()> {
+        if !self.ctx.config.bar.flash_errors {
+            return Ok(());
+        }
+
+        self.error_flashes += 1;
+        for screen in self.screens.iter_mut() {
+            screen.set_error(true)?;
+        }
+
+        let tx = self.task_tx.clone();
+        spawn_named_thread("error-flash".to_owned(), move || {
+            std::thread::sleep(ERROR_FLASH_DURATION);
+            let _ = tx.send(Task::ClearError);
+        });
+        Ok(())
+    }
+
+    fn clear_error(&mut self) -> Result<()> {
+        self.error_flashes = self.error_flashes.saturating_sub(1);
+        if self.error_flashes == 0 {
+            for screen in self.screens.iter_mut() {
+                screen.set_error(false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows the message on the focused monitor for a while.
+    fn show_osd(&mut self, text: &str) -> Result<()> {
+        let area = self.focused_screen_mut()?.monitor().map(|mon| mon.rect());
+        if let Some(area) = area {
+            self.osd.show(area, text);
+        }
+        Ok(())
+    }
+
+    /// Selects the layout of the focused screen by its name.
+    fn set_layout(&mut self, name: &str) -> Result<()> {
+        let screen = self.focused_screen_mut()?;
+        if !screen.select_layout(name) {
+            warn!("Command::SetLayout: unknown layout {:?}", name);
+            return self.flash_error();
+        }
+        screen.refresh_layout()?;
+        self.layout_changed()
+    }
+
+    /// Shows the new layout of the focused screen and runs the hook.
+    fn layout_changed(&mut self) -> Result<()> {
+        let screen = self.focused_screen_mut()?;
+        let envs = [
+            ("DAILY_SCREEN", screen.id.to_string()),
+            ("DAILY_LAYOUT", screen.layout_name().to_owned()),
+        ];
+        self.show_osd(&envs[1].1)?;
+        self.run_hook(Hook::LayoutChanged, &envs)
+    }
+
+    /// Switches the colors of the backgrounds, frames and bars without restart.
+    fn set_theme(&mut self, name: &str) -> Result<()> {
+        let theme = match self.ctx.config.themes.get(name) {
+            Some(theme) => *theme,
+            None => {
+                warn!("Command::SetTheme: unknown theme {:?}", name);
+                return self.flash_error();
+            }
+        };
+        self.ctx.set_theme(theme);
+        for screen in self.screens.iter_mut() {
+            screen.apply_theme()?;
+        }
+        self.show_osd(name)
+    }
+
+    /// Runs a media command outside the main thread to show its output in the bars.
+    fn run_media_command(&self, label: &'static str, cmd: String) {
+        let tx = self.task_tx.clone();
+        spawn_named_thread("media".to_owned(), move || {
+            let output = match run_with_input(&cmd, "") {
+                Ok(out) => Some(out),
+                Err(e) => {
+                    error!("media: cannot run {:?}: {}", cmd, e);
+                    None
+                }
+            };
+            let _ = tx.send(Task::MediaLevel(label, output));
+        });
+    }
+
+    fn show_level(&mut self, label: &str, output: Option<String>) -> Result<()> {
+        let level = output
+            .as_deref()
+            .and_then(|out| out.lines().next())
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+        let level = match level {
+            Some(level) => format!("{} {}", label, level),
+            None => return self.flash_error(),
+        };
+        debug!("media level: {:?}", level);
+        self.show_osd(&level)?;
+
+        self.level_shows += 1;
+        for screen in self.screens.iter_mut() {
+            screen.set_level(Some(&level))?;
+        }
+
+        let tx = self.task_tx.clone();
+        spawn_named_thread("level".to_owned(), move || {
+            std::thread::sleep(LEVEL_DURATION);
+            let _ = tx.send(Task::ClearLevel);
+        });
+        Ok(())
+    }
+
+    fn clear_level(&mut self) -> Result<()> {
+        self.level_shows = self.level_shows.saturating_sub(1);
+        if self.level_shows == 0 {
+            for screen in self.screens.iter_mut() {
+                screen.set_level(None)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn update_keyboard_layout(&mut self, group: usize) -> Result<()> {
+        let layout = keyboard_layout::layout_name(&self.ctx, group)?;
+        debug!("keyboard layout: {:?}", layout);
+        for screen in self.screens.iter_mut() {
+            screen.set_keyboard_layout(&layout)?;
+        }
         Ok(())
     }
 
     fn focus_changed(&mut self) -> Result<()> {
+        self.update_click_through()?;
+        self.check_resize_mode()?;
         self.refresh_layout()?;
+        self.update_current_desktop()?;
+        Ok(())
+    }
+
+    /// Lets the clicks through to the focused window, and grabs them again
+    /// on the window focused before, so that its first click focuses it.
+    fn update_click_through(&mut self) -> Result<()> {
+        let focused = self.focused_managed_window()?;
+        if focused == self.click_through {
+            return Ok(());
+        }
+        if let Some(win) = self.click_through.and_then(|wid| self.window_mut(wid)) {
+            win.set_click_through(false)?;
+        }
+        if let Some(win) = focused.and_then(|wid| self.window_mut(wid)) {
+            win.set_click_through(true)?;
+        }
+        self.click_through = focused;
+        Ok(())
+    }
+
+    /// Publishes the screen of the focused monitor as _NET_CURRENT_DESKTOP.
+    fn update_current_desktop(&mut self) -> Result<()> {
+        let id = self.focused_screen_mut()?.id;
+        if self.current_desktop == Some(id) {
+            return Ok(());
+        }
+        self.current_desktop = Some(id);
+        self.ctx.conn.change_property32(
+            PropMode::REPLACE,
+            self.ctx.root,
+            self.ctx.atom._NET_CURRENT_DESKTOP,
+            AtomEnum::CARDINAL,
+            &[id as u32],
+        )?;
         Ok(())
     }
 
@@ -312,6 +1062,7 @@ impl WinMan {
         debug!("switch to screen: {}", id);
 
         let current_id = self.focused_screen_mut()?.id;
+        let mon_id = self.screens[current_id].monitor().map(|mon| mon.id);
 
         if self.screens[id].monitor().is_none() {
             // HACK:
@@ -324,7 +1075,7 @@ impl WinMan {
 
             let new = &mut self.screens[id];
             new.attach(mon_info)?;
-            new.focus_any()?;
+            new.focus_last()?;
         } else {
             let a = current_id;
             let b = id;
@@ -337,11 +1088,83 @@ impl WinMan {
             // perfom swap
             let (screen_a, screen_b) = get_mut_pair(&mut self.screens, a, b);
             Screen::swap_monitors(screen_a, screen_b)?;
-            screen_b.focus_any()?;
+            screen_b.focus_last()?;
+        }
+
+        // The following windows stay on the monitor.
+        let following: Vec<Wid> = self.screens[current_id]
+            .windows()
+            .filter(|win| win.is_following())
+            .map(|win| win.frame())
+            .collect();
+        for frame in following {
+            if let Some(win) = self.screens[current_id].forget_window(frame)? {
+                self.screens[id].add_window(win)?;
+            }
+        }
+
+        if let Some(mon_id) = mon_id {
+            let history = self.screen_history.entry(mon_id).or_default();
+            if history.last() != Some(&current_id) {
+                history.push(current_id);
+            }
+            if history.len() > SCREEN_HISTORY_LEN {
+                history.remove(0);
+            }
         }
 
         self.focus_changed()?;
         self.last_focused_screen = id;
+        self.show_osd(&format!("Screen {}", id + 1))?;
+        self.run_hook(Hook::ScreenChanged, &[("DAILY_SCREEN", id.to_string())])?;
+        Ok(())
+    }
+
+    /// Switches to the screen viewed last on the focused monitor.
+    fn screen_back(&mut self) -> Result<()> {
+        let screen = self.focused_screen_mut()?;
+        let (current_id, mon_id) = (screen.id, screen.monitor().map(|mon| mon.id));
+        let history = unwrap_or_return!(mon_id.and_then(|id| self.screen_history.get_mut(&id)));
+        while let Some(id) = history.pop() {
+            if id != current_id {
+                return self.switch_screen(id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Switches to the screen `offset` away from the focused one, wrapping around.
+    fn switch_screen_rel(&mut self, offset: isize) -> Result<()> {
+        let n = self.screens.len() as isize;
+        let current_id = self.focused_screen_mut()?.id as isize;
+        self.switch_screen((current_id + offset).rem_euclid(n) as usize)
+    }
+
+    /// Exchanges all the windows of the focused screen and the screen `id`,
+    /// while the monitors stay as they are.
+    fn swap_with_screen(&mut self, id: usize) -> Result<()> {
+        if id >= self.screens.len() {
+            error!("winman.swap_with_screen: invalid id = {}", id);
+            return Ok(());
+        }
+
+        let current_id = self.focused_screen_mut()?.id;
+        if current_id == id {
+            return Ok(());
+        }
+
+        let (current, other) = get_mut_pair(&mut self.screens, current_id, id);
+        let current_wins = current.take_windows()?;
+        let other_wins = other.take_windows()?;
+        for win in current_wins {
+            other.add_window(win)?;
+        }
+        for win in other_wins {
+            current.add_window(win)?;
+        }
+        current.focus_any()?;
+
+        self.focus_changed()?;
         Ok(())
     }
 
@@ -354,13 +1177,13 @@ impl WinMan {
         let focus = self.ctx.get_focused_window()?;
         if let Some(wid) = focus {
             if let Some(src) = self.container_of_mut(wid) {
-                if src.background().contains(wid) {
+                if src.is_background(wid) {
                     return Ok(());
                 }
 
                 debug!("move_window_to_screen: wid = {:08X}", wid);
 
-                let win = src.forget_window(wid)?;
+                let win = unwrap_or_return!(src.forget_window(wid)?);
                 src.focus_any()?;
 
                 let dst = &mut self.screens[id];
@@ -372,383 +1195,3306 @@ impl WinMan {
         Ok(())
     }
 
+    /// Focuses the monitor next to the focused one in the direction, if any.
+    fn focus_monitor_in_direction(&mut self, dir: Direction) -> Result<()> {
+        let screen = self.focused_screen_mut()?;
+        let rect = Rect::from(screen.monitor().expect("focus inconsistent").rect());
+        // A point just past the edge, across from the center.
+        let (x, y) = rect.center();
+        let (x, y) = match dir {
+            Direction::Left => (rect.x - 1, y),
+            Direction::Right => (rect.right(), y),
+            Direction::Up => (x, rect.y - 1),
+            Direction::Down => (x, rect.bottom()),
+        };
+        match self.monitor_at(x, y) {
+            Some(mon_id) => self.focus_monitor(mon_id),
+            None => Ok(()),
+        }
+    }
+
     fn focus_monitor(&mut self, mon_id: usize) -> Result<()> {
         let screen = self.screen_mut_by_mon(mon_id);
-        screen.focus_any()?;
+        screen.focus_last()?;
         self.last_focused_screen = screen.id;
         self.focus_changed()?;
         Ok(())
     }
 
-    fn process_command(&mut self, cmd: Command) -> Result<()> {
-        match cmd {
-            Command::Quit => return Err(Error::Quit),
-            Command::Restart => return Err(Error::Restart),
-
-            Command::ShowBorder => {
-                for screen in self.screens.iter_mut() {
-                    screen.show_border();
-                }
-                self.refresh_layout()?;
-            }
-            Command::HideBorder => {
-                for screen in self.screens.iter_mut() {
-                    screen.hide_border();
-                }
-                self.refresh_layout()?;
-            }
-
-            Command::Close => {
-                if let Some(wid) = self.ctx.get_focused_window()? {
-                    if let Some(screen) = self.container_of_mut(wid) {
-                        if !screen.background().contains(wid) {
-                            screen.forget_window(wid)?.close()?;
-                            self.refresh_layout()?;
-                        }
-                    }
-                }
-            }
-
-            Command::Sink => {
-                if let Some(wid) = self.ctx.get_focused_window()? {
-                    if let Some(screen) = self.container_of_mut(wid) {
-                        if !screen.background().contains(wid) {
-                            let win = screen.window_mut(wid).unwrap();
-                            win.sink()?;
-                            self.refresh_layout()?;
-                        }
-                    }
-                }
-            }
-
-            Command::FocusNext => {
-                self.focused_screen_mut()?.focus_next()?;
-                self.focus_changed()?;
-            }
-            Command::FocusPrev => {
-                warn!("Command::FocusPrev: not yet implemented");
-            }
-
-            Command::FocusNextMonitor => {
-                let focused_monitor = self
-                    .focused_screen_mut()?
-                    .monitor()
-                    .expect("focus inconsistent")
-                    .id;
-                let next_monitor = (focused_monitor + 1) % self.monitor_num;
-                self.focus_monitor(next_monitor)?;
-            }
-            Command::FocusPrevMonitor => {
-                let focused_monitor = self
-                    .focused_screen_mut()?
-                    .monitor()
-                    .expect("focus inconsistent")
-                    .id;
-                let prev_monitor = (focused_monitor + self.monitor_num - 1) % self.monitor_num;
-                self.focus_monitor(prev_monitor)?;
-            }
+    fn focus_urgent(&mut self) -> Result<()> {
+        // Forget windows which are gone or no longer urgent.
+        let screens = &self.screens;
+        self.urgent_history.retain(|&frame| {
+            screens
+                .iter()
+                .find_map(|screen| screen.window(frame))
+                .is_some_and(|win| win.is_urgent())
+        });
 
-            Command::NextLayout => {
-                let screen = self.focused_screen_mut()?;
-                screen.next_layout()?;
+        let frame = match self.urgent_history.last() {
+            Some(&frame) => frame,
+            None => {
+                debug!("focus_urgent: no urgent window");
+                return Ok(());
             }
+        };
 
-            Command::Screen(id) => self.switch_screen(id)?,
-            Command::MoveToScreen(id) => self.move_window_to_screen(id)?,
+        debug!("focus_urgent: wid = {:08X}", frame);
+        self.focus_window_anywhere(frame)
+    }
 
-            Command::MovePointerRel(dx, dy) => move_pointer(&self.ctx.conn, dx, dy)?,
-            Command::MouseClickLeft => simulate_click(&self.ctx.conn, 1, 10)?, // left, 10ms
-            Command::Spawn(cmd) => spawn_process(&cmd)?,
+    /// Returns the focused window if it is managed by a screen, except the backgrounds.
+    fn focused_managed_window(&mut self) -> Result<Option<Wid>> {
+        let wid = match self.ctx.get_focused_window()? {
+            Some(wid) => wid,
+            None => return Ok(None),
+        };
+        let managed = self
+            .container_of_mut(wid)
+            .is_some_and(|screen| !screen.is_background(wid));
+        Ok(managed.then_some(wid))
+    }
 
-            Command::LayoutCommand(cmd) => {
-                self.focused_screen_mut()?.layout_command(cmd)?;
-            }
+    /// Whether the window is one the WM has created, e.g. the bars and the OSD.
+    fn is_own_window(&mut self, wid: Wid) -> bool {
+        let setup = self.ctx.conn.setup();
+        if wid & !setup.resource_id_mask == setup.resource_id_base {
+            return true;
         }
-        Ok(())
+        // The bars and the OSD have connections of their own.
+        self.osd.is_osd(wid) || self.screens.iter_mut().any(|screen| screen.is_bar(wid))
     }
 
-    pub fn alarm(&mut self) -> Result<()> {
-        for screen in self.screens.iter_mut() {
-            screen.alarm()?;
-        }
+    /// Remembers the window focused before the override-redirect window is mapped.
+    fn on_popup_mapped(&mut self, popup: Wid) -> Result<()> {
+        // Popups opened from another popup give the focus back to the same window.
+        let prev = match self.focused_managed_window()? {
+            Some(wid) => Some(wid),
+            None => self.popups.last().and_then(|&(_, prev)| prev),
+        };
+        debug!("popup {:08X} mapped, focused before: {:?}", popup, prev);
+        self.popups.push((popup, prev));
         Ok(())
     }
-}
 
-macro_rules! unwrap_or_return {
-    ( $e:expr ) => {
-        match $e {
-            Some(x) => x,
-            None => return Ok(()),
+    /// Focuses the window focused before the popup, unless the focus has moved to another one.
+    fn on_popup_unmapped(&mut self, prev: Option<Wid>) -> Result<()> {
+        if let Some(focused) = self.ctx.get_focused_window()? {
+            let popup_focused = self.popups.iter().any(|&(wid, _)| wid == focused);
+            if popup_focused || self.focused_managed_window()?.is_some() {
+                return Ok(());
+            }
         }
-    };
-}
 
-impl EventHandlerMethods for WinMan {
-    fn on_key_press(&mut self, e: KeyPressEvent) -> Result<()> {
-        let cmd = unwrap_or_return!(self.ctx.config.keybind_match(
-            KeybindAction::Press,
-            e.state,
-            e.detail
-        ));
-        debug!("on_key_press: cmd = {:?}", cmd);
-        self.process_command(cmd)?;
-        Ok(())
+        let prev = unwrap_or_return!(prev);
+        let screen = unwrap_or_return!(self.container_of_mut(prev));
+        let id = screen.id;
+        let win = unwrap_or_return!(screen.window_mut(prev));
+        // The window may have gone out of sight, e.g. by switching the screen.
+        if !win.is_mapped() || win.is_hidden() {
+            return Ok(());
+        }
+        debug!("give the focus back to {:08X}", prev);
+        win.focus()?;
+        self.last_focused_screen = id;
+        self.focus_changed()
     }
 
-    fn on_key_release(&mut self, e: KeyReleaseEvent) -> Result<()> {
-        let cmd = unwrap_or_return!(self.ctx.config.keybind_match(
-            KeybindAction::Release,
-            e.state,
-            e.detail
-        ));
-        debug!("on_key_release: cmd = {:?}", cmd);
-        self.process_command(cmd)?;
+    /// Focuses the window, switching to its screen if the screen is not shown.
+    fn focus_window_anywhere(&mut self, frame: Wid) -> Result<()> {
+        let id = unwrap_or_return!(self.container_of_mut(frame)).id;
+        if self.screens[id].monitor().is_none() {
+            self.switch_screen(id)?;
+        }
+        // The window may be on another page of the layout.
+        self.screens[id].turn_to(frame)?;
+
+        self.screens[id].window_mut(frame).unwrap().focus()?;
+        self.last_focused_screen = id;
+        self.focus_changed()?;
         Ok(())
     }
 
-    fn on_button_press(&mut self, e: ButtonPressEvent) -> Result<()> {
-        // Focus the window just clicked.
-        if let Some(win) = self.window_mut(e.child) {
-            win.focus()?;
-            self.focus_changed()?;
+    /// Floats the window containing `wid` and starts dragging it from (`root_x`, `root_y`).
+    fn start_drag(&mut self, wid: Wid, mode: DragMode, root_x: i16, root_y: i16) -> Result<()> {
+        let owner = unwrap_or_return!(self.container_of_mut(wid));
+        if owner.is_background(wid) {
+            return Ok(());
         }
 
-        if e.state & u16::from(ModMask::M1) > 0 {
-            // button + Alt
+        let win = unwrap_or_return!(owner.window_mut(wid));
+        let wid = win.frame();
+        let window = Rect::from(self.ctx.conn.get_geometry(wid)?.reply()?);
 
-            self.ctx
-                .conn
-                .allow_events(Allow::SYNC_POINTER, x11rb::CURRENT_TIME)?;
+        let screen = unwrap_or_return!(self.container_of_mut(wid));
+        let mon = Rect::from(unwrap_or_return!(screen.monitor()).rect());
 
-            let owner = unwrap_or_return!(self.container_of_mut(e.child));
-            if owner.background().contains(e.child) {
-                return Ok(());
-            }
+        let win = screen.window_mut(wid).unwrap();
+        if !win.is_floating() {
+            win.float(window.offset(-mon.x, -mon.y).into())?;
+        }
 
-            let win = unwrap_or_return!(owner.window_mut(e.child));
-            let wid = win.frame();
-            let geo = self.ctx.conn.get_geometry(wid)?.reply()?;
-
-            let screen = unwrap_or_return!(self.container_of_mut(wid));
-            let mon_info = unwrap_or_return!(screen.monitor().map(|mon| &mon.info));
-            let mon_x = mon_info.x;
-            let mon_y = mon_info.y;
-
-            let rel_x = geo.x - mon_x;
-            let rel_y = geo.y - mon_y;
-
-            let win = screen.window_mut(wid).unwrap();
-            if !win.is_floating() {
-                win.float(Rectangle {
-                    x: rel_x,
-                    y: rel_y,
-                    width: geo.width,
-                    height: geo.height,
-                })?;
-            }
-
-            self.drag = Some(MouseDrag {
-                wid,
-                start_x: e.root_x,
-                start_y: e.root_y,
-                window_x: geo.x,
-                window_y: geo.y,
-                window_w: geo.width,
-                window_h: geo.height,
-            });
+        self.drag = Some(MouseDrag {
+            wid,
+            mode,
+            pointer_grabbed: false,
+            start_x: root_x as i32,
+            start_y: root_y as i32,
+            window,
+        });
 
-            self.refresh_layout()?;
-            Ok(())
-        } else {
-            self.ctx
-                .conn
-                .allow_events(Allow::REPLAY_POINTER, x11rb::CURRENT_TIME)?;
-            Ok(())
-        }
+        self.refresh_layout()?;
+        Ok(())
     }
 
-    fn on_motion_notify(&mut self, e: MotionNotifyEvent) -> Result<()> {
-        let left_mask: u16 = ButtonMask::M1.into();
-        let right_mask: u16 = ButtonMask::M3.into();
+    /// Handles _NET_WM_MOVERESIZE sent by a client dragging its own title bar or edge.
+    fn on_moveresize_request(&mut self, wid: Wid, data: [u32; 5]) -> Result<()> {
+        let [x_root, y_root, direction, ..] = data;
+        debug!("moveresize request: {:08X}, direction = {}", wid, direction);
 
-        if e.state & u16::from(ModMask::M1) == 0 || e.state & (left_mask | right_mask) == 0 {
+        if direction == MOVERESIZE_CANCEL {
+            if self.drag.as_ref().is_some_and(|drag| drag.pointer_grabbed) {
+                self.finish_drag()?;
+            }
+            return Ok(());
+        }
+        let mode = unwrap_or_return!(DragMode::from_moveresize_direction(direction));
+        if self.drag.is_some() || self.window_mut(wid).is_none() {
             return Ok(());
         }
 
-        let drag = unwrap_or_return!(self.drag.clone());
-        let dx = e.root_x - drag.start_x;
-        let dy = e.root_y - drag.start_y;
-
-        let win = unwrap_or_return!(self.window_mut(drag.wid));
-        if e.state & left_mask > 0 {
-            // Left button
-            let aux = ConfigureWindowAux::new()
-                .x((drag.window_x + dx) as i32)
-                .y((drag.window_y + dy) as i32);
-            win.configure(&aux)?;
-        } else if e.state & right_mask > 0 {
-            // Right button
-            let w = drag.window_w as i32 + dx as i32;
-            let h = drag.window_h as i32 + dy as i32;
-            let w = std::cmp::max(w, 1);
-            let h = std::cmp::max(h, 1);
+        // The client has released its pointer grab before sending the message.
+        let mask: u32 = (EventMask::BUTTON_RELEASE | EventMask::BUTTON_MOTION).into();
+        let status = self
+            .ctx
+            .conn
+            .grab_pointer(
+                false,
+                self.ctx.root,
+                mask as u16,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                x11rb::NONE,
+                x11rb::CURRENT_TIME,
+            )?
+            .reply()?
+            .status;
+        if status != GrabStatus::SUCCESS {
+            warn!("moveresize: cannot grab the pointer: {:?}", status);
+            return Ok(());
+        }
 
-            let aux = ConfigureWindowAux::new().width(w as u32).height(h as u32);
-            win.configure(&aux)?;
+        self.start_drag(wid, mode, x_root as i16, y_root as i16)?;
+        match self.drag.as_mut() {
+            Some(drag) => drag.pointer_grabbed = true,
+            None => {
+                self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
+            }
         }
         Ok(())
     }
 
-    fn on_button_release(&mut self, _: ButtonReleaseEvent) -> Result<()> {
+    fn finish_drag(&mut self) -> Result<()> {
         let drag = unwrap_or_return!(self.drag.take());
         let wid = drag.wid;
+        if drag.pointer_grabbed {
+            self.ctx.conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
+        }
 
-        let geo = self.ctx.conn.get_geometry(wid)?.reply()?;
-
-        let screen = unwrap_or_return!(self.container_of_mut(wid));
-        let mon = unwrap_or_return!(screen.monitor());
-        let mon_info = mon.info.clone();
+        let geo = Rect::from(self.ctx.conn.get_geometry(wid)?.reply()?);
+        let src = unwrap_or_return!(self.container_of_mut(wid)).id;
 
-        let win = screen.window_mut(wid).unwrap();
-        win.set_float_geometry(Rectangle {
-            x: geo.x - mon_info.x,
-            y: geo.y - mon_info.y,
-            width: geo.width,
-            height: geo.height,
-        });
+        // The window dropped on another monitor moves to the screen shown there.
+        let dst = match self.monitor_of_rect(geo) {
+            Some(mon_id) => self.screen_mut_by_mon(mon_id).id,
+            None => src,
+        };
+        let mon = Rect::from(unwrap_or_return!(self.screens[dst].monitor()).rect());
+        if dst != src {
+            debug!("finish_drag: {:08X} moves to screen {}", wid, dst);
+            let win = unwrap_or_return!(self.screens[src].forget_window(wid)?);
+            self.screens[dst].add_window(win)?;
+        }
 
-        // TODO: move the ownership of the window to appropriate screen
+        let win = unwrap_or_return!(self.screens[dst].window_mut(wid));
+        win.set_float_geometry(geo.offset(-mon.x, -mon.y).into());
+        if dst != src {
+            win.focus()?;
+            self.last_focused_screen = dst;
+            self.focus_changed()?;
+        }
 
         self.refresh_layout()?;
 
         Ok(())
     }
 
-    fn on_map_request(&mut self, req: MapRequestEvent) -> Result<()> {
-        if req.parent == self.ctx.root {
-            let wid = req.window;
-            if self.window_mut(wid).is_some() {
-                return Ok(());
+    fn close_window(&mut self, wid: Wid) -> Result<()> {
+        if let Some(screen) = self.container_of_mut(wid) {
+            if !screen.is_background(wid) {
+                let id = screen.id;
+                let win = unwrap_or_return!(screen.forget_window(wid)?);
+                let envs = hook::window_envs(&win, id);
+                win.close()?;
+                self.refresh_layout()?;
+                self.run_hook(Hook::WindowClosed, &envs)?;
             }
+        }
+        Ok(())
+    }
 
-            let attr = self.ctx.conn.get_window_attributes(wid)?.reply()?;
-            if attr.class == WindowClass::INPUT_ONLY {
-                return Ok(());
-            }
+    /// Whether `WindowCommand::Close` may close the window. Those of a `confirm_close` rule
+    /// need a second Close within CONFIRM_CLOSE_TIMEOUT, which the OSD asks for.
+    fn close_confirmed(&mut self, wid: Wid) -> Result<bool> {
+        let now = Instant::now();
+        let pending = self.close_pending.take();
+        let class = match self.window_mut(wid) {
+            Some(win) => win.class().to_owned(),
+            None => return Ok(true),
+        };
+        let confirm = self
+            .ctx
+            .config
+            .rule_for(&class)
+            .is_some_and(|rule| rule.confirm_close);
+        let again = pending.is_some_and(|(pending, since)| {
+            pending == wid && now.duration_since(since) < CONFIRM_CLOSE_TIMEOUT
+        });
+        if !confirm || again {
+            return Ok(true);
+        }
 
-            let screen_id = self.focused_screen_mut()?.id;
+        self.close_pending = Some((wid, now));
+        self.show_osd(&format!("Close again to close {}", class))?;
+        Ok(false)
+    }
 
-            let border_width = self.ctx.config.border.width;
-            let mut win = Window::new(self.ctx.clone(), wid, WindowState::Created, border_width)?;
-            win.map()?;
+    /// Handles _NET_ACTIVE_WINDOW, sent by an application (`source` = 1)
+    /// or a pager (`source` = 2) asking to activate the window.
+    fn on_activation_request(&mut self, wid: Wid, source: u32) -> Result<()> {
+        const SOURCE_PAGER: u32 = 2;
 
-            self.screens[screen_id].add_window(win)?;
-        } else {
-            let win = unwrap_or_return!(self.window_mut(req.parent));
-            win.on_map_request(req)?;
+        let policy = self.ctx.config.activation;
+        debug!(
+            "activation request: {:08X}, source = {}, {:?}",
+            wid, source, policy
+        );
+        let win = unwrap_or_return!(self.window_mut(wid));
+        let frame = win.frame();
+        if source != SOURCE_PAGER && policy == ActivationPolicy::Urgent {
+            win.set_urgent()?;
+            self.urgent_history.retain(|&wid| wid != frame);
+            self.urgent_history.push(frame);
+            return Ok(());
         }
-        Ok(())
+        self.focus_window_anywhere(frame)
     }
 
-    fn on_map_notify(&mut self, notif: MapNotifyEvent) -> Result<()> {
-        if notif.override_redirect {
+    /// Lists windows of all screens as lines like `1: [2] class - title`.
+    fn window_menu_entries(&self) -> Result<(Vec<Wid>, String)> {
+        let mut frames = Vec::new();
+        let mut lines = String::new();
+        for screen in self.screens.iter() {
+            for win in screen.windows() {
+                frames.push(win.frame());
+                lines += &format!(
+                    "{}: [{}] {} - {}\n",
+                    frames.len(),
+                    (screen.id + 1) % 10,
+                    win.class(),
+                    win.title()?.replace('\n', " ")
+                );
+            }
+        }
+        Ok((frames, lines))
+    }
+
+    fn show_window_menu(&mut self) -> Result<()> {
+        if self.window_menu.is_some() {
+            debug!("window menu is already shown");
             return Ok(());
         }
 
-        let win = unwrap_or_return!(self.window_mut(notif.event));
-        win.on_map_notify(notif)?;
-        self.focus_changed()?;
+        let (frames, lines) = self.window_menu_entries()?;
+        self.window_menu = Some(frames);
+
+        let cmd = self.ctx.config.window_menu.command.clone();
+        let tx = self.task_tx.clone();
+        spawn_named_thread("window-menu".to_owned(), move || {
+            let selected = match run_with_input(&cmd, &lines) {
+                Ok(out) => Some(out),
+                Err(e) => {
+                    error!("window menu: cannot run {:?}: {}", cmd, e);
+                    None
+                }
+            };
+            let _ = tx.send(Task::WindowMenu(selected));
+        });
         Ok(())
     }
 
-    fn on_unmap_notify(&mut self, notif: UnmapNotifyEvent) -> Result<()> {
-        let win = unwrap_or_return!(self.window_mut(notif.event));
-        win.on_unmap_notify(notif)?;
-        self.focus_changed()?;
+    fn show_keybindings(&mut self) -> Result<()> {
+        let lines = cheatsheet::lines(&self.ctx)?;
+        let cmd = self.ctx.config.keybind_menu.command.clone();
+        spawn_named_thread("keybind-menu".to_owned(), move || {
+            if let Err(e) = run_with_input(&cmd, &lines) {
+                error!("keybind menu: cannot run {:?}: {}", cmd, e);
+            }
+        });
         Ok(())
     }
 
-    fn on_destroy_notify(&mut self, notif: DestroyNotifyEvent) -> Result<()> {
-        let screen = unwrap_or_return!(self.container_of_mut(notif.window));
-        let _ = screen.forget_window(notif.window)?;
-        self.focus_changed()?;
-        Ok(())
+    /// Logs the properties of the focused window and its screen, and shows the summary.
+    fn debug_window_info(&mut self) -> Result<()> {
+        let wid = unwrap_or_return!(self.ctx.get_focused_window()?);
+        let screen = unwrap_or_return!(self.container_of_mut(wid));
+        let id = screen.id;
+        let win = unwrap_or_return!(screen.window(wid));
+        info!("window info: {}, screen={}", win.debug_info()?, id + 1);
+        let summary = format!("0x{:08X} {} (screen {})", win.inner(), win.class(), id + 1);
+        self.show_osd(&summary)
     }
 
-    fn on_configure_request(&mut self, req: ConfigureRequestEvent) -> Result<()> {
-        if req.parent == self.ctx.root {
-            let aux = ConfigureWindowAux::from_configure_request(&req);
-            self.ctx.conn.configure_window(req.window, &aux)?;
-        } else {
-            let win = unwrap_or_return!(self.window_mut(req.parent));
-            win.on_configure_request(req)?;
-        }
-        Ok(())
+    fn on_window_menu_selected(&mut self, selected: Option<String>) -> Result<()> {
+        let frames = unwrap_or_return!(self.window_menu.take());
+        let index: Option<usize> = selected
+            .as_deref()
+            .and_then(|line| line.split(':').next())
+            .and_then(|num| num.trim().parse().ok());
+        debug!("window menu: selected {:?}", index);
+
+        // The window may have been closed while the menu was shown.
+        let frame = unwrap_or_return!(index.and_then(|i| frames.get(i.checked_sub(1)?)));
+        self.focus_window_anywhere(*frame)
     }
 
-    fn on_configure_notify(&mut self, notif: ConfigureNotifyEvent) -> Result<()> {
-        if notif.override_redirect {
-            return Ok(());
+    /// The connection of the WM, e.g. for an extension command to make its own requests.
+    pub fn connection(&self) -> &XConnection {
+        &self.ctx.conn
+    }
+
+    /// Returns the channel to receive tasks to be passed to `process_task`.
+    pub fn task_receiver(&self) -> Receiver<Task> {
+        self.task_rx.clone()
+    }
+
+    pub fn process_task(&mut self, task: Task) -> Result<()> {
+        match task {
+            Task::WindowMenu(selected) => self.on_window_menu_selected(selected),
+            Task::SpawnFailed => self.flash_error(),
+            Task::ClearError => self.clear_error(),
+            Task::MediaLevel(label, output) => self.show_level(label, output),
+            Task::ClearLevel => self.clear_level(),
         }
+    }
 
-        let win = unwrap_or_return!(self.window_mut(notif.event));
-        win.on_configure_notify(notif)?;
-        Ok(())
+    /// The time of the next check of the bars hidden by `bar.autohide`.
+    pub fn bar_deadline(&self) -> Option<Instant> {
+        self.bar_checks.values().min().copied()
     }
 
-    fn on_expose(&mut self, ev: ExposeEvent) -> Result<()> {
-        let screen = unwrap_or_return!(self.container_of_mut(ev.window));
-        screen.on_expose(ev)?;
+    /// Checks the bars whose time has come.
+    pub fn check_bars(&mut self) -> Result<()> {
+        let now = Instant::now();
+        let due: Vec<usize> = self
+            .bar_checks
+            .iter()
+            .filter(|(_, &deadline)| deadline <= now)
+            .map(|(&mon_id, _)| mon_id)
+            .collect();
+        for mon_id in due {
+            self.bar_checks.remove(&mon_id);
+            self.check_bar(mon_id)?;
+        }
         Ok(())
     }
 
-    fn on_focus_in(&mut self, focus_in: FocusInEvent) -> Result<()> {
-        if focus_in.event == self.ctx.root
-            && (focus_in.detail == NotifyDetail::POINTER_ROOT
-                || focus_in.detail == NotifyDetail::NONE)
-        {
-            // Focus the last focused monitor
-            let last_focused = self.last_focused_screen;
-            if let Some(screen) = self.find_screen_mut(|sc| sc.id == last_focused) {
-                screen.focus_any()?;
-            } else {
-                self.screen_mut_by_mon(0).focus_any()?;
-            }
+    /// Keeps the bar hidden by `bar.autohide` shown while the pointer is at the top of the monitor,
+    /// i.e. at the top edge or on the bar, checking it again after a while.
+    fn check_bar(&mut self, mon_id: usize) -> Result<()> {
+        let pointer = self.ctx.conn.query_pointer(self.ctx.root)?.reply()?;
+        let bar = self.ctx.config.bar.clone();
+        let screen = unwrap_or_return!(
+            self.find_screen_mut(|screen| screen.monitor().is_some_and(|mon| mon.id == mon_id))
+        );
+        let mon = screen.monitor().unwrap();
+        let rect = Rect::from(mon.rect());
+        let bar_bottom = Rect::from(bar.geometry(&mon.rect(), mon.scale)).bottom();
+        let (top, _) = rect.split_y((bar_bottom - rect.y).max(0) as u32);
+        let at_top = top.contains_point(pointer.root_x as i32, pointer.root_y as i32);
+        screen.reveal_bar(at_top)?;
+        if at_top {
+            self.bar_checks
+                .insert(mon_id, Instant::now() + BAR_CHECK_INTERVAL);
         }
         Ok(())
     }
 
-    fn on_client_message(&mut self, ev: ClientMessageEvent) -> Result<()> {
-        let name = self.ctx.conn.get_atom_name(ev.type_)?.reply()?.name;
-        debug!("ClientMessageEvent.type_: {:?}", String::from_utf8(name));
+    fn monitor_changed(&mut self) -> Result<()> {
+        self.setup_monitor()?;
+        self.screens[0].focus_any()?;
+        self.focus_changed()?;
+        let envs = [("DAILY_MONITORS", self.monitor_num.to_string())];
+        self.run_hook(Hook::MonitorChanged, &envs)?;
+        Ok(())
+    }
 
-        if ev.window == self.ctx.root {
+    fn show_window_list(&mut self) -> Result<()> {
+        if self.window_list.is_some() {
             return Ok(());
         }
 
-        let win = unwrap_or_return!(self.window_mut(ev.window));
-        win.on_client_message(ev)?;
+        let screen = self.focused_screen_mut()?;
+        let area = screen.monitor().expect("focus inconsistent").rect();
+        let mut entries = Vec::new();
+        for win in screen.windows().filter(|win| win.is_mapped()) {
+            entries.push(window_list::Entry {
+                wid: win.frame(),
+                class: win.class().to_owned(),
+                title: win.title()?,
+            });
+        }
+
+        self.window_list = Some(WindowList::show(self.ctx.clone(), area, entries)?);
         Ok(())
     }
 
-    fn on_randr_notify(&mut self, notif: randr::NotifyEvent) -> Result<()> {
-        match notif.sub_code {
-            randr::Notify::CRTC_CHANGE => {
-                debug!("CRTC_CHANGE: {:?}", notif.u.as_cc());
-                self.setup_monitor()?;
-                self.screens[0].focus_any()?;
-                self.focus_changed()?;
-            }
+    /// Handles keys while the window list is shown.
+    fn process_window_list_key(&mut self, keycode: u8) -> Result<()> {
+        let list = self.window_list.as_ref().unwrap();
+        let selected = list.select(keycode);
+        if selected.is_none() && keycode != KEY_ESCAPE {
+            return Ok(());
+        }
 
-            randr::Notify::OUTPUT_CHANGE => {
-                debug!("OUTPUT_CHANGE: {:?}", notif.u.as_oc());
-                self.setup_monitor()?;
-                self.screens[0].focus_any()?;
-                self.focus_changed()?;
+        self.window_list = None;
+        if let Some(frame) = selected {
+            if let Some(win) = self.window_mut(frame) {
+                win.focus()?;
             }
-            _ => {}
+            self.focus_changed()?;
         }
         Ok(())
     }
+
+    fn enter_resize_mode(&mut self) -> Result<()> {
+        if self.resize_mode.is_some() {
+            return Ok(());
+        }
+
+        let wid = unwrap_or_return!(self.ctx.get_focused_window()?);
+        let win = unwrap_or_return!(self.window_mut(wid));
+        if !win.is_floating() {
+            debug!("enter_resize_mode: the focused window is not floating");
+            return Ok(());
+        }
+
+        // Grab the keys (plain and with Shift) while the mode is active,
+        // all of them or none.
+        if let Err(err) = self.grab_resize_mode_keys() {
+            self.ungrab_resize_mode_keys()?;
+            return Err(err);
+        }
+
+        debug!("enter resize mode");
+        self.resize_mode = Some(wid);
+        Ok(())
+    }
+
+    fn exit_resize_mode(&mut self) -> Result<()> {
+        if self.resize_mode.is_none() {
+            return Ok(());
+        }
+
+        self.ungrab_resize_mode_keys()?;
+        debug!("exit resize mode");
+        self.resize_mode = None;
+        Ok(())
+    }
+
+    /// Leaves the resize mode once its window has lost the focus.
+    fn check_resize_mode(&mut self) -> Result<()> {
+        let wid = unwrap_or_return!(self.resize_mode);
+        if self.focused_managed_window()? != Some(wid) {
+            self.exit_resize_mode()?;
+        }
+        Ok(())
+    }
+
+    /// Grabs the key on the root window, which fails if another client has grabbed it.
+    fn grab_key(&self, modif: u16, keycode: u8) -> Result<()> {
+        self.ctx
+            .conn
+            .grab_key(
+                true,
+                self.ctx.root,
+                modif,
+                keycode,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?
+            .check()
+            .map_err(|_| Error::KeyAlreadyGrabbed)?;
+        Ok(())
+    }
+
+    /// The resize mode uses the keys plain and with Shift.
+    fn resize_mode_modifiers() -> [u16; 2] {
+        [0, ModMask::SHIFT.into()]
+    }
+
+    fn grab_resize_mode_keys(&self) -> Result<()> {
+        for keycode in RESIZE_MODE_KEYS {
+            for modif in Self::resize_mode_modifiers() {
+                self.grab_key(modif, keycode)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Releases the keys of the resize mode, including those not grabbed,
+    /// except the keybindings on them.
+    fn ungrab_resize_mode_keys(&self) -> Result<()> {
+        let modifiers = Self::resize_mode_modifiers();
+        for keycode in RESIZE_MODE_KEYS {
+            for modif in modifiers {
+                self.ctx.conn.ungrab_key(keycode, self.ctx.root, modif)?;
+            }
+        }
+        for (&(_, modif, keycode), _) in self.ctx.config.keybind_iter() {
+            if RESIZE_MODE_KEYS.contains(&keycode) && modifiers.contains(&modif) {
+                self.grab_key(modif, keycode)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a key press in the resize mode.
+    /// Returns false if the key is not the one used in the mode.
+    fn process_resize_mode_key(&mut self, state: u16, keycode: u8) -> Result<bool> {
+        let shift: u16 = ModMask::SHIFT.into();
+        if state != 0 && state != shift {
+            return Ok(false);
+        }
+
+        let step = self.ctx.config.resize_mode;
+        let (dx, dy) = match keycode {
+            KEY_ESCAPE | KEY_ENTER => {
+                self.exit_resize_mode()?;
+                return Ok(true);
+            }
+            KEY_UP => (0, -1),
+            KEY_DOWN => (0, 1),
+            KEY_LEFT => (-1, 0),
+            KEY_RIGHT => (1, 0),
+            _ => return Ok(false),
+        };
+
+        if state == shift {
+            let dw = dx * step.resize_step as i16;
+            let dh = dy * step.resize_step as i16;
+            self.move_resize_focused(0, 0, dw, dh)?;
+        } else {
+            let dx = dx * step.move_step as i16;
+            let dy = dy * step.move_step as i16;
+            self.move_resize_focused(dx, dy, 0, 0)?;
+        }
+        Ok(true)
+    }
+
+    fn move_resize_focused(&mut self, dx: i16, dy: i16, dw: i16, dh: i16) -> Result<()> {
+        let wid = unwrap_or_return!(self.ctx.get_focused_window()?);
+        let win = unwrap_or_return!(self.window_mut(wid));
+        let mut geo = unwrap_or_return!(win.get_float_geometry());
+
+        // The keys may be held long enough to reach the limits.
+        geo.x = geo.x.saturating_add(dx);
+        geo.y = geo.y.saturating_add(dy);
+        geo.width = geo.width.saturating_add_signed(dw).max(1);
+        geo.height = geo.height.saturating_add_signed(dh).max(1);
+        win.set_float_geometry(geo);
+
+        self.refresh_layout()?;
+        Ok(())
+    }
+
+    /// Floats the focused window if needed, and moves it to the part of its monitor.
+    fn float_snap(&mut self, pos: SnapPosition) -> Result<()> {
+        let wid = unwrap_or_return!(self.ctx.get_focused_window()?);
+        let ctx = self.ctx.clone();
+        let screen = unwrap_or_return!(self.container_of_mut(wid));
+        if screen.is_background(wid) {
+            return Ok(());
+        }
+        let mon = unwrap_or_return!(screen.monitor());
+        let reserved = ctx.config.bar.reserved_height(mon.scale);
+        let mon = &mon.info;
+        let area = Rectangle {
+            x: 0,
+            y: reserved as i16,
+            width: mon.width,
+            height: mon.height.saturating_sub(reserved),
+        };
+
+        let win = screen.window_mut(wid).unwrap();
+        let size = match win.get_float_geometry() {
+            Some(geo) => (geo.width, geo.height),
+            None => {
+                let geo = win.frame_geometry();
+                (geo.width, geo.height)
+            }
+        };
+        let rect = snap_geometry(area, pos, size, win.border_width() as u16);
+        if !win.is_floating() {
+            win.float(rect)?;
+        }
+        win.set_float_geometry(rect);
+
+        self.refresh_layout()?;
+        Ok(())
+    }
+
+    /// Executes the command as if its keybinding was pressed. Only the fatal errors
+    /// (see `Error::is_fatal`) are returned, and the others are shown as a failure of the command.
+    pub fn process_command(&mut self, cmd: Command) -> Result<()> {
+        let started = Instant::now();
+        let res = self.run_command(cmd.clone());
+        self.metrics.record_command(started.elapsed());
+        match res {
+            Err(err) if !err.is_fatal() => self.command_failed(&cmd, &err),
+            res => res,
+        }
+    }
+
+    /// Tells the failure of the command in the OSD and the bars, and keeps running.
+    fn command_failed(&mut self, cmd: &Command, err: &Error) -> Result<()> {
+        error!("command {:?} failed: {}", cmd, err);
+        let text = format!("command failed: {}", err);
+        match self.show_osd(&text).and_then(|()| self.flash_error()) {
+            Err(err) if !err.is_fatal() => {
+                warn!("cannot tell the failure of {:?}: {}", cmd, err);
+                Ok(())
+            }
+            res => res,
+        }
+    }
+
+    fn run_command(&mut self, cmd: Command) -> Result<()> {
+        match cmd {
+            Command::Quit => return Err(Error::Quit),
+            Command::Restart => {
+                self.save_state()?;
+                return Err(Error::Restart);
+            }
+
+            Command::ShowBorder => {
+                for screen in self.screens.iter_mut() {
+                    screen.show_border();
+                }
+                self.refresh_layout()?;
+            }
+            Command::HideBorder => {
+                for screen in self.screens.iter_mut() {
+                    screen.hide_border();
+                }
+                self.refresh_layout()?;
+            }
+
+            Command::ShowWindowList => self.show_window_list()?,
+            Command::WindowMenu => self.show_window_menu()?,
+            Command::ShowKeybindings => self.show_keybindings()?,
+            Command::DebugWindowInfo => self.debug_window_info()?,
+            Command::ToggleMetricsHud => {
+                self.metrics_hud = !self.metrics_hud;
+                if self.metrics_hud {
+                    self.report_metrics()?;
+                } else {
+                    self.show_osd("Metrics off")?;
+                }
+            }
+
+            Command::VolumeUp => {
+                self.run_media_command("vol", self.ctx.config.media.volume_up.clone())
+            }
+            Command::VolumeDown => {
+                self.run_media_command("vol", self.ctx.config.media.volume_down.clone())
+            }
+            Command::Mute => self.run_media_command("vol", self.ctx.config.media.mute.clone()),
+            Command::BrightnessUp => {
+                self.run_media_command("bri", self.ctx.config.media.brightness_up.clone())
+            }
+            Command::BrightnessDown => {
+                self.run_media_command("bri", self.ctx.config.media.brightness_down.clone())
+            }
+
+            Command::SetTheme(name) => self.set_theme(&name)?,
+            Command::Spawn(cmd) => self.spawn(&cmd),
+
+            Command::Extension(name) => {
+                let ctx = self.ctx.clone();
+                match ctx.extensions.command_handler(&name) {
+                    Some(handler) => handler(self)?,
+                    None => warn!("Command::Extension: unknown command {:?}", name),
+                }
+            }
+
+            Command::Focus(cmd) => self.run_focus_command(cmd)?,
+            Command::Window(cmd) => self.run_window_command(cmd)?,
+            Command::Screen(cmd) => self.run_screen_command(cmd)?,
+            Command::Pointer(cmd) => self.run_pointer_command(cmd)?,
+            Command::Layout(cmd) => self.run_layout_command(cmd)?,
+        }
+        Ok(())
+    }
+
+    fn run_focus_command(&mut self, cmd: FocusCommand) -> Result<()> {
+        match cmd {
+            FocusCommand::Next => {
+                self.focused_screen_mut()?.focus_next()?;
+                self.focus_changed()?;
+            }
+            FocusCommand::Prev => {
+                warn!("FocusCommand::Prev: not yet implemented");
+            }
+            FocusCommand::NextInGroup => {
+                self.focused_screen_mut()?.focus_in_group(1)?;
+                self.focus_changed()?;
+            }
+            FocusCommand::PrevInGroup => {
+                self.focused_screen_mut()?.focus_in_group(-1)?;
+                self.focus_changed()?;
+            }
+            FocusCommand::Master => {
+                self.focused_screen_mut()?.focus_group(layout::MAIN_GROUP)?;
+                self.focus_changed()?;
+            }
+            FocusCommand::Stack => {
+                self.focused_screen_mut()?
+                    .focus_group(layout::STACK_GROUP)?;
+                self.focus_changed()?;
+            }
+
+            FocusCommand::NextMonitor => {
+                let focused_monitor = self
+                    .focused_screen_mut()?
+                    .monitor()
+                    .expect("focus inconsistent")
+                    .id;
+                let next_monitor = (focused_monitor + 1) % self.monitor_num;
+                self.focus_monitor(next_monitor)?;
+            }
+            FocusCommand::PrevMonitor => {
+                let focused_monitor = self
+                    .focused_screen_mut()?
+                    .monitor()
+                    .expect("focus inconsistent")
+                    .id;
+                let prev_monitor = (focused_monitor + self.monitor_num - 1) % self.monitor_num;
+                self.focus_monitor(prev_monitor)?;
+            }
+
+            FocusCommand::Monitor(dir) => self.focus_monitor_in_direction(dir)?,
+
+            FocusCommand::Urgent => self.focus_urgent()?,
+        }
+        Ok(())
+    }
+
+    fn run_window_command(&mut self, cmd: WindowCommand) -> Result<()> {
+        match cmd {
+            WindowCommand::Close => {
+                if let Some(wid) = self.ctx.get_focused_window()? {
+                    if self.close_confirmed(wid)? {
+                        self.close_window(wid)?;
+                    }
+                }
+            }
+
+            WindowCommand::Sink => {
+                if let Some(wid) = self.ctx.get_focused_window()? {
+                    if let Some(screen) = self.container_of_mut(wid) {
+                        if !screen.is_background(wid) {
+                            let win = screen.window_mut(wid).unwrap();
+                            win.sink()?;
+                            self.refresh_layout()?;
+                        }
+                    }
+                }
+            }
+
+            WindowCommand::TogglePin => {
+                if let Some(wid) = self.ctx.get_focused_window()? {
+                    if let Some(screen) = self.container_of_mut(wid) {
+                        if !screen.is_background(wid) {
+                            let win = screen.window_mut(wid).unwrap();
+                            if !win.is_floating() {
+                                let pinned = win.is_pinned();
+                                win.set_pinned(!pinned)?;
+                                self.refresh_layout()?;
+                            }
+                        }
+                    }
+                }
+            }
+
+            WindowCommand::ToggleFollow => {
+                if let Some(wid) = self.ctx.get_focused_window()? {
+                    if let Some(screen) = self.container_of_mut(wid) {
+                        if !screen.is_background(wid) {
+                            let win = screen.window_mut(wid).unwrap();
+                            let following = win.is_following();
+                            win.set_following(!following);
+                            self.show_osd(if following { "Unfollow" } else { "Follow" })?;
+                        }
+                    }
+                }
+            }
+
+            WindowCommand::Rescue => {
+                for screen in self.screens.iter_mut() {
+                    screen.center_floating_windows()?;
+                }
+            }
+
+            WindowCommand::FloatSnap(pos) => self.float_snap(pos)?,
+            WindowCommand::ResizeMode => self.enter_resize_mode()?,
+
+            WindowCommand::MoveLeft => self.focused_screen_mut()?.move_focused(Direction::Left)?,
+            WindowCommand::MoveRight => {
+                self.focused_screen_mut()?.move_focused(Direction::Right)?
+            }
+            WindowCommand::MoveUp => self.focused_screen_mut()?.move_focused(Direction::Up)?,
+            WindowCommand::MoveDown => self.focused_screen_mut()?.move_focused(Direction::Down)?,
+        }
+        Ok(())
+    }
+
+    fn run_screen_command(&mut self, cmd: ScreenCommand) -> Result<()> {
+        match cmd {
+            ScreenCommand::Switch(id) => {
+                let current_id = self.focused_screen_mut()?.id;
+                if id == current_id && self.ctx.config.screen_back_and_forth {
+                    self.screen_back()?;
+                } else {
+                    self.switch_screen(id)?;
+                }
+            }
+            ScreenCommand::Back => self.screen_back()?,
+            ScreenCommand::Prev => self.switch_screen_rel(-1)?,
+            ScreenCommand::Next => self.switch_screen_rel(1)?,
+            ScreenCommand::MoveWindow(id) => self.move_window_to_screen(id)?,
+            ScreenCommand::SwapWith(id) => self.swap_with_screen(id)?,
+        }
+        Ok(())
+    }
+
+    fn run_pointer_command(&mut self, cmd: PointerCommand) -> Result<()> {
+        match cmd {
+            PointerCommand::MoveRel(dx, dy) => move_pointer(&self.ctx.conn, dx, dy)?,
+            _ if !self.xtest_enabled => warn!("{:?}: XTEST is not available", cmd),
+            PointerCommand::ClickLeft => self.click(BUTTON_LEFT)?,
+            PointerCommand::ClickRight => self.click(BUTTON_RIGHT)?,
+            PointerCommand::ClickMiddle => self.click(BUTTON_MIDDLE)?,
+            PointerCommand::DoubleClick => {
+                let duration = self.ctx.config.mouse.click_duration_ms;
+                simulate_double_click(&self.ctx.conn, BUTTON_LEFT, duration)?;
+            }
+            PointerCommand::DragStart => {
+                simulate_button(&self.ctx.conn, true, BUTTON_LEFT, x11rb::CURRENT_TIME)?
+            }
+            PointerCommand::DragEnd => {
+                simulate_button(&self.ctx.conn, false, BUTTON_LEFT, x11rb::CURRENT_TIME)?
+            }
+        }
+        Ok(())
+    }
+
+    fn run_layout_command(&mut self, cmd: LayoutCommand) -> Result<()> {
+        match cmd {
+            LayoutCommand::Next => {
+                self.focused_screen_mut()?.next_layout()?;
+                self.layout_changed()?;
+            }
+            LayoutCommand::Prev => {
+                self.focused_screen_mut()?.prev_layout()?;
+                self.layout_changed()?;
+            }
+            LayoutCommand::Set(name) => self.set_layout(&name)?,
+            LayoutCommand::Command(cmd) => {
+                self.focused_screen_mut()?.layout_command(cmd)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.screens.iter().any(|screen| screen.is_animating())
+    }
+
+    /// The time of the next step while windows are moving.
+    /// It is kept until the step is taken, so that events coming in between do not delay it.
+    pub fn animation_deadline(&mut self) -> Option<Instant> {
+        if !self.is_animating() {
+            self.next_animation = None;
+            return None;
+        }
+        let interval = self.ctx.config.animation.interval();
+        let deadline = self
+            .next_animation
+            .get_or_insert_with(|| Instant::now() + interval);
+        Some(*deadline)
+    }
+
+    /// Advances running transitions of all screens by one step.
+    pub fn animate(&mut self) -> Result<()> {
+        self.next_animation = None;
+        for screen in self.screens.iter_mut() {
+            screen.animate()?;
+        }
+        Ok(())
+    }
+
+    /// The time of the next move of the pointer, if a `PointerCommand::MoveRel` key is held.
+    pub fn pointer_repeat_deadline(&self) -> Option<Instant> {
+        let repeat = self.pointer_repeat.filter(|r| r.released.is_none())?;
+        Some(repeat.next)
+    }
+
+    /// Moves the pointer again, faster and faster up to `max_speed`.
+    pub fn repeat_pointer(&mut self) -> Result<()> {
+        let config = self.ctx.config.pointer_repeat;
+        let repeat = unwrap_or_return!(self.pointer_repeat.as_mut());
+        if repeat.released.is_some() {
+            self.pointer_repeat = None;
+            return Ok(());
+        }
+        repeat.steps = repeat.steps.saturating_add(1);
+        repeat.next = Instant::now() + config.interval;
+        let speed = (1 + repeat.steps / 8).min(config.max_speed);
+        move_pointer(&self.ctx.conn, repeat.dx * speed, repeat.dy * speed)
+    }
+
+    pub fn alarm(&mut self) -> Result<()> {
+        for screen in self.screens.iter_mut() {
+            screen.alarm()?;
+        }
+
+        // Without RandR, no event tells that the root window has been resized.
+        if !self.randr_enabled {
+            let geo = self.ctx.conn.get_geometry(self.ctx.root)?.reply()?;
+            if self.root_size != (geo.width, geo.height) {
+                info!("the root window is resized to {}x{}", geo.width, geo.height);
+                self.monitor_changed()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts an event taken from the queue, where `queued` ones are left.
+    pub fn count_event(&mut self, queued: usize) {
+        self.metrics.record_event(queued);
+    }
+
+    /// Whether the metrics are to be reported every second, otherwise only on demand.
+    pub fn reports_metrics(&self) -> bool {
+        self.metrics_hud || self.ctx.config.metrics
+    }
+
+    /// Publishes the metrics of the last period in _DAILY_METRICS, and shows them if asked.
+    pub fn report_metrics(&mut self) -> Result<()> {
+        let report = self.metrics.report(Instant::now());
+        trace!("metrics: {}", report);
+        self.ctx.conn.change_property8(
+            PropMode::REPLACE,
+            self.ctx.root,
+            self.ctx.atom._DAILY_METRICS,
+            AtomEnum::STRING,
+            report.as_bytes(),
+        )?;
+        if self.metrics_hud {
+            self.show_osd(&report)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for WinMan {
+    fn drop(&mut self) {
+        // Releases WM_Sn for the next window manager, e.g. after a restart.
+        if self.selection_owner != x11rb::NONE {
+            let _ = self.ctx.conn.destroy_window(self.selection_owner);
+        }
+    }
+}
+
+impl EventHandlerMethods for WinMan {
+    fn on_key_press(&mut self, e: KeyPressEvent) -> Result<()> {
+        if self.window_list.is_some() {
+            return self.process_window_list_key(e.detail);
+        }
+        if self.resize_mode.is_some() && self.process_resize_mode_key(e.state, e.detail)? {
+            return Ok(());
+        }
+
+        // Ignore the key repeat of X while the pointer is moved by `repeat_pointer`.
+        if let Some(repeat) = self.pointer_repeat.as_mut() {
+            if repeat.keycode == e.detail && repeat.released.is_none_or(|t| t == e.time) {
+                repeat.released = None;
+                return Ok(());
+            }
+        }
+
+        let cmd = unwrap_or_return!(self.ctx.config.keybind_match(
+            KeybindAction::Press,
+            e.state,
+            e.detail
+        ));
+        debug!("on_key_press: cmd = {:?}", cmd);
+        if let Command::Pointer(PointerCommand::MoveRel(dx, dy)) = cmd {
+            if self.ctx.config.pointer_repeat.enabled {
+                self.pointer_repeat = Some(PointerRepeat {
+                    keycode: e.detail,
+                    dx,
+                    dy,
+                    steps: 0,
+                    next: Instant::now() + self.ctx.config.pointer_repeat.delay,
+                    released: None,
+                });
+            }
+        }
+        self.process_command(cmd)?;
+        Ok(())
+    }
+
+    fn on_key_release(&mut self, e: KeyReleaseEvent) -> Result<()> {
+        // The grab of the key lasts until the release, which stops the repeat
+        // without a Release binding.
+        if let Some(repeat) = self.pointer_repeat.as_mut() {
+            if repeat.keycode == e.detail {
+                repeat.released = Some(e.time);
+            }
+        }
+
+        let cmd = unwrap_or_return!(self.ctx.config.keybind_match(
+            KeybindAction::Release,
+            e.state,
+            e.detail
+        ));
+        debug!("on_key_release: cmd = {:?}", cmd);
+        self.process_command(cmd)?;
+        Ok(())
+    }
+
+    fn on_enter_notify(&mut self, e: EnterNotifyEvent) -> Result<()> {
+        // The pointer has reached the top edge of a monitor hiding the bar.
+        let mon_id = unwrap_or_return!(self
+            .screens
+            .iter()
+            .filter_map(|screen| screen.monitor())
+            .find(|mon| mon.edge == e.event && !mon.bar_revealed)
+            .map(|mon| mon.id));
+        // The bar is checked once the pointer has rested there, however often it enters.
+        self.bar_checks
+            .entry(mon_id)
+            .or_insert_with(|| Instant::now() + BAR_REVEAL_DELAY);
+        Ok(())
+    }
+
+    fn on_barrier_hit(&mut self, e: BarrierHitEvent) -> Result<()> {
+        self.edge_resistance.on_hit(e)
+    }
+
+    fn on_button_press(&mut self, e: ButtonPressEvent) -> Result<()> {
+        // A click in an unfocused window, grabbed on its frame, is replayed to the client
+        // after focusing it. The others reach the root: Alt + button, or the clicks
+        // on the bars and the tab strips, which do not select ButtonPress themselves.
+        if e.event != self.ctx.root {
+            if let Some(screen) = self.container_of_mut(e.event) {
+                screen.window_mut(e.event).unwrap().focus()?;
+                screen.raise(e.event)?;
+                self.focus_changed()?;
+            }
+            self.ctx
+                .conn
+                .allow_events(Allow::REPLAY_POINTER, x11rb::CURRENT_TIME)?;
+            return Ok(());
+        }
+
+        if let Some(cmd) = self.ctx.config.bar.clock_command.clone() {
+            let clock = self
+                .screens
+                .iter_mut()
+                .any(|screen| screen.clock_at(e.child, e.root_x));
+            if clock {
+                self.spawn(&cmd);
+                return Ok(());
+            }
+        }
+
+        // Focus the window just clicked, or the one of the tab or the taskbar clicked.
+        let tab = self
+            .container_of_mut(e.child)
+            .and_then(|screen| screen.tab_at(e.child, e.root_x));
+        let task = self
+            .screens
+            .iter_mut()
+            .find_map(|screen| screen.task_at(e.child, e.root_x));
+        if let Some(win) = self.window_mut(tab.or(task).unwrap_or(e.child)) {
+            win.focus()?;
+            self.focus_changed()?;
+        }
+        if let Some(screen) = self.container_of_mut(e.child) {
+            screen.raise(e.child)?;
+        }
+
+        if e.state & u16::from(ModMask::M1) > 0 {
+            // button + Alt
+            let mode = if e.detail == u8::from(ButtonIndex::M3) {
+                DragMode::Resize { dx: 1, dy: 1 }
+            } else {
+                DragMode::Move
+            };
+            self.start_drag(e.child, mode, e.root_x, e.root_y)?;
+        }
+        Ok(())
+    }
+
+    fn on_motion_notify(&mut self, e: MotionNotifyEvent) -> Result<()> {
+        let button_mask: u16 = (ButtonMask::M1 | ButtonMask::M2 | ButtonMask::M3).into();
+
+        let drag = unwrap_or_return!(self.drag.clone());
+        if !drag.pointer_grabbed && e.state & u16::from(ModMask::M1) == 0 {
+            return Ok(());
+        }
+        if e.state & button_mask == 0 {
+            return Ok(());
+        }
+
+        let dx = e.root_x as i32 - drag.start_x;
+        let dy = e.root_y as i32 - drag.start_y;
+        let rect = drag.mode.apply(drag.window, dx, dy);
+
+        let win = unwrap_or_return!(self.window_mut(drag.wid));
+        match drag.mode {
+            DragMode::Move => {
+                let aux = ConfigureWindowAux::new().x(rect.x).y(rect.y);
+                win.configure(&aux)?;
+            }
+            DragMode::Resize {
+                dx: edge_x,
+                dy: edge_y,
+            } => {
+                let mut aux = ConfigureWindowAux::new()
+                    .width(rect.width)
+                    .height(rect.height);
+                if edge_x < 0 {
+                    aux = aux.x(rect.x);
+                }
+                if edge_y < 0 {
+                    aux = aux.y(rect.y);
+                }
+                win.configure(&aux)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_button_release(&mut self, _: ButtonReleaseEvent) -> Result<()> {
+        self.finish_drag()
+    }
+
+    fn on_map_request(&mut self, req: MapRequestEvent) -> Result<()> {
+        if req.parent == self.ctx.root {
+            let wid = req.window;
+            if self.window_mut(wid).is_some() {
+                return Ok(());
+            }
+
+            // The window may be destroyed right after being mapped.
+            let attr = match self.ctx.conn.get_window_attributes(wid)?.reply() {
+                Ok(attr) => attr,
+                Err(err) => {
+                    warn!("failed to get the attributes of {:08X}: {}", wid, err);
+                    return Ok(());
+                }
+            };
+            if attr.class == WindowClass::INPUT_ONLY {
+                return Ok(());
+            }
+
+            let focused_id = self.focused_screen_mut()?.id;
+            let focused = self.ctx.get_focused_window()?;
+
+            let metrics = FrameMetrics::unscaled(&self.ctx.config.border);
+            let mut win = Window::new(self.ctx.clone(), wid, WindowState::Created, metrics)?;
+            win.map()?;
+
+            let rule = self.ctx.config.rule_for(win.class()).cloned();
+            if let Some(rule) = &rule {
+                info!("rule matched: {:08X} ({}), {:?}", wid, win.class(), rule);
+                if let Some(opacity) = rule.opacity {
+                    win.set_opacity(opacity)?;
+                }
+            }
+            // Rules take precedence over _NET_WM_DESKTOP set by the client.
+            let requested = win
+                .requested_screen()?
+                .filter(|&id| id < self.screens.len());
+            let (screen_id, overflowed) = match rule.as_ref().and_then(|rule| rule.screen) {
+                Some(id) => (id, false),
+                None => {
+                    let id = requested.unwrap_or(focused_id);
+                    let dest = self.screen_with_space(id);
+                    (dest, dest != id)
+                }
+            };
+            let envs = hook::window_envs(&win, screen_id);
+
+            let frame = win.frame();
+            self.screens[screen_id].add_window(win)?;
+            if screen_id != focused_id {
+                // The new window has taken the focus, so give it back to the current screen.
+                match focused {
+                    Some(focused) => self.ctx.focus_window(focused)?,
+                    None => self.screens[focused_id].focus_any()?,
+                }
+                let follow = if overflowed {
+                    self.ctx.config.overflow_follow
+                } else {
+                    rule.is_some_and(|rule| rule.follow)
+                };
+                if follow {
+                    self.focus_window_anywhere(frame)?;
+                } else {
+                    self.focus_changed()?;
+                }
+            }
+            self.run_hook(Hook::WindowMapped, &envs)?;
+        } else {
+            let win = unwrap_or_return!(self.window_mut(req.parent));
+            win.on_map_request(req)?;
+        }
+        Ok(())
+    }
+
+    fn on_map_notify(&mut self, notif: MapNotifyEvent) -> Result<()> {
+        // The frames are override-redirect as well, mapped on every screen switch.
+        if notif.override_redirect
+            && self.container_of_mut(notif.window).is_none()
+            && !self.is_own_window(notif.window)
+        {
+            return self.on_popup_mapped(notif.window);
+        }
+
+        let win = unwrap_or_return!(self.window_mut(notif.event));
+        win.on_map_notify(notif)?;
+        self.focus_changed()?;
+        Ok(())
+    }
+
+    fn on_unmap_notify(&mut self, notif: UnmapNotifyEvent) -> Result<()> {
+        if let Some(i) = self.popups.iter().position(|&(wid, _)| wid == notif.window) {
+            let (_, prev) = self.popups.remove(i);
+            return self.on_popup_unmapped(prev);
+        }
+
+        // The client withdraws the window by unmapping it, as the WM only unmaps the frames,
+        // or with a synthetic UnmapNotify to the root if it is unmapped already.
+        let wid = if notif.event != self.ctx.root {
+            notif.event
+        } else if notif.response_type & 0x80 != 0 {
+            notif.window
+        } else {
+            return Ok(());
+        };
+        let screen = unwrap_or_return!(self.container_of_mut(wid));
+        if screen.is_background(wid) {
+            return Ok(());
+        }
+        let id = screen.id;
+        let win = unwrap_or_return!(screen.forget_window(wid)?);
+        let envs = hook::window_envs(&win, id);
+        win.withdraw()?;
+        self.focus_changed()?;
+        self.run_hook(Hook::WindowClosed, &envs)?;
+        Ok(())
+    }
+
+    fn on_destroy_notify(&mut self, notif: DestroyNotifyEvent) -> Result<()> {
+        self.popups.retain(|&(wid, _)| wid != notif.window);
+
+        let screen = unwrap_or_return!(self.container_of_mut(notif.window));
+        if screen.is_background(notif.window) {
+            return Ok(());
+        }
+        let id = screen.id;
+        let win = unwrap_or_return!(screen.forget_window(notif.window)?);
+        self.focus_changed()?;
+        self.run_hook(Hook::WindowClosed, &hook::window_envs(&win, id))?;
+        Ok(())
+    }
+
+    fn on_configure_request(&mut self, req: ConfigureRequestEvent) -> Result<()> {
+        if req.parent == self.ctx.root {
+            let aux = ConfigureWindowAux::from_configure_request(&req);
+            self.ctx.conn.configure_window(req.window, &aux)?;
+        } else {
+            let win = unwrap_or_return!(self.window_mut(req.parent));
+            win.on_configure_request(req)?;
+        }
+        Ok(())
+    }
+
+    fn on_configure_notify(&mut self, notif: ConfigureNotifyEvent) -> Result<()> {
+        if notif.override_redirect {
+            return Ok(());
+        }
+
+        let win = unwrap_or_return!(self.window_mut(notif.event));
+        win.on_configure_notify(notif)?;
+        Ok(())
+    }
+
+    fn on_expose(&mut self, ev: ExposeEvent) -> Result<()> {
+        if let Some(list) = self
+            .window_list
+            .as_ref()
+            .filter(|list| list.wid() == ev.window)
+        {
+            return list.draw();
+        }
+
+        let screen = unwrap_or_return!(self.container_of_mut(ev.window));
+        screen.on_expose(ev)?;
+        Ok(())
+    }
+
+    fn on_focus_in(&mut self, focus_in: FocusInEvent) -> Result<()> {
+        if focus_in.event == self.ctx.root
+            && (focus_in.detail == NotifyDetail::POINTER_ROOT
+                || focus_in.detail == NotifyDetail::NONE)
+        {
+            // The focus may have been given back already, e.g. after a popup.
+            if self.focused_managed_window()?.is_none() {
+                // Focus the last focused monitor
+                let last_focused = self.last_focused_screen;
+                if let Some(screen) = self.find_screen_mut(|sc| sc.id == last_focused) {
+                    screen.focus_last()?;
+                } else {
+                    self.screen_mut_by_mon(0).focus_last()?;
+                }
+            }
+        }
+        // Clients may take the focus by themselves.
+        self.update_click_through()?;
+        self.check_resize_mode()
+    }
+
+    fn on_client_message(&mut self, ev: ClientMessageEvent) -> Result<()> {
+        let name = self.ctx.conn.get_atom_name(ev.type_)?.reply()?.name;
+        debug!("ClientMessageEvent.type_: {:?}", String::from_utf8(name));
+
+        if ev.window == self.ctx.root {
+            // Pagers ask to switch screens.
+            if ev.type_ == self.ctx.atom._NET_CURRENT_DESKTOP && ev.format == 32 {
+                let id = ev.data.as_data32()[0] as usize;
+                self.switch_screen(id)?;
+            }
+            return Ok(());
+        }
+
+        if ev.format == 32 {
+            let atom = &self.ctx.atom;
+            if ev.type_ == atom._NET_ACTIVE_WINDOW {
+                let source = ev.data.as_data32()[0];
+                return self.on_activation_request(ev.window, source);
+            } else if ev.type_ == atom._NET_CLOSE_WINDOW {
+                return self.close_window(ev.window);
+            } else if ev.type_ == atom._NET_WM_MOVERESIZE {
+                return self.on_moveresize_request(ev.window, ev.data.as_data32());
+            }
+        }
+
+        let net_wm_state = ev.type_ == self.ctx.atom._NET_WM_STATE;
+        let win = unwrap_or_return!(self.window_mut(ev.window));
+        win.on_client_message(ev)?;
+
+        // Apply changes of the stacking.
+        if net_wm_state {
+            self.refresh_layout()?;
+        }
+        Ok(())
+    }
+
+    fn on_property_notify(&mut self, notif: PropertyNotifyEvent) -> Result<()> {
+        let win = unwrap_or_return!(self.window_mut(notif.window));
+        let was_urgent = win.is_urgent();
+        win.on_property_notify(notif)?;
+
+        if !was_urgent && win.is_urgent() {
+            let frame = win.frame();
+            self.urgent_history.retain(|&wid| wid != frame);
+            self.urgent_history.push(frame);
+        }
+        Ok(())
+    }
+
+    fn on_xkb_state_notify(&mut self, notif: xkb::StateNotifyEvent) -> Result<()> {
+        if notif.changed & u16::from(xkb::StatePart::GROUP_STATE) != 0 {
+            self.update_keyboard_layout(u8::from(notif.group) as usize)?;
+        }
+        Ok(())
+    }
+
+    fn on_randr_notify(&mut self, notif: randr::NotifyEvent) -> Result<()> {
+        match notif.sub_code {
+            randr::Notify::CRTC_CHANGE => {
+                debug!("CRTC_CHANGE: {:?}", notif.u.as_cc());
+                self.monitor_changed()?;
+            }
+
+            randr::Notify::OUTPUT_CHANGE => {
+                debug!("OUTPUT_CHANGE: {:?}", notif.u.as_oc());
+                self.monitor_changed()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventHandler as _;
+    use crate::mock;
+    use x11rb::protocol::Event;
+
+    fn winman() -> WinMan {
+        let ctx = mock::context(&[(0, 0, 1920, 1080)]);
+        WinMan::new(ctx).expect("failed to initialize WinMan")
+    }
+
+    fn map_new_client(wm: &mut WinMan) -> Wid {
+        let wid = wm.ctx.conn.create_client(640, 480);
+        map_client(wm, wid);
+        wid
+    }
+
+    fn map_client(wm: &mut WinMan, wid: Wid) {
+        let req = MapRequestEvent {
+            response_type: MAP_REQUEST_EVENT,
+            sequence: 0,
+            parent: wm.ctx.root,
+            window: wid,
+        };
+        wm.handle_event(Event::MapRequest(req)).unwrap();
+    }
+
+    fn destroy_client(wm: &mut WinMan, wid: Wid) {
+        wm.ctx.conn.destroy_client(wid);
+        let notif = DestroyNotifyEvent {
+            response_type: DESTROY_NOTIFY_EVENT,
+            sequence: 0,
+            event: wm.ctx.root,
+            window: wid,
+        };
+        wm.handle_event(Event::DestroyNotify(notif)).unwrap();
+    }
+
+    fn set_urgency(wm: &mut WinMan, wid: Wid, urgent: bool) {
+        // WM_HINTS: flags followed by 8 fields, UrgencyHint is bit 8.
+        let flags: u32 = if urgent { 1 << 8 } else { 0 };
+        let mut data = flags.to_ne_bytes().to_vec();
+        data.resize(9 * 4, 0);
+        let wm_hints = AtomEnum::WM_HINTS.into();
+        wm.ctx.conn.set_property(wid, wm_hints, wm_hints, 32, data);
+
+        let notif = PropertyNotifyEvent {
+            response_type: PROPERTY_NOTIFY_EVENT,
+            sequence: 0,
+            window: wid,
+            atom: wm_hints,
+            time: 0,
+            state: Property::NEW_VALUE,
+        };
+        wm.handle_event(Event::PropertyNotify(notif)).unwrap();
+    }
+
+    #[test]
+    fn map_request_adds_and_focuses_window() {
+        let mut wm = winman();
+        let wid = map_new_client(&mut wm);
+
+        assert!(wm.screens[0].contains(wid));
+        assert!(wm.ctx.conn.is_viewable(wid));
+        assert_eq!(wm.ctx.conn.focus(), wid);
+
+        // The window is tiled below the bar.
+        let frame = wm.ctx.conn.window(wid).unwrap().parent;
+        let geo = wm.ctx.conn.window(frame).unwrap();
+        assert_eq!((geo.x, geo.y), (0, 16));
+    }
+
+    #[test]
+    fn switch_screen_hides_and_shows_windows() {
+        let mut wm = winman();
+        let wid = map_new_client(&mut wm);
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(1)))
+            .unwrap();
+        assert!(wm.screens[0].monitor().is_none());
+        assert!(wm.screens[1].monitor().is_some());
+        assert!(!wm.ctx.conn.is_viewable(wid));
+        assert_eq!(wm.ctx.conn.focus(), wm.screens[1].focus_holder());
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(0)))
+            .unwrap();
+        assert!(wm.screens[0].monitor().is_some());
+        assert!(wm.ctx.conn.is_viewable(wid));
+    }
+
+    #[test]
+    fn switch_screen_refocuses_the_last_focused_window() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        assert_eq!(wm.ctx.conn.focus(), b);
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(1)))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(0)))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), b);
+
+        // Falls back to another window once the last one has gone.
+        destroy_client(&mut wm, b);
+        wm.process_command(Command::Screen(ScreenCommand::Switch(1)))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(0)))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), a);
+    }
+
+    #[test]
+    fn pinned_window_keeps_its_geometry() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        wm.process_command(Command::Window(WindowCommand::TogglePin))
+            .unwrap();
+        let pinned = wm.screens[0].window(a).unwrap().frame_geometry();
+
+        // The layout gives the whole area to the other windows.
+        let b = map_new_client(&mut wm);
+        assert_eq!(wm.screens[0].window(a).unwrap().frame_geometry(), pinned);
+        assert_eq!(
+            wm.screens[0].window(b).unwrap().frame_geometry().width,
+            pinned.width
+        );
+
+        wm.ctx.focus_window(a).unwrap();
+        wm.process_command(Command::Window(WindowCommand::TogglePin))
+            .unwrap();
+        assert!(!wm.screens[0].window(a).unwrap().is_pinned());
+        assert!(wm.screens[0].window(b).unwrap().frame_geometry().width < pinned.width);
+    }
+
+    #[test]
+    fn following_window_moves_with_screen_switch() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        wm.process_command(Command::Window(WindowCommand::ToggleFollow))
+            .unwrap();
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(1)))
+            .unwrap();
+        assert!(wm.screens[0].contains(a) && !wm.screens[0].contains(b));
+        assert!(wm.screens[1].contains(b));
+        assert!(wm.ctx.conn.is_viewable(b));
+        assert!(!wm.ctx.conn.is_viewable(a));
+
+        wm.ctx.focus_window(b).unwrap();
+        wm.process_command(Command::Window(WindowCommand::ToggleFollow))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(0)))
+            .unwrap();
+        assert!(wm.screens[1].contains(b));
+        assert!(!wm.ctx.conn.is_viewable(b));
+    }
+
+    #[test]
+    fn focus_cycles_within_the_group() {
+        let mut wm = winman();
+        let wins = [
+            map_new_client(&mut wm),
+            map_new_client(&mut wm),
+            map_new_client(&mut wm),
+        ];
+
+        wm.process_command(Command::Focus(FocusCommand::Master))
+            .unwrap();
+        let master = wm.ctx.conn.focus();
+        wm.process_command(Command::Focus(FocusCommand::NextInGroup))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), master);
+
+        wm.process_command(Command::Focus(FocusCommand::Stack))
+            .unwrap();
+        let first = wm.ctx.conn.focus();
+        assert_ne!(first, master);
+        wm.process_command(Command::Focus(FocusCommand::NextInGroup))
+            .unwrap();
+        let second = wm.ctx.conn.focus();
+        assert!(second != first && second != master);
+        assert!(wins.contains(&second));
+        wm.process_command(Command::Focus(FocusCommand::PrevInGroup))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), first);
+        wm.process_command(Command::Focus(FocusCommand::PrevInGroup))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), second);
+    }
+
+    #[test]
+    fn screen_back_toggles_recent_screens() {
+        let mut wm = winman();
+        let focused = |wm: &mut WinMan| wm.focused_screen_mut().unwrap().id;
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(3)))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Back))
+            .unwrap();
+        assert_eq!(focused(&mut wm), 2);
+        wm.process_command(Command::Screen(ScreenCommand::Back))
+            .unwrap();
+        assert_eq!(focused(&mut wm), 3);
+
+        wm.process_command(Command::Screen(ScreenCommand::Next))
+            .unwrap();
+        assert_eq!(focused(&mut wm), 4);
+        wm.process_command(Command::Screen(ScreenCommand::Next))
+            .unwrap();
+        assert_eq!(focused(&mut wm), 0);
+        wm.process_command(Command::Screen(ScreenCommand::Prev))
+            .unwrap();
+        assert_eq!(focused(&mut wm), 4);
+    }
+
+    #[test]
+    fn screen_of_the_focused_one_goes_back_if_configured() {
+        let focused = |wm: &mut WinMan| wm.focused_screen_mut().unwrap().id;
+
+        let mut wm = winman();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+            .unwrap();
+        assert_eq!(focused(&mut wm), 2);
+
+        let config = crate::config::Config {
+            screen_back_and_forth: true,
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+            .unwrap();
+        assert_eq!(focused(&mut wm), 0);
+        wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+            .unwrap();
+        assert_eq!(focused(&mut wm), 2);
+    }
+
+    #[test]
+    fn switch_screen_ignores_invalid_id() {
+        let mut wm = winman();
+        let n = wm.screens.len();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(n)))
+            .unwrap();
+        assert!(wm.screens[0].monitor().is_some());
+    }
+
+    #[test]
+    fn move_window_to_screen() {
+        let mut wm = winman();
+        let wid = map_new_client(&mut wm);
+
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(2)))
+            .unwrap();
+        assert!(!wm.screens[0].contains(wid));
+        assert!(wm.screens[2].contains(wid));
+        assert!(!wm.ctx.conn.is_viewable(wid));
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+            .unwrap();
+        assert!(wm.ctx.conn.is_viewable(wid));
+    }
+
+    #[test]
+    fn swap_with_screen_exchanges_windows() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(2)))
+            .unwrap();
+        let b = map_new_client(&mut wm);
+        let c = map_new_client(&mut wm);
+
+        wm.process_command(Command::Screen(ScreenCommand::SwapWith(2)))
+            .unwrap();
+        assert!(wm.screens[0].contains(a));
+        assert!(wm.screens[2].contains(b) && wm.screens[2].contains(c));
+        // The monitor stays on the screen.
+        assert!(wm.screens[0].monitor().is_some());
+        assert!(wm.ctx.conn.is_viewable(a));
+        assert!(!wm.ctx.conn.is_viewable(b) && !wm.ctx.conn.is_viewable(c));
+        assert_eq!(wm.ctx.conn.focus(), a);
+    }
+
+    #[test]
+    fn focus_next_cycles_windows() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        let c = map_new_client(&mut wm);
+        assert_eq!(wm.ctx.conn.focus(), c);
+
+        let mut visited = Vec::new();
+        for _ in 0..3 {
+            wm.process_command(Command::Focus(FocusCommand::Next))
+                .unwrap();
+            visited.push(wm.ctx.conn.focus());
+        }
+        visited.sort_unstable();
+        assert_eq!(visited, vec![a, b, c]);
+    }
+
+    #[test]
+    fn destroyed_window_is_forgotten() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+
+        destroy_client(&mut wm, b);
+        assert!(!wm.screens[0].contains(b));
+        assert!(wm.screens[0].contains(a));
+        assert_eq!(wm.ctx.conn.focus(), a);
+
+        destroy_client(&mut wm, a);
+        assert!(!wm.screens[0].contains(a));
+        assert_eq!(wm.ctx.conn.focus(), wm.screens[0].focus_holder());
+    }
+
+    #[test]
+    fn window_destroyed_while_closing_is_forgotten_once() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+
+        // `b` without WM_DELETE_WINDOW has gone before the WM destroys it.
+        wm.ctx.conn.destroy_client(b);
+        wm.process_command(Command::Window(WindowCommand::Close))
+            .unwrap();
+        assert!(!wm.screens[0].contains(b));
+        assert_eq!(wm.ctx.conn.focus(), a);
+
+        // Its DestroyNotify comes after.
+        destroy_client(&mut wm, b);
+        assert!(wm.screens[0].contains(a));
+        assert_eq!(wm.ctx.conn.focus(), a);
+    }
+
+    #[test]
+    fn window_destroyed_right_after_mapped_is_ignored() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = wm.ctx.conn.create_client(640, 480);
+        wm.ctx.conn.destroy_client(b);
+
+        map_client(&mut wm, b);
+        destroy_client(&mut wm, b);
+        assert!(wm.screens.iter().all(|screen| !screen.contains(b)));
+        assert!(wm.screens[0].contains(a));
+    }
+
+    #[test]
+    fn withdrawn_window_is_given_back_to_the_root() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        let frame = wm.screens[0].window(b).unwrap().frame();
+
+        wm.ctx.conn.unmap_client(b);
+        let notif = UnmapNotifyEvent {
+            response_type: UNMAP_NOTIFY_EVENT,
+            sequence: 0,
+            event: frame,
+            window: b,
+            from_configure: false,
+        };
+        wm.handle_event(Event::UnmapNotify(notif)).unwrap();
+        assert!(!wm.screens[0].contains(b));
+        assert_eq!(wm.ctx.conn.focus(), a);
+        let client = wm.ctx.conn.window(b).unwrap();
+        assert_eq!(client.parent, wm.ctx.root);
+        assert_eq!(client.properties[&wm.ctx.atom.WM_STATE].2[..4], [0; 4]);
+        assert!(!client.properties.contains_key(&wm.ctx.atom._NET_WM_DESKTOP));
+        assert!(wm.ctx.conn.window(frame).is_none());
+
+        // Mapped again, the window is managed as a new one.
+        map_client(&mut wm, b);
+        let new_frame = wm.screens[0].window(b).unwrap().frame();
+        assert_ne!(new_frame, frame);
+
+        // The synthetic UnmapNotify to the root withdraws it as well.
+        let notif = UnmapNotifyEvent {
+            response_type: UNMAP_NOTIFY_EVENT | 0x80,
+            event: wm.ctx.root,
+            ..notif
+        };
+        wm.handle_event(Event::UnmapNotify(notif)).unwrap();
+        assert!(!wm.screens[0].contains(b));
+        assert!(wm.screens[0].contains(a));
+    }
+
+    #[test]
+    fn focus_urgent_switches_to_the_screen() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(3)))
+            .unwrap();
+        assert!(wm.screens[3].contains(b));
+        assert_eq!(wm.ctx.conn.focus(), a);
+
+        set_urgency(&mut wm, a, true);
+        set_urgency(&mut wm, b, true);
+        wm.process_command(Command::Focus(FocusCommand::Urgent))
+            .unwrap();
+        assert!(wm.screens[3].monitor().is_some());
+        assert_eq!(wm.ctx.conn.focus(), b);
+
+        // Windows which are no longer urgent are skipped.
+        set_urgency(&mut wm, b, false);
+        wm.process_command(Command::Focus(FocusCommand::Urgent))
+            .unwrap();
+        assert!(wm.screens[0].monitor().is_some());
+        assert_eq!(wm.ctx.conn.focus(), a);
+    }
+
+    #[test]
+    fn extension_command_runs_handler() {
+        let extensions = crate::Extensions::new().command("go-to-2", |wm| {
+            wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+        });
+        let ctx = mock::context_with_extensions(&[(0, 0, 1920, 1080)], extensions);
+        let mut wm = WinMan::new(ctx).unwrap();
+
+        wm.process_command(Command::Extension("go-to-2".to_owned()))
+            .unwrap();
+        assert!(wm.screens[2].monitor().is_some());
+
+        // Unknown commands are ignored.
+        wm.process_command(Command::Extension("unknown".to_owned()))
+            .unwrap();
+    }
+
+    #[test]
+    fn failed_command_keeps_the_wm_running() {
+        let extensions = crate::Extensions::new().command("fail", |_| Err(Error::NoMonitor));
+        let ctx = mock::context_with_extensions(&[(0, 0, 1920, 1080)], extensions);
+        let mut wm = WinMan::new(ctx).unwrap();
+
+        wm.process_command(Command::Extension("fail".to_owned()))
+            .unwrap();
+        assert_eq!(wm.error_flashes, 1);
+
+        // Quitting is not a failure.
+        let res = wm.process_command(Command::Quit);
+        assert!(matches!(res, Err(Error::Quit)));
+        assert_eq!(wm.error_flashes, 1);
+    }
+
+    fn press_key(wm: &mut WinMan, keycode: u8) {
+        let e = KeyPressEvent {
+            response_type: KEY_PRESS_EVENT,
+            detail: keycode,
+            sequence: 0,
+            time: 0,
+            root: wm.ctx.root,
+            event: wm.ctx.root,
+            child: x11rb::NONE,
+            root_x: 0,
+            root_y: 0,
+            event_x: 0,
+            event_y: 0,
+            state: 0,
+            same_screen: true,
+        };
+        wm.handle_event(Event::KeyPress(e)).unwrap();
+    }
+
+    #[test]
+    fn held_key_repeats_pointer_movement() {
+        let mut wm = winman();
+        let super_: u16 = ModMask::M4.into();
+        let key = |wm: &mut WinMan, press: bool, time: u32| {
+            let e = KeyPressEvent {
+                response_type: KEY_PRESS_EVENT,
+                detail: KEY_UP,
+                sequence: 0,
+                time,
+                root: wm.ctx.root,
+                event: wm.ctx.root,
+                child: x11rb::NONE,
+                root_x: 0,
+                root_y: 0,
+                event_x: 0,
+                event_y: 0,
+                state: super_,
+                same_screen: true,
+            };
+            let event = if press {
+                Event::KeyPress(e)
+            } else {
+                Event::KeyRelease(KeyReleaseEvent {
+                    response_type: KEY_RELEASE_EVENT,
+                    ..e
+                })
+            };
+            wm.handle_event(event).unwrap();
+        };
+
+        // Super + Up moves the pointer by 32 pixels, then repeats after the delay.
+        let config = wm.ctx.config.pointer_repeat;
+        let pressed = Instant::now();
+        key(&mut wm, true, 1);
+        assert_eq!(wm.ctx.conn.pointer(), (0, -32));
+        let deadline = wm.pointer_repeat_deadline().unwrap();
+        assert!(deadline >= pressed + config.delay);
+        assert!(deadline <= Instant::now() + config.delay);
+        let moved = Instant::now();
+        wm.repeat_pointer().unwrap();
+        assert_eq!(wm.ctx.conn.pointer(), (0, -64));
+        let deadline = wm.pointer_repeat_deadline().unwrap();
+        assert!(deadline >= moved + config.interval);
+
+        // The key repeat of X neither moves the pointer, stops the repeat nor puts it off.
+        key(&mut wm, false, 2);
+        key(&mut wm, true, 2);
+        assert_eq!(wm.ctx.conn.pointer(), (0, -64));
+        assert_eq!(wm.pointer_repeat_deadline(), Some(deadline));
+
+        // It speeds up while the key is held.
+        for _ in 0..8 {
+            wm.repeat_pointer().unwrap();
+        }
+        assert_eq!(wm.ctx.conn.pointer(), (0, -64 - 6 * 32 - 2 * 64));
+
+        key(&mut wm, false, 3);
+        assert_eq!(wm.pointer_repeat_deadline(), None);
+    }
+
+    #[test]
+    fn window_list_focuses_selected_window() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        assert_eq!(wm.ctx.conn.focus(), b);
+
+        wm.process_command(Command::ShowWindowList).unwrap();
+        let list = wm.window_list.as_ref().unwrap().wid();
+        assert!(wm.ctx.conn.is_viewable(list));
+
+        // Keys other than numbers and Escape are ignored.
+        press_key(&mut wm, KEY_ENTER);
+        assert!(wm.window_list.is_some());
+
+        let index = wm.screens[0]
+            .windows()
+            .filter(|win| win.is_mapped())
+            .position(|win| win.contains(a))
+            .unwrap();
+        press_key(&mut wm, 10 + index as u8);
+        assert!(wm.window_list.is_none());
+        assert!(wm.ctx.conn.window(list).is_none());
+        assert_eq!(wm.ctx.conn.focus(), a);
+    }
+
+    #[test]
+    fn window_list_is_not_shown_without_the_keyboard() {
+        let mut wm = winman();
+        map_new_client(&mut wm);
+        let windows = wm.ctx.conn.windows().len();
+
+        wm.ctx.conn.grab_keyboard_elsewhere();
+        wm.process_command(Command::ShowWindowList).unwrap();
+        assert!(wm.window_list.is_none());
+        assert_eq!(wm.ctx.conn.windows().len(), windows);
+    }
+
+    #[test]
+    fn window_menu_selection_switches_to_the_screen() {
+        let mut wm = winman();
+        map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(3)))
+            .unwrap();
+
+        let (frames, lines) = wm.window_menu_entries().unwrap();
+        let line = lines
+            .lines()
+            .zip(frames.iter())
+            .find(|(_, &frame)| wm.screens[3].window(frame).is_some_and(|w| w.contains(b)))
+            .map(|(line, _)| line.to_owned())
+            .unwrap();
+        assert!(line.contains("[4]"));
+
+        wm.window_menu = Some(frames);
+        wm.process_task(Task::WindowMenu(Some(line + "\n")))
+            .unwrap();
+        assert!(wm.window_menu.is_none());
+        assert!(wm.screens[3].monitor().is_some());
+        assert_eq!(wm.ctx.conn.focus(), b);
+
+        // Cancelling the menu keeps the focus.
+        let (frames, _) = wm.window_menu_entries().unwrap();
+        wm.window_menu = Some(frames);
+        wm.process_task(Task::WindowMenu(Some(String::new())))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), b);
+    }
+
+    #[test]
+    fn media_level_is_shown_for_a_while() {
+        let mut wm = winman();
+        wm.process_task(Task::MediaLevel("vol", Some("50%\n".to_owned())))
+            .unwrap();
+        wm.process_task(Task::MediaLevel("vol", Some("55%\n".to_owned())))
+            .unwrap();
+        assert_eq!(wm.screens[0].level(), Some("vol 55%"));
+
+        // The first timeout does not hide the newer level.
+        wm.process_task(Task::ClearLevel).unwrap();
+        assert_eq!(wm.screens[0].level(), Some("vol 55%"));
+        wm.process_task(Task::ClearLevel).unwrap();
+        assert!(wm.screens[0].level().is_none());
+
+        // An empty output means the command failed.
+        wm.process_task(Task::MediaLevel("bri", None)).unwrap();
+        assert!(wm.screens[0].level().is_none());
+        assert_eq!(wm.error_flashes, 1);
+    }
+
+    #[test]
+    fn bar_is_hidden_under_full_screen_until_revealed() {
+        let config = crate::config::Config {
+            bar: crate::config::BarConfig {
+                autohide: true,
+                ..crate::config::Config::default().bar
+            },
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        map_new_client(&mut wm);
+        let bar_visible = |wm: &WinMan| wm.screens[0].monitor().unwrap().bar.is_visible();
+        let edge = wm.screens[0].monitor().unwrap().edge;
+        assert!(bar_visible(&wm));
+
+        wm.process_command(Command::Layout(LayoutCommand::Set(
+            "full-screen".to_owned(),
+        )))
+        .unwrap();
+        assert!(!bar_visible(&wm));
+        assert!(wm.ctx.conn.window(edge).unwrap().mapped);
+
+        // The pointer rests at the top edge, entering it more than once.
+        move_pointer(&wm.ctx.conn, 100, 0).unwrap();
+        let enter = EnterNotifyEvent {
+            response_type: ENTER_NOTIFY_EVENT,
+            detail: NotifyDetail::ANCESTOR,
+            sequence: 0,
+            time: 0,
+            root: wm.ctx.root,
+            event: edge,
+            child: x11rb::NONE,
+            root_x: 100,
+            root_y: 0,
+            event_x: 100,
+            event_y: 0,
+            state: 0,
+            mode: NotifyMode::NORMAL,
+            same_screen_focus: 0,
+        };
+        wm.handle_event(Event::EnterNotify(enter)).unwrap();
+        let deadline = wm.bar_deadline().unwrap();
+        wm.handle_event(Event::EnterNotify(enter)).unwrap();
+        assert_eq!(wm.bar_deadline(), Some(deadline));
+        wm.check_bar(0).unwrap();
+        assert!(bar_visible(&wm));
+        assert!(!wm.ctx.conn.window(edge).unwrap().mapped);
+        let deadline = wm.bar_deadline().unwrap();
+        wm.handle_event(Event::EnterNotify(enter)).unwrap();
+        assert_eq!(wm.bar_deadline(), Some(deadline));
+        wm.check_bar(0).unwrap();
+        assert!(bar_visible(&wm));
+
+        // ... and leaves.
+        move_pointer(&wm.ctx.conn, 0, 500).unwrap();
+        wm.bar_checks.clear();
+        wm.check_bar(0).unwrap();
+        assert!(!bar_visible(&wm));
+        assert_eq!(wm.bar_deadline(), None);
+
+        wm.process_command(Command::Layout(LayoutCommand::Next))
+            .unwrap();
+        assert!(bar_visible(&wm));
+        assert!(!wm.ctx.conn.window(edge).unwrap().mapped);
+    }
+
+    #[test]
+    fn layout_is_selected_by_name() {
+        let mut wm = winman();
+        map_new_client(&mut wm);
+
+        wm.process_command(Command::Layout(LayoutCommand::Set("monocle".to_owned())))
+            .unwrap();
+        assert_eq!(wm.screens[0].layout_name(), "monocle");
+
+        // Unknown layouts are ignored.
+        wm.process_command(Command::Layout(LayoutCommand::Set("grid".to_owned())))
+            .unwrap();
+        assert_eq!(wm.screens[0].layout_name(), "monocle");
+        assert_eq!(wm.error_flashes, 1);
+
+        wm.process_command(Command::Layout(LayoutCommand::Next))
+            .unwrap();
+        wm.process_command(Command::Layout(LayoutCommand::Prev))
+            .unwrap();
+        assert_eq!(wm.screens[0].layout_name(), "monocle");
+    }
+
+    #[test]
+    fn theme_is_switched_at_runtime() {
+        let mut wm = winman();
+        map_new_client(&mut wm);
+        assert_eq!(wm.ctx.theme(), wm.ctx.config.themes["dark"]);
+
+        wm.process_command(Command::SetTheme("light".to_owned()))
+            .unwrap();
+        assert_eq!(wm.ctx.theme(), wm.ctx.config.themes["light"]);
+
+        // Unknown themes are ignored.
+        wm.process_command(Command::SetTheme("solarized".to_owned()))
+            .unwrap();
+        assert_eq!(wm.ctx.theme(), wm.ctx.config.themes["light"]);
+        assert_eq!(wm.error_flashes, 1);
+    }
+
+    #[test]
+    fn move_left_swaps_tiled_windows() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        let x_of = |wm: &WinMan, wid| wm.screens[0].window(wid).unwrap().frame_geometry().x;
+        assert!(x_of(&wm, a) < x_of(&wm, b));
+        assert_eq!(wm.ctx.conn.focus(), b);
+
+        wm.process_command(Command::Window(WindowCommand::MoveLeft))
+            .unwrap();
+        assert!(x_of(&wm, b) < x_of(&wm, a));
+        assert_eq!(wm.ctx.conn.focus(), b);
+
+        // There is nothing further to the left.
+        wm.process_command(Command::Window(WindowCommand::MoveLeft))
+            .unwrap();
+        assert!(x_of(&wm, b) < x_of(&wm, a));
+    }
+
+    #[test]
+    fn net_wm_state_above_is_tracked() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let above = wm.ctx.atom._NET_WM_STATE_ABOVE;
+        let send = |wm: &mut WinMan, action: u32| {
+            let data = [action, above, 0, 1, 0];
+            let ev = ClientMessageEvent::new(32, a, wm.ctx.atom._NET_WM_STATE, data);
+            wm.handle_event(Event::ClientMessage(ev)).unwrap();
+        };
+        let property = |wm: &WinMan| {
+            let win = wm.ctx.conn.window(a).unwrap();
+            win.properties[&wm.ctx.atom._NET_WM_STATE].2.clone()
+        };
+
+        send(&mut wm, 1); // add
+        assert!(wm.screens[0].window(a).unwrap().is_above());
+        assert_eq!(property(&wm), above.to_ne_bytes());
+
+        send(&mut wm, 2); // toggle
+        assert!(!wm.screens[0].window(a).unwrap().is_above());
+        assert!(property(&wm).is_empty());
+    }
+
+    #[test]
+    fn fullscreen_window_fits_the_swapped_monitor() {
+        let ctx = mock::context(&[(0, 0, 1920, 1080), (1920, 0, 1280, 1024)]);
+        let mut wm = WinMan::new(ctx).unwrap();
+        let a = map_new_client(&mut wm);
+        let data = [1, wm.ctx.atom._NET_WM_STATE_FULLSCREEN, 0, 1, 0];
+        let ev = ClientMessageEvent::new(32, a, wm.ctx.atom._NET_WM_STATE, data);
+        wm.handle_event(Event::ClientMessage(ev)).unwrap();
+
+        let geometry = |wm: &WinMan| {
+            let geo = wm.screens[0].window(a).unwrap().frame_geometry();
+            (geo.x, geo.y, geo.width, geo.height)
+        };
+        assert_eq!(geometry(&wm), (0, 0, 1920, 1080));
+
+        // The screen goes to the other monitor.
+        wm.process_command(Command::Screen(ScreenCommand::Switch(1)))
+            .unwrap();
+        assert_eq!(wm.screens[0].monitor().unwrap().id, 1);
+        assert_eq!(geometry(&wm), (1920, 0, 1280, 1024));
+    }
+
+    #[test]
+    fn window_modes_survive_layout_switches_and_moves() {
+        use crate::window::WindowMode;
+
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        let send = |wm: &mut WinMan, wid: Wid, action: u32, states: [Atom; 2]| {
+            let data = [action, states[0], states[1], 1, 0];
+            let ev = ClientMessageEvent::new(32, wid, wm.ctx.atom._NET_WM_STATE, data);
+            wm.handle_event(Event::ClientMessage(ev)).unwrap();
+        };
+        let state = |wm: &WinMan, id: usize, wid: Wid| {
+            let win = wm.screens[id].window(wid).unwrap();
+            let geo = win.frame_geometry();
+            (win.mode(), (geo.x, geo.y, geo.width, geo.height))
+        };
+
+        // The layouts leave the fullscreen window alone.
+        let fullscreen = [wm.ctx.atom._NET_WM_STATE_FULLSCREEN, x11rb::NONE];
+        send(&mut wm, a, 1, fullscreen);
+        let full = (WindowMode::Fullscreen, (0, 0, 1920, 1080));
+        assert_eq!(state(&wm, 0, a), full);
+        wm.process_command(Command::Layout(LayoutCommand::Next))
+            .unwrap();
+        assert_eq!(state(&wm, 0, a), full);
+        send(&mut wm, a, 0, fullscreen);
+        assert_eq!(state(&wm, 0, a).0, WindowMode::Tiled);
+
+        // The maximized window leaves the bar, also on another screen.
+        let atom = &wm.ctx.atom;
+        let maximized = [
+            atom._NET_WM_STATE_MAXIMIZED_VERT,
+            atom._NET_WM_STATE_MAXIMIZED_HORZ,
+        ];
+        send(&mut wm, b, 1, maximized);
+        let bar = wm.ctx.config.bar.reserved_height(1);
+        let max = (WindowMode::Maximized, (0, bar as i16, 1920, 1080 - bar));
+        assert_eq!(state(&wm, 0, b), max);
+        wm.ctx.focus_window(b).unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(1)))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(1)))
+            .unwrap();
+        assert_eq!(state(&wm, 1, b), max);
+    }
+
+    #[test]
+    fn resize_mode_ends_when_the_focus_moves() {
+        let mut wm = winman();
+        map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        wm.process_command(Command::Window(WindowCommand::FloatSnap(
+            SnapPosition::Left,
+        )))
+        .unwrap();
+        wm.process_command(Command::Window(WindowCommand::ResizeMode))
+            .unwrap();
+        assert_eq!(wm.resize_mode, Some(b));
+
+        wm.process_command(Command::Focus(FocusCommand::Next))
+            .unwrap();
+        assert_ne!(wm.ctx.conn.focus(), b);
+        assert_eq!(wm.resize_mode, None);
+    }
+
+    #[test]
+    fn resize_mode_gives_back_the_keybindings_on_its_keys() {
+        let mut config = crate::config::Config::default();
+        let binding = (KeybindAction::Press, 0, KEY_ESCAPE);
+        config.keybind.insert(binding, Command::ShowWindowList);
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        let shift: u16 = ModMask::SHIFT.into();
+        map_new_client(&mut wm);
+        wm.process_command(Command::Window(WindowCommand::FloatSnap(
+            SnapPosition::Left,
+        )))
+        .unwrap();
+
+        wm.process_command(Command::Window(WindowCommand::ResizeMode))
+            .unwrap();
+        let grabs = wm.ctx.conn.key_grabs(wm.ctx.root);
+        assert!(grabs.contains(&(KEY_UP, shift)));
+
+        press_key(&mut wm, KEY_ENTER);
+        assert_eq!(wm.resize_mode, None);
+        let grabs = wm.ctx.conn.key_grabs(wm.ctx.root);
+        assert!(grabs.contains(&(KEY_ESCAPE, 0)));
+        assert!(!grabs.contains(&(KEY_UP, shift)));
+    }
+
+    #[test]
+    fn resize_mode_stops_at_the_limits_of_the_geometry() {
+        let mut wm = winman();
+        let wid = map_new_client(&mut wm);
+        wm.process_command(Command::Window(WindowCommand::FloatSnap(
+            SnapPosition::Left,
+        )))
+        .unwrap();
+        let rect = Rectangle {
+            x: i16::MAX - 1,
+            y: i16::MIN + 1,
+            width: u16::MAX - 1,
+            height: 2,
+        };
+        wm.window_mut(wid).unwrap().set_float_geometry(rect);
+
+        wm.move_resize_focused(2, -2, 0, 0).unwrap();
+        wm.move_resize_focused(0, 0, 2, -3).unwrap();
+        let geo = wm.window_mut(wid).unwrap().get_float_geometry().unwrap();
+        assert!(geo.width >= 1 && geo.height >= 1);
+    }
+
+    #[test]
+    fn fullscreen_window_bypasses_the_compositor() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        wm.process_command(Command::Window(WindowCommand::FloatSnap(
+            SnapPosition::Left,
+        )))
+        .unwrap();
+        let frame = wm.screens[0].window(a).unwrap().frame();
+        let send = |wm: &mut WinMan, action: u32| {
+            let data = [action, wm.ctx.atom._NET_WM_STATE_FULLSCREEN, 0, 1, 0];
+            let ev = ClientMessageEvent::new(32, a, wm.ctx.atom._NET_WM_STATE, data);
+            wm.handle_event(Event::ClientMessage(ev)).unwrap();
+        };
+        let bypass = |wm: &WinMan| {
+            let atom = wm.ctx.atom._NET_WM_BYPASS_COMPOSITOR;
+            let win = wm.ctx.conn.window(frame).unwrap();
+            win.properties.get(&atom).map(|p| p.2.clone())
+        };
+
+        send(&mut wm, 1);
+        assert_eq!(bypass(&wm), Some(1u32.to_ne_bytes().to_vec()));
+        // The title bar is not over the client.
+        let client = wm.ctx.conn.window(a).unwrap();
+        assert_eq!((client.y, client.width, client.height), (0, 1920, 1080));
+
+        send(&mut wm, 0);
+        assert_eq!(bypass(&wm), None);
+        let client = wm.ctx.conn.window(a).unwrap();
+        assert_eq!(client.y, crate::window::TITLE_HEIGHT as i16);
+    }
+
+    #[test]
+    fn pointer_clicks_are_disabled_without_xtest() {
+        let capabilities = |wm: &WinMan| {
+            let root = wm.ctx.conn.window(wm.ctx.root).unwrap();
+            let value = &root.properties[&wm.ctx.atom._DAILY_CAPABILITIES].2;
+            String::from_utf8_lossy(value).into_owned()
+        };
+
+        let mut wm = winman();
+        assert!(capabilities(&wm).lines().any(|name| name == "xtest"));
+        wm.process_command(Command::Pointer(PointerCommand::ClickLeft))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.fake_buttons(), [(1, true), (1, false)]);
+
+        let ctx = mock::context(&[(0, 0, 1920, 1080)]);
+        ctx.conn.remove_extension("XTEST");
+        let mut wm = WinMan::new(ctx).unwrap();
+        assert!(!capabilities(&wm).lines().any(|name| name == "xtest"));
+        for cmd in [PointerCommand::ClickLeft, PointerCommand::DragStart] {
+            wm.process_command(Command::Pointer(cmd)).unwrap();
+        }
+        assert!(wm.ctx.conn.fake_buttons().is_empty());
+        // The pointer still moves without it.
+        wm.process_command(Command::Pointer(PointerCommand::MoveRel(10, 0)))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.pointer(), (10, 0));
+    }
+
+    #[test]
+    fn root_window_is_the_monitor_without_randr() {
+        let ctx = mock::context(&[(0, 0, 1280, 1024), (1280, 0, 640, 480)]);
+        ctx.conn.remove_extension("RANDR");
+        let mut wm = WinMan::new(ctx).unwrap();
+        assert_eq!(wm.monitor_num, 1);
+        let rect = |wm: &WinMan| {
+            let rect = wm.screens[0].monitor().unwrap().rect();
+            (rect.x, rect.y, rect.width, rect.height)
+        };
+        assert_eq!(rect(&wm), (0, 0, 1920, 1080));
+        let a = map_new_client(&mut wm);
+        assert!(wm.screens[0].contains(a));
+
+        // The size is polled.
+        wm.alarm().unwrap();
+        assert_eq!(rect(&wm), (0, 0, 1920, 1080));
+        wm.ctx.conn.resize_root(1024, 768);
+        wm.alarm().unwrap();
+        assert_eq!(rect(&wm), (0, 0, 1024, 768));
+        assert!(wm.screens[0].contains(a));
+    }
+
+    #[test]
+    fn xinerama_heads_replace_a_giant_randr_monitor() {
+        let heads = [(0, 0, 1920, 1080), (1920, 0, 1280, 1024)];
+        let rects = |source| {
+            let config = crate::config::Config {
+                monitor_source: source,
+                ..crate::config::Config::default()
+            };
+            let ctx = mock::context_with_config(&[(0, 0, 3200, 1080)], config);
+            ctx.conn.set_xinerama_screens(&heads);
+            let wm = WinMan::new(ctx).unwrap();
+            let mut rects: Vec<_> = wm
+                .monitors()
+                .map(|mon| (mon.rect().x, mon.rect().width))
+                .collect();
+            rects.sort();
+            rects
+        };
+        assert_eq!(rects(MonitorSource::Auto), [(0, 1920), (1920, 1280)]);
+        assert_eq!(rects(MonitorSource::Xinerama), [(0, 1920), (1920, 1280)]);
+        assert_eq!(rects(MonitorSource::RandR), [(0, 3200)]);
+
+        // RandR monitors are preferred unless it has a single one.
+        let ctx = mock::context(&[(0, 0, 1280, 1024), (1280, 0, 1280, 1024)]);
+        ctx.conn.set_xinerama_screens(&[(0, 0, 2560, 1024)]);
+        let wm = WinMan::new(ctx).unwrap();
+        assert_eq!(wm.monitor_num, 2);
+    }
+
+    #[test]
+    fn metrics_are_published_on_the_root() {
+        let mut wm = winman();
+        wm.count_event(0);
+        wm.process_command(Command::Focus(FocusCommand::Next))
+            .unwrap();
+        assert!(!wm.reports_metrics());
+        wm.process_command(Command::ToggleMetricsHud).unwrap();
+        assert!(wm.reports_metrics());
+
+        let root = wm.ctx.conn.window(wm.ctx.root).unwrap();
+        let report = &root.properties[&wm.ctx.atom._DAILY_METRICS].2;
+        let report = String::from_utf8_lossy(report);
+        assert!(report.starts_with("events "), "{}", report);
+        assert!(report.contains("command "), "{}", report);
+    }
+
+    fn map_client_of_class(wm: &mut WinMan, class: &str) -> Wid {
+        let wid = wm.ctx.conn.create_client(640, 480);
+        let wm_class = format!("{}\0{}\0", class.to_lowercase(), class).into_bytes();
+        let string = AtomEnum::STRING.into();
+        wm.ctx
+            .conn
+            .set_property(wid, AtomEnum::WM_CLASS.into(), string, 8, wm_class);
+        map_client(wm, wid);
+        wid
+    }
+
+    #[test]
+    fn rules_put_windows_on_screens() {
+        let mut config = crate::config::Config::default();
+        config.rules = vec![
+            crate::config::WindowRule {
+                class: "Gimp".to_owned(),
+                screen: Some(4),
+                follow: true,
+                opacity: None,
+                confirm_close: false,
+            },
+            crate::config::WindowRule {
+                class: "Mail".to_owned(),
+                screen: Some(2),
+                follow: false,
+                opacity: Some(0.5),
+                confirm_close: false,
+            },
+        ];
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+
+        let mail = map_client_of_class(&mut wm, "Mail");
+        assert!(wm.screens[2].contains(mail));
+        assert!(wm.screens[0].monitor().is_some());
+        let frame = wm.screens[2].window(mail).unwrap().frame();
+        let opacity = wm.ctx.atom._NET_WM_WINDOW_OPACITY;
+        let value = &wm.ctx.conn.window(frame).unwrap().properties[&opacity].2;
+        assert_eq!(value[..], (u32::MAX / 2).to_ne_bytes());
+
+        let gimp = map_client_of_class(&mut wm, "Gimp");
+        assert!(wm.screens[4].contains(gimp));
+        assert!(wm.screens[4].monitor().is_some());
+        assert_eq!(wm.ctx.conn.focus(), gimp);
+    }
+
+    #[test]
+    fn windows_of_confirm_close_rules_close_on_the_second_press() {
+        let mut config = crate::config::Config::default();
+        config.rules = vec![crate::config::WindowRule {
+            class: "Ide".to_owned(),
+            screen: None,
+            follow: false,
+            opacity: None,
+            confirm_close: true,
+        }];
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        let close = Command::Window(WindowCommand::Close);
+
+        let ide = map_client_of_class(&mut wm, "Ide");
+        let other = map_client_of_class(&mut wm, "Term");
+        wm.process_command(close.clone()).unwrap();
+        assert!(!wm.screens[0].contains(other));
+
+        wm.process_command(close.clone()).unwrap();
+        assert!(wm.screens[0].contains(ide));
+        wm.process_command(close.clone()).unwrap();
+        assert!(!wm.screens[0].contains(ide));
+    }
+
+    #[test]
+    fn new_windows_overflow_to_the_next_screen_with_space() {
+        let config = crate::config::Config {
+            max_windows_per_screen: Some(2),
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        let c = map_new_client(&mut wm);
+        assert!(wm.screens[0].contains(a) && wm.screens[0].contains(b));
+        assert!(wm.screens[1].contains(c));
+        // The view does not follow by default.
+        assert!(wm.screens[0].monitor().is_some());
+        assert_eq!(wm.ctx.conn.focus(), b);
+
+        // Screens with space are filled first.
+        destroy_client(&mut wm, a);
+        let d = map_new_client(&mut wm);
+        assert!(wm.screens[0].contains(d));
+    }
+
+    #[test]
+    fn windows_beyond_the_page_are_hidden() {
+        let config = crate::config::Config {
+            windows_per_page: 2,
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        let hidden = |wm: &WinMan, wid: Wid| wm.screens[0].window(wid).unwrap().is_hidden();
+
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        assert!(!hidden(&wm, a) && !hidden(&wm, b));
+
+        // The page follows the new window.
+        let c = map_new_client(&mut wm);
+        assert!(hidden(&wm, a) && hidden(&wm, b) && !hidden(&wm, c));
+        assert_eq!(wm.screens[0].layout_page(), Some((1, 2)));
+
+        wm.process_command(Command::Layout(LayoutCommand::Command(
+            "next-page".to_owned(),
+        )))
+        .unwrap();
+        assert!(!hidden(&wm, a) && !hidden(&wm, b) && hidden(&wm, c));
+        assert!([a, b].contains(&wm.ctx.conn.focus()));
+
+        destroy_client(&mut wm, a);
+        assert_eq!(wm.screens[0].layout_page(), None);
+        assert!(!hidden(&wm, b) && !hidden(&wm, c));
+    }
+
+    #[test]
+    fn screen_with_windows_on_other_pages_is_switched_away() {
+        let config = crate::config::Config {
+            windows_per_page: 2,
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        let wids = [(); 3].map(|_| map_new_client(&mut wm));
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(1)))
+            .unwrap();
+        assert!(wids.iter().all(|&wid| !wm.ctx.conn.is_viewable(wid)));
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(0)))
+            .unwrap();
+        assert_eq!(wm.screens[0].layout_page(), Some((1, 2)));
+        assert!(wm.ctx.conn.is_viewable(wids[2]));
+    }
+
+    #[test]
+    fn window_on_another_page_is_shown_before_focused() {
+        let config = crate::config::Config {
+            windows_per_page: 2,
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        let a = map_new_client(&mut wm);
+        map_new_client(&mut wm);
+        map_new_client(&mut wm);
+        assert!(!wm.ctx.conn.is_viewable(a));
+
+        set_urgency(&mut wm, a, true);
+        wm.process_command(Command::Focus(FocusCommand::Urgent))
+            .unwrap();
+        assert!(wm.ctx.conn.is_viewable(a));
+        assert_eq!(wm.ctx.conn.focus(), a);
+        assert_eq!(wm.screens[0].layout_page(), Some((0, 2)));
+        assert!(wm.ctx.conn.unviewable_focus().is_empty());
+    }
+
+    #[test]
+    fn focus_next_leaves_a_window_on_another_page() {
+        let config = crate::config::Config {
+            windows_per_page: 2,
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        let c = map_new_client(&mut wm);
+
+        wm.process_command(Command::Layout(LayoutCommand::Command(
+            "next-page".to_owned(),
+        )))
+        .unwrap();
+        assert!(wm.screens[0].window(c).unwrap().is_hidden());
+
+        // The client takes the focus although its window is paged out.
+        wm.ctx.focus_window(c).unwrap();
+        wm.process_command(Command::Focus(FocusCommand::Next))
+            .unwrap();
+        assert!([a, b].contains(&wm.ctx.conn.focus()));
+    }
+
+    #[test]
+    fn clicking_a_tab_focuses_its_window() {
+        let mut wm = winman();
+        assert!(wm.screens[0].select_layout("tabbed"));
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        assert_eq!(wm.ctx.conn.focus(), b);
+
+        // The strip is split into the tabs of `a` and `b`.
+        let (strip, geometry) = wm
+            .ctx
+            .conn
+            .windows()
+            .into_iter()
+            .find(|(wid, win)| {
+                win.mapped
+                    && !win.input_only
+                    && wm.screens[0].is_background(*wid)
+                    && win.height == 16
+            })
+            .expect("no tab strip");
+        assert_eq!(geometry.width, 1920);
+
+        let press = ButtonPressEvent {
+            response_type: BUTTON_PRESS_EVENT,
+            detail: ButtonIndex::M1.into(),
+            sequence: 0,
+            time: 0,
+            root: wm.ctx.root,
+            event: wm.ctx.root,
+            child: strip,
+            root_x: 100,
+            root_y: geometry.y + 4,
+            event_x: 100,
+            event_y: geometry.y + 4,
+            state: 0,
+            same_screen: true,
+        };
+        wm.handle_event(Event::ButtonPress(press)).unwrap();
+        assert_eq!(wm.ctx.conn.focus(), a);
+
+        // Other layouts hide the strip.
+        wm.process_command(Command::Layout(LayoutCommand::Next))
+            .unwrap();
+        assert!(!wm.ctx.conn.window(strip).unwrap().mapped);
+    }
+
+    #[test]
+    fn only_the_first_click_in_a_window_goes_through_the_wm() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        let frame_a = wm.screens[0].window(a).unwrap().frame();
+        let frame_b = wm.screens[0].window(b).unwrap().frame();
+        assert_eq!(wm.ctx.conn.focus(), b);
+        let focus_in = FocusInEvent {
+            response_type: FOCUS_IN_EVENT,
+            sequence: 0,
+            detail: NotifyDetail::NONLINEAR,
+            event: frame_b,
+            mode: NotifyMode::NORMAL,
+        };
+        wm.handle_event(Event::FocusIn(focus_in)).unwrap();
+
+        // The root grabs the buttons only with Alt.
+        let alt = u16::from(ModMask::M1);
+        let root_grabs = wm.ctx.conn.button_grabs(wm.ctx.root);
+        assert!(!root_grabs.is_empty());
+        assert!(root_grabs.iter().all(|grab| grab.1 & alt != 0));
+
+        let any = (u8::from(ButtonIndex::ANY), u16::from(ModMask::ANY));
+        assert_eq!(wm.ctx.conn.button_grabs(frame_a), vec![any]);
+        assert!(wm.ctx.conn.button_grabs(frame_b).is_empty());
+
+        let press = ButtonPressEvent {
+            response_type: BUTTON_PRESS_EVENT,
+            detail: ButtonIndex::M1.into(),
+            sequence: 0,
+            time: 0,
+            root: wm.ctx.root,
+            event: frame_a,
+            child: a,
+            root_x: 100,
+            root_y: 100,
+            event_x: 100,
+            event_y: 100,
+            state: 0,
+            same_screen: true,
+        };
+        wm.handle_event(Event::ButtonPress(press)).unwrap();
+        assert_eq!(wm.ctx.conn.focus(), a);
+        assert!(wm.ctx.conn.button_grabs(frame_a).is_empty());
+        assert_eq!(wm.ctx.conn.button_grabs(frame_b), vec![any]);
+    }
+
+    #[test]
+    fn net_wm_desktop_places_and_follows_windows() {
+        let mut wm = winman();
+        let desktop = wm.ctx.atom._NET_WM_DESKTOP;
+        let property = |wm: &WinMan, wid: Wid| {
+            let win = wm.ctx.conn.window(wid).unwrap();
+            win.properties[&desktop].2.clone()
+        };
+
+        // A session restorer asks for the third screen.
+        let a = wm.ctx.conn.create_client(640, 480);
+        let cardinal = AtomEnum::CARDINAL.into();
+        wm.ctx
+            .conn
+            .set_property(a, desktop, cardinal, 32, 2u32.to_ne_bytes().to_vec());
+        map_client(&mut wm, a);
+        assert!(wm.screens[2].contains(a));
+        assert!(wm.screens[0].monitor().is_some());
+
+        // Out of range, so the focused screen is used.
+        let b = wm.ctx.conn.create_client(640, 480);
+        wm.ctx
+            .conn
+            .set_property(b, desktop, cardinal, 32, 99u32.to_ne_bytes().to_vec());
+        map_client(&mut wm, b);
+        assert!(wm.screens[0].contains(b));
+        assert_eq!(property(&wm, b), 0u32.to_ne_bytes());
+
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(3)))
+            .unwrap();
+        assert!(wm.screens[3].contains(b));
+        assert_eq!(property(&wm, b), 3u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn current_desktop_is_published_and_switched_by_pagers() {
+        let mut wm = winman();
+        let property = |wm: &WinMan, atom: Atom| {
+            let root = wm.ctx.conn.window(wm.ctx.root).unwrap();
+            root.properties[&atom].2.clone()
+        };
+        let number = wm.ctx.atom._NET_NUMBER_OF_DESKTOPS;
+        let current = wm.ctx.atom._NET_CURRENT_DESKTOP;
+        assert_eq!(property(&wm, number), 5u32.to_ne_bytes());
+        assert_eq!(property(&wm, current), 0u32.to_ne_bytes());
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(3)))
+            .unwrap();
+        assert_eq!(property(&wm, current), 3u32.to_ne_bytes());
+
+        let ev = ClientMessageEvent::new(32, wm.ctx.root, current, [1, 0, 0, 0, 0]);
+        wm.handle_event(Event::ClientMessage(ev)).unwrap();
+        assert!(wm.screens[1].monitor().is_some());
+        assert_eq!(property(&wm, current), 1u32.to_ne_bytes());
+    }
+
+    fn request_activation(wm: &mut WinMan, wid: Wid, source: u32) {
+        let data = [source, 0, 0, 0, 0];
+        let ev = ClientMessageEvent::new(32, wid, wm.ctx.atom._NET_ACTIVE_WINDOW, data);
+        wm.handle_event(Event::ClientMessage(ev)).unwrap();
+    }
+
+    #[test]
+    fn activation_request_switches_to_the_screen() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(3)))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), a);
+
+        request_activation(&mut wm, b, 1);
+        assert!(wm.screens[3].monitor().is_some());
+        assert_eq!(wm.ctx.conn.focus(), b);
+    }
+
+    #[test]
+    fn activation_request_from_apps_can_only_mark_urgent() {
+        let config = crate::config::Config {
+            activation: ActivationPolicy::Urgent,
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(3)))
+            .unwrap();
+
+        request_activation(&mut wm, b, 1);
+        assert!(wm.screens[0].monitor().is_some());
+        assert_eq!(wm.ctx.conn.focus(), a);
+        assert!(wm.screens[3].window(b).unwrap().is_urgent());
+
+        // Pagers are always obeyed.
+        request_activation(&mut wm, b, 2);
+        assert!(wm.screens[3].monitor().is_some());
+        assert_eq!(wm.ctx.conn.focus(), b);
+    }
+
+    #[test]
+    fn close_request_closes_the_window() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let ev = ClientMessageEvent::new(32, a, wm.ctx.atom._NET_CLOSE_WINDOW, [0; 5]);
+        wm.handle_event(Event::ClientMessage(ev)).unwrap();
+        assert!(!wm.screens[0].contains(a));
+    }
+
+    fn drag_pointer_to(wm: &mut WinMan, root_x: i16, root_y: i16) {
+        let e = MotionNotifyEvent {
+            response_type: MOTION_NOTIFY_EVENT,
+            detail: Motion::NORMAL,
+            sequence: 0,
+            time: 0,
+            root: wm.ctx.root,
+            event: wm.ctx.root,
+            child: x11rb::NONE,
+            root_x,
+            root_y,
+            event_x: root_x,
+            event_y: root_y,
+            state: ButtonMask::M1.into(),
+            same_screen: true,
+        };
+        wm.handle_event(Event::MotionNotify(e)).unwrap();
+    }
+
+    #[test]
+    fn moveresize_request_drags_the_window() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let frame = wm.screens[0].window(a).unwrap().frame();
+        let geometry = |wm: &WinMan| {
+            let win = wm.ctx.conn.window(frame).unwrap();
+            (win.x, win.y, win.width, win.height)
+        };
+        let request = |wm: &mut WinMan, direction: u32| {
+            let data = [100, 100, direction, 1, 1];
+            let ev = ClientMessageEvent::new(32, a, wm.ctx.atom._NET_WM_MOVERESIZE, data);
+            wm.handle_event(Event::ClientMessage(ev)).unwrap();
+        };
+
+        // move
+        request(&mut wm, MOVERESIZE_MOVE);
+        assert!(wm.screens[0].window(a).unwrap().is_floating());
+        let (x, y, w, h) = geometry(&wm);
+        drag_pointer_to(&mut wm, 150, 130);
+        assert_eq!(geometry(&wm), (x + 50, y + 30, w, h));
+        request(&mut wm, MOVERESIZE_CANCEL);
+        assert!(wm.drag.is_none());
+
+        // resize by the top-left corner
+        request(&mut wm, MOVERESIZE_SIZE_TOPLEFT);
+        let (x, y, w, h) = geometry(&wm);
+        drag_pointer_to(&mut wm, 90, 120);
+        assert_eq!(geometry(&wm), (x - 10, y + 20, w + 10, h - 20));
+    }
+
+    fn two_monitors() -> WinMan {
+        let ctx = mock::context(&[(0, 0, 1920, 1080), (1920, 0, 1280, 1024)]);
+        WinMan::new(ctx).unwrap()
+    }
+
+    #[test]
+    fn monitors_are_found_by_point_and_by_window() {
+        let mut wm = two_monitors();
+        assert_eq!(wm.monitor_at(0, 0), Some(0));
+        assert_eq!(wm.monitor_at(2000, 1000), Some(1));
+        assert_eq!(wm.monitor_at(2000, 1050), None);
+        assert_eq!(wm.monitor_at(-1, 0), None);
+
+        let a = map_new_client(&mut wm);
+        let frame = wm.screens[0].window(a).unwrap().frame();
+        assert_eq!(wm.monitor_of_window(frame).unwrap(), Some(0));
+        assert_eq!(wm.monitor_of_rect(Rect::new(1900, 0, 100, 100)), Some(1));
+
+        // Without a focused window, the screen under the pointer is the focused one.
+        wm.ctx
+            .focus_window(InputFocus::POINTER_ROOT.into())
+            .unwrap();
+        wm.ctx.conn.set_pointer(2000, 10);
+        let on_second = wm.screen_mut_by_mon(1).id;
+        assert_eq!(wm.focused_screen_mut().unwrap().id, on_second);
+    }
+
+    #[test]
+    fn focus_moves_to_the_monitor_in_the_direction() {
+        let mut wm = two_monitors();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+        let on_second = wm.screen_mut_by_mon(1).id;
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(on_second)))
+            .unwrap();
+        assert!(wm.screens[on_second].contains(b));
+
+        wm.process_command(Command::Focus(FocusCommand::Monitor(Direction::Right)))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), b);
+        // There is no monitor above.
+        wm.process_command(Command::Focus(FocusCommand::Monitor(Direction::Up)))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), b);
+        wm.process_command(Command::Focus(FocusCommand::Monitor(Direction::Left)))
+            .unwrap();
+        assert_eq!(wm.ctx.conn.focus(), a);
+    }
+
+    #[test]
+    fn window_dropped_on_another_monitor_moves_to_its_screen() {
+        let mut wm = two_monitors();
+        let a = map_new_client(&mut wm);
+        let request = |wm: &mut WinMan, direction: u32| {
+            let data = [100, 100, direction, 1, 1];
+            let ev = ClientMessageEvent::new(32, a, wm.ctx.atom._NET_WM_MOVERESIZE, data);
+            wm.handle_event(Event::ClientMessage(ev)).unwrap();
+        };
+
+        request(&mut wm, MOVERESIZE_MOVE);
+        let x = wm.screens[0].window(a).unwrap().frame_geometry().x;
+        drag_pointer_to(&mut wm, 2100, 100);
+        request(&mut wm, MOVERESIZE_CANCEL);
+
+        let on_second = wm.screen_mut_by_mon(1).id;
+        assert!(!wm.screens[0].contains(a));
+        let win = wm.screens[on_second].window(a).unwrap();
+        assert!(win.is_floating());
+        assert_eq!(win.frame_geometry().x, x + 2000);
+        assert_eq!(wm.ctx.conn.focus(), a);
+    }
+
+    #[test]
+    fn focused_window_is_snapped_to_the_monitor() {
+        let area = Rectangle {
+            x: 0,
+            y: 20,
+            width: 1920,
+            height: 1060,
+        };
+        let snap = |pos| {
+            let r = snap_geometry(area, pos, (640, 480), 2);
+            (r.x, r.y, r.width, r.height)
+        };
+        assert_eq!(snap(SnapPosition::Left), (0, 20, 956, 1056));
+        assert_eq!(snap(SnapPosition::Bottom), (0, 550, 1916, 526));
+        assert_eq!(snap(SnapPosition::TopRight), (960, 20, 956, 526));
+        assert_eq!(snap(SnapPosition::Center), (638, 308, 640, 480));
+
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let frame = wm.screens[0].window(a).unwrap().frame();
+        wm.process_command(Command::Window(WindowCommand::FloatSnap(
+            SnapPosition::Right,
+        )))
+        .unwrap();
+        let win = wm.screens[0].window(a).unwrap();
+        assert!(win.is_floating());
+        let geo = win.get_float_geometry().unwrap();
+        let mock = wm.ctx.conn.window(frame).unwrap();
+        assert_eq!((mock.x, mock.y), (geo.x, geo.y));
+        assert_eq!(geo.x, 960);
+        assert_eq!(geo.y, wm.ctx.config.bar.reserved_height(1) as i16);
+    }
+
+    #[test]
+    fn clicked_floating_window_stays_on_top() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        wm.process_command(Command::Window(WindowCommand::FloatSnap(
+            SnapPosition::Left,
+        )))
+        .unwrap();
+        let b = map_new_client(&mut wm);
+        wm.process_command(Command::Window(WindowCommand::FloatSnap(
+            SnapPosition::Right,
+        )))
+        .unwrap();
+        let frame_a = wm.screens[0].window(a).unwrap().frame();
+        let frame_b = wm.screens[0].window(b).unwrap().frame();
+        let stack = |wm: &WinMan| {
+            let children = wm.ctx.conn.query_tree(wm.ctx.root).unwrap();
+            let children = children.reply().unwrap().children;
+            let pos = |frame| children.iter().position(|&w| w == frame).unwrap();
+            (pos(frame_a), pos(frame_b))
+        };
+        let (pos_a, pos_b) = stack(&wm);
+        assert!(pos_a < pos_b);
+
+        let press = ButtonPressEvent {
+            response_type: BUTTON_PRESS_EVENT,
+            detail: ButtonIndex::M1.into(),
+            sequence: 0,
+            time: 0,
+            root: wm.ctx.root,
+            event: wm.ctx.root,
+            child: frame_a,
+            root_x: 100,
+            root_y: 100,
+            event_x: 100,
+            event_y: 100,
+            state: 0,
+            same_screen: true,
+        };
+        wm.handle_event(Event::ButtonPress(press)).unwrap();
+        let (pos_a, pos_b) = stack(&wm);
+        assert!(pos_a > pos_b);
+
+        // `a` is still on top after switching the screens back and forth.
+        wm.process_command(Command::Screen(ScreenCommand::Switch(1)))
+            .unwrap();
+        map_new_client(&mut wm);
+        wm.process_command(Command::Screen(ScreenCommand::Switch(0)))
+            .unwrap();
+        let (pos_a, pos_b) = stack(&wm);
+        assert!(pos_a > pos_b);
+    }
+
+    #[test]
+    fn wm_manages_the_root_of_its_x_screen() {
+        let mut conn = mock::MockConnection::new();
+        conn.add_screen();
+        conn.add_monitor(0, 0, 1920, 1080);
+        let config = crate::config::Config::default();
+        let display = Some(":0.1".to_owned());
+        let ctx = crate::context::ContextInner::with_connection(conn, 1, config, display).unwrap();
+        let mut wm = WinMan::new(std::sync::Arc::new(ctx)).unwrap();
+        assert_eq!(wm.ctx.root, mock::SECOND_ROOT);
+
+        let wm_s1 = wm.ctx.conn.intern_atom(false, b"WM_S1").unwrap();
+        let wm_s1 = wm_s1.reply().unwrap().atom;
+        let owner = wm.ctx.conn.get_selection_owner(wm_s1).unwrap();
+        assert_eq!(owner.reply().unwrap().owner, wm.selection_owner);
+        // The processes run by the WM open their windows on the same X screen.
+        assert_eq!(wm.process_envs(), [("DISPLAY", ":0.1".to_owned())]);
+
+        let a = map_new_client(&mut wm);
+        assert!(wm.screens[0].contains(a));
+    }
+
+    #[test]
+    fn second_wm_reports_the_owner_of_wm_selection() {
+        let ctx = mock::context(&[(0, 0, 1920, 1080)]);
+        let wm = WinMan::new(ctx.clone()).unwrap();
+        let owner = wm.selection_owner;
+
+        match WinMan::new(ctx.clone()) {
+            Err(Error::WmAlreadyExists { owner: desc }) => {
+                assert!(desc.contains(&format!("0x{:08X}", owner)));
+                assert!(desc.contains("\"daily\""));
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+
+        // The selection is released with the WM.
+        drop(wm);
+        assert!(WinMan::new(ctx).is_ok());
+    }
+
+    #[test]
+    fn focus_returns_from_a_popup() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        map_new_client(&mut wm);
+        wm.screens[0].window_mut(a).unwrap().focus().unwrap();
+
+        // A menu like dmenu takes the focus and reverts it to the root when it goes away.
+        let popup = wm.ctx.conn.create_client(640, 16);
+        let notif = MapNotifyEvent {
+            response_type: MAP_NOTIFY_EVENT,
+            sequence: 0,
+            event: wm.ctx.root,
+            window: popup,
+            override_redirect: true,
+        };
+        wm.handle_event(Event::MapNotify(notif)).unwrap();
+        wm.ctx.focus_window(popup).unwrap();
+
+        wm.ctx
+            .focus_window(InputFocus::POINTER_ROOT.into())
+            .unwrap();
+        let notif = UnmapNotifyEvent {
+            response_type: UNMAP_NOTIFY_EVENT,
+            sequence: 0,
+            event: wm.ctx.root,
+            window: popup,
+            from_configure: false,
+        };
+        wm.handle_event(Event::UnmapNotify(notif)).unwrap();
+        assert_eq!(wm.ctx.conn.focus(), a);
+        assert!(wm.popups.is_empty());
+
+        // The focus in the root following the revert does not move the focus.
+        let focus_in = FocusInEvent {
+            response_type: FOCUS_IN_EVENT,
+            sequence: 0,
+            detail: NotifyDetail::POINTER_ROOT,
+            event: wm.ctx.root,
+            mode: NotifyMode::NORMAL,
+        };
+        wm.handle_event(Event::FocusIn(focus_in)).unwrap();
+        assert_eq!(wm.ctx.conn.focus(), a);
+    }
+
+    #[test]
+    fn frames_mapped_by_screen_switches_are_not_popups() {
+        let mut wm = winman();
+        let a = map_new_client(&mut wm);
+        let b = map_new_client(&mut wm);
+
+        wm.process_command(Command::Screen(ScreenCommand::Switch(1)))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(0)))
+            .unwrap();
+        // The server reports the frames mapped again, which are override-redirect.
+        for wid in [a, b] {
+            let frame = wm.ctx.conn.window(wid).unwrap().parent;
+            let notif = MapNotifyEvent {
+                response_type: MAP_NOTIFY_EVENT,
+                sequence: 0,
+                event: wm.ctx.root,
+                window: frame,
+                override_redirect: true,
+            };
+            wm.handle_event(Event::MapNotify(notif)).unwrap();
+        }
+        assert!(wm.popups.is_empty());
+    }
+
+    #[test]
+    fn state_is_restored_after_restart() {
+        let ctx = mock::context(&[(0, 0, 1920, 1080)]);
+        let mut wm = WinMan::new(ctx.clone()).unwrap();
+        let a = map_new_client(&mut wm);
+        wm.process_command(Command::Screen(ScreenCommand::MoveWindow(2)))
+            .unwrap();
+        wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+            .unwrap();
+        wm.process_command(Command::Layout(LayoutCommand::Next))
+            .unwrap();
+        let layout = wm.screens[2].layout_name();
+        assert_ne!(layout, wm.screens[0].layout_name());
+        assert!(matches!(
+            wm.process_command(Command::Restart),
+            Err(Error::Restart)
+        ));
+        drop(wm);
+
+        let wm = WinMan::new(ctx).unwrap();
+        assert!(wm.screens[2].contains(a));
+        assert!(wm.screens[2].monitor().is_some());
+        assert_eq!(wm.screens[2].layout_name(), layout);
+    }
+
+    #[test]
+    fn unmapped_window_of_a_hidden_screen_is_adopted() {
+        // A client left unmapped by the previous instance, on a screen not shown.
+        let ctx = mock::context(&[(0, 0, 1920, 1080)]);
+        let a = ctx.conn.create_client(640, 480);
+        let desktop = ctx.conn.intern_atom(false, b"_NET_WM_DESKTOP").unwrap();
+        let desktop = desktop.reply().unwrap().atom;
+        let cardinal = AtomEnum::CARDINAL.into();
+        ctx.conn
+            .set_property(a, desktop, cardinal, 32, 2u32.to_ne_bytes().to_vec());
+
+        let mut wm = WinMan::new(ctx).unwrap();
+        assert!(wm.screens[2].contains(a));
+        wm.process_command(Command::Screen(ScreenCommand::Switch(2)))
+            .unwrap();
+        assert!(!wm.ctx.conn.is_viewable(a));
+    }
+
+    #[test]
+    fn burst_of_events_does_not_delay_the_animation() {
+        let config = crate::config::Config {
+            animation: crate::config::AnimationConfig {
+                enabled: true,
+                duration_ms: 100,
+                steps: 5,
+            },
+            ..Default::default()
+        };
+        let ctx = mock::context_with_config(&[(0, 0, 1920, 1080)], config);
+        let mut wm = WinMan::new(ctx).unwrap();
+        let a = map_new_client(&mut wm);
+        map_new_client(&mut wm);
+        wm.process_command(Command::Layout(LayoutCommand::Next))
+            .unwrap();
+        let deadline = wm
+            .animation_deadline()
+            .expect("the windows should be moving");
+
+        // Each event wakes up the main loop, which must not put the next step off.
+        for i in 0..100 {
+            set_urgency(&mut wm, a, i % 2 == 0);
+        }
+        assert_eq!(wm.animation_deadline(), Some(deadline));
+
+        wm.animate().unwrap();
+        if let Some(next) = wm.animation_deadline() {
+            assert!(next > deadline);
+        }
+    }
 }
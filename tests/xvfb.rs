@@ -0,0 +1,219 @@
+//! Integration tests running Daily against a headless X server (Xvfb).
+//!
+//! These tests are ignored by default because they need `Xvfb` in PATH:
+//! ```text
+//! $ cargo test -- --ignored
+//! ```
+
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Window as Wid, *};
+use x11rb::rust_connection::RustConnection;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Tests run in parallel, so each harness takes its own display number.
+static NEXT_DISPLAY: AtomicU32 = AtomicU32::new(90);
+
+fn wait_until<F: FnMut() -> bool>(mut cond: F) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < TIMEOUT {
+        if cond() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+/// An Xvfb server with Daily running on it.
+struct Harness {
+    xvfb: Child,
+    wm: Child,
+    conn: RustConnection,
+    root: Wid,
+    tmp: PathBuf,
+}
+
+impl Harness {
+    fn start() -> Self {
+        // Find a free display number.
+        let display_num = std::iter::repeat_with(|| NEXT_DISPLAY.fetch_add(1, Ordering::SeqCst))
+            .take(100)
+            .find(|n| !PathBuf::from(format!("/tmp/.X{}-lock", n)).exists())
+            .expect("no free display");
+        let display = format!(":{}", display_num);
+
+        let xvfb = Command::new("Xvfb")
+            .arg(&display)
+            .args(["-screen", "0", "1280x720x24", "-nolisten", "tcp"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("cannot launch Xvfb");
+
+        let mut conn = None;
+        assert!(
+            wait_until(|| {
+                conn = RustConnection::connect(Some(&display)).ok();
+                conn.is_some()
+            }),
+            "Xvfb did not start"
+        );
+        let (conn, screen_num) = conn.unwrap();
+        let root = conn.setup().roots[screen_num].root;
+
+        // Run the WM with the default config.
+        let tmp = std::env::temp_dir().join(format!("daily-test-{}", display_num));
+        std::fs::create_dir_all(&tmp).unwrap();
+        let wm = Command::new(env!("CARGO_BIN_EXE_daily"))
+            .env("DISPLAY", &display)
+            .env("XDG_CONFIG_HOME", &tmp)
+            .env("HOME", &tmp)
+            .current_dir(&tmp)
+            .spawn()
+            .expect("cannot launch daily");
+
+        let harness = Self {
+            xvfb,
+            wm,
+            conn,
+            root,
+            tmp,
+        };
+        assert!(
+            wait_until(|| harness.wm_running()),
+            "daily did not become the window manager"
+        );
+        harness
+    }
+
+    fn wm_running(&self) -> bool {
+        let attr = self
+            .conn
+            .get_window_attributes(self.root)
+            .unwrap()
+            .reply()
+            .unwrap();
+        attr.all_event_masks & u32::from(EventMask::SUBSTRUCTURE_REDIRECT) != 0
+    }
+
+    fn create_client(&self, width: u16, height: u16) -> Wid {
+        let wid = self.conn.generate_id().unwrap();
+        let aux = CreateWindowAux::new().background_pixel(0xFFFFFF);
+        self.conn
+            .create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                wid,
+                self.root,
+                0,
+                0,
+                width,
+                height,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                x11rb::COPY_FROM_PARENT,
+                &aux,
+            )
+            .unwrap();
+        self.conn.map_window(wid).unwrap();
+        self.conn.flush().unwrap();
+        wid
+    }
+
+    fn parent(&self, wid: Wid) -> Wid {
+        self.conn.query_tree(wid).unwrap().reply().unwrap().parent
+    }
+
+    fn is_viewable(&self, wid: Wid) -> bool {
+        match self.conn.get_window_attributes(wid).unwrap().reply() {
+            Ok(attr) => attr.map_state == MapState::VIEWABLE,
+            Err(_) => false,
+        }
+    }
+
+    fn focus(&self) -> Wid {
+        self.conn.get_input_focus().unwrap().reply().unwrap().focus
+    }
+
+    /// Returns the geometry of the window in root coordinates.
+    fn root_geometry(&self, wid: Wid) -> Rectangle {
+        let geo = self.conn.get_geometry(wid).unwrap().reply().unwrap();
+        let pos = self
+            .conn
+            .translate_coordinates(wid, self.root, 0, 0)
+            .unwrap()
+            .reply()
+            .unwrap();
+        Rectangle {
+            x: pos.dst_x,
+            y: pos.dst_y,
+            width: geo.width,
+            height: geo.height,
+        }
+    }
+
+    /// Waits for the client to be framed and shown by the WM.
+    fn wait_managed(&self, wid: Wid) {
+        assert!(
+            wait_until(|| self.parent(wid) != self.root && self.is_viewable(wid)),
+            "window {:08X} was not managed",
+            wid
+        );
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = self.wm.kill();
+        let _ = self.wm.wait();
+        let _ = self.xvfb.kill();
+        let _ = self.xvfb.wait();
+        let _ = std::fs::remove_dir_all(&self.tmp);
+    }
+}
+
+#[test]
+#[ignore]
+fn mapped_window_is_framed_and_focused() {
+    let h = Harness::start();
+    let wid = h.create_client(300, 200);
+    h.wait_managed(wid);
+    assert!(wait_until(|| h.focus() == wid));
+}
+
+#[test]
+#[ignore]
+fn windows_are_tiled_side_by_side() {
+    let h = Harness::start();
+    let a = h.create_client(300, 200);
+    h.wait_managed(a);
+    let b = h.create_client(300, 200);
+    h.wait_managed(b);
+
+    assert!(wait_until(|| {
+        let ga = h.root_geometry(a);
+        let gb = h.root_geometry(b);
+        ga.y == gb.y && ga.x != gb.x && ga.width + gb.width <= 1280
+    }));
+}
+
+#[test]
+#[ignore]
+fn destroyed_window_frame_is_removed() {
+    let h = Harness::start();
+    let wid = h.create_client(300, 200);
+    h.wait_managed(wid);
+    let frame = h.parent(wid);
+
+    h.conn.destroy_window(wid).unwrap();
+    h.conn.flush().unwrap();
+    assert!(wait_until(|| !h.is_viewable(frame)));
+
+    let children = h.conn.query_tree(h.root).unwrap().reply().unwrap().children;
+    assert!(!children.contains(&frame));
+}